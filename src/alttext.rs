@@ -0,0 +1,56 @@
+//! Alternative text of pictures, from the `descr` (or failing that the
+//! `title`) of their `wp:docPr`, and of embedded objects' previews, from
+//! the `o:title` of their `v:imagedata`. docx_rs keeps neither.
+
+use std::collections::HashMap;
+
+use quick_xml::Reader;
+use quick_xml::escape::unescape;
+use quick_xml::events::{BytesStart, Event};
+
+use crate::{ConversionError, read_part};
+
+/// The unescaped, non-blank value of the attribute `name` of `element`.
+fn text_attribute(element: &BytesStart, name: &[u8]) -> Option<String> {
+    let attribute = element.attributes().flatten().find(|attribute| attribute.key == name)?;
+    let value = unescape(&attribute.value).ok()?;
+    let text = String::from_utf8_lossy(&value).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// Alternative text of the pictures and object previews in
+/// `word/document.xml`, by the relationship id of the image they show.
+pub fn read_alt_texts(docx: &[u8]) -> Result<HashMap<String, String>, ConversionError> {
+    let Some(xml) = read_part(docx, "word/document.xml")? else { return Ok(HashMap::new()) };
+
+    let mut alt_texts = HashMap::new();
+    let mut reader = Reader::from_reader(&xml[..]);
+    let mut buf = Vec::new();
+    //The docPr of a drawing comes before the blip naming its image
+    let mut pending = None;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => match e.local_name() {
+                b"docPr" => pending = text_attribute(e, b"descr").or_else(|| text_attribute(e, b"title")),
+                b"blip" => {
+                    let id = e.attributes().flatten().find(|attribute| attribute.key == b"r:embed");
+                    if let (Some(id), Some(alt)) = (id, pending.take()) {
+                        alt_texts.entry(String::from_utf8_lossy(&id.value).into_owned()).or_insert(alt);
+                    }
+                }
+                b"imagedata" => {
+                    let id = e.attributes().flatten().find(|attribute| attribute.key == b"r:id");
+                    if let (Some(id), Some(alt)) = (id, text_attribute(e, b"o:title")) {
+                        alt_texts.entry(String::from_utf8_lossy(&id.value).into_owned()).or_insert(alt);
+                    }
+                }
+                _ => {}
+            },
+            Ok(Event::End(ref e)) if e.local_name() == b"drawing" => pending = None,
+            Ok(Event::Eof) => return Ok(alt_texts),
+            Err(e) => return Err(ConversionError::InvalidInput(format!("Malformed word/document.xml: {}", e))),
+            _ => {}
+        }
+        buf.clear();
+    }
+}
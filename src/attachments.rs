@@ -0,0 +1,120 @@
+//! The source documents embedded in the PDF as attachments, so the docx
+//! can be recovered from it. printpdf cannot write embedded files, so they
+//! are added to the saved file.
+
+use std::collections::BTreeMap;
+
+use printpdf::lopdf::{self, Dictionary, Object, Stream, StringFormat};
+
+use crate::ConversionError;
+
+const DOCX_MIME_TYPE: &[u8] = b"application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+
+/// A docx to attach, under the file name it is saved as.
+pub struct Attachment<'d> {
+    pub name: String,
+    pub data: &'d [u8],
+}
+
+/// `text` as a PDF text string: UTF-16BE with a byte order mark.
+pub fn text_string(text: &str) -> Object {
+    let mut bytes = vec![0xFE, 0xFF];
+    bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+    Object::String(bytes, StringFormat::Hexadecimal)
+}
+
+/// Embeds `attachments` and lists them in the catalog's `/EmbeddedFiles`
+/// name tree and as associated files of the document.
+pub fn embed_attachments(pdf: &[u8], attachments: &[Attachment]) -> Result<Vec<u8>, ConversionError> {
+    let pdf_error = |e: lopdf::Error| ConversionError::Pdf(e.to_string());
+    let mut document = lopdf::Document::load_mem(pdf).map_err(pdf_error)?;
+
+    //Keyed by name, which the name tree needs sorted and unique
+    let mut file_specs = BTreeMap::new();
+    for attachment in attachments {
+        let mut name = attachment.name.clone();
+        for copy in 2.. {
+            if !file_specs.contains_key(&name) {
+                break;
+            }
+            name = format!("{} ({})", attachment.name, copy);
+        }
+
+        let mut params = Dictionary::new();
+        params.set("Size", Object::Integer(attachment.data.len() as i64));
+        let mut stream_dict = Dictionary::new();
+        stream_dict.set("Type", Object::Name(b"EmbeddedFile".to_vec()));
+        stream_dict.set("Subtype", Object::Name(DOCX_MIME_TYPE.to_vec()));
+        stream_dict.set("Params", params);
+        let mut stream = Stream::new(stream_dict, attachment.data.to_vec());
+        stream.compress().map_err(pdf_error)?;
+        let file_id = document.add_object(stream);
+
+        let mut embedded = Dictionary::new();
+        embedded.set("F", Object::Reference(file_id));
+        let mut file_spec = Dictionary::new();
+        file_spec.set("Type", Object::Name(b"Filespec".to_vec()));
+        file_spec.set("F", text_string(&name));
+        file_spec.set("UF", text_string(&name));
+        file_spec.set("Desc", Object::string_literal("Source document"));
+        file_spec.set("AFRelationship", Object::Name(b"Source".to_vec()));
+        file_spec.set("EF", embedded);
+        let spec_id = document.add_object(file_spec);
+        file_specs.insert(name, spec_id);
+    }
+
+    let mut names = Vec::new();
+    for (name, spec_id) in &file_specs {
+        names.push(text_string(name));
+        names.push(Object::Reference(*spec_id));
+    }
+    let mut tree = Dictionary::new();
+    tree.set("Names", Object::Array(names));
+    let tree_id = document.add_object(tree);
+
+    let catalog_id = document.trailer.get(b"Root").and_then(Object::as_reference).map_err(pdf_error)?;
+    let catalog = document.get_dictionary_mut(catalog_id).map_err(pdf_error)?;
+    let mut catalog_names = match catalog.get(b"Names") {
+        Ok(Object::Dictionary(names)) => names.clone(),
+        _ => Dictionary::new(),
+    };
+    catalog_names.set("EmbeddedFiles", Object::Reference(tree_id));
+    catalog.set("Names", catalog_names);
+    catalog.set("AF", Object::Array(file_specs.values().map(|id| Object::Reference(*id)).collect()));
+
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use crate::tests::sample_pdf;
+
+    #[test]
+    fn embeds_each_file_under_a_unique_name() {
+        let pdf = sample_pdf("Attached", &Config::new("report.docx", "report.pdf"));
+        //Long enough to be worth compressing
+        let second = b"second ".repeat(40);
+        let attachments = [Attachment { name: "report.docx".to_string(), data: b"first" }, Attachment { name: "report.docx".to_string(), data: &second }];
+        let document = lopdf::Document::load_mem(&embed_attachments(&pdf, &attachments).unwrap()).unwrap();
+
+        let catalog = document.catalog().unwrap();
+        let names = catalog.get(b"Names").and_then(Object::as_dict).unwrap();
+        let tree_id = names.get(b"EmbeddedFiles").and_then(Object::as_reference).unwrap();
+        let entries = document.get_dictionary(tree_id).and_then(|tree| tree.get(b"Names")).and_then(Object::as_array).unwrap();
+        let name = |entry: &Object| entry.as_str().map(<[u8]>::to_vec).unwrap();
+        assert_eq!(name(&entries[0]), name(&text_string("report.docx")));
+        assert_eq!(name(&entries[2]), name(&text_string("report.docx (2)")));
+
+        let spec = document.get_dictionary(entries[3].as_reference().unwrap()).unwrap();
+        assert_eq!(spec.get(b"AFRelationship").and_then(Object::as_name).unwrap(), b"Source");
+        let file_id = spec.get(b"EF").and_then(Object::as_dict).and_then(|ef| ef.get(b"F")).and_then(Object::as_reference).unwrap();
+        let file = document.get_object(file_id).and_then(Object::as_stream).unwrap();
+        assert_eq!(file.dict.get(b"Subtype").and_then(Object::as_name).unwrap(), DOCX_MIME_TYPE);
+        assert_eq!(file.decompressed_content().unwrap(), second);
+        assert_eq!(catalog.get(b"AF").and_then(Object::as_array).unwrap().len(), 2);
+    }
+}
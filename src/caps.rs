@@ -0,0 +1,52 @@
+//! All caps and small caps. Both change only how a run's text is drawn:
+//! all caps uppercases it, small caps sets lowercase letters as capitals at
+//! a reduced size.
+
+use docx_rs::Run;
+
+use crate::markup::{Marker, run_markers};
+use crate::{property_json, run_text};
+
+/// Size of small capitals relative to the run's font size.
+pub const SMALL_CAPS_SCALE: f32 = 0.8;
+
+/// The text of `run` as displayed, uppercased for all caps. Small caps keep
+/// their case here; they are set when drawn.
+pub fn display_text(run: &Run) -> String {
+    let text = run_text(run);
+    if property_json(&run.run_property)["caps"].as_bool().unwrap_or(false) { text.to_uppercase() } else { text }
+}
+
+pub fn is_small_caps(run: &Run) -> bool {
+    run_markers(run).contains(&Marker::SmallCaps)
+}
+
+/// Splits `text` into spans set in full-size or small capitals, uppercased
+/// for drawing. The flag is true for small capital spans.
+pub fn small_caps_spans(text: &str) -> Vec<(bool, String)> {
+    let mut spans: Vec<(bool, String)> = Vec::new();
+    for c in text.chars() {
+        let small = c.is_lowercase();
+        match spans.last_mut() {
+            Some((span_small, span)) if *span_small == small => span.extend(c.to_uppercase()),
+            _ => spans.push((small, c.to_uppercase().collect())),
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sets_lowercase_letters_as_small_capitals() {
+        let spans = small_caps_spans("Hello, World");
+        let expected = [(false, "H"), (true, "ELLO"), (false, ", W"), (true, "ORLD")];
+        assert_eq!(spans, expected.map(|(small, text)| (small, text.to_string())));
+        let mut loud = Run::new().add_text("Loud");
+        loud.run_property = loud.run_property.caps();
+        assert_eq!(display_text(&loud), "LOUD");
+        assert_eq!(display_text(&Run::new().add_text("Quiet")), "Quiet");
+    }
+}
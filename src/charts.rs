@@ -0,0 +1,65 @@
+//! Charts. Their DrawingML is not rendered; a chart is shown as the image
+//! cached for it, from the `mc:Fallback` of the drawing that holds it or
+//! else an image relationship of the chart part itself.
+
+use std::collections::HashMap;
+
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+use crate::{ConversionError, read_part, xml_attribute};
+
+/// The `Id`, `Type` and `Target` of each relationship in the part `rels`.
+pub fn relationships(docx: &[u8], rels: &str) -> Result<Vec<(String, String, String)>, ConversionError> {
+    let Some(xml) = read_part(docx, rels)? else { return Ok(Vec::new()) };
+
+    let mut found = Vec::new();
+    let mut reader = Reader::from_reader(&xml[..]);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) if e.local_name() == b"Relationship" => {
+                let attribute = |name: &[u8]| xml_attribute(e, name).unwrap_or_default();
+                found.push((attribute(b"Id"), attribute(b"Type"), attribute(b"Target")));
+            }
+            Ok(Event::Eof) => return Ok(found),
+            Err(e) => return Err(ConversionError::InvalidInput(format!("Malformed {}: {}", rels, e))),
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+/// The archive path `target` names relative to the folder `base`.
+pub fn resolve_target(base: &str, target: &str) -> String {
+    let mut path: Vec<&str> = if target.starts_with('/') { Vec::new() } else { base.split('/').collect() };
+    for segment in target.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                path.pop();
+            }
+            segment => path.push(segment),
+        }
+    }
+    path.join("/")
+}
+
+/// Archive paths of the images the chart parts relate to, by the
+/// relationship id the document gives each chart.
+pub fn read_chart_images(docx: &[u8]) -> Result<HashMap<String, String>, ConversionError> {
+    let mut images = HashMap::new();
+    for (id, kind, target) in relationships(docx, "word/_rels/document.xml.rels")? {
+        if !kind.ends_with("/chart") {
+            continue;
+        }
+        let chart = resolve_target("word", &target);
+        let (folder, name) = chart.rsplit_once('/').unwrap_or(("", &chart));
+        let rels = format!("{}/_rels/{}.rels", folder, name);
+        let image = relationships(docx, &rels)?.into_iter().find(|(_, kind, _)| kind.ends_with("/image"));
+        if let Some((_, _, image)) = image {
+            images.insert(id, resolve_target(folder, &image));
+        }
+    }
+    Ok(images)
+}
@@ -0,0 +1,177 @@
+//! Colour handling for text fills and embedded images.
+
+use ::image::DynamicImage;
+use printpdf::{Cmyk, Color, ColorBits, ColorSpace, Greyscale, Image, ImageXObject, Px, Rgb};
+
+/// The colour model written to the PDF.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorMode {
+    Rgb,
+    /// Converts colours to CMYK for print workflows.
+    Cmyk,
+    /// Flattens everything to black and white for fax or laser printing.
+    /// Text is black; fills and image pixels darker than the luminance
+    /// threshold (0-255) are black and the rest white.
+    Monochrome(u8),
+}
+
+impl std::str::FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "rgb" => Ok(ColorMode::Rgb),
+            "cmyk" => Ok(ColorMode::Cmyk),
+            other => Err(format!("Unknown color space: {}", other)),
+        }
+    }
+}
+
+/// Parses a docx `RRGGBB` colour value into components in `0.0..=1.0`.
+/// Returns `None` for `auto` and anything malformed.
+pub fn parse_hex(value: &str) -> Option<(f32, f32, f32)> {
+    if value.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(value.get(i..i + 2)?, 16).ok().map(|c| c as f32 / 255.0);
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Naive device conversion: black is pulled out into K and the remainder
+/// split across C, M and Y.
+fn rgb_to_cmyk(r: f32, g: f32, b: f32) -> (f32, f32, f32, f32) {
+    let k = 1.0 - r.max(g).max(b);
+    if k >= 1.0 {
+        return (0.0, 0.0, 0.0, 1.0);
+    }
+    ((1.0 - r - k) / (1.0 - k), (1.0 - g - k) / (1.0 - k), (1.0 - b - k) / (1.0 - k), k)
+}
+
+/// Luminance of a colour with components in `0.0..=1.0`, on a scale of 0-255.
+fn luminance(r: f32, g: f32, b: f32) -> f32 {
+    (0.299 * r + 0.587 * g + 0.114 * b) * 255.0
+}
+
+/// A fill colour in the requested colour model.
+pub fn fill_color(mode: ColorMode, (r, g, b): (f32, f32, f32)) -> Color {
+    match mode {
+        ColorMode::Rgb => Color::Rgb(Rgb::new(r, g, b, None)),
+        ColorMode::Cmyk => {
+            let (c, m, y, k) = rgb_to_cmyk(r, g, b);
+            Color::Cmyk(Cmyk::new(c, m, y, k, None))
+        }
+        ColorMode::Monochrome(threshold) => {
+            let white = luminance(r, g, b) >= threshold as f32;
+            Color::Greyscale(Greyscale::new(if white { 1.0 } else { 0.0 }, None))
+        }
+    }
+}
+
+/// A 1-bit greyscale image of `img`, each pixel black if darker than
+/// `threshold` and white otherwise. Transparent pixels are laid on white.
+fn monochrome_image(img: &DynamicImage, threshold: u8) -> ImageXObject {
+    let pixels = img.to_rgba8();
+    let (width, height) = pixels.dimensions();
+    //Rows start on a byte boundary, the leftmost pixel in the high bit
+    let row_bytes = (width as usize).div_ceil(8);
+    let mut data = vec![0u8; row_bytes * height as usize];
+    for (x, y, pixel) in pixels.enumerate_pixels() {
+        let [r, g, b, a] = pixel.0.map(|c| c as f32 / 255.0);
+        let over_white = |c: f32| c * a + (1.0 - a);
+        if luminance(over_white(r), over_white(g), over_white(b)) >= threshold as f32 {
+            data[y as usize * row_bytes + x as usize / 8] |= 0x80 >> (x % 8);
+        }
+    }
+    ImageXObject {
+        width: Px(width as usize),
+        height: Px(height as usize),
+        color_space: ColorSpace::Greyscale,
+        bits_per_component: ColorBits::Bit1,
+        interpolate: false,
+        image_data: data,
+        image_filter: None,
+        smask: None,
+        clipping_bbox: None,
+    }
+}
+
+/// Wraps a decoded image for embedding, converting RGB pixel data to CMYK
+/// or any image to black and white when requested. Greyscale images are
+/// otherwise left as they are.
+pub fn pdf_image(img: &DynamicImage, mode: ColorMode) -> Image {
+    if let ColorMode::Monochrome(threshold) = mode {
+        return Image::from(monochrome_image(img, threshold));
+    }
+    let mut object = ImageXObject::from_dynamic_image(img);
+    if mode == ColorMode::Cmyk
+        && matches!(object.color_space, ColorSpace::Rgb)
+        && matches!(object.bits_per_component, ColorBits::Bit8)
+    {
+        object.image_data = object
+            .image_data
+            .chunks_exact(3)
+            .flat_map(|pixel| {
+                let (c, m, y, k) = rgb_to_cmyk(
+                    pixel[0] as f32 / 255.0,
+                    pixel[1] as f32 / 255.0,
+                    pixel[2] as f32 / 255.0,
+                );
+                [c, m, y, k].map(|v| (v * 255.0).round() as u8)
+            })
+            .collect();
+        object.color_space = ColorSpace::Cmyk;
+    }
+    Image::from(object)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(parse_hex("FF0080"), Some((1.0, 0.0, 128.0 / 255.0)));
+        assert_eq!(parse_hex("auto"), None);
+        assert_eq!(parse_hex("FF00GG"), None);
+    }
+
+    #[test]
+    fn converts_fills_to_cmyk() {
+        let cmyk = |rgb| match fill_color(ColorMode::Cmyk, rgb) {
+            Color::Cmyk(color) => (color.c, color.m, color.y, color.k),
+            other => panic!("not CMYK: {:?}", other),
+        };
+        assert_eq!(cmyk((1.0, 0.0, 0.0)), (0.0, 1.0, 1.0, 0.0));
+        assert_eq!(cmyk((0.0, 0.0, 0.0)), (0.0, 0.0, 0.0, 1.0));
+        assert_eq!(cmyk((0.5, 0.5, 0.5)), (0.0, 0.0, 0.0, 0.5));
+    }
+
+    #[test]
+    fn converts_rgb_image_pixels_to_cmyk() {
+        let img = DynamicImage::ImageRgb8(::image::RgbImage::from_pixel(2, 1, ::image::Rgb([0, 0, 255])));
+        let object = pdf_image(&img, ColorMode::Cmyk).image;
+        assert!(matches!(object.color_space, ColorSpace::Cmyk));
+        assert_eq!(object.image_data, [255, 255, 0, 0, 255, 255, 0, 0]);
+    }
+
+    #[test]
+    fn splits_images_and_fills_at_the_monochrome_threshold() {
+        //Greys either side of the threshold, then a transparent pixel laid on white
+        let mut pixels = ::image::RgbaImage::new(7, 1);
+        for (x, grey) in [0, 100, 127, 128, 200, 255].into_iter().enumerate() {
+            pixels.put_pixel(x as u32, 0, ::image::Rgba([grey, grey, grey, 255]));
+        }
+        pixels.put_pixel(6, 0, ::image::Rgba([0, 0, 0, 0]));
+        let object = pdf_image(&DynamicImage::ImageRgba8(pixels), ColorMode::Monochrome(128)).image;
+        assert!(matches!(object.color_space, ColorSpace::Greyscale));
+        assert!(matches!(object.bits_per_component, ColorBits::Bit1));
+        assert_eq!(object.image_data, [0b0001_1110]);
+
+        let grey = |rgb| match fill_color(ColorMode::Monochrome(128), rgb) {
+            Color::Greyscale(color) => color.percent,
+            other => panic!("not greyscale: {:?}", other),
+        };
+        assert_eq!(grey((1.0, 0.0, 0.0)), 0.0);
+        assert_eq!(grey((1.0, 1.0, 0.0)), 1.0);
+    }
+}
@@ -0,0 +1,139 @@
+//! Document comments, rendered as a "Comments" section after the body.
+
+use std::collections::BTreeMap;
+
+use docx_rs::{CommentChild, Docx, DocumentChild, Paragraph, ParagraphChild, RunChild};
+
+use crate::{Layout, paragraph_runs, property_json, run_text};
+
+/// A comment together with the body text it is anchored to.
+pub struct CommentNote {
+    pub author: String,
+    pub text: String,
+    pub anchor: String,
+}
+
+/// Collects every comment in document order of its anchor.
+pub fn collect_comments(docx: &Docx) -> Vec<CommentNote> {
+    let bodies: BTreeMap<usize, (&str, String)> = docx
+        .comments
+        .inner()
+        .iter()
+        .map(|comment| {
+            let text = comment
+                .children
+                .iter()
+                .filter_map(|child| match child {
+                    CommentChild::Paragraph(paragraph) => Some(paragraph_text(paragraph)),
+                    CommentChild::Table(_) => None,
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            (comment.id, (comment.author.as_str(), text))
+        })
+        .collect();
+
+    //Anchor text is everything between a comment's range start and end markers
+    let mut order = Vec::new();
+    let mut anchors: BTreeMap<usize, String> = BTreeMap::new();
+    let mut open: Vec<usize> = Vec::new();
+    for child in &docx.document.children {
+        let DocumentChild::Paragraph(paragraph) = child else { continue };
+        for child in &paragraph.children {
+            match child {
+                ParagraphChild::CommentStart(start) => {
+                    order.push(start.id);
+                    open.push(start.id);
+                }
+                ParagraphChild::CommentEnd(end) => {
+                    if let Some(id) = property_json(end)["id"].as_u64() {
+                        open.retain(|open_id| *open_id as u64 != id);
+                    }
+                }
+                ParagraphChild::Run(run) => {
+                    for run_child in &run.children {
+                        match run_child {
+                            RunChild::Text(text) => {
+                                for id in &open {
+                                    anchors.entry(*id).or_default().push_str(&text.text);
+                                }
+                            }
+                            RunChild::CommentStart(start) => {
+                                order.push(start.id);
+                                open.push(start.id);
+                            }
+                            RunChild::CommentEnd(end) => {
+                                if let Some(id) = property_json(end)["id"].as_u64() {
+                                    open.retain(|open_id| *open_id as u64 != id);
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    order
+        .into_iter()
+        .filter_map(|id| {
+            let (author, text) = bodies.get(&id)?;
+            Some(CommentNote {
+                author: author.to_string(),
+                text: text.clone(),
+                anchor: anchors.remove(&id).unwrap_or_default().trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+fn paragraph_text(paragraph: &Paragraph) -> String {
+    paragraph_runs(paragraph).map(run_text).collect()
+}
+
+/// Appends a numbered list of comments, each with its author and anchor text.
+pub fn render_comments(layout: &mut Layout, notes: &[CommentNote]) {
+    if notes.is_empty() {
+        return;
+    }
+    let bold = layout.fonts.bold.clone();
+    let regular = layout.fonts.regular.clone();
+    let italic = layout.fonts.italic.clone();
+
+    layout.y_position -= layout.line_height;
+    layout.write_lines("Comments", &bold);
+    for (i, note) in notes.iter().enumerate() {
+        layout.tags.get_mut().begin("Note");
+        layout.write_lines(&format!("[{}] {}: {}", i + 1, note.author, note.text), &regular);
+        if !note.anchor.is_empty() {
+            layout.write_lines(&format!("On \"{}\"", note.anchor), &italic);
+        }
+        layout.tags.get_mut().end();
+        layout.y_position -= layout.line_height / 2.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::edited_docx;
+    use docx_rs::{Comment, Run};
+
+    #[test]
+    fn collects_each_comment_with_its_anchor_text() {
+        let comment = Comment::new(1).author("Ann").add_paragraph(Paragraph::new().add_run(Run::new().add_text("Cite this")));
+        let paragraph = Paragraph::new()
+            .add_run(Run::new().add_text("Before "))
+            .add_comment_start(comment)
+            .add_run(Run::new().add_text("the claim"))
+            .add_comment_end(1)
+            .add_run(Run::new().add_text(" after"));
+        //Comment bodies are only gathered into their part when packed
+        let packed = edited_docx(Docx::new().add_paragraph(paragraph), |_, content| content);
+        let notes = collect_comments(&docx_rs::read_docx(&packed).unwrap());
+        assert_eq!(notes.len(), 1);
+        assert_eq!((notes[0].author.as_str(), notes[0].text.as_str(), notes[0].anchor.as_str()), ("Ann", "Cite this", "the claim"));
+    }
+}
@@ -0,0 +1,88 @@
+//! Compatibility settings that change layout. Of the ones Word reads from
+//! `word/settings.xml` and the document defaults, these are honoured:
+//!
+//! - `w:defaultTabStop`, the interval of default tab stops (see `tabs`).
+//! - `w:autoSpaceDE` and `w:autoSpaceDN` of the default paragraph
+//!   properties, which put a space between East Asian characters and Latin
+//!   letters or digits. Word does so unless they are turned off, and so
+//!   does the converter in body paragraphs.
+//!
+//! Others are not: `w:doNotExpandShiftReturn` (line breaks within a run are
+//! not kept), `w:characterSpacingControl` (punctuation is never compressed)
+//! and the rest of `w:compat`, which emulate older versions of Word.
+
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+use crate::text::is_cjk;
+use crate::{ConversionError, read_part, xml_attribute};
+
+/// Where a space is put between East Asian text and the text beside it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoSpace {
+    /// Beside Latin letters (`w:autoSpaceDE`).
+    pub latin: bool,
+    /// Beside digits (`w:autoSpaceDN`).
+    pub digits: bool,
+}
+
+impl Default for AutoSpace {
+    fn default() -> Self {
+        AutoSpace { latin: true, digits: true }
+    }
+}
+
+impl AutoSpace {
+    /// Whether a space goes between `before` and `after`.
+    fn between(self, before: char, after: char) -> bool {
+        let east_asian = |c: char| is_cjk(c) && c.is_alphabetic();
+        let other = |c: char| {
+            (self.latin && c.is_alphabetic() && !is_cjk(c)) || (self.digits && c.is_ascii_digit())
+        };
+        (east_asian(before) && other(after)) || (other(before) && east_asian(after))
+    }
+
+    /// `text` with the spaces it needs, following the character `before`.
+    pub fn apply(self, before: Option<char>, text: &str) -> String {
+        let mut spaced = String::with_capacity(text.len());
+        let mut previous = before;
+        for c in text.chars() {
+            if previous.is_some_and(|previous| self.between(previous, c)) {
+                spaced.push(' ');
+            }
+            spaced.push(c);
+            previous = Some(c);
+        }
+        spaced
+    }
+}
+
+/// Reads `w:autoSpaceDE` and `w:autoSpaceDN` from the default paragraph
+/// properties in `word/styles.xml`.
+pub fn read_auto_space(docx: &[u8]) -> Result<AutoSpace, ConversionError> {
+    let mut auto_space = AutoSpace::default();
+    let Some(xml) = read_part(docx, "word/styles.xml")? else { return Ok(auto_space) };
+
+    let mut reader = Reader::from_reader(&xml[..]);
+    let mut buf = Vec::new();
+    let mut in_defaults = false;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) if e.local_name() == b"pPrDefault" => in_defaults = true,
+            Ok(Event::End(ref e)) if e.local_name() == b"pPrDefault" => return Ok(auto_space),
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) if in_defaults => {
+                //An on/off property without a value is on
+                let on = || !matches!(xml_attribute(e, b"val").as_deref(), Some("0" | "false" | "off"));
+                match e.local_name() {
+                    b"autoSpaceDE" => auto_space.latin = on(),
+                    b"autoSpaceDN" => auto_space.digits = on(),
+                    _ => {}
+                }
+            }
+            Ok(Event::Eof) => return Ok(auto_space),
+            Err(e) => return Err(ConversionError::InvalidInput(format!("Malformed word/styles.xml: {}", e))),
+            _ => {}
+        }
+        buf.clear();
+    }
+}
@@ -0,0 +1,120 @@
+//! Compression of the PDF's streams. printpdf only compresses in release
+//! builds and the passes that rewrite the saved file leave streams as they
+//! find them, so `--compression` recompresses the finished file.
+
+use std::io::Write;
+
+use flate2::write::ZlibEncoder;
+use printpdf::lopdf::{self, Object};
+
+use crate::ConversionError;
+
+/// How the PDF's streams are compressed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    /// Uncompressed, so content streams can be read in a text editor.
+    None,
+    /// Flate at its fastest level.
+    Fast,
+    /// Flate at its best level.
+    Max,
+}
+
+impl std::str::FromStr for Compression {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "none" => Ok(Compression::None),
+            "fast" => Ok(Compression::Fast),
+            "max" => Ok(Compression::Max),
+            other => Err(format!("Unknown compression: {} (expected none, fast or max)", other)),
+        }
+    }
+}
+
+/// `content` deflated at `level`, in the zlib format `/FlateDecode` reads.
+fn deflate(content: &[u8], level: flate2::Compression) -> std::io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), level);
+    encoder.write_all(content)?;
+    encoder.finish()
+}
+
+/// Rewrites every stream of `pdf` that is plain or Flate-compressed with
+/// `compression`. Streams under other filters, such as JPEG images, are
+/// left alone, as are Flate-compressed images and the XMP packet, which
+/// indexers read from the file's bytes.
+pub fn set_compression(pdf: &[u8], compression: Compression) -> Result<Vec<u8>, ConversionError> {
+    let pdf_error = |e: lopdf::Error| ConversionError::Pdf(e.to_string());
+    let mut document = lopdf::Document::load_mem(pdf).map_err(pdf_error)?;
+    let level = match compression {
+        Compression::None => None,
+        Compression::Fast => Some(flate2::Compression::fast()),
+        Compression::Max => Some(flate2::Compression::best()),
+    };
+    for object in document.objects.values_mut() {
+        let Object::Stream(stream) = object else { continue };
+        if stream.dict.type_is(b"Metadata") {
+            continue;
+        }
+        let plain = match stream.filters() {
+            //lopdf declines to inflate images, which then keep their compression
+            Ok(filters) if filters.iter().all(|filter| filter == "FlateDecode") => match stream.decompressed_content() {
+                Ok(plain) => plain,
+                Err(_) => continue,
+            },
+            Ok(_) => continue,
+            //No filter at all
+            Err(_) => stream.content.clone(),
+        };
+        match level {
+            None => stream.set_plain_content(plain),
+            Some(level) => {
+                stream.dict.remove(b"DecodeParms");
+                stream.dict.set("Filter", Object::Name(b"FlateDecode".to_vec()));
+                stream.set_content(deflate(&plain, level)?);
+            }
+        }
+    }
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use crate::tests::sample_pdf;
+    use crate::xmp::write_xmp;
+
+    /// The content stream of the first page of `pdf`.
+    fn page_content(pdf: &[u8]) -> lopdf::Stream {
+        let document = lopdf::Document::load_mem(pdf).unwrap();
+        let page_id = *document.get_pages().values().next().unwrap();
+        let content_id = document.get_dictionary(page_id).and_then(|page| page.get(b"Contents")).and_then(Object::as_reference).unwrap();
+        document.get_object(content_id).and_then(Object::as_stream).unwrap().clone()
+    }
+
+    #[test]
+    fn compresses_streams_or_leaves_them_plain() {
+        let pdf = sample_pdf("Compressed", &Config::new("compress.docx", "compress.pdf"));
+        let plain = page_content(&set_compression(&pdf, Compression::None).unwrap());
+        assert!(plain.dict.get(b"Filter").is_err());
+        assert!(plain.content.windows(2).any(|window| window == b"Tj"));
+
+        for compression in [Compression::Fast, Compression::Max] {
+            let compressed = page_content(&set_compression(&pdf, compression).unwrap());
+            assert_eq!(compressed.dict.get(b"Filter").and_then(Object::as_name).unwrap(), b"FlateDecode");
+            assert_eq!(compressed.decompressed_content().unwrap(), plain.content);
+        }
+    }
+
+    #[test]
+    fn leaves_the_metadata_packet_plain() {
+        let pdf = sample_pdf("Indexed", &Config::new("meta.docx", "meta.pdf"));
+        let pdf = write_xmp(&pdf, &[("project".to_string(), "Apollo".to_string())]).unwrap();
+        let pdf = set_compression(&pdf, Compression::Max).unwrap();
+        assert!(pdf.windows(b"<pdfx:project>".len()).any(|window| window == b"<pdfx:project>"));
+    }
+}
@@ -0,0 +1,100 @@
+//! The `--debug-boxes` overlay: thin outlines around the margin area and
+//! each placed paragraph, line and image, to make overflow and overlap
+//! visible.
+
+use crate::color::fill_color;
+use crate::{DESCENT, Layout, draw_rect};
+
+/// Outline width in points.
+const THICKNESS: f32 = 0.25;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugBox {
+    Margin,
+    Paragraph,
+    Line,
+    Image,
+}
+
+impl DebugBox {
+    fn color(self) -> (f32, f32, f32) {
+        match self {
+            DebugBox::Margin => (1.0, 0.6, 0.6),
+            DebugBox::Paragraph => (0.5, 0.85, 0.5),
+            DebugBox::Line => (0.6, 0.75, 1.0),
+            DebugBox::Image => (1.0, 0.75, 0.4),
+        }
+    }
+}
+
+/// Outlines a placed rectangle, if the overlay is on.
+pub fn draw_debug_box(layout: &Layout, kind: DebugBox, x: f32, y: f32, width: f32, height: f32) {
+    if !layout.config.debug_boxes {
+        return;
+    }
+    let color_space = layout.config.color_space;
+    layout.layer.set_outline_color(fill_color(color_space, kind.color()));
+    layout.layer.set_outline_thickness(THICKNESS);
+    draw_rect(&layout.layer, x, y, width, height);
+    layout.layer.set_outline_thickness(1.0);
+    layout.layer.set_outline_color(fill_color(color_space, (0.0, 0.0, 0.0)));
+}
+
+/// Outlines the text area of the current page.
+pub fn draw_margin_box(layout: &Layout) {
+    let config = layout.config;
+    let (width, height) = (config.page_width - 2.0 * config.margin, config.page_height - 2.0 * config.margin);
+    draw_debug_box(layout, DebugBox::Margin, config.margin, config.margin, width, height);
+}
+
+/// Outlines a line of text of `width` mm starting at `x` on the baseline `y`.
+pub fn draw_line_box(layout: &Layout, x: f32, y: f32, width: f32) {
+    let height = layout.line_height;
+    draw_debug_box(layout, DebugBox::Line, x, y - DESCENT * height, width, height);
+}
+
+/// Starts the paragraph box at the cursor.
+pub fn begin_paragraph(layout: &mut Layout) {
+    if layout.config.debug_boxes {
+        layout.paragraph_top = Some(layout.y_position);
+    }
+}
+
+/// Closes the open paragraph box above the cursor. A paragraph broken
+/// across pages is outlined piecewise, one box per page.
+pub fn end_paragraph(layout: &mut Layout) {
+    if let Some(top) = layout.paragraph_top.take() {
+        let config = layout.config;
+        let bottom = layout.y_position;
+        //Nothing was placed if the first line went straight to a new page
+        if top <= bottom {
+            return;
+        }
+        draw_debug_box(layout, DebugBox::Paragraph, config.margin, bottom, config.page_width - 2.0 * config.margin, top - bottom);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use printpdf::lopdf;
+
+    use crate::Config;
+    use crate::compression::Compression;
+    use crate::tests::sample_pdf;
+
+    /// Rectangles drawn on the first page of a one-line document.
+    fn rectangles(debug_boxes: bool) -> usize {
+        let mut config = Config::new("boxes.docx", "boxes.pdf");
+        config.compression = Some(Compression::None);
+        config.debug_boxes = debug_boxes;
+        let document = lopdf::Document::load_mem(&sample_pdf("Outlined", &config)).unwrap();
+        let page_id = *document.get_pages().values().next().unwrap();
+        let content = document.get_page_content(page_id).unwrap();
+        lopdf::content::Content::decode(&content).unwrap().operations.iter().filter(|operation| operation.operator == "re").count()
+    }
+
+    #[test]
+    fn outlines_the_margins_paragraph_and_line() {
+        assert_eq!(rectangles(true), rectangles(false) + 3);
+    }
+}
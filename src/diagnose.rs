@@ -0,0 +1,56 @@
+//! Diagnostics for documents that fail to parse. docx_rs only reports what
+//! kind of failure it hit, so `--verbose-errors` looks through
+//! `word/document.xml` itself: an XML syntax error is logged with its byte
+//! offset and the text around it, and the whole part is logged at trace
+//! level.
+
+use log::{error, trace};
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+use crate::read_part;
+
+/// Bytes of context logged on either side of a syntax error.
+const EXCERPT_RADIUS: usize = 200;
+
+/// Byte offset and description of the first syntax error in `xml`.
+fn first_xml_error(xml: &[u8]) -> Option<(usize, quick_xml::Error)> {
+    let mut reader = Reader::from_reader(xml);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Eof) => return None,
+            Err(e) => return Some((reader.buffer_position(), e)),
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+/// The text of `xml` around `offset`.
+fn excerpt(xml: &[u8], offset: usize) -> String {
+    let start = offset.saturating_sub(EXCERPT_RADIUS);
+    let end = (offset + EXCERPT_RADIUS).min(xml.len());
+    String::from_utf8_lossy(&xml[start..end]).into_owned()
+}
+
+/// Logs what can be found out about why the document in `docx` was rejected.
+pub fn log_document_xml(docx: &[u8]) {
+    let xml = match read_part(docx, "word/document.xml") {
+        Ok(Some(xml)) => xml,
+        Ok(None) => return error!("The archive has no word/document.xml"),
+        Err(e) => return error!("Could not read word/document.xml: {}", e),
+    };
+    match first_xml_error(&xml) {
+        Some((offset, e)) => {
+            error!("word/document.xml is malformed at byte {}: {}", offset, e);
+            error!("Around byte {}: {}", offset, excerpt(&xml, offset));
+        }
+        None => error!(
+            "word/document.xml is well-formed ({} bytes), so an element's content was rejected; \
+             docx_rs does not say where. Set RUST_LOG=trace to log the whole part",
+            xml.len()
+        ),
+    }
+    trace!("word/document.xml:\n{}", String::from_utf8_lossy(&xml));
+}
@@ -0,0 +1,208 @@
+//! Document variables (`w:docVars` in `word/settings.xml`), which templates
+//! fill in through `DOCVARIABLE` fields. Other fields are not evaluated, so
+//! a `DOCVARIABLE` field shows the variable's current value in place of its
+//! cached result, and keeps the cached result if the variable is not set.
+
+use std::collections::BTreeMap;
+
+use docx_rs::{
+    DocumentChild, FieldCharType, Paragraph, ParagraphChild, RunChild, Table, TableCellContent, TableChild,
+    TableRowChild, Text,
+};
+
+use quick_xml::Reader;
+use quick_xml::escape::unescape;
+use quick_xml::events::Event;
+
+use crate::{ConversionError, read_part};
+
+/// The document variables in `word/settings.xml`, by name. docx_rs reads
+/// them but keeps them private, and escaped.
+pub fn read_document_variables(docx: &[u8]) -> Result<BTreeMap<String, String>, ConversionError> {
+    let mut variables = BTreeMap::new();
+    let Some(xml) = read_part(docx, "word/settings.xml")? else { return Ok(variables) };
+
+    let mut reader = Reader::from_reader(&xml[..]);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) if e.local_name() == b"docVar" => {
+                let attribute = |name: &[u8]| {
+                    let attribute = e.attributes().flatten().find(|a| a.key.rsplit(|b| *b == b':').next() == Some(name))?;
+                    Some(String::from_utf8_lossy(&unescape(&attribute.value).ok()?).into_owned())
+                };
+                if let (Some(name), Some(value)) = (attribute(b"name"), attribute(b"val")) {
+                    variables.insert(name, value);
+                }
+            }
+            Ok(Event::Eof) => return Ok(variables),
+            Err(e) => return Err(ConversionError::InvalidInput(format!("Malformed word/settings.xml: {}", e))),
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+/// The name of the variable a field instruction such as
+/// `DOCVARIABLE Client \* MERGEFORMAT` refers to.
+fn variable_name(instruction: &str) -> Option<&str> {
+    let mut words = instruction.split_whitespace();
+    if !words.next()?.eq_ignore_ascii_case("DOCVARIABLE") {
+        return None;
+    }
+    Some(words.next()?.trim_matches('"'))
+}
+
+/// A `w:t` holding `value` as it is. `Text::new` escapes it for writing XML.
+fn plain_text(value: String) -> Text {
+    let mut text = Text::new("");
+    text.text = value;
+    text
+}
+
+/// A complex field (`w:fldChar` begin, instruction, separate, result, end)
+/// being read.
+#[derive(Default)]
+struct Field {
+    instruction: String,
+    /// The value its result is replaced with, once the instruction is known.
+    value: Option<String>,
+    in_result: bool,
+    /// The value has been written into the result.
+    written: bool,
+}
+
+/// Copy of the body with the results of `DOCVARIABLE` fields replaced by
+/// the values in `variables`.
+pub fn substitute_variables(children: &[DocumentChild], variables: &BTreeMap<String, String>) -> Vec<DocumentChild> {
+    let mut children = children.to_vec();
+    if variables.is_empty() {
+        return children;
+    }
+    for child in &mut children {
+        match child {
+            DocumentChild::Paragraph(paragraph) => substitute_in_paragraph(paragraph, variables),
+            DocumentChild::Table(table) => substitute_in_table(table, variables),
+            _ => {}
+        }
+    }
+    children
+}
+
+fn substitute_in_table(table: &mut Table, variables: &BTreeMap<String, String>) {
+    for TableChild::TableRow(row) in &mut table.rows {
+        for TableRowChild::TableCell(cell) in &mut row.cells {
+            for content in &mut cell.children {
+                match content {
+                    TableCellContent::Paragraph(paragraph) => substitute_in_paragraph(paragraph, variables),
+                    TableCellContent::Table(table) => substitute_in_table(table, variables),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+fn substitute_in_paragraph(paragraph: &mut Paragraph, variables: &BTreeMap<String, String>) {
+    //Fields can span runs, but not paragraphs
+    let mut fields = Vec::new();
+    substitute_in_children(&mut paragraph.children, variables, &mut fields);
+}
+
+fn substitute_in_children(children: &mut [ParagraphChild], variables: &BTreeMap<String, String>, fields: &mut Vec<Field>) {
+    for child in children {
+        let run = match child {
+            ParagraphChild::Run(run) => run,
+            ParagraphChild::Hyperlink(link) => {
+                substitute_in_children(&mut link.children, variables, fields);
+                continue;
+            }
+            _ => continue,
+        };
+        let mut run_children = Vec::with_capacity(run.children.len());
+        for run_child in std::mem::take(&mut run.children) {
+            match run_child {
+                RunChild::FieldChar(ref field_char) => match field_char.field_char_type {
+                    FieldCharType::Begin => fields.push(Field::default()),
+                    FieldCharType::Separate => {
+                        if let Some(field) = fields.last_mut() {
+                            field.in_result = true;
+                            field.value = variable_name(&field.instruction).and_then(|name| variables.get(name)).cloned();
+                        }
+                    }
+                    FieldCharType::End => {
+                        //A variable whose field has no cached result is still shown
+                        if let Some(Field { value: Some(value), written: false, .. }) = fields.pop() {
+                            run_children.push(RunChild::Text(plain_text(value)));
+                        }
+                    }
+                    _ => {}
+                },
+                RunChild::InstrTextString(ref instruction) => {
+                    if let Some(field) = fields.last_mut().filter(|field| !field.in_result) {
+                        field.instruction.push_str(instruction);
+                    }
+                }
+                RunChild::Text(_) => {
+                    let substituted = fields.last_mut().filter(|field| field.in_result && field.value.is_some());
+                    if let Some(field) = substituted {
+                        //The value takes the place of the first piece of cached text
+                        if !field.written {
+                            field.written = true;
+                            run_children.push(RunChild::Text(plain_text(field.value.clone().unwrap_or_default())));
+                        }
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            run_children.push(run_child);
+        }
+        run.children = run_children;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use docx_rs::Run;
+
+    use crate::run_text;
+    use crate::tests::archive;
+
+    /// A paragraph holding a field with `instruction` whose cached result is `cached`.
+    fn field(instruction: &str, cached: &str) -> DocumentChild {
+        let mut run = Run::new().add_field_char(FieldCharType::Begin, false);
+        run.children.push(RunChild::InstrTextString(instruction.to_string()));
+        let run = run.add_field_char(FieldCharType::Separate, false).add_text(cached).add_field_char(FieldCharType::End, false);
+        DocumentChild::Paragraph(Box::new(Paragraph::new().add_run(run)))
+    }
+
+    fn text(child: &DocumentChild) -> String {
+        let DocumentChild::Paragraph(paragraph) = child else { panic!("not a paragraph") };
+        paragraph
+            .children
+            .iter()
+            .map(|child| match child {
+                ParagraphChild::Run(run) => run_text(run),
+                _ => String::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn replaces_docvariable_results() {
+        let settings = "<w:settings xmlns:w=\"w\"><w:docVars>\
+            <w:docVar w:name=\"Client\" w:val=\"Smith &amp; Co\"/></w:docVars></w:settings>";
+        let variables = read_document_variables(&archive(&[("word/settings.xml", settings)])).unwrap();
+        assert_eq!(variables.get("Client").map(String::as_str), Some("Smith & Co"));
+
+        let children = [
+            field(" DOCVARIABLE Client \\* MERGEFORMAT ", "Old client"),
+            field(" DOCVARIABLE Missing ", "Kept"),
+            field(" PAGE ", "3"),
+        ];
+        let substituted = substitute_variables(&children, &variables);
+        assert_eq!(substituted.iter().map(text).collect::<Vec<_>>(), ["Smith & Co", "Kept", "3"]);
+    }
+}
@@ -0,0 +1,81 @@
+//! Drop caps. Word stores the enlarged initial as its own framed paragraph;
+//! the paragraph after it is wrapped around the letter.
+
+use docx_rs::Paragraph;
+
+use crate::{Layout, PT_TO_MM, TWIP_TO_MM, paragraph_runs, run_text};
+
+/// Cap height of Helvetica as a fraction of the font size.
+const CAP_HEIGHT: f32 = 0.718;
+/// Gap between the letter and the wrapped text when the frame sets none, in mm.
+const DEFAULT_GAP: f32 = 1.0;
+
+/// The space a drawn drop cap takes from the following paragraph.
+pub struct DropCap {
+    /// Width taken from the start of each wrapped line, in mm.
+    pub indent: f32,
+    /// Lines of the following paragraph that still sit beside the letter.
+    pub lines: usize,
+}
+
+/// Draws the initial of a drop-cap paragraph spanning `lines` body lines,
+/// and leaves the cursor where the following paragraph starts.
+pub fn render_drop_cap(layout: &mut Layout, paragraph: &Paragraph, lines: usize) {
+    let Some(run) = paragraph_runs(paragraph).next() else { return };
+    let letter: String = paragraph_runs(paragraph).map(run_text).collect::<String>().trim().to_string();
+    if letter.is_empty() {
+        return;
+    }
+    let lines = lines.max(1);
+
+    //The letter's cap height runs from the first line's cap height down to the last line's baseline
+    let drop_height = (lines - 1) as f32 * layout.line_height + CAP_HEIGHT * layout.font_size * PT_TO_MM;
+    let size = drop_height / (CAP_HEIGHT * PT_TO_MM);
+    layout.ensure_space(lines as f32 * layout.line_height);
+    let baseline = layout.baseline() - (lines - 1) as f32 * layout.line_height;
+
+    let font = layout.fonts.for_run(run).clone();
+    layout.set_run_style(run);
+    let width = layout.draw_text(&letter, (layout.config.margin, baseline), size, &font, 0.0);
+
+    let gap = paragraph
+        .property
+        .frame_property
+        .as_ref()
+        .and_then(|frame| frame.h_space)
+        .map_or(DEFAULT_GAP, |space| space as f32 * TWIP_TO_MM);
+    layout.drop_cap = Some(DropCap { indent: width + gap, lines });
+}
+
+#[cfg(test)]
+mod tests {
+    use docx_rs::{Docx, Paragraph, Run};
+
+    use crate::Config;
+    use crate::tests::{edited_docx, laid_out_archive};
+
+    #[test]
+    fn enlarges_the_initial_and_indents_the_lines_beside_it() {
+        let docx = Docx::new()
+            .add_paragraph(Paragraph::new().add_run(Run::new().add_text("O")))
+            .add_paragraph(Paragraph::new().add_run(Run::new().add_text("nce upon a time there was a letter. ".repeat(20))));
+        let packed = edited_docx(docx, |name, content| match name {
+            "word/document.xml" => content.replacen("<w:pPr>", r#"<w:pPr><w:framePr w:dropCap="drop" w:lines="3" />"#, 1),
+            _ => content,
+        });
+        let config = Config::new("dropcap.docx", "dropcap.pdf");
+        let runs = laid_out_archive(&packed, &config);
+        let (letter, body) = runs.split_first().unwrap();
+        assert_eq!(letter.0, "O");
+        assert_eq!(letter.1, config.margin);
+
+        let mut baselines: Vec<(u32, f32)> = body.iter().map(|(_, x, y, _)| (y.to_bits(), *x)).collect();
+        baselines.dedup_by_key(|(y, _)| *y);
+        assert!(baselines.len() > 4);
+        //The three lines beside the letter start after it, the rest at the margin
+        assert!(baselines[..3].iter().all(|(_, x)| *x > config.margin + letter.3));
+        assert!(baselines[3..].iter().all(|(_, x)| *x == config.margin));
+        //The letter sits on the third line's baseline
+        assert!((letter.2 - f32::from_bits(baselines[2].0)).abs() < 0.01);
+    }
+}
@@ -0,0 +1,166 @@
+//! Fonts embedded in the docx, which `word/fontTable.xml` lists with
+//! `w:embedRegular`, `w:embedBold` and the like. Word obfuscates them: the
+//! first 32 bytes of each are XORed with the GUID in its `w:fontKey`
+//! (ECMA-376 Part 1, 17.8.1). Runs set in an embedded family are drawn in
+//! it, and measured with its widths, instead of the built-in Helvetica.
+
+use std::collections::HashMap;
+
+use log::warn;
+use printpdf::{IndirectFontRef, PdfDocumentReference};
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use ttf_parser::{Face, name_id};
+
+use crate::charts::{relationships, resolve_target};
+use crate::text::is_cjk;
+use crate::{ConversionError, PT_TO_MM, read_part, xml_attribute};
+
+/// The bytes of an embedded font, de-obfuscated.
+pub struct EmbeddedFont {
+    pub family: String,
+    pub bold: bool,
+    pub italic: bool,
+    pub data: Vec<u8>,
+}
+
+impl EmbeddedFont {
+    /// Whether the font has a glyph for every character of `text`.
+    pub fn has_glyphs(&self, text: &str) -> bool {
+        Face::parse(&self.data, 0).is_ok_and(|face| text.chars().all(|c| face.glyph_index(c).is_some()))
+    }
+}
+
+/// Undoes Word's obfuscation of the font `data` with the GUID `key`, such
+/// as `{6C3E2A4B-...}`. The key's bytes are used last to first.
+fn deobfuscate(data: &mut [u8], key: &str) -> bool {
+    let digits: Vec<u8> = key.bytes().filter(u8::is_ascii_hexdigit).collect();
+    if digits.len() != 32 || data.len() < 32 {
+        return false;
+    }
+    let key: Vec<u8> = digits
+        .chunks(2)
+        .rev()
+        .map(|pair| std::str::from_utf8(pair).ok().and_then(|pair| u8::from_str_radix(pair, 16).ok()).unwrap_or(0))
+        .collect();
+    for (i, byte) in data[..32].iter_mut().enumerate() {
+        *byte ^= key[i % 16];
+    }
+    true
+}
+
+/// Whether the embedded face an element such as `w:embedBold` names is
+/// bold and italic.
+fn embed_style(element: &[u8]) -> Option<(bool, bool)> {
+    match element {
+        b"embedRegular" => Some((false, false)),
+        b"embedBold" => Some((true, false)),
+        b"embedItalic" => Some((false, true)),
+        b"embedBoldItalic" => Some((true, true)),
+        _ => None,
+    }
+}
+
+/// The fonts embedded in `docx`, in the order the font table lists them.
+pub fn read_embedded_fonts(docx: &[u8]) -> Result<Vec<EmbeddedFont>, ConversionError> {
+    let Some(xml) = read_part(docx, "word/fontTable.xml")? else { return Ok(Vec::new()) };
+    let targets: HashMap<String, String> = relationships(docx, "word/_rels/fontTable.xml.rels")?
+        .into_iter()
+        .map(|(id, _, target)| (id, resolve_target("word", &target)))
+        .collect();
+
+    let mut fonts = Vec::new();
+    let mut reader = Reader::from_reader(&xml[..]);
+    let mut buf = Vec::new();
+    let mut family = None;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) if e.local_name() == b"font" => family = xml_attribute(e, b"name"),
+            Ok(Event::End(ref e)) if e.local_name() == b"font" => family = None,
+            Ok(Event::Empty(ref e)) | Ok(Event::Start(ref e)) => {
+                if let (Some((bold, italic)), Some(family)) = (embed_style(e.local_name()), &family)
+                    && let Some(path) = xml_attribute(e, b"id").and_then(|id| targets.get(&id))
+                    && let Some(mut data) = read_part(docx, path)?
+                {
+                    //Fonts stored without a key are plain TrueType
+                    match xml_attribute(e, b"fontKey") {
+                        Some(key) if !deobfuscate(&mut data, &key) => {
+                            warn!("Embedded font {} has an unusable key and is not used", path);
+                        }
+                        _ => fonts.push(EmbeddedFont { family: family.clone(), bold, italic, data }),
+                    }
+                }
+            }
+            Ok(Event::Eof) => return Ok(fonts),
+            Err(e) => return Err(ConversionError::InvalidInput(format!("Malformed word/fontTable.xml: {}", e))),
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+/// Advance widths of the characters a font has glyphs for, in ems.
+pub struct FontMetrics {
+    advances: HashMap<char, f32>,
+}
+
+impl FontMetrics {
+    fn read(face: &Face) -> FontMetrics {
+        let units = face.units_per_em().max(1) as f32;
+        let mut advances = HashMap::new();
+        let subtables = face.tables().cmap.into_iter().flat_map(|cmap| cmap.subtables).filter(|subtable| subtable.is_unicode());
+        for subtable in subtables {
+            subtable.codepoints(|code| {
+                let Some(c) = char::from_u32(code) else { return };
+                let advance = face.glyph_index(c).and_then(|glyph| face.glyph_hor_advance(glyph));
+                if let Some(advance) = advance {
+                    advances.insert(c, advance as f32 / units);
+                }
+            });
+        }
+        FontMetrics { advances }
+    }
+
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.advances.contains_key(&c)
+    }
+
+    /// Width of `text` at `font_size` points, in mm. Characters without a
+    /// glyph are drawn as the font's missing glyph and take half an em.
+    pub fn width(&self, text: &str, font_size: f32) -> f32 {
+        let ems: f32 = text
+            .chars()
+            .map(|c| self.advances.get(&c).copied().unwrap_or(if is_cjk(c) { 1.0 } else { 0.5 }))
+            .sum();
+        ems * font_size * PT_TO_MM
+    }
+}
+
+/// An embedded face added to the PDF.
+pub struct EmbeddedFace {
+    pub family: String,
+    pub bold: bool,
+    pub italic: bool,
+    /// The PostScript name from the font's `name` table, else the family.
+    pub name: String,
+    pub font: IndirectFontRef,
+    pub metrics: FontMetrics,
+}
+
+impl EmbeddedFace {
+    /// Adds `embedded` to `doc`, or warns and returns `None` if it is not a
+    /// font printpdf can embed.
+    pub fn add(doc: &PdfDocumentReference, embedded: &EmbeddedFont) -> Option<EmbeddedFace> {
+        let unusable = |reason: String| warn!("Embedded font {} cannot be used ({}); Helvetica is used instead", embedded.family, reason);
+        let face = Face::parse(&embedded.data, 0).inspect_err(|e| unusable(e.to_string())).ok()?;
+        let name = face
+            .names()
+            .into_iter()
+            .find(|name| name.name_id == name_id::POST_SCRIPT_NAME)
+            .and_then(|name| name.to_string())
+            .unwrap_or_else(|| embedded.family.clone());
+        let metrics = FontMetrics::read(&face);
+        let font = doc.add_external_font(&embedded.data[..]).inspect_err(|e| unusable(e.to_string())).ok()?;
+        Some(EmbeddedFace { family: embedded.family.clone(), bold: embedded.bold, italic: embedded.italic, name, font, metrics })
+    }
+}
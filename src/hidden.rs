@@ -0,0 +1,141 @@
+//! Hidden text. Runs formatted with `w:vanish` are left out of the body,
+//! including those inside hyperlinks, tables and text boxes, as Word
+//! neither shows nor prints them, unless `--show-hidden` is given.
+
+use docx_rs::{
+    DocumentChild, DrawingData, Paragraph, ParagraphChild, Run, RunChild, Table, TableCellContent, TableChild,
+    TableRowChild, TextBoxContentChild,
+};
+
+/// Copy of the body without its hidden runs.
+pub fn remove_hidden(children: &[DocumentChild]) -> Vec<DocumentChild> {
+    let mut children = children.to_vec();
+    for child in &mut children {
+        match child {
+            DocumentChild::Paragraph(paragraph) => remove_from_paragraph(paragraph),
+            DocumentChild::Table(table) => remove_from_table(table),
+            _ => {}
+        }
+    }
+    children
+}
+
+fn remove_from_table(table: &mut Table) {
+    for TableChild::TableRow(row) in &mut table.rows {
+        for TableRowChild::TableCell(cell) in &mut row.cells {
+            for content in &mut cell.children {
+                match content {
+                    TableCellContent::Paragraph(paragraph) => remove_from_paragraph(paragraph),
+                    TableCellContent::Table(table) => remove_from_table(table),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+fn remove_from_paragraph(paragraph: &mut Paragraph) {
+    paragraph.children = remove_from_children(std::mem::take(&mut paragraph.children));
+}
+
+fn remove_from_children(paragraph_children: Vec<ParagraphChild>) -> Vec<ParagraphChild> {
+    let mut children = Vec::with_capacity(paragraph_children.len());
+    for child in paragraph_children {
+        match child {
+            ParagraphChild::Run(run) if is_hidden(&run) => {}
+            ParagraphChild::Run(mut run) => {
+                remove_from_text_boxes(&mut run);
+                children.push(ParagraphChild::Run(run));
+            }
+            ParagraphChild::Hyperlink(mut link) => {
+                link.children = remove_from_children(std::mem::take(&mut link.children));
+                children.push(ParagraphChild::Hyperlink(link));
+            }
+            child => children.push(child),
+        }
+    }
+    children
+}
+
+/// Whether `run` is formatted as hidden text. docx_rs drops the `w:val`
+/// of `w:vanish`, so one switched off is removed before parsing.
+fn is_hidden(run: &Run) -> bool {
+    run.run_property.vanish.is_some()
+}
+
+fn remove_from_text_boxes(run: &mut Run) {
+    for child in &mut run.children {
+        let RunChild::Drawing(drawing) = child else { continue };
+        let Some(DrawingData::TextBox(text_box)) = &mut drawing.data else { continue };
+        for content in &mut text_box.children {
+            match content {
+                TextBoxContentChild::Paragraph(paragraph) => remove_from_paragraph(paragraph),
+                TextBoxContentChild::Table(table) => remove_from_table(table),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{edited_docx, temp_directory};
+    use crate::{Config, convert_file, run_text};
+
+    #[test]
+    fn leaves_hidden_runs_out() {
+        let paragraph = Paragraph::new()
+            .add_run(Run::new().add_text("Shown "))
+            .add_run(Run::new().add_text("secret ").vanish())
+            .add_run(Run::new().add_text("text"));
+        let children = remove_hidden(&[DocumentChild::Paragraph(Box::new(paragraph))]);
+        let DocumentChild::Paragraph(paragraph) = &children[0] else { panic!("not a paragraph") };
+        let text: String = paragraph
+            .children
+            .iter()
+            .map(|child| match child {
+                ParagraphChild::Run(run) => run_text(run),
+                _ => String::new(),
+            })
+            .collect();
+        assert_eq!(text, "Shown text");
+    }
+
+    /// The text of every page of the PDF at `path`, and its raw bytes.
+    fn extracted(path: &std::path::Path) -> (String, String) {
+        let bytes = std::fs::read(path).unwrap();
+        let document = printpdf::lopdf::Document::load_mem(&bytes).unwrap();
+        let pages: Vec<u32> = document.get_pages().keys().copied().collect();
+        (document.extract_text(&pages).unwrap(), String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    #[test]
+    fn sanitized_text_has_no_hidden_runs_or_author() {
+        let paragraph = Paragraph::new()
+            .add_run(Run::new().add_text("Shown "))
+            .add_run(Run::new().add_text("secret ").vanish())
+            .add_run(Run::new().add_text("unhidden").italic());
+        //docx_rs cannot write a w:vanish switched off, so the italic is swapped for one
+        let docx = edited_docx(docx_rs::Docx::new().add_paragraph(paragraph), |name, content| match name {
+            "word/document.xml" => content.replacen("<w:i />", "<w:vanish w:val=\"0\" />", 1),
+            "docProps/core.xml" => content.replace(">unknown</dc:creator>", ">Jane Roe</dc:creator>"),
+            _ => content,
+        });
+        let directory = temp_directory("sanitize", &[]);
+        std::fs::write(directory.join("in.docx"), docx).unwrap();
+        let output = directory.join("out.pdf");
+        let mut config = Config::new(&directory.join("in.docx").to_string_lossy(), &output.to_string_lossy());
+        config.title_page = true;
+
+        convert_file(&config).unwrap();
+        let (text, _) = extracted(&output);
+        assert!(text.contains("Jane Roe"));
+
+        config.sanitize = true;
+        convert_file(&config).unwrap();
+        let (text, bytes) = extracted(&output);
+        assert!(text.contains("Shown") && text.contains("unhidden"), "{}", text);
+        assert!(!text.contains("secret"));
+        assert!(!text.contains("Jane Roe") && !bytes.contains("Jane Roe"));
+    }
+}
@@ -0,0 +1,191 @@
+//! Search-term highlighting. Every run of text is fed through a matcher as
+//! it is laid out, with the position of each character, so a term is found
+//! wherever it is drawn, including across the break between two lines. The
+//! rectangles behind the matches are written into the saved file ahead of
+//! each page's content, which keeps them under text drawn before the match
+//! was complete.
+
+use std::collections::VecDeque;
+
+use printpdf::lopdf::{self, Dictionary, Document, Object, Stream};
+
+use crate::color::ColorMode;
+use crate::text::text_width;
+use crate::{ConversionError, DESCENT, LaidOutRun, PT_TO_MM};
+
+/// A character as drawn, with the box it takes on its page.
+#[derive(Debug, Clone, Copy)]
+struct Glyph {
+    c: char,
+    page: usize,
+    x: f32,
+    y: f32,
+    width: f32,
+    font_size: f32,
+}
+
+/// Finds the highlighted terms in the text laid out so far.
+#[derive(Debug, Default)]
+pub struct Highlighter {
+    /// The terms, lowercased.
+    terms: Vec<Vec<char>>,
+    /// The last characters seen, as many as the longest term has.
+    recent: VecDeque<Glyph>,
+    /// Rectangles to fill, by page, as `[x, y, width, height]` in mm.
+    rects: Vec<(usize, [f32; 4])>,
+}
+
+/// `c` for case-insensitive comparison, with every kind of space alike.
+fn fold(c: char) -> char {
+    if c.is_whitespace() { ' ' } else { c.to_lowercase().next().unwrap_or(c) }
+}
+
+impl Highlighter {
+    pub fn new(terms: &[String]) -> Self {
+        let terms = terms.iter().filter(|term| !term.is_empty()).map(|term| term.chars().map(fold).collect()).collect();
+        Highlighter { terms, ..Highlighter::default() }
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.terms.is_empty()
+    }
+
+    /// Feeds the characters of `run`. A run that does not continue the
+    /// previous one on its line is separated from it by a space, so a term
+    /// can span the wrap between two lines.
+    pub fn add_run(&mut self, run: &LaidOutRun) {
+        if let Some(&last) = self.recent.back()
+            && last.c != ' '
+        {
+            let continues =
+                last.page == run.page && (last.y - run.y).abs() < 0.01 && (last.x + last.width - run.x).abs() < 0.01;
+            if !continues {
+                self.push(Glyph { c: ' ', x: last.x + last.width, width: 0.0, ..last });
+            }
+        }
+        //Letter spacing, spread evenly over the characters
+        let count = run.text.chars().count().max(1) as f32;
+        let tracking = (run.width - text_width(run.text, run.font_size)) / count;
+        let mut x = run.x;
+        for c in run.text.chars() {
+            let width = text_width(c.encode_utf8(&mut [0; 4]), run.font_size) + tracking;
+            self.push(Glyph { c: fold(c), page: run.page, x, y: run.y, width, font_size: run.font_size });
+            x += width;
+        }
+    }
+
+    fn push(&mut self, glyph: Glyph) {
+        let longest = self.terms.iter().map(Vec::len).max().unwrap_or(0);
+        self.recent.push_back(glyph);
+        while self.recent.len() > longest {
+            self.recent.pop_front();
+        }
+        for term in &self.terms {
+            let Some(start) = self.recent.len().checked_sub(term.len()) else { continue };
+            if self.recent.range(start..).map(|glyph| glyph.c).eq(term.iter().copied()) {
+                let matched: Vec<Glyph> = self.recent.range(start..).copied().collect();
+                add_rects(&mut self.rects, &matched);
+            }
+        }
+    }
+
+    /// The rectangles found, by page.
+    pub fn rects(&self) -> &[(usize, [f32; 4])] {
+        &self.rects
+    }
+}
+
+/// Adds one rectangle per line the glyphs of a match are drawn on.
+fn add_rects(rects: &mut Vec<(usize, [f32; 4])>, glyphs: &[Glyph]) {
+    let drawn = glyphs.iter().filter(|glyph| glyph.width > 0.0);
+    let mut lines: Vec<(usize, f32, f32, f32, f32)> = Vec::new();
+    for glyph in drawn {
+        match lines.last_mut() {
+            Some((page, y, _, right, size)) if *page == glyph.page && (*y - glyph.y).abs() < 0.01 => {
+                *right = glyph.x + glyph.width;
+                *size = size.max(glyph.font_size);
+            }
+            _ => lines.push((glyph.page, glyph.y, glyph.x, glyph.x + glyph.width, glyph.font_size)),
+        }
+    }
+    for (page, y, left, right, size) in lines {
+        let height = size * 1.2 * PT_TO_MM;
+        rects.push((page, [left, y - DESCENT * height, right - left, height]));
+    }
+}
+
+/// Fills `rects` in yellow under the content of their pages.
+pub fn write_highlights(pdf: &[u8], rects: &[(usize, [f32; 4])], mode: ColorMode) -> Result<Vec<u8>, ConversionError> {
+    let pdf_error = |e: lopdf::Error| ConversionError::Pdf(e.to_string());
+    let mut document = Document::load_mem(pdf).map_err(pdf_error)?;
+    let fill = match mode {
+        ColorMode::Rgb => "1 1 0 rg",
+        ColorMode::Cmyk => "0 0 1 0 k",
+        //Yellow has no place in black and white, and black would hide the text
+        ColorMode::Monochrome(_) => return Ok(pdf.to_vec()),
+    };
+    for (number, page_id) in document.get_pages() {
+        let on_page: Vec<&[f32; 4]> = rects.iter().filter(|(page, _)| *page + 1 == number as usize).map(|(_, rect)| rect).collect();
+        if on_page.is_empty() {
+            continue;
+        }
+        let points = |mm: f32| mm / PT_TO_MM;
+        let mut content = format!("q {}\n", fill);
+        for [x, y, width, height] in on_page {
+            content.push_str(&format!("{} {} {} {} re f\n", points(*x), points(*y), points(*width), points(*height)));
+        }
+        content.push_str("Q\n");
+        let content_id = document.add_object(Stream::new(Dictionary::new(), content.into_bytes()));
+        let mut contents = vec![Object::Reference(content_id)];
+        contents.extend(document.get_page_contents(page_id).into_iter().map(Object::Reference));
+        let page = document.get_dictionary_mut(page_id).map_err(pdf_error)?;
+        page.set("Contents", Object::Array(contents));
+    }
+
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use crate::compression::Compression;
+    use crate::tests::sample_pdf;
+
+    /// A run of `text` at `x` on the baseline `y` of the first page.
+    fn run(text: &str, x: f32, y: f32) -> LaidOutRun<'_> {
+        LaidOutRun { text, page: 0, x, y, width: text_width(text, 12.0), font_size: 12.0, font: "Helvetica" }
+    }
+
+    #[test]
+    fn finds_terms_in_any_case_and_across_a_wrap() {
+        let mut highlighter = Highlighter::new(&["red fox".to_string()]);
+        highlighter.add_run(&run("A Red Fox and a", 20.0, 250.0));
+        //The second match is wrapped after "red"
+        highlighter.add_run(&run("red", 60.0, 250.0 - 40.0));
+        highlighter.add_run(&run("fox ran", 20.0, 250.0 - 45.0));
+        let rects = highlighter.rects();
+        assert_eq!(rects.len(), 3);
+        let [x, _, width, _] = rects[0].1;
+        assert!((x - (20.0 + text_width("A ", 12.0))).abs() < 0.01);
+        assert!((width - text_width("Red Fox", 12.0)).abs() < 0.01);
+        //One rectangle for each line the wrapped match is on
+        assert!((rects[1].1[2] - text_width("red", 12.0)).abs() < 0.01);
+        assert!((rects[2].1[0] - 20.0).abs() < 0.01 && (rects[2].1[2] - text_width("fox", 12.0)).abs() < 0.01);
+    }
+
+    #[test]
+    fn fills_the_matches_under_the_page_content() {
+        let mut config = Config::new("highlight.docx", "highlight.pdf");
+        config.compression = Some(Compression::None);
+        config.highlight = vec!["needle".to_string()];
+        let document = Document::load_mem(&sample_pdf("Needle in a haystack, and a needle", &config)).unwrap();
+        let page_id = *document.get_pages().values().next().unwrap();
+        let first = document.get_page_contents(page_id)[0];
+        let content = String::from_utf8_lossy(&document.get_object(first).and_then(Object::as_stream).unwrap().content).into_owned();
+        assert!(content.starts_with("q 1 1 0 rg\n"));
+        assert_eq!(content.matches(" re f").count(), 2);
+    }
+}
@@ -0,0 +1,200 @@
+//! Liang-style hyphenation from TeX patterns.
+//!
+//! No patterns ship with the converter: `--hyphenate` names a directory of
+//! the plain text pattern files of the hyph-utf8 package, such as
+//! `hyph-en-us.pat.txt` with its exceptions in `hyph-en-us.hyp.txt`. The
+//! file for each language is loaded the first time a run in it is wrapped.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use log::warn;
+
+/// Characters that must stay before the first and after the last break.
+const LEFT_MIN: usize = 2;
+const RIGHT_MIN: usize = 3;
+
+pub struct Hyphenator {
+    /// Pattern letters mapped to the break value before each letter (and after the last).
+    patterns: HashMap<String, Vec<u8>>,
+    longest: usize,
+    /// Words hyphenated by hand, lowercased, with the character counts before their breaks.
+    exceptions: HashMap<String, Vec<usize>>,
+}
+
+impl Hyphenator {
+    /// Builds a hyphenator from the whitespace-separated `patterns` and
+    /// `exceptions` of a hyph-utf8 `.pat.txt` and `.hyp.txt` file.
+    pub fn from_patterns(patterns: &str, exceptions: &str) -> Self {
+        let mut table = HashMap::new();
+        let mut longest = 0;
+        for pattern in patterns.split_whitespace() {
+            let mut letters = String::new();
+            let mut values = vec![0];
+            for c in pattern.chars() {
+                match c.to_digit(10) {
+                    Some(value) => *values.last_mut().unwrap() = value as u8,
+                    None => {
+                        letters.push(c);
+                        values.push(0);
+                    }
+                }
+            }
+            longest = longest.max(letters.chars().count());
+            table.insert(letters, values);
+        }
+        let exceptions = exceptions
+            .split_whitespace()
+            .map(|word| {
+                let mut breaks = Vec::new();
+                let mut letters = String::new();
+                for c in word.chars() {
+                    match c {
+                        '-' => breaks.push(letters.chars().count()),
+                        c => letters.extend(c.to_lowercase()),
+                    }
+                }
+                (letters, breaks)
+            })
+            .collect();
+        Hyphenator { patterns: table, longest, exceptions }
+    }
+
+    /// Byte offsets in `word` where a hyphen may be inserted.
+    pub fn break_points(&self, word: &str) -> Vec<usize> {
+        let letters: Vec<char> = word.chars().collect();
+        if letters.len() < LEFT_MIN + RIGHT_MIN || !letters.iter().all(|c| c.is_alphabetic()) {
+            return Vec::new();
+        }
+
+        let dotted: Vec<char> = std::iter::once('.')
+            .chain(letters.iter().flat_map(|c| c.to_lowercase()))
+            .chain(std::iter::once('.'))
+            .collect();
+        if dotted.len() != letters.len() + 2 {
+            //Case mapping changed the length, so offsets would not line up
+            return Vec::new();
+        }
+
+        let breaks: Vec<bool> = match self.exceptions.get(&dotted[1..dotted.len() - 1].iter().collect::<String>()) {
+            Some(exception) => (0..letters.len()).map(|i| exception.contains(&i)).collect(),
+            None => {
+                let mut values = vec![0u8; dotted.len() + 1];
+                for start in 0..dotted.len() {
+                    for end in start + 1..=(start + self.longest).min(dotted.len()) {
+                        let key: String = dotted[start..end].iter().collect();
+                        if let Some(pattern) = self.patterns.get(&key) {
+                            for (offset, value) in pattern.iter().enumerate() {
+                                let slot = &mut values[start + offset];
+                                *slot = (*slot).max(*value);
+                            }
+                        }
+                    }
+                }
+                //values[i + 1] is the break value before letters[i] (index 0 is the leading dot)
+                (0..letters.len()).map(|i| values[i + 1] % 2 == 1).collect()
+            }
+        };
+
+        let mut points = Vec::new();
+        let mut byte_offset = 0;
+        for (i, c) in letters.iter().enumerate() {
+            if i >= LEFT_MIN && letters.len() - i >= RIGHT_MIN && breaks[i] {
+                points.push(byte_offset);
+            }
+            byte_offset += c.len_utf8();
+        }
+        points
+    }
+}
+
+/// The hyphenators of the languages met so far, loaded from a directory
+/// of pattern files.
+pub struct Hyphenation {
+    directory: PathBuf,
+    /// Keyed by the language as the document gives it; `None` where no file was found.
+    loaded: RefCell<HashMap<String, Option<Rc<Hyphenator>>>>,
+}
+
+impl Hyphenation {
+    pub fn new(directory: &Path) -> Self {
+        Hyphenation { directory: directory.to_path_buf(), loaded: RefCell::new(HashMap::new()) }
+    }
+
+    /// Names of the pattern files tried for a BCP 47 `language`: its full
+    /// tag, then its primary language. English without a region is the US
+    /// patterns, the only ones hyph-utf8 has no regionless file for.
+    fn file_stems(language: &str) -> Vec<String> {
+        let tag = language.replace('_', "-").to_ascii_lowercase();
+        let primary = tag.split('-').next().unwrap_or_default().to_string();
+        let mut stems = vec![format!("hyph-{}", tag), format!("hyph-{}", primary)];
+        if primary == "en" {
+            stems.push("hyph-en-us".to_string());
+        }
+        stems.dedup();
+        stems
+    }
+
+    /// The hyphenator for `language`, warning once if there are no patterns for it.
+    pub fn for_language(&self, language: &str) -> Option<Rc<Hyphenator>> {
+        if let Some(loaded) = self.loaded.borrow().get(language) {
+            return loaded.clone();
+        }
+        let found = Self::file_stems(language).into_iter().find_map(|stem| {
+            let patterns = std::fs::read_to_string(self.directory.join(format!("{}.pat.txt", stem))).ok()?;
+            let exceptions = std::fs::read_to_string(self.directory.join(format!("{}.hyp.txt", stem))).unwrap_or_default();
+            Some(Rc::new(Hyphenator::from_patterns(&patterns, &exceptions)))
+        });
+        if found.is_none() {
+            warn!("No hyphenation patterns for language {} in {}; its words are not hyphenated", language, self.directory.display());
+        }
+        self.loaded.borrow_mut().insert(language.to_string(), found.clone());
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::temp_directory;
+
+    /// `word` with a hyphen at each of the break points `hyphenator` finds.
+    fn hyphenated(hyphenator: &Hyphenator, word: &str) -> String {
+        let mut parts = Vec::new();
+        let mut start = 0;
+        for point in hyphenator.break_points(word) {
+            parts.push(&word[start..point]);
+            start = point;
+        }
+        parts.push(&word[start..]);
+        parts.join("-")
+    }
+
+    #[test]
+    fn breaks_words_at_odd_pattern_values() {
+        let hyphenator = Hyphenator::from_patterns("1ter 2t1t\n.ot2ter", "ta-ble");
+        assert_eq!(hyphenated(&hyphenator, "Butter"), "But-ter");
+        //The even value of a longer pattern keeps the letters together
+        assert_eq!(hyphenated(&hyphenator, "otter"), "otter");
+        assert_eq!(hyphenated(&hyphenator, "table"), "ta-ble");
+        //Too short, or not all letters, is never broken
+        assert_eq!(hyphenated(&hyphenator, "bell"), "bell");
+        assert_eq!(hyphenated(&hyphenator, "R2D2unit"), "R2D2unit");
+    }
+
+    #[test]
+    fn loads_the_patterns_of_each_language() {
+        let directory = temp_directory("hyphenation", &[("hyph-en-us.pat.txt", "1ter"), ("hyph-de.pat.txt", "1ben")]);
+        let hyphenation = Hyphenation::new(&directory);
+
+        let english = hyphenation.for_language("en").unwrap();
+        assert_eq!(hyphenated(&english, "letters"), "let-ters");
+        assert_eq!(hyphenated(&hyphenation.for_language("en-US").unwrap(), "letters"), "let-ters");
+        let german = hyphenation.for_language("de-DE").unwrap();
+        assert_eq!(hyphenated(&german, "letters"), "letters");
+        assert_eq!(hyphenated(&german, "Leben"), "Le-ben");
+        assert!(hyphenation.for_language("fr-FR").is_none());
+    }
+}
@@ -0,0 +1,102 @@
+//! ICC colour profiles embedded as the document's output intent, telling
+//! viewers and printers how the device colours in the file are meant.
+
+use std::fs;
+
+use printpdf::lopdf::{self, Dictionary, Object, Stream};
+
+use crate::ConversionError;
+
+/// Length of the fixed ICC profile header.
+const HEADER_LEN: usize = 128;
+
+pub struct IccProfile {
+    data: Vec<u8>,
+    /// Colour components of the profile's data colour space.
+    components: i64,
+    /// The device colour space used where the profile is not understood.
+    alternate: &'static str,
+}
+
+/// Reads the profile at `path`, rejecting files without a valid ICC header
+/// or with a colour space PDF devices cannot use.
+pub fn read_icc_profile(path: &str) -> Result<IccProfile, ConversionError> {
+    let data = fs::read(path)?;
+    let invalid = |reason: &str| ConversionError::InvalidInput(format!("{} is not a usable ICC profile: {}", path, reason));
+    if data.len() < HEADER_LEN || &data[36..40] != b"acsp" {
+        return Err(invalid("missing profile header"));
+    }
+    let declared = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+    if declared > data.len() {
+        return Err(invalid("file is truncated"));
+    }
+    let (components, alternate) = match &data[16..20] {
+        b"RGB " => (3, "DeviceRGB"),
+        b"CMYK" => (4, "DeviceCMYK"),
+        b"GRAY" => (1, "DeviceGray"),
+        _ => return Err(invalid("unsupported colour space")),
+    };
+    Ok(IccProfile { data, components, alternate })
+}
+
+/// Embeds `profile` and lists it in the catalog's `/OutputIntents`.
+pub fn add_output_intent(pdf: &[u8], profile: &IccProfile) -> Result<Vec<u8>, ConversionError> {
+    let pdf_error = |e: lopdf::Error| ConversionError::Pdf(e.to_string());
+    let mut document = lopdf::Document::load_mem(pdf).map_err(pdf_error)?;
+
+    let mut stream_dict = Dictionary::new();
+    stream_dict.set("N", Object::Integer(profile.components));
+    stream_dict.set("Alternate", Object::Name(profile.alternate.as_bytes().to_vec()));
+    let mut stream = Stream::new(stream_dict, profile.data.clone());
+    stream.compress().map_err(pdf_error)?;
+    let profile_id = document.add_object(stream);
+
+    let mut intent = Dictionary::new();
+    intent.set("Type", Object::Name(b"OutputIntent".to_vec()));
+    intent.set("S", Object::Name(b"GTS_PDFA1".to_vec()));
+    intent.set("OutputConditionIdentifier", Object::string_literal("Custom"));
+    intent.set("DestOutputProfile", Object::Reference(profile_id));
+    let intent_id = document.add_object(intent);
+
+    let catalog_id = document.trailer.get(b"Root").and_then(Object::as_reference).map_err(pdf_error)?;
+    let catalog = document.get_object_mut(catalog_id).and_then(Object::as_dict_mut).map_err(pdf_error)?;
+    catalog.set("OutputIntents", Object::Array(vec![Object::Reference(intent_id)]));
+
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use crate::tests::{sample_pdf, temp_directory};
+
+    /// A minimal RGB profile: its header and nothing else.
+    fn rgb_profile() -> Vec<u8> {
+        let mut data = vec![0; HEADER_LEN];
+        data[..4].copy_from_slice(&(HEADER_LEN as u32).to_be_bytes());
+        data[16..20].copy_from_slice(b"RGB ");
+        data[36..40].copy_from_slice(b"acsp");
+        data
+    }
+
+    #[test]
+    fn references_the_profile_from_the_output_intent() {
+        let directory = temp_directory("icc-profile", &[("not.icc", "plain text")]);
+        let path = directory.join("rgb.icc");
+        fs::write(&path, rgb_profile()).unwrap();
+        assert!(read_icc_profile(&directory.join("not.icc").to_string_lossy()).is_err());
+
+        let mut config = Config::new("icc.docx", "icc.pdf");
+        config.icc_profile = Some(path.to_string_lossy().into_owned());
+        let document = lopdf::Document::load_mem(&sample_pdf("Profiled", &config)).unwrap();
+        let intents = document.catalog().unwrap().get(b"OutputIntents").and_then(Object::as_array).unwrap();
+        let intent = document.dereference(&intents[0]).unwrap().1.as_dict().unwrap();
+        assert_eq!(intent.get(b"S").and_then(Object::as_name_str).unwrap(), "GTS_PDFA1");
+        let profile = document.dereference(intent.get(b"DestOutputProfile").unwrap()).unwrap().1.as_stream().unwrap();
+        assert_eq!(profile.dict.get(b"N").and_then(Object::as_i64).unwrap(), 3);
+        assert_eq!(profile.decompressed_content().unwrap(), rgb_profile());
+    }
+}
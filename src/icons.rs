@@ -0,0 +1,157 @@
+//! Small inline pictures, such as icons set between words. A `wp:inline`
+//! picture no taller than about a line is set in the text flow rather than
+//! as a block: it takes the width of as many placeholder characters as
+//! cover it, and is drawn standing on the baseline in their place.
+
+use std::borrow::Cow;
+
+use docx_rs::{DrawingData, DrawingPositionType, Run, RunChild};
+use printpdf::{ImageTransform, Mm};
+
+use crate::color::pdf_image;
+use crate::debug::{DebugBox, draw_debug_box};
+use crate::text::text_width;
+use crate::{ASCENT, EMU_TO_MM, Layout, effective_dpi};
+
+/// Stands in for an icon in the flow's text, so lines are wrapped around it.
+const PLACEHOLDER: char = '\u{FFFC}';
+
+/// Pictures up to this many lines tall are set in the text.
+const MAX_LINES: f32 = 1.5;
+
+/// A picture set in the text, with its size in mm once fitted to the line.
+pub struct Icon {
+    id: String,
+    width: f32,
+    height: f32,
+}
+
+/// The icon `child` shows, if it is a small inline picture that can be decoded.
+fn child_icon(layout: &Layout, child: &RunChild) -> Option<Icon> {
+    let RunChild::Drawing(drawing) = child else { return None };
+    let Some(DrawingData::Pic(pic)) = &drawing.data else { return None };
+    if !matches!(pic.position_type, DrawingPositionType::Inline) || !layout.previews.contains_key(&pic.id) {
+        return None;
+    }
+    let (width, height) = (pic.size.0 as f32 * EMU_TO_MM, pic.size.1 as f32 * EMU_TO_MM);
+    if width <= 0.0 || height <= 0.0 || height > MAX_LINES * layout.line_height {
+        return None;
+    }
+    //No taller than the line above the baseline, so it does not reach into the line before
+    let scale = (ASCENT * layout.line_height / height).min(1.0);
+    Some(Icon { id: pic.id.clone(), width: width * scale, height: height * scale })
+}
+
+/// The icon `run` holds, if it holds nothing else.
+pub fn run_icon(layout: &Layout, run: &Run) -> Option<Icon> {
+    match &run.children[..] {
+        [child] => child_icon(layout, child),
+        _ => None,
+    }
+}
+
+/// `run` split so that each of its icons is a run of its own.
+pub fn icon_segments<'r>(layout: &Layout, run: Cow<'r, Run>) -> Vec<Cow<'r, Run>> {
+    let is_icon = |child: &RunChild| child_icon(layout, child).is_some();
+    if run.children.len() < 2 || !run.children.iter().any(is_icon) {
+        return vec![run];
+    }
+    let segment = |children: Vec<RunChild>| Cow::Owned(Run { run_property: run.run_property.clone(), children });
+    let mut segments = Vec::new();
+    let mut rest = Vec::new();
+    for child in &run.children {
+        if is_icon(child) {
+            if !rest.is_empty() {
+                segments.push(segment(std::mem::take(&mut rest)));
+            }
+            segments.push(segment(vec![child.clone()]));
+        } else {
+            rest.push(child.clone());
+        }
+    }
+    if !rest.is_empty() {
+        segments.push(segment(rest));
+    }
+    segments
+}
+
+/// The placeholder text `icon` takes in a flow set at `font_size`.
+pub fn icon_text(icon: &Icon, font_size: f32) -> String {
+    let advance = text_width(&PLACEHOLDER.to_string(), font_size);
+    let count = (icon.width / advance).ceil().max(1.0) as usize;
+    std::iter::repeat_n(PLACEHOLDER, count).collect()
+}
+
+/// Draws `icon` standing on the baseline `y`, centred in the `advance` mm
+/// of its placeholders from `x`.
+pub fn draw_icon(layout: &mut Layout, icon: &Icon, (x, y): (f32, f32), advance: f32) {
+    let Some(&(path, image)) = layout.previews.get(&icon.id) else { return };
+    let x = x + (advance - icon.width).max(0.0) / 2.0;
+    let dpi = effective_dpi(image.width(), icon.width);
+    let alt = layout.alt_texts.get(&icon.id).map(String::as_str);
+    let marked = layout.tags.get_mut().begin_figure(&layout.layer, layout.page, alt);
+    pdf_image(image, layout.config.color_space).add_to_layer(
+        layout.layer.clone(),
+        ImageTransform {
+            translate_x: Some(Mm(x)),
+            translate_y: Some(Mm(y)),
+            scale_y: Some(icon.height / (image.height() as f32 / dpi * 25.4)),
+            dpi: Some(dpi),
+            ..ImageTransform::default()
+        },
+    );
+    layout.tags.get_mut().end_figure(&layout.layer, marked);
+    layout.report_image(path, (x, y), icon.width, icon.height);
+    layout.placed_previews.insert(path.to_string());
+    draw_debug_box(layout, DebugBox::Image, x, y, icon.width, icon.height);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use docx_rs::{Docx, Paragraph, Pic};
+
+    use super::*;
+    use crate::model::LayoutModel;
+    use crate::{Config, build_layout_model};
+
+    /// The layout of a paragraph with a square picture `side` EMU across between two runs of text.
+    fn picture_between_words(side: u32) -> LayoutModel {
+        let mut png = Cursor::new(Vec::new());
+        ::image::RgbImage::from_pixel(16, 16, ::image::Rgb([255, 0, 0])).write_to(&mut png, ::image::ImageFormat::Png).unwrap();
+        let paragraph = Paragraph::new()
+            .add_run(Run::new().add_text("Before "))
+            .add_run(Run::new().add_image(Pic::new(png.get_ref()).size(side, side)))
+            .add_run(Run::new().add_text(" after"));
+        let mut packed = Cursor::new(Vec::new());
+        Docx::new().add_paragraph(paragraph).build().pack(&mut packed).unwrap();
+        let docx = docx_rs::read_docx(packed.get_ref()).unwrap();
+        build_layout_model(&docx, &Config::new("icon.docx", "icon.pdf")).unwrap()
+    }
+
+    #[test]
+    fn sets_a_small_picture_on_the_baseline_between_words() {
+        let model = picture_between_words(100_800);
+        let page = &model.pages[0];
+        assert_eq!(page.images.len(), 1);
+        let icon = &page.images[0];
+        let line = page.lines.iter().find(|line| line.runs.iter().any(|run| run.text.starts_with("Before"))).unwrap();
+        let before = line.runs.iter().find(|run| run.text.starts_with("Before")).unwrap();
+        let after = line.runs.iter().find(|run| run.text.contains("after")).expect("the words after the icon share its line");
+        assert!((icon.y - line.y).abs() < 0.01, "{} on {}", icon.y, line.y);
+        assert!((icon.width - 2.8).abs() < 0.01 && (icon.height - 2.8).abs() < 0.01, "{:?}", icon);
+        assert!(before.x + before.width <= icon.x && icon.x + icon.width <= after.x, "{:?}", line.runs);
+
+        //A little taller than the line above the baseline, it is scaled down to fit it
+        let icon = &picture_between_words(151_200).pages[0].images[0];
+        assert!(icon.height < 4.2 && (icon.width - icon.height).abs() < 0.01, "{:?}", icon);
+
+        //A picture taller than a line and a half is a block of its own
+        let model = picture_between_words(900_000);
+        let page = &model.pages[0];
+        let picture = &page.images[0];
+        assert!((picture.height - 25.0).abs() < 0.01);
+        assert!(page.lines.iter().all(|line| (picture.y - line.y).abs() > 0.01));
+    }
+}
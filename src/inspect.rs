@@ -0,0 +1,264 @@
+//! A summary of a docx without converting it: what it contains and which
+//! of its features the converter leaves out.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use docx_rs::{DocumentChild, Table, TableCellContent, TableChild, TableRowChild};
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use serde_json::{Value, json};
+
+use crate::{Config, EMU_TO_MM, LoadedDocument, MathMode, TWIP_TO_MM, image_extents, property_json, read_part, xml_attribute};
+
+/// How much of a feature the converter keeps.
+#[derive(Clone, Copy, PartialEq)]
+enum Coverage {
+    /// Left out of the PDF.
+    Dropped,
+    /// Shown, but not as Word draws it.
+    Approximated,
+    /// Left out unless `--math text`.
+    Math,
+    /// Left out unless `--include-comments`; Word does not print them either.
+    Comments,
+}
+
+/// Elements of `word/document.xml` marking content that is not converted,
+/// or only with an option, and how it is described.
+const UNSUPPORTED: &[(&[u8], &str, Coverage)] = &[
+    (b"headerReference", "headers", Coverage::Dropped),
+    (b"footerReference", "footers", Coverage::Dropped),
+    (b"chart", "charts (shown as their cached image)", Coverage::Approximated),
+    (b"relIds", "SmartArt diagrams", Coverage::Dropped),
+    (b"OLEObject", "embedded objects (shown as their preview image)", Coverage::Approximated),
+    (b"ffData", "form fields", Coverage::Dropped),
+    (b"oMath", "equations (written as text with --math text)", Coverage::Math),
+    (b"commentReference", "comments (appended with --include-comments)", Coverage::Comments),
+];
+
+/// How tables in vertical (`tbRl`) sections are described; they are left
+/// out, as the columns have no layout for them.
+const VERTICAL_TABLES: &str = "tables in vertical text sections";
+
+/// An image in the archive, with the size it is shown at if it is placed in the body.
+pub struct ImageSummary {
+    pub path: String,
+    pub pixels: (u32, u32),
+    /// Displayed width and height, in mm.
+    pub extent: Option<(f32, f32)>,
+}
+
+/// What `inspect` reports about a document.
+pub struct Inspection {
+    /// Page width and height of the last section, in mm.
+    pub page_size: (f32, f32),
+    pub landscape: bool,
+    /// Paragraphs and tables anywhere in the body, including inside tables.
+    pub paragraphs: usize,
+    pub tables: usize,
+    pub images: Vec<ImageSummary>,
+    /// Fonts named by the styles and runs.
+    pub fonts: BTreeSet<String>,
+    /// Document variables, which `DOCVARIABLE` fields show.
+    pub variables: BTreeMap<String, String>,
+    pub unsupported: Vec<&'static str>,
+}
+
+fn count_table(table: &Table, paragraphs: &mut usize, tables: &mut usize) {
+    *tables += 1;
+    for TableChild::TableRow(row) in &table.rows {
+        for TableRowChild::TableCell(cell) in &row.cells {
+            for content in &cell.children {
+                match content {
+                    TableCellContent::Paragraph(_) => *paragraphs += 1,
+                    TableCellContent::Table(table) => count_table(table, paragraphs, tables),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Adds the fonts of every `fonts` property inside `value`.
+fn collect_fonts(value: &Value, fonts: &mut BTreeSet<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                if key == "fonts" {
+                    let names = ["ascii", "hiAnsi", "eastAsia", "cs"].into_iter().filter_map(|slot| value[slot].as_str());
+                    fonts.extend(names.map(str::to_string));
+                } else {
+                    collect_fonts(value, fonts);
+                }
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|item| collect_fonts(item, fonts)),
+        _ => {}
+    }
+}
+
+/// The entries of `UNSUPPORTED` found in the document, in table order,
+/// then any tables in vertical sections.
+fn unsupported_entries(docx: &[u8]) -> Vec<(&'static str, Coverage)> {
+    let Ok(Some(xml)) = read_part(docx, "word/document.xml") else { return Vec::new() };
+    let mut found = BTreeSet::new();
+    let mut reader = Reader::from_reader(&xml[..]);
+    let mut buf = Vec::new();
+    //Body tables belong to the section whose sectPr follows them
+    let (mut table_depth, mut section_tables) = (0, 0);
+    let (mut in_section, mut vertical) = (false, false);
+    let mut vertical_tables = false;
+    loop {
+        let event = reader.read_event(&mut buf);
+        if let Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) = event
+            && let Some(index) = UNSUPPORTED.iter().position(|(name, ..)| *name == e.local_name())
+        {
+            found.insert(index);
+        }
+        let section_ended = match &event {
+            Ok(Event::End(e)) => e.local_name() == b"sectPr",
+            Ok(Event::Empty(e)) => e.local_name() == b"sectPr",
+            _ => false,
+        };
+        if section_ended {
+            vertical_tables |= vertical && section_tables > 0;
+            (in_section, vertical, section_tables) = (false, false, 0);
+        }
+        match event {
+            Ok(Event::Start(ref e)) if e.local_name() == b"tbl" => {
+                section_tables += usize::from(table_depth == 0);
+                table_depth += 1;
+            }
+            Ok(Event::End(ref e)) if e.local_name() == b"tbl" => table_depth -= 1,
+            Ok(Event::Start(ref e)) if e.local_name() == b"sectPr" => in_section = true,
+            Ok(Event::Empty(ref e)) if e.local_name() == b"textDirection" && in_section => {
+                vertical = matches!(xml_attribute(e, b"val").as_deref(), Some("tbRl" | "tbRlV"));
+            }
+            //A document that parsed but has a broken tail still reports what came before
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    let mut entries: Vec<(&'static str, Coverage)> = found.into_iter().map(|index| (UNSUPPORTED[index].1, UNSUPPORTED[index].2)).collect();
+    if vertical_tables {
+        entries.push((VERTICAL_TABLES, Coverage::Dropped));
+    }
+    entries
+}
+
+fn unsupported_features(docx: &[u8]) -> Vec<&'static str> {
+    unsupported_entries(docx).into_iter().map(|(description, _)| description).collect()
+}
+
+/// The features of `docx` that converting it with `config` leaves out of
+/// the page, which `--strict` refuses to do.
+pub fn dropped_features(docx: &[u8], config: &Config) -> Vec<&'static str> {
+    unsupported_entries(docx)
+        .into_iter()
+        .filter(|(_, coverage)| match coverage {
+            Coverage::Dropped => true,
+            Coverage::Math => config.math == MathMode::Omit,
+            Coverage::Approximated | Coverage::Comments => false,
+        })
+        .map(|(description, _)| description)
+        .collect()
+}
+
+/// Summarises `document`, parsed from the archive `docx`.
+pub fn inspect_document(docx: &[u8], document: &LoadedDocument) -> Inspection {
+    let mut paragraphs = 0;
+    let mut tables = 0;
+    for child in &document.docx.document.children {
+        match child {
+            DocumentChild::Paragraph(_) => paragraphs += 1,
+            DocumentChild::Table(table) => count_table(table, &mut paragraphs, &mut tables),
+            _ => {}
+        }
+    }
+
+    let extents = image_extents(&document.docx);
+    let images = document
+        .images
+        .iter()
+        .map(|(path, image)| ImageSummary {
+            path: path.clone(),
+            pixels: (image.width(), image.height()),
+            extent: extents.get(path).map(|(cx, cy)| (*cx as f32 * EMU_TO_MM, *cy as f32 * EMU_TO_MM)),
+        })
+        .collect();
+
+    let mut fonts = BTreeSet::new();
+    collect_fonts(&property_json(&document.docx.styles), &mut fonts);
+    collect_fonts(&property_json(&document.docx.document), &mut fonts);
+
+    let page = &property_json(&document.docx.document.section_property)["pageSize"];
+    let twips = |key: &str| page[key].as_f64().unwrap_or(0.0) as f32 * TWIP_TO_MM;
+    Inspection {
+        page_size: (twips("w"), twips("h")),
+        landscape: page["orient"].as_str() == Some("landscape"),
+        paragraphs,
+        tables,
+        images,
+        fonts,
+        variables: document.parts.variables.clone(),
+        unsupported: unsupported_features(docx),
+    }
+}
+
+impl Inspection {
+    pub fn to_json(&self) -> Value {
+        let images: Vec<Value> = self
+            .images
+            .iter()
+            .map(|image| {
+                json!({
+                    "path": image.path,
+                    "width_px": image.pixels.0,
+                    "height_px": image.pixels.1,
+                    "display_mm": image.extent.map(|(width, height)| [width, height]),
+                })
+            })
+            .collect();
+        json!({
+            "page_size_mm": [self.page_size.0, self.page_size.1],
+            "landscape": self.landscape,
+            "paragraphs": self.paragraphs,
+            "tables": self.tables,
+            "images": images,
+            "fonts": self.fonts,
+            "variables": self.variables,
+            "unsupported": self.unsupported,
+        })
+    }
+}
+
+impl fmt::Display for Inspection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let orientation = if self.landscape { "landscape" } else { "portrait" };
+        writeln!(f, "Page size: {:.1} x {:.1} mm ({})", self.page_size.0, self.page_size.1, orientation)?;
+        writeln!(f, "Paragraphs: {}", self.paragraphs)?;
+        writeln!(f, "Tables: {}", self.tables)?;
+        writeln!(f, "Images: {}", self.images.len())?;
+        for image in &self.images {
+            write!(f, "  {}: {} x {} px", image.path, image.pixels.0, image.pixels.1)?;
+            match image.extent {
+                Some((width, height)) => writeln!(f, ", shown at {:.1} x {:.1} mm", width, height)?,
+                None => writeln!(f)?,
+            }
+        }
+        let fonts: Vec<&str> = self.fonts.iter().map(String::as_str).collect();
+        writeln!(f, "Fonts: {}", if fonts.is_empty() { "none named".to_string() } else { fonts.join(", ") })?;
+        if !self.variables.is_empty() {
+            writeln!(f, "Document variables: {}", self.variables.len())?;
+            for (name, value) in &self.variables {
+                writeln!(f, "  {} = {}", name, value)?;
+            }
+        }
+        match self.unsupported.as_slice() {
+            [] => write!(f, "Unsupported features: none"),
+            features => write!(f, "Unsupported features: {}", features.join(", ")),
+        }
+    }
+}
@@ -0,0 +1,2780 @@
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use zip::read::ZipArchive;
+use docx_rs::{
+    Docx,
+    DocumentChild,
+    DrawingData,
+    Paragraph,
+    ParagraphChild,
+    Run,
+    RunChild,
+    StructuredDataTagChild,
+    Table,
+    TableCellContent,
+    TableChild,
+    TableRowChild,
+    read_docx
+};
+use printpdf::*;
+use printpdf::path::{PaintMode, WindingOrder};
+use log::{debug, info, warn};
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+use ::image::{DynamicImage, GenericImageView};
+use serde::Serialize;
+use serde_json::Value;
+use thiserror::Error;
+use regex::Regex;
+use uuid::Uuid;
+
+mod alttext;
+mod attachments;
+mod caps;
+mod charts;
+mod color;
+mod comments;
+mod compat;
+mod compression;
+mod debug;
+mod diagnose;
+mod docvars;
+mod embedfonts;
+mod dropcap;
+mod hidden;
+mod highlight;
+mod hyphen;
+mod icc;
+mod icons;
+mod inspect;
+mod limits;
+mod links;
+mod lists;
+mod markup;
+mod math;
+mod model;
+mod notes;
+mod nup;
+mod objects;
+mod pagenumbers;
+mod paragraph;
+mod properties;
+mod redact;
+mod revisions;
+mod sections;
+mod split;
+mod stream;
+mod styles;
+mod table;
+mod tabs;
+mod tagged;
+mod template;
+mod text;
+mod textbox;
+mod theme;
+mod titlepage;
+mod toc;
+mod vertical;
+mod viewer;
+mod watermark;
+mod xmp;
+
+use alttext::read_alt_texts;
+use attachments::{Attachment, embed_attachments};
+use comments::{collect_comments, render_comments};
+use compat::{AutoSpace, read_auto_space};
+pub use compression::Compression;
+use compression::set_compression;
+pub use color::ColorMode;
+use color::{fill_color, parse_hex, pdf_image};
+use debug::{DebugBox, draw_debug_box, draw_line_box, draw_margin_box};
+use docvars::{read_document_variables, substitute_variables};
+use embedfonts::{EmbeddedFace, EmbeddedFont, read_embedded_fonts};
+use dropcap::DropCap;
+use hidden::remove_hidden;
+use highlight::{Highlighter, write_highlights};
+use hyphen::Hyphenation;
+use icc::{add_output_intent, read_icc_profile};
+use inspect::{dropped_features, inspect_document};
+use limits::{drop_oversized_media, exceeds_limit};
+use links::{LinkTable, write_links};
+use lists::{ListCounters, Numbering, read_numbering};
+use markup::{Fill, add_markers, marker_text};
+pub use math::MathMode;
+use math::MathSymbols;
+pub use model::{ImageModel, LayoutModel, LineModel, PageModel, RunModel};
+use nup::{impose, validate_nup};
+use notes::{EndnoteList, FootnoteArea, MARK_FONT_SIZE, NOTE_FONT_SIZE, NoteTexts};
+use diagnose::log_document_xml;
+pub use split::SplitBy;
+use split::{part_path, split_pdf};
+use stream::{STDIN_PATH, read_docx_stream};
+use styles::{TableStyles, read_default_font, read_default_language, read_table_styles};
+use pagenumbers::{PageNumbers, write_page_labels};
+use paragraph::{measure_paragraph, paragraph_content_height, render_paragraph};
+use properties::{CoreProperties, read_core_properties};
+use redact::Redactor;
+pub use revisions::RevisionMode;
+use revisions::resolve_revisions;
+use sections::{PageAlign, SectionExtras, SectionStart, begin_section, read_sections, section_ranges};
+use table::{render_table, table_height};
+use tabs::{DEFAULT_TAB_STOP, TabStop, TabStops, read_default_tab_stop};
+use tagged::{StructureTree, write_structure};
+use titlepage::render_title_page;
+use template::{add_template, read_template};
+pub use text::{TextRenderMode, WrapAlgorithm};
+use text::{bold_text_width, is_cjk, is_win_ansi, no_hyphenation, text_width, wrap_text};
+use toc::{Heading, TocLine, collect_headings, fill_toc_lines, heading_subtree, render_toc, replace_toc_fields, toc_height};
+pub use viewer::{PageLayout, Zoom};
+use viewer::set_view;
+use vertical::render_vertical_section;
+use watermark::{Watermark, draw_watermark, read_watermark};
+use xmp::{parse_meta, write_xmp};
+
+#[derive(Debug, Error)]
+pub enum ConversionError{
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Zip error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Docx parsing error: {0}")]
+    Docx(#[from] docx_rs::ReaderError),
+    #[error("Image processing error: {0}")]
+    Image(#[from] ::image::ImageError),
+    #[error("PDF creation error: {0}")]
+    Pdf(String),
+    #[error("PDF creation error: {0}")]
+    Printpdf(#[from] printpdf::Error),
+    #[error("Invalid input file: {0}")]
+    InvalidInput(String),
+    #[error("Cannot be converted faithfully: {0}")]
+    Unsupported(String),
+}
+
+impl ConversionError {
+    /// A short category name, for grouping failures in batch summaries.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ConversionError::Io(_) => "io",
+            ConversionError::Zip(_) => "zip",
+            ConversionError::Docx(_) => "parse",
+            ConversionError::Image(_) => "image",
+            ConversionError::Pdf(_) | ConversionError::Printpdf(_) => "pdf",
+            ConversionError::InvalidInput(_) => "input",
+            ConversionError::Unsupported(_) => "unsupported",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Config{
+    /// Documents rendered one after another into the output, `-` for standard input.
+    pub input_paths: Vec<String>,
+    pub output_path: String,
+    pub page_width: f32,
+    pub page_height: f32,
+    pub margin: f32,
+    pub reproducible: bool,
+    pub wrap_algorithm: WrapAlgorithm,
+    /// Directory of hyph-utf8 pattern files to hyphenate words with.
+    pub hyphenate: Option<String>,
+    pub color_space: ColorMode,
+    pub include_comments: bool,
+    /// Version written to the file header; `None` writes printpdf's 1.3, or
+    /// the version the features in use need.
+    pub pdf_version: Option<PdfVersion>,
+    /// Load images linked by external relationships from the local filesystem.
+    pub fetch_external: bool,
+    /// How tracked insertions and deletions are rendered.
+    pub revisions: RevisionMode,
+    pub math: MathMode,
+    /// Outline the margin area and every placed paragraph, line and image.
+    pub debug_boxes: bool,
+    /// ICC profile embedded as the output intent.
+    pub icc_profile: Option<String>,
+    /// Log where `word/document.xml` is broken when a document fails to parse.
+    pub verbose_errors: bool,
+    /// Drawn in place of characters the built-in fonts have no glyph for.
+    pub fallback_glyph: char,
+    /// BCP 47 language used instead of the documents' own `w:lang`.
+    pub lang: Option<String>,
+    /// PDF whose first page is drawn behind every page.
+    pub template: Option<String>,
+    /// Terms marked in yellow wherever they appear, ignoring case.
+    pub highlight: Vec<String>,
+    /// Regular expressions whose matches are left out of the output and
+    /// covered with black boxes.
+    pub redact: Vec<String>,
+    /// Attach each input docx to the PDF as an embedded file.
+    pub embed_source: bool,
+    /// TrueType font embedded for Chinese, Japanese and Korean characters.
+    pub east_asian_font: Option<String>,
+    /// Zoom the document opens at.
+    pub zoom: Option<Zoom>,
+    /// How viewers arrange the pages when the document opens.
+    pub page_layout: Option<PageLayout>,
+    /// Write a structure tree tagging headings, paragraphs, lists, tables
+    /// and figures, for screen readers.
+    pub tagged: bool,
+    /// Factor the advance from one line of text to the next is scaled by.
+    pub line_height: f32,
+    /// Factor every font size is scaled by, and the lines with them, for
+    /// large print.
+    pub font_scale: f32,
+    /// How the PDF's streams are compressed; as printpdf leaves them if unset.
+    pub compression: Option<Compression>,
+    /// Draw hidden (`w:vanish`) text, which Word neither shows nor prints.
+    pub show_hidden: bool,
+    /// Start with a cover page showing the document's title and author.
+    pub title_page: bool,
+    /// Pages placed on each sheet, scaled down to share it.
+    pub nup: Option<u32>,
+    /// Images declaring more pixels than this are left out undecoded.
+    pub max_image_pixels: Option<u64>,
+    /// Keep everything that identifies the author or the document's history
+    /// out of the PDF: hidden text, comments, tracked changes, the attached
+    /// source and the metadata.
+    pub sanitize: bool,
+    /// Fail rather than write a PDF missing content the converter cannot
+    /// show: unsupported elements and characters without a glyph.
+    pub strict: bool,
+    /// Render only the heading with this text and the content under it.
+    pub section: Option<String>,
+    /// Start a new line at each line break (`w:br`) rather than running the
+    /// text on, wrapping only between them.
+    pub preserve_breaks: bool,
+    /// Whether body text is filled, stroked hollow, or both.
+    pub text_render_mode: TextRenderMode,
+    /// Write the PDF as several files, numbered after the output path.
+    pub split_by: Option<SplitBy>,
+    /// Custom entries written to the XMP metadata, in order, each key once.
+    pub meta: Vec<(String, String)>,
+}
+
+/// PDF versions the header can be set to. The body is written the same way
+/// whatever the version (Flate streams, no transparency groups, object
+/// streams or cross-reference streams), but tagging, output intents and
+/// XMP metadata need PDF 1.4.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum PdfVersion {
+    V1_3,
+    V1_4,
+    V1_5,
+    V1_7,
+}
+
+impl PdfVersion {
+    fn as_str(self) -> &'static str {
+        match self {
+            PdfVersion::V1_3 => "1.3",
+            PdfVersion::V1_4 => "1.4",
+            PdfVersion::V1_5 => "1.5",
+            PdfVersion::V1_7 => "1.7",
+        }
+    }
+}
+
+impl std::str::FromStr for PdfVersion {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "1.3" => Ok(PdfVersion::V1_3),
+            "1.4" => Ok(PdfVersion::V1_4),
+            "1.5" => Ok(PdfVersion::V1_5),
+            "1.7" => Ok(PdfVersion::V1_7),
+            other => Err(format!("Unsupported PDF version: {}", other)),
+        }
+    }
+}
+
+impl Config{
+    pub fn new(input_path: &str, output_path: &str) -> Self{
+        Config{
+            input_paths: vec![input_path.to_string()],
+            output_path: output_path.to_string(),
+            page_width: 210.0,
+            page_height: 297.0,
+            margin: 20.0,
+            reproducible: false,
+            wrap_algorithm: WrapAlgorithm::Greedy,
+            hyphenate: None,
+            color_space: ColorMode::Rgb,
+            include_comments: false,
+            pdf_version: None,
+            fetch_external: false,
+            revisions: RevisionMode::Accept,
+            math: MathMode::Omit,
+            debug_boxes: false,
+            icc_profile: None,
+            verbose_errors: false,
+            fallback_glyph: '?',
+            lang: None,
+            template: None,
+            highlight: Vec::new(),
+            redact: Vec::new(),
+            embed_source: false,
+            east_asian_font: None,
+            zoom: None,
+            page_layout: None,
+            tagged: false,
+            line_height: 1.0,
+            font_scale: 1.0,
+            compression: None,
+            show_hidden: false,
+            title_page: false,
+            nup: None,
+            max_image_pixels: None,
+            sanitize: false,
+            strict: false,
+            section: None,
+            preserve_breaks: false,
+            text_render_mode: TextRenderMode::Fill,
+            split_by: None,
+            meta: Vec::new(),
+        }
+    }
+
+    /// The same settings on the page turned to landscape.
+    fn landscape(&self) -> Config {
+        Config {
+            page_width: self.page_width.max(self.page_height),
+            page_height: self.page_width.min(self.page_height),
+            ..self.clone()
+        }
+    }
+
+    /// Whether hidden text is drawn; sanitizing leaves it out regardless.
+    fn keeps_hidden(&self) -> bool {
+        self.show_hidden && !self.sanitize
+    }
+
+    /// Whether the input docx is attached. Sanitizing leaves it out, and
+    /// so does redacting, as it still holds the redacted text.
+    fn embeds_source(&self) -> bool {
+        self.embed_source && !self.sanitize && self.redact.is_empty()
+    }
+
+    /// The lowest PDF version the features in use need, with the option
+    /// that needs it. Tagging, output intents and metadata streams came with
+    /// PDF 1.4.
+    fn required_pdf_version(&self) -> Option<(PdfVersion, &'static str)> {
+        [(self.tagged, "--tagged"), (self.icc_profile.is_some(), "--icc-profile"), (!self.meta.is_empty(), "--meta")]
+            .into_iter()
+            .find(|(used, _)| *used)
+            .map(|(_, option)| (PdfVersion::V1_4, option))
+    }
+
+    /// Version written to the file header: the one asked for, else 1.3
+    /// raised to what the features in use need. One asked for below that
+    /// is refused.
+    fn header_version(&self) -> Result<PdfVersion, String> {
+        match (self.pdf_version, self.required_pdf_version()) {
+            (Some(asked), Some((needed, option))) if asked < needed => {
+                Err(format!("{} needs PDF {} or later, not {}", option, needed.as_str(), asked.as_str()))
+            }
+            (Some(asked), _) => Ok(asked),
+            (None, needed) => Ok(needed.map_or(PdfVersion::V1_3, |(version, _)| version)),
+        }
+    }
+
+    //Builds a config from the arguments following the program name
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut paths = Vec::new();
+        let mut reproducible = false;
+        let mut wrap_algorithm = WrapAlgorithm::Greedy;
+        let mut hyphenate = None;
+        let mut color_space = ColorMode::Rgb;
+        let mut include_comments = false;
+        let mut pdf_version = None;
+        let mut fetch_external = false;
+        let mut revisions = RevisionMode::Accept;
+        let mut math = MathMode::Omit;
+        let mut debug_boxes = false;
+        let mut icc_profile = None;
+        let mut verbose_errors = false;
+        let mut fallback_glyph = '?';
+        let mut lang = None;
+        let mut template = None;
+        let mut highlight = Vec::new();
+        let mut redact = Vec::new();
+        let mut embed_source = false;
+        let mut east_asian_font = None;
+        let mut zoom = None;
+        let mut page_layout = None;
+        let mut tagged = false;
+        let mut line_height = 1.0;
+        let mut font_scale = 1.0;
+        let mut compression = None;
+        let mut show_hidden = false;
+        let mut title_page = false;
+        let mut nup = None;
+        let mut max_image_pixels = None;
+        let mut sanitize = false;
+        let mut strict = false;
+        let mut section = None;
+        let mut preserve_breaks = false;
+        let mut text_render_mode = TextRenderMode::Fill;
+        let mut split_by = None;
+        let mut meta: Vec<(String, String)> = Vec::new();
+        let mut monochrome = None;
+        let mut args = args.iter().peekable();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--reproducible" => reproducible = true,
+                "--hyphenate" => {
+                    let value = args.next().ok_or("--hyphenate needs a value")?;
+                    hyphenate = Some(value.clone());
+                }
+                "--include-comments" => include_comments = true,
+                "--fetch-external" => fetch_external = true,
+                "--embed-source" => embed_source = true,
+                "--tagged" => tagged = true,
+                "--show-hidden" => show_hidden = true,
+                "--sanitize" => sanitize = true,
+                "--strict" => strict = true,
+                "--preserve-breaks" => preserve_breaks = true,
+                "--title-page" => title_page = true,
+                "--debug-boxes" => debug_boxes = true,
+                "--verbose-errors" => verbose_errors = true,
+                "--color-space" => {
+                    let value = args.next().ok_or("--color-space needs a value")?;
+                    color_space = value.parse()?;
+                }
+                "--monochrome" => {
+                    //The threshold is optional, so only a number is taken as one
+                    monochrome = Some(args.next_if(|value| value.parse::<u8>().is_ok()).and_then(|value| value.parse().ok()).unwrap_or(128));
+                }
+                "--section" => {
+                    let value = args.next().ok_or("--section needs a heading")?;
+                    section = Some(value.clone());
+                }
+                "--pdf-version" => {
+                    let value = args.next().ok_or("--pdf-version needs a value")?;
+                    pdf_version = Some(value.parse()?);
+                }
+                "--icc-profile" => {
+                    let value = args.next().ok_or("--icc-profile needs a value")?;
+                    icc_profile = Some(value.clone());
+                }
+                "--fallback-glyph" => {
+                    let value = args.next().ok_or("--fallback-glyph needs a value")?;
+                    let mut chars = value.chars();
+                    fallback_glyph = match (chars.next(), chars.next()) {
+                        (Some(c), None) if is_win_ansi(c) => c,
+                        _ => return Err(format!("--fallback-glyph needs one character the built-in fonts can show: {}", value)),
+                    };
+                }
+                "--template" => {
+                    let value = args.next().ok_or("--template needs a value")?;
+                    template = Some(value.clone());
+                }
+                "--highlight" => {
+                    let value = args.next().ok_or("--highlight needs a value")?;
+                    highlight.push(value.clone());
+                }
+                "--meta" => {
+                    let value = args.next().ok_or("--meta needs a value")?;
+                    let (key, value) = parse_meta(value)?;
+                    //A key given again takes the later value
+                    meta.retain(|(other, _)| *other != key);
+                    meta.push((key, value));
+                }
+                "--redact" => {
+                    let value = args.next().ok_or("--redact needs a value")?;
+                    Regex::new(value).map_err(|e| format!("Invalid --redact pattern {}: {}", value, e))?;
+                    redact.push(value.clone());
+                }
+                "--lang" => {
+                    let value = args.next().ok_or("--lang needs a value")?;
+                    lang = Some(value.clone());
+                }
+                "--math" => {
+                    let value = args.next().ok_or("--math needs a value")?;
+                    math = value.parse()?;
+                }
+                "--east-asian-font" => {
+                    let value = args.next().ok_or("--east-asian-font needs a value")?;
+                    east_asian_font = Some(value.clone());
+                }
+                "--zoom" => {
+                    let value = args.next().ok_or("--zoom needs a value")?;
+                    zoom = Some(value.parse()?);
+                }
+                "--page-layout" => {
+                    let value = args.next().ok_or("--page-layout needs a value")?;
+                    page_layout = Some(value.parse()?);
+                }
+                "--line-height" => {
+                    let value = args.next().ok_or("--line-height needs a value")?;
+                    line_height = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|factor| factor.is_finite() && *factor > 0.0)
+                        .ok_or_else(|| format!("--line-height needs a positive factor: {}", value))?;
+                }
+                "--font-scale" => {
+                    let value = args.next().ok_or("--font-scale needs a value")?;
+                    font_scale = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|factor| factor.is_finite() && *factor > 0.0)
+                        .ok_or_else(|| format!("--font-scale needs a positive factor: {}", value))?;
+                }
+                "--nup" => {
+                    let value = args.next().ok_or("--nup needs a value")?;
+                    let count = value.parse::<u32>().map_err(|_| format!("--nup needs a number of pages: {}", value))?;
+                    nup = Some(validate_nup(count)?);
+                }
+                "--max-image-pixels" => {
+                    let value = args.next().ok_or("--max-image-pixels needs a value")?;
+                    let pixels = value.parse::<u64>().ok().filter(|pixels| *pixels > 0);
+                    max_image_pixels = Some(pixels.ok_or_else(|| format!("--max-image-pixels needs a positive count: {}", value))?);
+                }
+                "--compression" => {
+                    let value = args.next().ok_or("--compression needs a value")?;
+                    compression = Some(value.parse()?);
+                }
+                "--revisions" => {
+                    let value = args.next().ok_or("--revisions needs a value")?;
+                    revisions = value.parse()?;
+                }
+                "--wrap-algorithm" => {
+                    let value = args.next().ok_or("--wrap-algorithm needs a value")?;
+                    wrap_algorithm = value.parse()?;
+                }
+                "--text-render-mode" => {
+                    let value = args.next().ok_or("--text-render-mode needs a value")?;
+                    text_render_mode = value.parse()?;
+                }
+                "--split-by" => {
+                    let value = args.next().ok_or("--split-by needs a value")?;
+                    split_by = Some(value.parse()?);
+                }
+                flag if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+                path => paths.push(path),
+            }
+        }
+        let Some((output_path, input_paths)) = paths.split_last().filter(|(_, inputs)| !inputs.is_empty()) else {
+            return Err("Expected one or more input paths and one output path".to_string());
+        };
+
+        if tagged && nup.is_some() {
+            return Err("--tagged cannot be combined with --nup".to_string());
+        }
+        //The attached original would hold the text redacted from the pages
+        if embed_source && !redact.is_empty() {
+            return Err("--embed-source cannot be combined with --redact".to_string());
+        }
+        //Sheets hold pages of more than one section
+        if split_by == Some(SplitBy::Section) && nup.is_some() {
+            return Err("--split-by section cannot be combined with --nup".to_string());
+        }
+
+        let mut config = Config::new(input_paths[0], output_path);
+        config.input_paths = input_paths.iter().map(|path| path.to_string()).collect();
+        config.reproducible = reproducible;
+        config.wrap_algorithm = wrap_algorithm;
+        config.hyphenate = hyphenate;
+        config.color_space = monochrome.map_or(color_space, ColorMode::Monochrome);
+        config.include_comments = include_comments;
+        config.pdf_version = pdf_version;
+        config.fetch_external = fetch_external;
+        config.revisions = revisions;
+        config.math = math;
+        config.debug_boxes = debug_boxes;
+        config.icc_profile = icc_profile;
+        config.verbose_errors = verbose_errors;
+        config.fallback_glyph = fallback_glyph;
+        config.lang = lang;
+        config.template = template;
+        config.highlight = highlight;
+        config.redact = redact;
+        config.embed_source = embed_source;
+        config.east_asian_font = east_asian_font;
+        config.zoom = zoom;
+        config.page_layout = page_layout;
+        config.tagged = tagged;
+        config.line_height = line_height;
+        config.font_scale = font_scale;
+        config.compression = compression;
+        config.show_hidden = show_hidden;
+        config.title_page = title_page;
+        config.nup = nup;
+        config.max_image_pixels = max_image_pixels;
+        config.sanitize = sanitize;
+        config.strict = strict;
+        config.section = section;
+        config.preserve_breaks = preserve_breaks;
+        config.text_render_mode = text_render_mode;
+        config.split_by = split_by;
+        config.meta = meta;
+        config.header_version()?;
+        Ok(config)
+    }
+}
+
+pub const OPTIONS: &str = "Several inputs are rendered into one PDF, each starting on a new page.
+An input of - reads the docx from standard input.
+
+Options:
+  --reproducible    Fix dates and document ID so identical input yields identical bytes
+  --wrap-algorithm <greedy|balanced>
+                    Line breaking strategy (default greedy)
+  --hyphenate <patterns-dir>
+                    Break words at hyphenation points, in the language of each run, with
+                    the hyph-utf8 pattern files in this directory, such as hyph-en-us.pat.txt
+  --color-space <rgb|cmyk>
+                    Colour model for text and images (default rgb)
+  --monochrome [threshold]
+                    Black text, and fills and images flattened to 1-bit black and white
+                    at the given luminance threshold, 0-255 (default 128)
+  --include-comments
+                    Append document comments with their author and anchor text
+  --pdf-version <1.3|1.4|1.5|1.7>
+                    Version written to the PDF header (default 1.3, or 1.4 with
+                    --tagged, --icc-profile or --meta, which need it)
+  --fetch-external  Embed linked (not embedded) images found on the local filesystem
+  --revisions <accept|reject|show>
+                    Tracked changes: apply them, discard them, or mark them up (default accept)
+  --math <omit|text>
+                    Leave equations out or write them as linear text (default omit)
+  --debug-boxes     Outline the margins and each paragraph, line and image
+  --icc-profile <path>
+                    Embed an RGB, CMYK or grey ICC profile as the output intent
+  --verbose-errors  When a document fails to parse, log where its document.xml is broken
+  --fallback-glyph <char>
+                    Drawn for characters the built-in fonts cannot show (default ?)
+  --lang <bcp47>    Language of the documents, overriding their own, for hyphenation
+  --template <pdf>  Draw the first page of a PDF, such as a letterhead, behind every page
+  --highlight <term>
+                    Mark every occurrence of a term in yellow, ignoring case; may be repeated
+  --redact <regex>  Leave text matching a pattern out of the PDF and black it out; matches
+                    cannot span lines; may be repeated
+  --embed-source    Attach each input docx to the PDF so it can be recovered; not with --redact
+  --east-asian-font <ttf>
+                    Embed a TrueType font for Chinese, Japanese and Korean characters
+  --zoom <fit|width|percent>
+                    Zoom viewers open the first page at
+  --page-layout <single|continuous|two-up>
+                    How viewers arrange the pages when the document opens
+  --line-height <factor>
+                    Scale the spacing between lines of text, such as 1.15 (default 1)
+  --font-scale <factor>
+                    Scale every font size, and the lines with it, such as 2 for large print
+  --tagged          Tag headings, paragraphs, lists, tables and figures for screen readers
+  --compression <none|fast|max>
+                    Compress the PDF's streams, or leave them readable for debugging
+  --show-hidden     Draw hidden text, which is left out by default
+  --title-page      Start with an unnumbered cover showing the title and author
+  --nup <n>         Print n pages on each sheet, scaled down: 2 or 8 on a turned sheet, 4 or 9
+                    in a grid; links are kept; not with --tagged
+  --max-image-pixels <count>
+                    Leave out images larger than this many pixels without decoding them
+  --sanitize        Leave out hidden text, comments, tracked changes, attachments and
+                    metadata that could identify the author
+  --section <heading-text>
+                    Render only the heading with this text and what follows it up to the next
+                    heading of the same or a higher level
+  --preserve-breaks Start a new line at each line break in the source, such as in poems and
+                    addresses, wrapping only between them
+  --text-render-mode <fill|stroke|outline>
+                    Paint text filled, as hollow outlines, or filled and outlined (default fill)
+  --split-by <section|page-count:n|size:mb>
+                    Write several PDFs, out-1.pdf, out-2.pdf and so on, starting a new one
+                    at each section, every n pages, or before one would pass mb megabytes
+  --meta <key=value>
+                    Write a custom entry to the PDF's XMP metadata, for document management
+                    systems to index; may be repeated
+  --strict          Fail instead of leaving out headers, footers, equations and other content
+                    that cannot be converted, or characters without a glyph";
+
+/// Share of the line height that sits below the baseline.
+const DESCENT: f32 = 0.25;
+/// Share of the line height above the baseline, from the top of the line box.
+const ASCENT: f32 = 1.0 - DESCENT;
+/// How far, in mm, content may reach off the page before it is warned of.
+const OVERFLOW_TOLERANCE: f32 = 0.5;
+/// How far, in mm, content may seem to run past the bottom margin and still fit.
+const FIT_TOLERANCE: f32 = 0.001;
+/// Millimetres per PostScript point.
+const PT_TO_MM: f32 = 0.352_778;
+/// Millimetres per twip (1/20 pt), the unit docx uses for table grids.
+const TWIP_TO_MM: f32 = PT_TO_MM / 20.0;
+/// Millimetres per EMU, the unit DrawingML uses for shape extents and offsets.
+const EMU_TO_MM: f32 = 1.0 / 36_000.0;
+
+/// Document content read straight from the archive, for the parts of a
+/// docx that docx_rs does not model.
+struct ArchiveParts {
+    notes: NoteTexts,
+    table_styles: TableStyles,
+    sections: Vec<SectionExtras>,
+    numbering: Numbering,
+    /// The `w:lang` of the document's default run properties.
+    language: Option<String>,
+    /// Default tab width in mm. docx_rs substitutes its own value when
+    /// settings.xml sets none, so the fallback could not be told apart.
+    default_tab_stop: f32,
+    /// Spacing between East Asian and Latin text from the document defaults.
+    auto_space: AutoSpace,
+    /// Alternative text of images, by relationship id.
+    alt_texts: HashMap<String, String>,
+    properties: CoreProperties,
+    /// Document variables, by name.
+    variables: BTreeMap<String, String>,
+    embedded_fonts: Vec<EmbeddedFont>,
+    /// The font family of the document's default run properties.
+    default_font: Option<String>,
+    watermark: Option<Watermark>,
+}
+
+impl Default for ArchiveParts {
+    fn default() -> Self {
+        ArchiveParts {
+            notes: NoteTexts::default(),
+            table_styles: TableStyles::new(),
+            sections: Vec::new(),
+            numbering: Numbering::default(),
+            language: None,
+            default_tab_stop: DEFAULT_TAB_STOP,
+            auto_space: AutoSpace::default(),
+            alt_texts: HashMap::new(),
+            properties: CoreProperties::default(),
+            variables: BTreeMap::new(),
+            embedded_fonts: Vec::new(),
+            default_font: None,
+            watermark: None,
+        }
+    }
+}
+
+impl ArchiveParts {
+    fn read(docx: &[u8], config: &Config) -> Result<Self, ConversionError> {
+        Ok(ArchiveParts {
+            notes: NoteTexts::read(docx, config.keeps_hidden())?,
+            table_styles: read_table_styles(docx)?,
+            sections: read_sections(docx)?,
+            numbering: read_numbering(docx)?,
+            language: read_default_language(docx)?,
+            default_tab_stop: read_default_tab_stop(docx)?,
+            auto_space: read_auto_space(docx)?,
+            alt_texts: read_alt_texts(docx)?,
+            properties: read_core_properties(docx)?,
+            variables: read_document_variables(docx)?,
+            embedded_fonts: read_embedded_fonts(docx)?,
+            default_font: read_default_font(docx)?,
+            watermark: read_watermark(docx)?,
+        })
+    }
+}
+
+/// A parsed document to render, with the content read straight from its archive.
+struct SourceDocument<'d> {
+    docx: &'d Docx,
+    images: &'d [(String, DynamicImage)],
+    parts: ArchiveParts,
+    /// The docx file itself, with `--embed-source`.
+    attachment: Option<Attachment<'d>>,
+}
+
+/// A docx as read from its file, before it is borrowed for rendering.
+struct LoadedDocument {
+    docx: Docx,
+    images: Vec<(String, DynamicImage)>,
+    parts: ArchiveParts,
+}
+
+/// Reads the docx archive at `path`, or from standard input for `-`.
+fn read_input(path: &str) -> Result<Vec<u8>, ConversionError> {
+    //This validates the input file
+    if path != STDIN_PATH && (!Path::new(path).exists() || !path.ends_with(".docx")) {
+        return Err(ConversionError::InvalidInput(format!("Error: Invalid input file {}", path)));
+    }
+    if path == STDIN_PATH {
+        read_docx_stream(&mut std::io::stdin().lock())
+    } else {
+        Ok(fs::read(path)?)
+    }
+}
+
+/// The symbols equations are written with. Math runs name no font, so
+/// they are drawn in the default family when the document embeds it and
+/// otherwise in Helvetica, which has no glyphs for UnicodeMath's.
+fn math_symbols(parts: &ArchiveParts) -> MathSymbols {
+    let face = parts.default_font.as_deref().and_then(|family| {
+        parts.embedded_fonts.iter().find(|font| !font.bold && !font.italic && font.family.eq_ignore_ascii_case(family))
+    });
+    if face.is_some_and(|font| font.has_glyphs(math::UNICODE_SYMBOLS)) { MathSymbols::Unicode } else { MathSymbols::Ascii }
+}
+
+/// Parses `docx_content`, read from `path`.
+fn parse_document(docx_content: &[u8], path: &str, config: &Config) -> Result<LoadedDocument, ConversionError> {
+    let from_stdin = path == STDIN_PATH;
+    //Oversized images are emptied before anything decodes them
+    let limited = config.max_image_pixels.map(|limit| drop_oversized_media(docx_content, limit)).transpose()?;
+    let docx_content = limited.as_deref().unwrap_or(docx_content);
+    let mut parts = ArchiveParts::read(docx_content, config)?;
+    let mut docx = add_markers(docx_content, config.math, math_symbols(&parts))
+        .and_then(|marked| Ok(read_docx(&marked)?))
+        .inspect_err(|_| {
+            if config.verbose_errors {
+                log_document_xml(docx_content);
+            }
+        })?;
+    if config.strict {
+        let dropped = dropped_features(docx_content, config);
+        if !dropped.is_empty() {
+            return Err(ConversionError::Unsupported(format!("{} has {}", path, dropped.join(", "))));
+        }
+    }
+
+    //Extracts images
+    let mut images = extract_images(docx_content)?;
+    //The watermark's picture is drawn on each page instead
+    if let Some(path) = parts.watermark.as_ref().and_then(Watermark::picture_path) {
+        images.retain(|(name, _)| name != path);
+    }
+    if let Some(title) = &config.section {
+        let Some((children, sections_before)) = heading_subtree(&flatten_tags(&docx.document.children), title) else {
+            return Err(ConversionError::InvalidInput(format!("{} has no heading \"{}\"", path, title)));
+        };
+        //Pictures placed under other headings are left out with them
+        let all_placed = image_extents(&docx);
+        docx.document.children = children;
+        let placed = image_extents(&docx);
+        images.retain(|(name, _)| placed.contains_key(name) || !all_placed.contains_key(name));
+        parts.sections.drain(..sections_before.min(parts.sections.len()));
+    }
+    if config.fetch_external {
+        //Linked images of a piped document are looked up from the working directory
+        let base = if from_stdin { Path::new("") } else { Path::new(path).parent().unwrap_or(Path::new("")) };
+        images.extend(extract_external_images(docx_content, base, config.max_image_pixels)?);
+    }
+    Ok(LoadedDocument { docx, images, parts })
+}
+
+/// Converts the docx files at `config.input_paths` and writes them as one PDF
+/// to `config.output_path`.
+pub fn convert_file(config: &Config) -> Result<(), ConversionError> {
+    convert_file_reporting(config, None)
+}
+
+/// Like `convert_file`, calling `on_run` with each run of text as it is laid
+/// out, before it is drawn.
+pub fn convert_file_with(config: &Config, on_run: &mut dyn FnMut(&LaidOutRun)) -> Result<(), ConversionError> {
+    convert_file_reporting(config, Some(on_run))
+}
+
+fn convert_file_reporting(config: &Config, on_run: Option<&mut RunCallback>) -> Result<(), ConversionError> {
+    info!("Starting conversion from {} to {}", config.input_paths.join(", "), config.output_path);
+
+    let mut documents = Vec::new();
+    let mut parts = Vec::new();
+    for path in &config.input_paths {
+        let docx_content = read_input(path)?;
+        let LoadedDocument { docx, images, parts: document_parts } = parse_document(&docx_content, path, config)?;
+        documents.push((docx, images, path, docx_content));
+        parts.push(document_parts);
+    }
+    let sources = documents
+        .iter()
+        .zip(parts)
+        .map(|((docx, images, path, docx_content), parts)| {
+            let attachment = config.embeds_source().then(|| Attachment { name: attachment_name(path), data: docx_content });
+            SourceDocument { docx, images, parts, attachment }
+        })
+        .collect();
+
+    //Generate PDF
+    let (bytes, section_starts) = render_pdf(sources, config, on_run, None)?;
+    match config.split_by {
+        Some(split_by) => {
+            for (i, part) in split_pdf(&bytes, split_by, &section_starts)?.iter().enumerate() {
+                let path = part_path(Path::new(&config.output_path), i + 1);
+                write_atomically(&path, part)?;
+                info!("Wrote part {}", path.display());
+            }
+        }
+        None => write_atomically(Path::new(&config.output_path), &bytes)?,
+    }
+
+    info!("Conversion completed successfully: {}", config.output_path);
+    Ok(())
+}
+
+/// The file name an input is attached under; a piped document has none of its own.
+fn attachment_name(path: &str) -> String {
+    match Path::new(path).file_name().and_then(|name| name.to_str()) {
+        Some(name) if path != STDIN_PATH => name.to_string(),
+        _ => "document.docx".to_string(),
+    }
+}
+
+/// Summarises the docx at `path` without converting it, as text or, with
+/// `json`, as a JSON object.
+pub fn inspect_file(path: &str, json: bool) -> Result<String, ConversionError> {
+    let docx_content = read_input(path)?;
+    let document = parse_document(&docx_content, path, &Config::new(path, ""))?;
+    let inspection = inspect_document(&docx_content, &document);
+    Ok(if json { inspection.to_json().to_string() } else { inspection.to_string() })
+}
+
+/// Writes `bytes` to a temporary file beside `path` and renames it into
+/// place, so an interrupted run leaves either the old file or the complete
+/// new one, never a truncated PDF.
+fn write_atomically(path: &Path, bytes: &[u8]) -> Result<(), ConversionError> {
+    let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or("output.pdf");
+    let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, Uuid::new_v4()));
+    let result = File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(bytes)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    Ok(result?)
+}
+
+/// Renders a docx that is already in memory, such as one built with
+/// docx_rs, and returns the PDF bytes.
+///
+/// A parsed `Docx` does not carry the archive it came from, so images must be
+/// supplied by the caller as `(name, image)` pairs; they are placed after the
+/// body in the order given. Parts docx_rs does not model (footnote and
+/// endnote text, table style banding, section alignment) are likewise
+/// unavailable here.
+pub fn convert_parsed(
+    docx: &Docx,
+    images: &[(String, DynamicImage)],
+    config: &Config,
+) -> Result<Vec<u8>, ConversionError> {
+    render_pdf_bytes(vec![SourceDocument { docx, images, parts: ArchiveParts::default(), attachment: None }], config, None, None)
+}
+
+/// Like `convert_parsed`, calling `on_run` with each run of text as it is
+/// laid out, before it is drawn.
+pub fn convert_parsed_with(
+    docx: &Docx,
+    images: &[(String, DynamicImage)],
+    config: &Config,
+    on_run: &mut dyn FnMut(&LaidOutRun),
+) -> Result<Vec<u8>, ConversionError> {
+    render_pdf_bytes(vec![SourceDocument { docx, images, parts: ArchiveParts::default(), attachment: None }], config, Some(on_run), None)
+}
+
+/// Lays out a docx that is already in memory, as `convert_parsed` would,
+/// and returns the pages with the text and pictures placed on them. The
+/// pictures are those docx_rs read with the document.
+pub fn build_layout_model(docx: &Docx, config: &Config) -> Result<LayoutModel, ConversionError> {
+    let images: Vec<(String, DynamicImage)> = docx
+        .images
+        .iter()
+        .filter_map(|(_, path, data, _)| Some((path.clone(), ::image::load_from_memory(&data.0).ok()?)))
+        .collect();
+    let mut model = LayoutModel::default();
+    let source = SourceDocument { docx, images: &images, parts: ArchiveParts::default(), attachment: None };
+    render_pdf_bytes(vec![source], config, None, Some(&mut model))?;
+    Ok(model)
+}
+
+fn extract_images(docx: &[u8]) -> Result<Vec<(String, DynamicImage)>, ConversionError>{
+    let mut archive = ZipArchive::new(Cursor::new(docx))?;
+    let mut images = Vec::new();
+
+    for i in 0..archive.len(){
+        let mut zip_file = archive.by_index(i)  ?;
+        let file_name = zip_file.name().to_string();
+        if file_name.starts_with("word/media"){
+            let mut buffer = Vec::new();
+            zip_file.read_to_end(&mut buffer)?;
+            if let Ok(img) = ::image::load_from_memory(&buffer) {
+                info!("Extracted image: {}", file_name);
+                images.push((file_name, img));
+        }
+    }
+}
+    Ok(images)
+}
+
+/// `text` split into non-empty stretches that are all East Asian or all
+/// not, flagged true for East Asian ones. With `split` false it is one
+/// stretch.
+fn script_segments(text: &str, split: bool) -> Vec<(bool, &str)> {
+    let mut segments: Vec<(bool, &str)> = Vec::new();
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        let east_asian = split && is_cjk(c);
+        match segments.last_mut() {
+            Some((last, segment)) if *last == east_asian => *segment = &text[start..i + c.len_utf8()],
+            _ => {
+                start = i;
+                segments.push((east_asian, &text[i..i + c.len_utf8()]));
+            }
+        }
+    }
+    segments
+}
+
+/// Scale that fits a drawing of `width` by `height` mm into `usable_width`
+/// and the height of the text area, never enlarging it.
+fn fit_scale(config: &Config, usable_width: f32, width: f32, height: f32) -> f32 {
+    let usable_height = config.page_height - 2.0 * config.margin;
+    (usable_width / width).min(usable_height / height).min(1.0)
+}
+
+/// Resolution at which `pixels` span `extent` mm.
+fn effective_dpi(pixels: u32, extent: f32) -> f32 {
+    pixels as f32 / (extent / 25.4)
+}
+
+/// The display extent (in EMU) of each embedded image placed in the body,
+/// keyed by its archive path.
+fn image_extents(docx: &Docx) -> HashMap<String, (u32, u32)> {
+    fn collect_paragraph(paragraph: &Paragraph, sizes: &mut HashMap<String, (u32, u32)>) {
+        for run in paragraph_runs(paragraph) {
+            for child in &run.children {
+                if let RunChild::Drawing(drawing) = child
+                    && let Some(DrawingData::Pic(pic)) = &drawing.data
+                {
+                    sizes.entry(pic.id.clone()).or_insert(pic.size);
+                }
+            }
+        }
+    }
+    fn collect_table(table: &Table, sizes: &mut HashMap<String, (u32, u32)>) {
+        for TableChild::TableRow(row) in &table.rows {
+            for TableRowChild::TableCell(cell) in &row.cells {
+                for content in &cell.children {
+                    match content {
+                        TableCellContent::Paragraph(paragraph) => collect_paragraph(paragraph, sizes),
+                        TableCellContent::Table(table) => collect_table(table, sizes),
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    //Sizes by relationship id first, then mapped to the media paths
+    let mut sizes = HashMap::new();
+    for child in &docx.document.children {
+        match child {
+            DocumentChild::Paragraph(paragraph) => collect_paragraph(paragraph, &mut sizes),
+            DocumentChild::Table(table) => collect_table(table, &mut sizes),
+            _ => {}
+        }
+    }
+    docx.images
+        .iter()
+        .filter_map(|(id, path, ..)| Some((path.clone(), *sizes.get(id)?)))
+        .filter(|(_, (cx, cy))| *cx > 0 && *cy > 0)
+        .collect()
+}
+
+/// Loads images the document links to rather than embeds. Only local paths
+/// (plain or `file://`) are followed, relative ones from `base`; anything
+/// unreachable, or declaring more than `max_pixels` pixels, is skipped with
+/// a warning.
+fn extract_external_images(
+    docx: &[u8],
+    base: &Path,
+    max_pixels: Option<u64>,
+) -> Result<Vec<(String, DynamicImage)>, ConversionError> {
+    let Some(xml) = read_part(docx, "word/_rels/document.xml.rels")? else { return Ok(Vec::new()) };
+
+    let mut targets = Vec::new();
+    let mut reader = Reader::from_reader(&xml[..]);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Empty(ref e)) | Ok(Event::Start(ref e))
+                if e.local_name() == b"Relationship" =>
+            {
+                let attributes: HashMap<Vec<u8>, String> = e
+                    .attributes()
+                    .flatten()
+                    .map(|a| (a.key.to_vec(), String::from_utf8_lossy(&a.value).into_owned()))
+                    .collect();
+                let external = attributes.get(&b"TargetMode"[..]).map(String::as_str) == Some("External");
+                let image = attributes.get(&b"Type"[..]).is_some_and(|t| t.ends_with("/image"));
+                if let (true, true, Some(target)) = (external, image, attributes.get(&b"Target"[..])) {
+                    targets.push(target.clone());
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(ConversionError::InvalidInput(format!("Malformed document.xml.rels: {}", e))),
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    let mut images = Vec::new();
+    for target in targets {
+        let local = match target.strip_prefix("file://") {
+            //file:///C:/x.png keeps its drive letter; file:///srv/x.png keeps its root
+            Some(path) if path.len() > 3 && path.as_bytes()[2] == b':' => path[1..].replace("%20", " "),
+            Some(path) => path.replace("%20", " "),
+            None if target.contains("://") => {
+                warn!("Skipping linked image {}: only local files are loaded", target);
+                continue;
+            }
+            None => target.clone(),
+        };
+        let path = base.join(&local);
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                warn!("Skipping linked image {}: {}", target, e);
+                continue;
+            }
+        };
+        if max_pixels.is_some_and(|limit| exceeds_limit(&target, &bytes, limit)) {
+            continue;
+        }
+        match ::image::load_from_memory(&bytes) {
+            Ok(img) => {
+                info!("Loaded linked image: {}", path.display());
+                images.push((target, img));
+            }
+            Err(e) => warn!("Skipping linked image {}: {}", target, e),
+        }
+    }
+    Ok(images)
+}
+
+/// Raw bytes of a part inside the docx archive, or `None` if it is absent.
+fn read_part(docx: &[u8], part: &str) -> Result<Option<Vec<u8>>, ConversionError> {
+    let mut archive = ZipArchive::new(Cursor::new(docx))?;
+    let mut xml = Vec::new();
+    match archive.by_name(part) {
+        Ok(mut entry) => entry.read_to_end(&mut xml)?,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(Some(xml))
+}
+
+/// Value of the attribute of `element` whose local name is `name`, whatever its prefix.
+fn xml_attribute(element: &BytesStart, name: &[u8]) -> Option<String> {
+    element
+        .attributes()
+        .flatten()
+        .find(|attribute| attribute.key.rsplit(|b| *b == b':').next() == Some(name))
+        .map(|attribute| String::from_utf8_lossy(&attribute.value).into_owned())
+}
+
+/// docx_rs keeps most element properties private but serializes all of
+/// them, so property lookups go through their JSON form.
+fn property_json<T: Serialize>(property: &T) -> Value {
+    serde_json::to_value(property).unwrap_or(Value::Null)
+}
+
+struct Fonts {
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+    italic: IndirectFontRef,
+    /// Font for Chinese, Japanese and Korean characters, from `--east-asian-font`.
+    east_asian: Option<IndirectFontRef>,
+    /// Faces embedded in the document being rendered.
+    embedded: Vec<EmbeddedFace>,
+    /// The family of runs that name none, in the document being rendered.
+    default_family: Option<String>,
+}
+
+impl Fonts {
+    /// Adds the built-in fonts to `doc`. A bold or italic face that cannot
+    /// be added is replaced by the regular one, with a warning, rather than
+    /// failing the conversion.
+    fn load(doc: &PdfDocumentReference, east_asian_path: Option<&str>) -> Result<Self, ConversionError> {
+        let regular = doc.add_builtin_font(BuiltinFont::Helvetica)?;
+        let styled = |font: BuiltinFont| {
+            doc.add_builtin_font(font).unwrap_or_else(|e| {
+                warn!("Could not add the built-in font {:?} ({}); using Helvetica instead", font, e);
+                regular.clone()
+            })
+        };
+        //Without it East Asian characters get the fallback glyph, as before
+        let east_asian = east_asian_path.and_then(|path| {
+            File::open(path)
+                .map_err(ConversionError::from)
+                .and_then(|file| Ok(doc.add_external_font(file)?))
+                .inspect_err(|e| warn!("Could not load the East Asian font {} ({}); its characters are replaced", path, e))
+                .ok()
+        });
+        Ok(Fonts {
+            bold: styled(BuiltinFont::HelveticaBold),
+            italic: styled(BuiltinFont::HelveticaOblique),
+            regular,
+            east_asian,
+            embedded: Vec::new(),
+            default_family: None,
+        })
+    }
+
+    /// Switches to the embedded fonts and default family of the next
+    /// document, adding its fonts to `doc`.
+    fn use_embedded(&mut self, doc: &PdfDocumentReference, fonts: &[EmbeddedFont], default_family: Option<String>) {
+        self.embedded = fonts.iter().filter_map(|font| EmbeddedFace::add(doc, font)).collect();
+        self.default_family = default_family;
+    }
+
+    /// Whether `font` is one of the built-in fonts, written with a one-byte
+    /// encoding rather than Identity-H.
+    fn is_built_in(&self, font: &IndirectFontRef) -> bool {
+        [&self.regular, &self.bold, &self.italic].contains(&font)
+    }
+
+    fn embedded_face(&self, font: &IndirectFontRef) -> Option<&EmbeddedFace> {
+        self.embedded.iter().find(|face| face.font == *font)
+    }
+
+    /// PostScript name of `font`: one of the built-in fonts above, that of
+    /// an embedded font, or `EastAsian` for the East Asian font.
+    fn name<'f>(&'f self, font: &IndirectFontRef) -> &'f str {
+        //Embedded and East Asian faces first; then regular before bold and
+        //oblique, as a face that failed to load is the regular font
+        if let Some(face) = self.embedded_face(font) {
+            &face.name
+        } else if self.east_asian.as_ref() == Some(font) {
+            "EastAsian"
+        } else if *font == self.regular {
+            "Helvetica"
+        } else if *font == self.bold {
+            "Helvetica-Bold"
+        } else {
+            "Helvetica-Oblique"
+        }
+    }
+
+    /// Width of `text` set in `font` at `font_size` points, in mm.
+    fn text_width(&self, font: &IndirectFontRef, text: &str, font_size: f32) -> f32 {
+        match self.embedded_face(font) {
+            Some(face) => face.metrics.width(text, font_size),
+            //Helvetica-Oblique slants the regular glyphs without changing their widths
+            None if *font == self.bold && *font != self.regular => bold_text_width(text, font_size),
+            None => text_width(text, font_size),
+        }
+    }
+
+    /// The face of `family` the document embeds in the weight and style of
+    /// `properties`, those of a run.
+    fn embedded_in(&self, family: &str, properties: &Value) -> Option<&EmbeddedFace> {
+        let bold = properties["bold"].as_bool() == Some(true);
+        let italic = properties["italic"].as_bool() == Some(true);
+        self.embedded.iter().find(|face| face.family.eq_ignore_ascii_case(family) && face.bold == bold && face.italic == italic)
+    }
+
+    fn for_run(&self, run: &Run) -> &IndirectFontRef {
+        let properties = property_json(&run.run_property);
+        let bold = properties["bold"].as_bool() == Some(true);
+        let italic = properties["italic"].as_bool() == Some(true);
+        //A family the document embeds is used in the face the run asks for
+        let family = properties["fonts"]["ascii"].as_str().or(self.default_family.as_deref());
+        if let Some(face) = family.and_then(|family| self.embedded_in(family, &properties)) {
+            &face.font
+        } else if bold {
+            &self.bold
+        } else if italic {
+            &self.italic
+        } else {
+            &self.regular
+        }
+    }
+
+    /// Font for the East Asian characters of `run`: its `w:eastAsia` family
+    /// if the document embeds it, else the one from `--east-asian-font`.
+    fn east_asian_for_run(&self, run: &Run) -> Option<&IndirectFontRef> {
+        let properties = property_json(&run.run_property);
+        let embedded = properties["fonts"]["eastAsia"].as_str().and_then(|family| self.embedded_in(family, &properties));
+        embedded.map(|face| &face.font).or(self.east_asian.as_ref())
+    }
+}
+
+/// A run of text placed on a page, as passed to the callbacks of
+/// `convert_file_with` and `convert_parsed_with` before it is drawn.
+/// Positions are in mm from the bottom left corner of the page.
+#[derive(Debug, Clone, Copy)]
+pub struct LaidOutRun<'t> {
+    /// The text as drawn, with small capitals uppercased and characters the
+    /// fonts cannot show replaced by the fallback glyph.
+    pub text: &'t str,
+    /// Zero-based index of the page in the output.
+    pub page: usize,
+    /// Left end of the text.
+    pub x: f32,
+    /// The baseline the text sits on.
+    pub y: f32,
+    /// Advance width of the text, including any letter spacing.
+    pub width: f32,
+    /// Font size in points.
+    pub font_size: f32,
+    /// PostScript name of the font, such as `Helvetica-Bold`.
+    pub font: &'t str,
+}
+
+/// Receives each run of text laid out.
+type RunCallback<'c> = dyn FnMut(&LaidOutRun) + 'c;
+
+/// Tracks the current page layer and vertical cursor while laying out content.
+struct Layout<'a> {
+    doc: &'a PdfDocumentReference,
+    layer: PdfLayerReference,
+    /// Top of the next line box, in mm from the bottom of the page.
+    y_position: f32,
+    config: &'a Config,
+    hyphenation: Option<Hyphenation>,
+    /// Language of the document being laid out, for runs that give none.
+    language: String,
+    /// Characters replaced by the fallback glyph so far.
+    missing_glyphs: RefCell<BTreeSet<char>>,
+    /// Called with each run of text before it is drawn.
+    on_run: RefCell<Option<&'a mut RunCallback<'a>>>,
+    /// Takes down the pages laid out, for `build_layout_model`.
+    model: RefCell<Option<&'a mut LayoutModel>>,
+    /// Finds the `--highlight` terms in the runs drawn.
+    highlighter: RefCell<Highlighter>,
+    /// The `--redact` patterns.
+    redactor: Redactor,
+    fonts: Fonts,
+    font_size: f32,
+    line_height: f32,
+    footnotes: FootnoteArea,
+    endnotes: EndnoteList,
+    table_styles: TableStyles,
+    numbering: Numbering,
+    list_counters: ListCounters,
+    /// Left indent of the paragraph being laid out, in mm.
+    left_indent: f32,
+    /// Extra advance after each space of the line being drawn, in mm, while
+    /// a justified line is stretched.
+    word_spacing: f32,
+    /// Where the last body line ended, for placing reference marks after it.
+    last_line_end: Option<(f32, f32)>,
+    /// Top left corner of the drawing canvas whose shapes are being drawn.
+    canvas_origin: (f32, f32),
+    /// The drop cap the current paragraph wraps around.
+    drop_cap: Option<DropCap>,
+    /// Top of the paragraph box still open on this page, with `--debug-boxes`.
+    paragraph_top: Option<f32>,
+    /// Interval between default tab stops, in mm.
+    tab_stop: f32,
+    /// Where East Asian text is spaced from the text beside it.
+    auto_space: AutoSpace,
+    /// Zero-based index of the current page.
+    page: usize,
+    /// How the pages laid out so far are numbered.
+    page_numbers: PageNumbers,
+    links: LinkTable,
+    /// The body's headings, listed by tables of contents.
+    headings: Vec<Heading>,
+    /// Headings whose destinations have been placed so far.
+    headings_placed: usize,
+    /// Table of contents lines waiting for their page numbers.
+    toc_lines: Vec<TocLine>,
+    /// The document's images by relationship id, and by archive path, with
+    /// their archive paths, for the previews of embedded objects and charts.
+    previews: HashMap<String, (&'a str, &'a DynamicImage)>,
+    /// Archive paths of the previews drawn in the body, which are not
+    /// repeated after it.
+    placed_previews: HashSet<String>,
+    /// Alternative text of the document's images, by relationship id.
+    alt_texts: HashMap<String, String>,
+    /// The structure elements recorded with `--tagged`.
+    tags: RefCell<StructureTree>,
+    /// The watermark of the document being laid out, drawn on each new page.
+    watermark: Option<Watermark>,
+    /// The colour text is being drawn in, which outlines are stroked in
+    /// with `--text-render-mode`.
+    text_color: Cell<(f32, f32, f32)>,
+    /// The font East Asian characters are being drawn in, when the run
+    /// being drawn names one.
+    east_asian: RefCell<Option<IndirectFontRef>>,
+    /// Angle, counter-clockwise in degrees, and origin of the line being
+    /// drawn when it is turned, as in vertical table cells. The line is
+    /// laid out level and turned about its origin.
+    rotation: Cell<Option<(f32, (f32, f32))>>,
+    /// The first page of each section laid out so far, for `--split-by`.
+    section_starts: Vec<usize>,
+}
+
+impl<'a> Layout<'a> {
+    fn new_page(&mut self) {
+        self.flush_footnotes();
+        let in_paragraph = self.paragraph_top.is_some();
+        debug::end_paragraph(self);
+        let (new_page, new_layer) = self.doc.add_page(
+            Mm(self.config.page_width),
+            Mm(self.config.page_height),
+            "Layer 1",
+        );
+        self.layer = self.doc.get_page(new_page).get_layer(new_layer);
+        self.page += 1;
+        if let Some(model) = self.model.get_mut() {
+            model.add_page(self.config.page_width, self.config.page_height);
+        }
+        self.y_position = self.config.page_height - self.config.margin;
+        draw_watermark(self);
+        draw_margin_box(self);
+        if in_paragraph {
+            debug::begin_paragraph(self);
+        }
+    }
+
+    /// Moves to the top of the next column. The body is laid out in a single
+    /// column, so that is the top of the next page.
+    fn next_column(&mut self) {
+        self.new_page();
+    }
+
+    /// Baseline of a line of body text whose box starts at the cursor.
+    fn baseline(&self) -> f32 {
+        self.y_position - ASCENT * self.line_height
+    }
+
+    /// Byte offsets where `word`, in a run of `language`, may be hyphenated;
+    /// none without `--hyphenate`. Runs that give no language are in the
+    /// document's, and `--lang` overrides both.
+    fn break_points(&self, language: Option<&str>, word: &str) -> Vec<usize> {
+        let Some(hyphenation) = &self.hyphenation else { return Vec::new() };
+        let language = self.config.lang.as_deref().or(language).unwrap_or(&self.language);
+        hyphenation.for_language(language).map_or_else(Vec::new, |hyphenator| hyphenator.break_points(word))
+    }
+
+    /// Wraps `content`, set in `font`, to `max_width` mm using the configured
+    /// line breaking. Each newline in it starts a new line, and one after
+    /// another leave an empty line between them.
+    fn wrap(&self, content: &str, max_width: f32, font_size: f32, font: &IndirectFontRef) -> Vec<String> {
+        let tabs = TabStops::defaults(self.tab_stop);
+        let measure = |_, text: &str| self.fonts.text_width(font, text, font_size);
+        let hyphenate = |_, word: &str| self.break_points(None, word);
+        let wrap_line = |line: &str| wrap_text(line, max_width, &measure, tabs, self.config.wrap_algorithm, &hyphenate);
+        if !content.contains('\n') {
+            return wrap_line(content);
+        }
+        content
+            .split('\n')
+            .flat_map(|line| {
+                let lines = wrap_line(line);
+                if lines.is_empty() { vec![String::new()] } else { lines }
+            })
+            .collect()
+    }
+
+    /// Wraps body text across the usable width, measuring it with `measure`,
+    /// hyphenating it with `hyphenate` and moving tabs to `tab_stops`. Lines
+    /// beside a drop cap are narrowed and returned with the indent they need.
+    fn wrap_body(
+        &mut self,
+        content: &str,
+        measure: &dyn Fn(&Layout, usize, &str) -> f32,
+        hyphenate: &dyn Fn(&Layout, usize, &str) -> Vec<usize>,
+        tab_stops: &[TabStop],
+    ) -> Vec<(f32, String)> {
+        //Stops are measured from the margin, lines from their indent
+        let tabs = |indent: f32| TabStops { explicit: tab_stops, origin: -indent, interval: self.tab_stop };
+        let left = self.left_indent;
+        let usable_width = self.config.page_width - 2.0 * self.config.margin - left;
+        let (indent, beside) = match self.drop_cap.as_ref() {
+            Some(drop_cap) if drop_cap.lines > 0 => {
+                //Wrapped greedily and unhyphenated so the characters used can be counted off
+                let measure = |offset, text: &str| measure(self, offset, text);
+                let beside: Vec<String> =
+                    wrap_text(content, usable_width - drop_cap.indent, &measure, tabs(left + drop_cap.indent), WrapAlgorithm::Greedy, &no_hyphenation)
+                        .into_iter()
+                        .take(drop_cap.lines)
+                        .collect();
+                (drop_cap.indent, beside)
+            }
+            _ => (0.0, Vec::new()),
+        };
+        //The rest is wrapped where it lies in `content`, so `measure` still finds its runs
+        let is_set = |c: &char| !c.is_whitespace() || *c == '\t';
+        let used = beside.iter().flat_map(|line| line.chars()).filter(is_set).count();
+        let rest_start = content.char_indices().filter(|(_, c)| is_set(c)).nth(used).map_or(content.len(), |(i, _)| i);
+        let rest_measure = |offset: usize, text: &str| measure(self, rest_start + offset, text);
+        let rest_hyphenate = |offset: usize, word: &str| hyphenate(self, rest_start + offset, word);
+        let algorithm = self.config.wrap_algorithm;
+        let rest_lines = wrap_text(&content[rest_start..], usable_width, &rest_measure, tabs(left), algorithm, &rest_hyphenate);
+
+        if let Some(drop_cap) = self.drop_cap.as_mut() {
+            drop_cap.lines -= beside.len();
+        }
+        let mut lines: Vec<(f32, String)> = beside.into_iter().map(|line| (left + indent, line)).collect();
+        lines.extend(rest_lines.into_iter().map(|line| (left, line)));
+        lines
+    }
+
+    /// Sets the fill colour and East Asian font for the text of `run`; runs
+    /// without an explicit colour are black.
+    fn set_run_style(&self, run: &Run) {
+        self.set_run_style_or(run, (0.0, 0.0, 0.0));
+    }
+
+    /// Sets the fill colour and East Asian font for the text of `run`, using
+    /// `default` if it has no explicit colour.
+    fn set_run_style_or(&self, run: &Run, default: (f32, f32, f32)) {
+        let properties = property_json(&run.run_property);
+        let rgb = properties["color"].as_str().and_then(parse_hex).unwrap_or(default);
+        //In black and white, light text would vanish against the page
+        let rgb = if matches!(self.config.color_space, ColorMode::Monochrome(_)) { (0.0, 0.0, 0.0) } else { rgb };
+        self.set_text_color(rgb);
+        *self.east_asian.borrow_mut() = self.fonts.east_asian_for_run(run).cloned();
+    }
+
+    /// Sets the colour of the text drawn next, which belongs to no run and
+    /// so takes the East Asian font from `--east-asian-font`.
+    fn set_text_color(&self, rgb: (f32, f32, f32)) {
+        self.layer.set_fill_color(fill_color(self.config.color_space, rgb));
+        self.text_color.set(rgb);
+        *self.east_asian.borrow_mut() = self.fonts.east_asian.clone();
+    }
+
+    /// `text` with the characters the built-in fonts cannot show replaced by
+    /// the fallback glyph, noting the characters replaced.
+    fn printable<'t>(&self, text: &'t str, font: &IndirectFontRef) -> Cow<'t, str> {
+        //Control characters such as tabs are handled by the caller
+        let has_east_asian = self.east_asian.borrow().is_some();
+        let embedded = self.fonts.embedded_face(font).map(|face| &face.metrics);
+        let has_glyph = |c: char| embedded.map_or(is_win_ansi(c), |metrics| metrics.has_glyph(c));
+        let missing = |c: &char| !(has_glyph(*c) || c.is_control() || has_east_asian && is_cjk(*c));
+        if !text.chars().any(|c| missing(&c)) {
+            return Cow::Borrowed(text);
+        }
+        self.missing_glyphs.borrow_mut().extend(text.chars().filter(missing));
+        Cow::Owned(text.chars().map(|c| if missing(&c) { self.config.fallback_glyph } else { c }).collect())
+    }
+
+    /// Tells the `on_run` callback and the layout model, if there are any,
+    /// that `text` is about to be drawn on `page` at `x` on the baseline `y`.
+    fn report_run(&self, page: usize, text: &str, (x, y): (f32, f32), width: f32, font_size: f32, font: &IndirectFontRef) {
+        let mut on_run = self.on_run.borrow_mut();
+        let mut highlighter = self.highlighter.borrow_mut();
+        let mut model = self.model.borrow_mut();
+        if on_run.is_none() && !highlighter.is_active() && model.is_none() {
+            return;
+        }
+        let run = LaidOutRun { text, page, x, y, width, font_size, font: self.fonts.name(font) };
+        if highlighter.is_active() {
+            highlighter.add_run(&run);
+        }
+        if let Some(model) = model.as_mut() {
+            model.add_run(&run);
+        }
+        if let Some(on_run) = on_run.as_mut() {
+            on_run(&run);
+        }
+    }
+
+    /// Takes down in the layout model, if there is one, that the picture at
+    /// the archive path `name` is drawn on the current page with its bottom
+    /// left corner at `(x, y)`.
+    fn report_image(&self, name: &str, (x, y): (f32, f32), width: f32, height: f32) {
+        self.check_overflow(self.page, (x, y), width, height, || format!("picture {}", name));
+        if let Some(model) = self.model.borrow_mut().as_mut() {
+            model.add_image(self.page, ImageModel { name: name.to_string(), x, y, width, height });
+        }
+    }
+
+    /// Warns if the box `width` by `height` mm with its bottom left corner
+    /// at `(x, y)` reaches off page `page`, naming the page, the edge and
+    /// the element `describe` gives.
+    fn check_overflow(&self, page: usize, position: (f32, f32), width: f32, height: f32, describe: impl FnOnce() -> String) {
+        if let Some((edge, distance)) = overflow(self.config, position, width, height) {
+            warn!("Page {}: {} reaches {:.1} mm past the {} edge", page + 1, describe(), distance, edge);
+        }
+    }
+
+    /// Writes `text` at `x` on the baseline `y` of the current page, with
+    /// `tracking` mm after each character, and returns its width in mm.
+    fn draw_text(&self, text: &str, position: (f32, f32), font_size: f32, font: &IndirectFontRef, tracking: f32) -> f32 {
+        self.draw_text_on((&self.layer, self.page), text, position, font_size, font, tracking)
+    }
+
+    /// Like `draw_text`, on the layer of an earlier page. Every line of text
+    /// reaches the PDF through here: characters without a glyph are
+    /// replaced, redacted text is cut out and boxed, and the rest reported
+    /// and tagged as content of the structure element open.
+    fn draw_text_on(
+        &self,
+        (layer, page): (&PdfLayerReference, usize),
+        text: &str,
+        (x, y): (f32, f32),
+        font_size: f32,
+        font: &IndirectFontRef,
+        tracking: f32,
+    ) -> f32 {
+        let text = self.printable(text, font);
+        let east_asian = self.east_asian.borrow().clone();
+        let advance = |part: &str, font: &IndirectFontRef| {
+            self.fonts.text_width(font, part, font_size) + tracking * part.chars().count() as f32 + self.word_spacing * part.matches(' ').count() as f32
+        };
+        let marked = self.tags.borrow_mut().begin_content(layer, page);
+        let mut cursor = x;
+        let mut drawn = 0;
+        let draw_visible = |visible: &str, cursor: &mut f32| {
+            //Split where the script changes, East Asian text taking its own font
+            for (is_east_asian, segment) in script_segments(visible, east_asian.is_some()) {
+                let font = if is_east_asian { east_asian.as_ref().unwrap_or(font) } else { font };
+                let width = advance(segment, font);
+                //A turned line is kept inside its table cell
+                if !segment.trim().is_empty() && self.rotation.get().is_none() {
+                    //Spaces at the end of a line reaching the edge are not seen
+                    let (inked, height) = (advance(segment.trim_end(), font), font_size * PT_TO_MM);
+                    let excerpt = || format!("text \"{}\"", segment.trim().chars().take(40).collect::<String>());
+                    self.check_overflow(page, (*cursor, y - DESCENT * height), inked, height, excerpt);
+                }
+                self.report_run(page, segment, self.on_page((*cursor, y)), width, font_size, font);
+                if self.config.text_render_mode != TextRenderMode::Fill {
+                    layer.save_graphics_state();
+                    layer.set_text_rendering_mode(self.config.text_render_mode.rendering_mode());
+                    layer.set_outline_color(fill_color(self.config.color_space, self.text_color.get()));
+                }
+                if self.word_spacing != 0.0 && !self.fonts.is_built_in(font) {
+                    //Word spacing only widens the one-byte spaces of the built-in
+                    //fonts, so words in a two-byte font are placed one by one
+                    let mut x = *cursor;
+                    for word in segment.split_inclusive(' ') {
+                        self.place_text(layer, word, font_size, (x, y), font);
+                        x += advance(word, font);
+                    }
+                } else {
+                    self.place_text(layer, segment, font_size, (*cursor, y), font);
+                }
+                if self.config.text_render_mode != TextRenderMode::Fill {
+                    layer.restore_graphics_state();
+                }
+                *cursor += width;
+            }
+        };
+        for hidden in self.redactor.matches(&text) {
+            draw_visible(&text[drawn..hidden.start], &mut cursor);
+            let width = advance(&text[hidden.clone()], font);
+            self.draw_redaction_box(layer, (cursor, y), width, font_size);
+            cursor += width;
+            drawn = hidden.end;
+        }
+        draw_visible(&text[drawn..], &mut cursor);
+        if marked {
+            StructureTree::end_content(layer);
+        }
+        cursor - x
+    }
+
+    /// Where the point `(x, y)` of the line being drawn lies on the page,
+    /// once the line is turned.
+    fn on_page(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let Some((angle, (origin_x, origin_y))) = self.rotation.get() else { return (x, y) };
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let (dx, dy) = (x - origin_x, y - origin_y);
+        (origin_x + dx * cos - dy * sin, origin_y + dx * sin + dy * cos)
+    }
+
+    /// Writes `text` with its baseline starting at `(x, y)`, turned with the
+    /// line being drawn.
+    fn place_text(&self, layer: &PdfLayerReference, text: &str, font_size: f32, (x, y): (f32, f32), font: &IndirectFontRef) {
+        let Some((angle, _)) = self.rotation.get() else {
+            layer.use_text(text, font_size, Mm(x), Mm(y), font);
+            return;
+        };
+        let (x, y) = self.on_page((x, y));
+        layer.begin_text_section();
+        layer.set_font(font, font_size);
+        layer.set_text_matrix(TextMatrix::TranslateRotate(Mm(x).into_pt(), Mm(y).into_pt(), angle));
+        layer.write_text(text, font);
+        layer.end_text_section();
+    }
+
+    /// Draws the black box standing in for `width` mm of redacted text on
+    /// the baseline `y`, turned with the line being drawn.
+    fn draw_redaction_box(&self, layer: &PdfLayerReference, (x, y): (f32, f32), width: f32, font_size: f32) {
+        let height = font_size * 1.2 * PT_TO_MM;
+        let black = fill_color(self.config.color_space, (0.0, 0.0, 0.0));
+        layer.save_graphics_state();
+        match self.rotation.get() {
+            Some((angle, _)) => {
+                let (x, y) = self.on_page((x, y));
+                layer.set_ctm(CurTransMat::TranslateRotate(Mm(x).into_pt(), Mm(y).into_pt(), angle));
+                fill_rect(layer, 0.0, -DESCENT * height, width, height, black);
+            }
+            None => fill_rect(layer, x, y - DESCENT * height, width, height, black),
+        }
+        layer.restore_graphics_state();
+    }
+
+    /// Wraps `text` across the full line width and writes it in black at the cursor.
+    fn write_lines(&mut self, text: &str, font: &IndirectFontRef) {
+        let usable_width = self.config.page_width - 2.0 * self.config.margin;
+        for line in self.wrap(text, usable_width, self.font_size, font) {
+            self.ensure_space(self.line_height);
+            self.set_text_color((0.0, 0.0, 0.0));
+            let baseline = self.baseline();
+            let width = self.draw_text(&line, (self.config.margin, baseline), self.font_size, font, 0.0);
+            draw_line_box(self, self.config.margin, baseline, width);
+            self.y_position -= self.line_height;
+        }
+    }
+
+    /// Starts a new page if `height` mm would not fit above the bottom margin
+    /// and any footnotes already placed on this page.
+    fn ensure_space(&mut self, height: f32) {
+        //Measured heights are sums of rounded lengths, so a block that fits exactly may come out a hair over
+        if self.y_position - height < self.config.margin + self.footnotes.height - FIT_TOLERANCE {
+            self.new_page();
+        }
+    }
+
+    /// Draws a superscript note number after the last body line.
+    fn draw_reference_mark(&mut self, number: usize) {
+        let (x, y) = self.last_line_end.unwrap_or((self.config.margin, self.baseline()));
+        let mark = number.to_string();
+        let font = self.fonts.regular.clone();
+        self.set_text_color((0.0, 0.0, 0.0));
+        //Superscript: raised by a third of the body size
+        let mark_size = MARK_FONT_SIZE * self.config.font_scale;
+        let width = self.draw_text(&mark, (x, y + self.font_size * PT_TO_MM / 3.0), mark_size, &font, 0.0);
+        self.last_line_end = Some((x + width, y));
+    }
+
+    /// Marks a footnote reference after the last body line and reserves
+    /// room for the note at the bottom of the page.
+    fn add_footnote(&mut self, id: u32) {
+        let (number, text) = self.footnotes.reference(id);
+        self.draw_reference_mark(number);
+
+        let usable_width = self.config.page_width - 2.0 * self.config.margin;
+        let lines = self.wrap(&format!("{} {}", number, text), usable_width, self.footnotes.font_size, &self.fonts.regular);
+        if self.y_position - self.footnotes.height_for(lines.len()) < self.config.margin + self.footnotes.height {
+            //No room left on this page, so the note moves to the next one
+            self.new_page();
+        }
+        self.footnotes.push(lines);
+    }
+
+    /// Draws the pending footnotes under a short rule at the bottom of the page.
+    fn flush_footnotes(&mut self) {
+        let Some((notes, rule_height)) = self.footnotes.take() else { return };
+        let margin = self.config.margin;
+        let usable_width = self.config.page_width - 2.0 * self.config.margin;
+        let black = fill_color(self.config.color_space, (0.0, 0.0, 0.0));
+        let rule_y = margin + rule_height;
+        self.layer.set_outline_color(black.clone());
+        self.layer.set_outline_thickness(0.5);
+        self.layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(margin), Mm(rule_y)), false),
+                (Point::new(Mm(margin + usable_width / 3.0), Mm(rule_y)), false),
+            ],
+            is_closed: false,
+        });
+
+        let font = self.fonts.regular.clone();
+        let (font_size, line_height) = (self.footnotes.font_size, self.footnotes.line_height());
+        let mut y = rule_y - 1.5 - ASCENT * line_height;
+        self.set_text_color((0.0, 0.0, 0.0));
+        for note in &notes {
+            self.tags.get_mut().begin("Note");
+            for line in note {
+                self.draw_text(line, (margin, y), font_size, &font, 0.0);
+                y -= line_height;
+            }
+            self.tags.get_mut().end();
+        }
+    }
+
+    /// Completes the last page and reports the characters that had no
+    /// glyph, which is an error with `--strict`.
+    fn finish(&mut self) -> Result<(), ConversionError> {
+        self.flush_footnotes();
+        let missing = self.missing_glyphs.take();
+        if !missing.is_empty() {
+            let list: Vec<String> = missing.iter().map(|c| format!("U+{:04X} {}", *c as u32, c)).collect();
+            if self.config.strict {
+                return Err(ConversionError::Unsupported(format!("no glyph in the built-in fonts for {}", list.join(", "))));
+            }
+            warn!("No glyph in the built-in fonts for {}; drawn as {}", list.join(", "), self.config.fallback_glyph);
+        }
+        Ok(())
+    }
+}
+
+fn run_text(run: &Run) -> String {
+    let mut text = String::new();
+    for child in &run.children {
+        match child {
+            RunChild::Text(t) => text.push_str(&t.text),
+            RunChild::Tab(_) => text.push('\t'),
+            RunChild::Sym(sym) => text.push_str(marker_text(sym).unwrap_or_default()),
+            //Drawn elsewhere, or field codes and markers with nothing to show
+            RunChild::Break(_)
+            | RunChild::Drawing(_)
+            | RunChild::CommentStart(_)
+            | RunChild::CommentEnd(_)
+            | RunChild::FieldChar(_)
+            | RunChild::InstrText(_)
+            | RunChild::InstrTextString(_)
+            | RunChild::DeleteInstrText(_) => {}
+            other => text.push_str(&fallback_text(other)),
+        }
+    }
+    text
+}
+
+/// Whatever text a run child the converter has no handling for holds, so
+/// that it is not silently dropped.
+fn fallback_text(child: &RunChild) -> String {
+    fn collect(value: &Value, text: &mut String) {
+        match value {
+            Value::Object(fields) => {
+                for (key, value) in fields {
+                    match value.as_str() {
+                        Some(s) if key == "text" => text.push_str(s),
+                        _ => collect(value, text),
+                    }
+                }
+            }
+            Value::Array(items) => items.iter().for_each(|item| collect(item, text)),
+            _ => {}
+        }
+    }
+    let value = property_json(child);
+    let mut text = String::new();
+    collect(&value, &mut text);
+    if !text.is_empty() {
+        debug!("Showing the raw text of an unhandled {} run child", value["type"].as_str().unwrap_or("unknown"));
+    }
+    text
+}
+
+/// The runs of `paragraph` in order, including those inside hyperlinks.
+fn paragraph_runs(paragraph: &Paragraph) -> impl Iterator<Item = &Run> {
+    fn direct_run(child: &ParagraphChild) -> Option<&Run> {
+        match child {
+            ParagraphChild::Run(run) => Some(run.as_ref()),
+            _ => None,
+        }
+    }
+    paragraph.children.iter().flat_map(|child| match child {
+        ParagraphChild::Hyperlink(link) => link.children.iter().filter_map(direct_run).collect(),
+        child => direct_run(child).into_iter().collect::<Vec<_>>(),
+    })
+}
+
+/// `children` with the paragraphs and tables of content controls lifted into
+/// the body, as Word wraps its tables of contents in one.
+fn flatten_tags(children: &[DocumentChild]) -> Vec<DocumentChild> {
+    fn lift(tag_children: &[StructuredDataTagChild], body: &mut Vec<DocumentChild>) {
+        for child in tag_children {
+            match child {
+                StructuredDataTagChild::Paragraph(paragraph) => body.push(DocumentChild::Paragraph(paragraph.clone())),
+                StructuredDataTagChild::Table(table) => body.push(DocumentChild::Table(table.clone())),
+                StructuredDataTagChild::StructuredDataTag(tag) => lift(&tag.children, body),
+                _ => {}
+            }
+        }
+    }
+    let mut body = Vec::with_capacity(children.len());
+    for child in children {
+        match child {
+            DocumentChild::StructuredDataTag(tag) => lift(&tag.children, &mut body),
+            child => body.push(child.clone()),
+        }
+    }
+    body
+}
+
+/// Starts a final page listing the endnotes referenced in the body.
+fn render_endnotes(layout: &mut Layout) {
+    let entries = std::mem::take(&mut layout.endnotes.entries);
+    if entries.is_empty() {
+        return;
+    }
+    let bold = layout.fonts.bold.clone();
+    let regular = layout.fonts.regular.clone();
+    layout.new_page();
+    layout.write_lines("Endnotes", &bold);
+    layout.y_position -= layout.line_height / 2.0;
+    for (number, text) in entries {
+        layout.tags.get_mut().begin("Note");
+        layout.write_lines(&format!("{} {}", number, text), &regular);
+        layout.tags.get_mut().end();
+    }
+}
+
+/// Lays out one document from the layout cursor: its body, then its
+/// images, endnotes, comments and table of contents page numbers.
+fn render_document<'a>(layout: &mut Layout<'a>, source: SourceDocument<'a>, portrait: &'a Config, landscape: &'a Config) {
+    let SourceDocument { docx, images, parts, .. } = source;
+    layout.previews = docx
+        .images
+        .iter()
+        .filter_map(|(id, path, ..)| {
+            let (name, image) = images.iter().find(|(name, _)| name == path)?;
+            Some((id.clone(), (name.as_str(), image)))
+        })
+        .collect();
+    //Chart parts' own images are named by path
+    layout.previews.extend(images.iter().map(|(name, image)| (name.clone(), (name.as_str(), image))));
+    let section_configs: Vec<&Config> =
+        parts.sections.iter().map(|section| page_config(Some(section), portrait, landscape)).collect();
+    layout.footnotes = FootnoteArea::new(parts.notes.footnotes, NOTE_FONT_SIZE * portrait.font_scale);
+    layout.endnotes = EndnoteList::new(parts.notes.endnotes);
+    layout.table_styles = parts.table_styles;
+    layout.numbering = parts.numbering;
+    layout.list_counters.clear();
+    layout.tab_stop = parts.default_tab_stop;
+    layout.auto_space = parts.auto_space;
+    layout.alt_texts = parts.alt_texts;
+    layout.fonts.use_embedded(layout.doc, &parts.embedded_fonts, parts.default_font);
+    layout.language = parts.language.clone().unwrap_or_else(|| "en".to_string());
+
+    //Processes document content
+    //Sanitizing shows the document as if every change were accepted
+    let revisions = if portrait.sanitize { RevisionMode::Accept } else { portrait.revisions };
+    let children = resolve_revisions(&flatten_tags(&docx.document.children), revisions);
+    let children = if portrait.keeps_hidden() { children } else { remove_hidden(&children) };
+    let children = substitute_variables(&children, &parts.variables);
+    let children = &replace_toc_fields(children);
+    layout.headings = collect_headings(children);
+    layout.headings_placed = 0;
+    let ranges = section_ranges(children);
+    for (i, range) in ranges.iter().enumerate() {
+        let align = parts.sections.get(i).map_or(PageAlign::Top, |section| section.v_align);
+        let section_config = section_configs.get(i).copied().unwrap_or(portrait);
+        if i > 0 {
+            let start = parts.sections.get(i).map_or(SectionStart::NextPage, |section| section.start);
+            let resized = section_config.page_width != layout.config.page_width;
+            layout.config = section_config;
+            begin_section(layout, start, resized);
+        }
+        //A section starting part way down a page numbers the pages after it
+        let at_page_top = layout.y_position >= layout.config.page_height - layout.config.margin;
+        let numbering = parts.sections.get(i).map(|section| section.numbering).unwrap_or_default();
+        layout.page_numbers.number_from(layout.page + usize::from(!at_page_top), Some(numbering));
+        layout.section_starts.push(layout.page + usize::from(!at_page_top));
+        if parts.sections.get(i).is_some_and(|section| section.vertical) {
+            render_vertical_section(layout, &children[range.clone()]);
+        } else {
+            render_section(layout, &children[range.clone()], align);
+        }
+        //A vertically aligned section fills its page, so what follows starts a new one
+        if align != PageAlign::Top && i + 1 < ranges.len() {
+            layout.new_page();
+        }
+    }
+
+    // Adds the images if they exist, on pages sized like the last section's
+    let config = layout.config;
+    let extents = image_extents(docx);
+    let usable_width = config.page_width - 2.0 * config.margin;
+    let placed_previews = std::mem::take(&mut layout.placed_previews);
+    for (name, img) in images.iter().filter(|(name, _)| !placed_previews.contains(name)) {
+        let (width, height) = img.dimensions();
+        // Size the document displays the image at; without one it is fitted to the line at 300 dpi
+        let (dpi, display_width, display_height) = match extents.get(name) {
+            Some(&(cx, cy)) => {
+                let (cx, cy) = (cx as f32 * EMU_TO_MM, cy as f32 * EMU_TO_MM);
+                (effective_dpi(width, cx), cx, cy)
+            }
+            None => (300.0, usable_width, height as f32 * usable_width / width as f32),
+        };
+        // Never wider or taller than the text area, and centred when narrower
+        let fit = fit_scale(config, usable_width, display_width, display_height);
+        let (display_width, display_height) = (display_width * fit, display_height * fit);
+        let left = config.margin + (usable_width - display_width) / 2.0;
+        layout.ensure_space(display_height);
+
+        // Create an Image in the PDF
+        let image = pdf_image(img, config.color_space);
+
+        // Add the image to the current layer
+        let id = docx.images.iter().find(|(_, path, ..)| path == name).map(|(id, ..)| id);
+        let alt = id.and_then(|id| layout.alt_texts.get(id)).map(String::as_str);
+        let marked = layout.tags.get_mut().begin_figure(&layout.layer, layout.page, alt);
+        image.add_to_layer(
+            layout.layer.clone(),
+            ImageTransform {
+                translate_x: Some(Mm(left)),
+                translate_y: Some(Mm(layout.y_position - display_height)),
+                rotate: None,
+                scale_x: Some(display_width / (width as f32 / dpi * 25.4)),
+                scale_y: Some(display_height / (height as f32 / dpi * 25.4)),
+                dpi: Some(dpi),
+            },
+        );
+        layout.tags.get_mut().end_figure(&layout.layer, marked);
+        layout.report_image(name, (left, layout.y_position - display_height), display_width, display_height);
+        draw_debug_box(layout, DebugBox::Image, left, layout.y_position - display_height, display_width, display_height);
+        layout.y_position -= display_height + 10.0;
+    }
+    render_endnotes(layout);
+    if config.include_comments && !config.sanitize {
+        render_comments(layout, &collect_comments(docx));
+    }
+    fill_toc_lines(layout);
+}
+
+/// The configuration pages of `section` are laid out with.
+fn page_config<'c>(section: Option<&SectionExtras>, portrait: &'c Config, landscape: &'c Config) -> &'c Config {
+    if section.is_some_and(|section| section.landscape) { landscape } else { portrait }
+}
+
+fn render_pdf_bytes(
+    sources: Vec<SourceDocument>,
+    config: &Config,
+    on_run: Option<&mut RunCallback>,
+    model: Option<&mut LayoutModel>,
+) -> Result<Vec<u8>, ConversionError> {
+    render_pdf(sources, config, on_run, model).map(|(bytes, _)| bytes)
+}
+
+/// Like `render_pdf_bytes`, also returning the zero-based first page of
+/// each section laid out.
+fn render_pdf(
+    sources: Vec<SourceDocument>,
+    config: &Config,
+    on_run: Option<&mut RunCallback>,
+    model: Option<&mut LayoutModel>,
+) -> Result<(Vec<u8>, Vec<usize>), ConversionError> {
+    let version = config.header_version().map_err(ConversionError::Unsupported)?;
+    //Landscape sections turn their pages; the rest keep the configured size
+    let landscape = config.landscape();
+    let first_section = sources.first().and_then(|source| source.parts.sections.first());
+    let first_config = page_config(first_section, config, &landscape);
+    let (doc, page1, layer1) = PdfDocument::new(
+        "Word to PDF",
+        Mm(first_config.page_width),
+        Mm(first_config.page_height),
+        "Layer 1",
+
+    );
+    //Pins every timestamp printpdf would otherwise take from the clock
+    let doc = if config.reproducible || config.sanitize {
+        doc.with_creation_date(OffsetDateTime::UNIX_EPOCH)
+            .with_mod_date(OffsetDateTime::UNIX_EPOCH)
+            .with_metadata_date(OffsetDateTime::UNIX_EPOCH)
+    } else {
+        doc
+    };
+    //Neither the converter nor the time of conversion is recorded
+    let doc = if config.sanitize { doc.with_title("").with_creator("").with_producer("") } else { doc };
+    let fonts = Fonts::load(&doc, config.east_asian_font.as_deref())?;
+    let east_asian = RefCell::new(fonts.east_asian.clone());
+
+    let font_size = 12.0 * config.font_scale;
+    let mut layout = Layout {
+        doc: &doc,
+        layer: doc.get_page(page1).get_layer(layer1),
+        y_position: first_config.page_height - first_config.margin,
+        config: first_config,
+        missing_glyphs: RefCell::new(BTreeSet::new()),
+        on_run: RefCell::new(on_run.map(|on_run| on_run as &mut RunCallback)),
+        model: RefCell::new(model),
+        highlighter: RefCell::new(Highlighter::new(&config.highlight)),
+        redactor: Redactor::new(&config.redact)?,
+        hyphenation: config.hyphenate.as_deref().map(|directory| Hyphenation::new(Path::new(directory))),
+        language: "en".to_string(),
+        fonts,
+        font_size,
+        line_height: font_size * 1.2 * PT_TO_MM * config.line_height,
+        footnotes: FootnoteArea::new(HashMap::new(), NOTE_FONT_SIZE * config.font_scale),
+        endnotes: EndnoteList::new(HashMap::new()),
+        table_styles: TableStyles::new(),
+        numbering: Numbering::default(),
+        list_counters: ListCounters::new(),
+        left_indent: 0.0,
+        word_spacing: 0.0,
+        last_line_end: None,
+        canvas_origin: (0.0, 0.0),
+        drop_cap: None,
+        paragraph_top: None,
+        tab_stop: DEFAULT_TAB_STOP,
+        auto_space: AutoSpace::default(),
+        page: 0,
+        page_numbers: PageNumbers::default(),
+        links: LinkTable::default(),
+        headings: Vec::new(),
+        headings_placed: 0,
+        toc_lines: Vec::new(),
+        previews: HashMap::new(),
+        placed_previews: HashSet::new(),
+        alt_texts: HashMap::new(),
+        //Marked content moved into a sheet's forms would no longer belong to its page
+        tags: RefCell::new(StructureTree::new(config.tagged && config.nup.is_none())),
+        watermark: None,
+        text_color: Cell::new((0.0, 0.0, 0.0)),
+        east_asian,
+        rotation: Cell::new(None),
+        section_starts: Vec::new(),
+    };
+    if let Some(model) = layout.model.get_mut() {
+        model.add_page(first_config.page_width, first_config.page_height);
+    }
+    draw_margin_box(&layout);
+    if config.title_page {
+        let properties = sources.first().map(|source| source.parts.properties.clone()).unwrap_or_default();
+        //Documents without a title are named after their file
+        let path = config.input_paths.first().filter(|path| *path != STDIN_PATH);
+        let fallback = path.and_then(|path| Path::new(path).file_stem()).map_or("Untitled".into(), |stem| stem.to_string_lossy());
+        render_title_page(&mut layout, &properties, &fallback, !config.sanitize);
+    }
+
+    let mut attachments = Vec::new();
+    for (i, mut source) in sources.into_iter().enumerate() {
+        attachments.extend(source.attachment.take());
+        //Each further document starts on a page of its own, under its own watermark
+        layout.watermark = source.parts.watermark.take();
+        if i > 0 {
+            layout.config = page_config(source.parts.sections.first(), config, &landscape);
+            layout.new_page();
+        } else {
+            draw_watermark(&layout);
+        }
+        layout.links.start_document(i);
+        render_document(&mut layout, source, config, &landscape);
+    }
+    layout.finish()?;
+    let links = std::mem::take(&mut layout.links);
+    let highlighter = layout.highlighter.take();
+    let tags = layout.tags.take();
+    let page_numbers = std::mem::take(&mut layout.page_numbers);
+    let section_starts = std::mem::take(&mut layout.section_starts);
+    //Saves the PDF
+
+    let mut bytes = doc.save_to_bytes()?;
+    //Before the template, which goes under the highlights
+    if !highlighter.rects().is_empty() {
+        bytes = write_highlights(&bytes, highlighter.rects(), config.color_space)?;
+    }
+    if let Some(path) = &config.template {
+        bytes = add_template(&bytes, read_template(path)?)?;
+    }
+    if !links.is_empty() {
+        bytes = write_links(&bytes, &links)?;
+    }
+    //Sheets of several pages are not labelled
+    if !page_numbers.is_plain() && config.nup.is_none() {
+        bytes = write_page_labels(&bytes, &page_numbers)?;
+    }
+    //After the passes that work on the laid-out pages
+    if let Some(count) = config.nup {
+        bytes = impose(&bytes, count)?;
+    }
+    if !tags.is_empty() {
+        bytes = write_structure(&bytes, &tags)?;
+    }
+    if !attachments.is_empty() {
+        bytes = embed_attachments(&bytes, &attachments)?;
+    }
+    if config.zoom.is_some() || config.page_layout.is_some() {
+        bytes = set_view(&bytes, config.zoom, config.page_layout)?;
+    }
+    if let Some(path) = &config.icc_profile {
+        bytes = add_output_intent(&bytes, &read_icc_profile(path)?)?;
+    }
+    if !config.meta.is_empty() {
+        bytes = write_xmp(&bytes, &config.meta)?;
+    }
+    //After every pass that adds streams
+    if let Some(compression) = config.compression {
+        bytes = set_compression(&bytes, compression)?;
+    }
+    set_header_version(&mut bytes, version);
+    if config.reproducible {
+        bytes = with_content_id(&bytes)?;
+    }
+    Ok((bytes, section_starts))
+}
+
+fn measure_child(layout: &Layout, child: &DocumentChild) -> f32 {
+    match child {
+        DocumentChild::Paragraph(paragraph) => measure_paragraph(layout, paragraph),
+        DocumentChild::Table(table) => table_height(layout, table) + layout.line_height,
+        DocumentChild::TableOfContents(toc) => toc_height(layout, toc),
+        _ => 0.0,
+    }
+}
+
+fn render_child(layout: &mut Layout, child: &DocumentChild) {
+    match child {
+        DocumentChild::Paragraph(paragraph) => render_paragraph(layout, paragraph),
+        DocumentChild::Table(table) => {
+            render_table(layout, table);
+            layout.y_position -= layout.line_height;
+        }
+        DocumentChild::TableOfContents(toc) => render_toc(layout, toc),
+        _ => {}
+    }
+}
+
+/// `child` if it is a paragraph with `w:keepLines`, which is kept on one
+/// page unless it is taller than a page.
+fn kept_lines(child: &DocumentChild) -> Option<&Paragraph> {
+    match child {
+        DocumentChild::Paragraph(paragraph) if paragraph.property.keep_lines == Some(true) => Some(paragraph),
+        _ => None,
+    }
+}
+
+/// Lays out one section's body content. Sections aligned other than to the
+/// top start on a fresh page; if their content fits on it, the free space
+/// goes above it (center, bottom) or between its blocks (both).
+fn render_section(layout: &mut Layout, children: &[DocumentChild], align: PageAlign) {
+    //Measured before anything is placed, so placement can look ahead
+    let heights: Vec<f32> = children.iter().map(|child| measure_child(layout, child)).collect();
+    let mut gap = 0.0;
+    if align != PageAlign::Top {
+        if layout.y_position < layout.config.page_height - layout.config.margin {
+            layout.new_page();
+        }
+        //The gap after the last paragraph is not content
+        let content = heights.iter().sum::<f32>() - layout.line_height;
+        let free = layout.y_position - layout.config.margin - content;
+        if free > 0.0 {
+            match align {
+                PageAlign::Center => layout.y_position -= free / 2.0,
+                PageAlign::Bottom => layout.y_position -= free,
+                PageAlign::Both => {
+                    let blocks = heights.iter().filter(|height| **height > 0.0).count();
+                    gap = free / blocks.saturating_sub(1).max(1) as f32;
+                }
+                PageAlign::Top => {}
+            }
+        }
+    }
+    for (child, height) in children.iter().zip(&heights) {
+        // The gap after the paragraph may fall on the next page
+        if let Some(paragraph) = kept_lines(child)
+            && *height > 0.0
+        {
+            let lines_height = paragraph_content_height(layout, paragraph);
+            if lines_height <= layout.config.page_height - 2.0 * layout.config.margin {
+                layout.ensure_space(lines_height);
+            }
+        }
+        render_child(layout, child);
+        if *height > 0.0 {
+            layout.y_position -= gap;
+        }
+    }
+}
+
+/// Rewrites the `%PDF-1.x` header printpdf writes to `version`.
+fn set_header_version(pdf: &mut [u8], version: PdfVersion) {
+    if pdf.starts_with(b"%PDF-1.") && pdf.len() >= 8 {
+        pdf[5..8].copy_from_slice(version.as_str().as_bytes());
+    }
+}
+
+/// Replaces the random trailer `/ID` printpdf writes with one derived from
+/// the rest of the file, so identical input always yields identical bytes.
+fn with_content_id(pdf: &[u8]) -> Result<Vec<u8>, ConversionError> {
+    let mut document = lopdf::Document::load_mem(pdf).map_err(|e| ConversionError::Pdf(e.to_string()))?;
+    document.trailer.remove(b"ID");
+    let mut unstamped = Vec::new();
+    document.save_to(&mut unstamped)?;
+
+    let mut hasher = DefaultHasher::new();
+    unstamped.hash(&mut hasher);
+    let id = format!("{:032x}", hasher.finish());
+    document.trailer.set(
+        "ID",
+        lopdf::Object::Array(vec![
+            lopdf::Object::string_literal(id.clone()),
+            lopdf::Object::string_literal(id),
+        ]),
+    );
+
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn draw_rect(layer: &PdfLayerReference, x: f32, y: f32, width: f32, height: f32) {
+    layer.add_rect(Rect::new(Mm(x), Mm(y), Mm(x + width), Mm(y + height)).with_mode(PaintMode::Stroke));
+}
+
+fn fill_rect(layer: &PdfLayerReference, x: f32, y: f32, width: f32, height: f32, color: Color) {
+    layer.set_fill_color(color);
+    layer.add_rect(Rect::new(Mm(x), Mm(y), Mm(x + width), Mm(y + height)).with_mode(PaintMode::Fill));
+}
+
+/// The edge of a page set by `config` that the box `width` by `height` mm
+/// with its bottom left corner at `(x, y)` reaches furthest past, and how
+/// far in mm, if that is more than the tolerance.
+fn overflow(config: &Config, (x, y): (f32, f32), width: f32, height: f32) -> Option<(&'static str, f32)> {
+    let past = [
+        ("left", -x),
+        ("right", x + width - config.page_width),
+        ("bottom", -y),
+        ("top", y + height - config.page_height),
+    ];
+    past.into_iter().max_by(|a, b| a.1.total_cmp(&b.1)).filter(|(_, distance)| *distance > OVERFLOW_TOLERANCE)
+}
+
+/// Bands a gradient fill is drawn in.
+const GRADIENT_BANDS: usize = 32;
+
+/// Fills a rectangle with `fill`. A gradient is drawn as bands of solid
+/// colour across its direction, clipped to the rectangle.
+fn fill_shape(layer: &PdfLayerReference, (x, y): (f32, f32), width: f32, height: f32, fill: Fill, color_space: ColorMode) {
+    let (from, to, angle) = match fill {
+        Fill::Solid(rgb) => return fill_rect(layer, x, y, width, height, fill_color(color_space, rgb)),
+        Fill::Linear { from, to, angle } => (from, to, angle),
+    };
+    //DrawingML angles turn clockwise with y down; the page has y up
+    let (dx, dy) = (angle.to_radians().cos(), -angle.to_radians().sin());
+    let (centre_x, centre_y) = (x + width / 2.0, y + height / 2.0);
+    let reach = (dx * width).abs() / 2.0 + (dy * height).abs() / 2.0;
+    let half_diagonal = width.hypot(height) / 2.0;
+    let point = |along: f32, across: f32| {
+        (Point::new(Mm(centre_x + dx * along - dy * across), Mm(centre_y + dy * along + dx * across)), false)
+    };
+    layer.save_graphics_state();
+    layer.add_rect(Rect::new(Mm(x), Mm(y), Mm(x + width), Mm(y + height)).with_mode(PaintMode::Clip));
+    for band in 0..GRADIENT_BANDS {
+        let share = (band as f32 + 0.5) / GRADIENT_BANDS as f32;
+        let mix = |from: f32, to: f32| from + (to - from) * share;
+        layer.set_fill_color(fill_color(color_space, (mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))));
+        //Each band reaches a little into the next so no seam shows between them
+        let start = -reach + 2.0 * reach * band as f32 / GRADIENT_BANDS as f32;
+        let end = start + 2.0 * reach / GRADIENT_BANDS as f32 + 0.05;
+        layer.add_polygon(Polygon {
+            rings: vec![vec![
+                point(start, -half_diagonal),
+                point(end, -half_diagonal),
+                point(end, half_diagonal),
+                point(start, half_diagonal),
+            ]],
+            mode: PaintMode::Fill,
+            winding_order: WindingOrder::NonZero,
+        });
+    }
+    layer.restore_graphics_state();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use docx_rs::{RunFonts, TableCell, TableRow};
+
+    /// A document of one paragraph holding `text`.
+    pub(crate) fn sample_docx(text: &str) -> Docx {
+        Docx::new().add_paragraph(Paragraph::new().add_run(Run::new().add_text(text)))
+    }
+
+    /// `sample_docx(text)` converted with `config`.
+    pub(crate) fn sample_pdf(text: &str, config: &Config) -> Vec<u8> {
+        convert_parsed(&sample_docx(text), &[], config).unwrap()
+    }
+
+    /// A zip archive holding `entries`, deflated.
+    pub(crate) fn archive(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            writer.start_file(*name, zip::write::FileOptions::default()).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    /// A directory of the system's temporary one, named for `test` and
+    /// holding only `files`.
+    pub(crate) fn temp_directory(test: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let directory = std::env::temp_dir().join(format!("word_pdf_c-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        for (name, content) in files {
+            fs::write(directory.join(name), content).unwrap();
+        }
+        directory
+    }
+
+    /// `docx` packed, with the text of each part passed through `edit`, for
+    /// markup docx_rs does not write. Binary parts such as images are kept.
+    pub(crate) fn edited_docx(docx: Docx, edit: impl Fn(&str, String) -> String) -> Vec<u8> {
+        let mut packed = Cursor::new(Vec::new());
+        docx.build().pack(&mut packed).unwrap();
+        let mut archive = ZipArchive::new(packed).unwrap();
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i).unwrap();
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content).unwrap();
+            let content = match String::from_utf8(content) {
+                Ok(text) => edit(entry.name(), text).into_bytes(),
+                Err(binary) => binary.into_bytes(),
+            };
+            writer.start_file(entry.name(), zip::write::FileOptions::default()).unwrap();
+            writer.write_all(&content).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    /// The `packed` docx with `parts` added, for parts docx_rs does not write.
+    pub(crate) fn with_parts(packed: &[u8], parts: &[(&str, &str)]) -> Vec<u8> {
+        let mut archive = ZipArchive::new(Cursor::new(packed)).unwrap();
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..archive.len() {
+            writer.raw_copy_file(archive.by_index(i).unwrap()).unwrap();
+        }
+        for (name, content) in parts {
+            writer.start_file(*name, zip::write::FileOptions::default()).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    /// Like `laid_out`, for a docx archive, with the parts docx_rs does not
+    /// read such as notes and section settings.
+    pub(crate) fn laid_out_archive(docx_content: &[u8], config: &Config) -> Vec<(String, f32, f32, f32)> {
+        let document = parse_document(docx_content, "test.docx", config).unwrap();
+        let source = SourceDocument { docx: &document.docx, images: &document.images, parts: document.parts, attachment: None };
+        let mut runs = Vec::new();
+        render_pdf_bytes(vec![source], config, Some(&mut |run| runs.push((run.text.to_string(), run.x, run.y, run.width))), None).unwrap();
+        runs
+    }
+
+    /// The text, left end, baseline and width of each run of `docx` laid out with `config`.
+    pub(crate) fn laid_out(docx: &Docx, config: &Config) -> Vec<(String, f32, f32, f32)> {
+        let mut runs = Vec::new();
+        convert_parsed_with(docx, &[], config, &mut |run| runs.push((run.text.to_string(), run.x, run.y, run.width))).unwrap();
+        runs
+    }
+
+    #[test]
+    fn wraps_mixed_latin_and_east_asian_text() {
+        let text = "Word 2019で作成した文書をPDFに変換すると、空白のない日本語の段落も行末で折り返されます。".repeat(3);
+        let run = Run::new().add_text(text).fonts(RunFonts::new().ascii("Arial").east_asia("MS Mincho"));
+        let docx = Docx::new().add_paragraph(Paragraph::new().add_run(run));
+        let config = Config::new("mixed.docx", "mixed.pdf");
+        let runs = laid_out(&docx, &config);
+        let baselines: HashSet<u32> = runs.iter().map(|(_, _, y, _)| y.to_bits()).collect();
+        assert!(baselines.len() > 1);
+        let right = config.page_width - config.margin;
+        assert!(runs.iter().all(|(_, x, _, width)| x + width <= right + 0.01));
+    }
+
+    #[test]
+    fn reproducible_output_is_the_same_every_time() {
+        let mut config = Config::new("same.docx", "same.pdf");
+        config.reproducible = true;
+        let first = sample_pdf("Same bytes", &config);
+        //A second later, when a creation date would have moved on
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        assert_eq!(first, sample_pdf("Same bytes", &config));
+    }
+
+    #[test]
+    fn writes_the_requested_header_version() {
+        for (version, header) in [(PdfVersion::V1_4, "%PDF-1.4"), (PdfVersion::V1_5, "%PDF-1.5"), (PdfVersion::V1_7, "%PDF-1.7")] {
+            let mut config = Config::new("version.docx", "version.pdf");
+            config.pdf_version = Some(version);
+            assert!(sample_pdf("Versioned", &config).starts_with(header.as_bytes()));
+        }
+        assert_eq!("1.6".parse::<PdfVersion>(), Err("Unsupported PDF version: 1.6".to_string()));
+    }
+
+    #[test]
+    fn raises_the_version_for_the_features_used() {
+        let mut config = Config::new("version.docx", "version.pdf");
+        assert!(sample_pdf("Versioned", &config).starts_with(b"%PDF-1.3"));
+        config.tagged = true;
+        assert!(sample_pdf("Versioned", &config).starts_with(b"%PDF-1.4"));
+
+        //Asked for explicitly, a version too low for them is refused
+        config.pdf_version = Some(PdfVersion::V1_3);
+        let error = convert_parsed(&sample_docx("Versioned"), &[], &config).unwrap_err();
+        assert_eq!(error.kind(), "unsupported");
+        let args = |extra: &[&str]| ["in.docx", "out.pdf", "--pdf-version", "1.3"].iter().chain(extra).map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert!(Config::from_args(&args(&[])).is_ok());
+        assert_eq!(Config::from_args(&args(&["--tagged"])).err().as_deref(), Some("--tagged needs PDF 1.4 or later, not 1.3"));
+        assert_eq!(Config::from_args(&args(&["--icc-profile", "sRGB.icc"])).err().as_deref(), Some("--icc-profile needs PDF 1.4 or later, not 1.3"));
+    }
+
+    #[test]
+    fn each_error_has_a_kind() {
+        let image = ::image::ImageError::Unsupported(::image::error::UnsupportedError::from(::image::error::ImageFormatHint::Unknown));
+        let errors = [
+            (ConversionError::from(std::io::Error::other("disk")), "io"),
+            (ConversionError::from(zip::result::ZipError::FileNotFound), "zip"),
+            (ConversionError::from(docx_rs::ReaderError::DocumentNotFoundError), "parse"),
+            (ConversionError::from(image), "image"),
+            (ConversionError::Pdf("broken".to_string()), "pdf"),
+            (ConversionError::from(printpdf::Error::Pdf(printpdf::PdfError::FontFaceError)), "pdf"),
+            (ConversionError::InvalidInput("empty".to_string()), "input"),
+            (ConversionError::Unsupported("charts".to_string()), "unsupported"),
+        ];
+        for (error, kind) in errors {
+            assert_eq!(error.kind(), kind, "{}", error);
+        }
+        assert_eq!(ConversionError::InvalidInput("empty".to_string()).to_string(), "Invalid input file: empty");
+    }
+
+    #[test]
+    fn first_baseline_sits_an_ascent_below_the_margin() {
+        let config = Config::new("baseline.docx", "baseline.pdf");
+        let runs = laid_out(&sample_docx("First line"), &config);
+        let line_height = 12.0 * 1.2 * PT_TO_MM;
+        let expected = config.page_height - config.margin - ASCENT * line_height;
+        assert!((runs[0].2 - expected).abs() < 0.001, "baseline at {} mm, expected {} mm", runs[0].2, expected);
+        assert_eq!(runs[0].1, config.margin);
+    }
+
+    #[test]
+    fn writes_the_text_render_mode() {
+        let content = |mode: TextRenderMode| {
+            let mut config = Config::new("render.docx", "render.pdf");
+            config.compression = Some(crate::compression::Compression::None);
+            config.text_render_mode = mode;
+            let pdf = sample_pdf("Rendered", &config);
+            let document = lopdf::Document::load_mem(&pdf).unwrap();
+            let page_id = *document.get_pages().values().next().unwrap();
+            String::from_utf8_lossy(&document.get_page_content(page_id).unwrap()).into_owned()
+        };
+        assert!(!content(TextRenderMode::Fill).contains(" Tr"));
+        assert!(content(TextRenderMode::Stroke).contains("1 Tr"));
+        assert!(content(TextRenderMode::Outline).contains("2 Tr"));
+    }
+
+    #[test]
+    fn draws_the_fallback_glyph_for_missing_characters() {
+        let args = |glyph: &str| ["in.docx", "out.pdf", "--fallback-glyph", glyph].map(String::from);
+        let config = Config::from_args(&args("#")).unwrap();
+        assert_eq!(config.fallback_glyph, '#');
+        assert!(Config::from_args(&args("##")).is_err());
+        assert!(Config::from_args(&args("\u{2603}")).is_err());
+
+        let runs = laid_out(&sample_docx("snow \u{2603} man"), &config);
+        let text: String = runs.iter().map(|run| run.0.as_str()).collect();
+        assert_eq!(text, "snow # man");
+    }
+
+    #[test]
+    fn strict_mode_fails_on_missing_glyphs() {
+        let mut config = Config::new("strict.docx", "strict.pdf");
+        config.strict = true;
+        assert!(convert_parsed(&sample_docx("plain text"), &[], &config).is_ok());
+        let error = convert_parsed(&sample_docx("snow \u{2603}"), &[], &config).unwrap_err();
+        assert_eq!(error.kind(), "unsupported");
+        assert!(error.to_string().contains("U+2603"), "{}", error);
+    }
+
+    #[test]
+    fn strict_mode_fails_on_a_dropped_table() {
+        let cell = TableCell::new().add_paragraph(Paragraph::new().add_run(Run::new().add_text("cell")));
+        let mut docx = Docx::new().add_table(Table::new(vec![TableRow::new(vec![cell])]));
+        let mut config = Config::new("strict.docx", "strict.pdf");
+        config.strict = true;
+        assert!(parse_document(&edited_docx(docx.clone(), |_, content| content), "strict.docx", &config).is_ok());
+
+        //The columns of a vertical section have no layout for tables, so they are left out
+        docx.document.section_property = docx.document.section_property.text_direction("tbRl".to_string());
+        let Err(error) = parse_document(&edited_docx(docx, |_, content| content), "strict.docx", &config) else {
+            panic!("a dropped table should fail in strict mode");
+        };
+        assert_eq!(error.kind(), "unsupported");
+        assert!(error.to_string().contains("tables in vertical text sections"), "{}", error);
+    }
+
+    #[test]
+    fn font_scale_enlarges_text() {
+        let mut config = Config::new("scale.docx", "scale.pdf");
+        let normal = laid_out(&sample_docx("Scaled"), &config);
+        config.font_scale = 2.0;
+        let scaled = laid_out(&sample_docx("Scaled"), &config);
+        assert!((scaled[0].3 - normal[0].3 * 2.0).abs() < 0.001, "{} mm against {} mm", scaled[0].3, normal[0].3);
+        assert!(scaled[0].2 < normal[0].2);
+
+        //Lines twice as tall no longer fit on the pages they did
+        let docx = (0..20).fold(Docx::new(), |docx, i| docx.add_paragraph(Paragraph::new().add_run(Run::new().add_text(format!("Line {}", i)))));
+        let pages = |config: &Config| lopdf::Document::load_mem(&convert_parsed(&docx, &[], config).unwrap()).unwrap().get_pages().len();
+        config.font_scale = 1.0;
+        assert_eq!(pages(&config), 1);
+        config.font_scale = 2.0;
+        assert!(pages(&config) > 1);
+    }
+
+    #[test]
+    fn redacting_never_embeds_the_source() {
+        let args = |extra: &[&str]| ["in.docx", "out.pdf", "--embed-source"].iter().chain(extra).map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert!(Config::from_args(&args(&[])).unwrap().embeds_source());
+        assert!(Config::from_args(&args(&["--redact", "secret"])).is_err());
+        assert!(!Config::from_args(&args(&["--sanitize"])).unwrap().embeds_source());
+
+        let mut config = Config::new("in.docx", "out.pdf");
+        config.embed_source = true;
+        config.redact = vec!["secret".to_string()];
+        assert!(!config.embeds_source());
+    }
+
+    #[test]
+    fn hyphenates_each_run_in_its_own_language() {
+        let paragraph = Paragraph::new().add_run(Run::new().add_text("letters ")).add_run(Run::new().add_text("letters").bold());
+        //docx_rs writes no w:lang, so the second run's bold is swapped for one
+        let docx_content = edited_docx(Docx::new().add_paragraph(paragraph), |name, content| match name {
+            "word/document.xml" => content.replacen("<w:b />", "<w:lang w:val=\"de-DE\" />", 1),
+            _ => content,
+        });
+
+        let patterns = temp_directory("run-language", &[("hyph-en-us.pat.txt", "1ter"), ("hyph-de.pat.txt", "1ben")]);
+        let mut config = Config::new("lang.docx", "lang.pdf");
+        config.hyphenate = Some(patterns.to_string_lossy().into_owned());
+        config.page_width = 2.0 * config.margin + 8.0;
+        let document = parse_document(&docx_content, "lang.docx", &config).unwrap();
+        let lines: Vec<String> = laid_out(&document.docx, &config).into_iter().map(|run| run.0).collect();
+        //The English run breaks at its pattern; the German one has none to break at
+        assert_eq!(lines, ["let-", "ters", "letters"]);
+    }
+
+    #[test]
+    fn fetches_only_reachable_local_linked_images() {
+        let directory = temp_directory("linked-images", &[]);
+        ::image::RgbImage::from_pixel(2, 1, ::image::Rgb([0, 0, 255])).save(directory.join("linked.png")).unwrap();
+        let image = "http://schemas.openxmlformats.org/officeDocument/2006/relationships/image";
+        let rels = format!(
+            r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+                <Relationship Id="rId1" Type="{image}" Target="linked.png" TargetMode="External"/>
+                <Relationship Id="rId2" Type="{image}" Target="missing.png" TargetMode="External"/>
+                <Relationship Id="rId3" Type="{image}" Target="https://example.com/remote.png" TargetMode="External"/>
+                <Relationship Id="rId4" Type="{image}" Target="media/image1.png"/>
+            </Relationships>"#
+        );
+        let docx_content = archive(&[("word/_rels/document.xml.rels", &rels)]);
+        let images = extract_external_images(&docx_content, &directory, None).unwrap();
+        let loaded: Vec<(&str, u32)> = images.iter().map(|(target, img)| (target.as_str(), img.width())).collect();
+        assert_eq!(loaded, [("linked.png", 2)]);
+        //A pixel limit below the image's size skips it too
+        assert!(extract_external_images(&docx_content, &directory, Some(1)).unwrap().is_empty());
+    }
+
+    /// Where a 200 by 100 pixel picture shown at `cx` by `cy` EMU is placed,
+    /// alone in a document, on whichever page it lands.
+    fn placed_image((cx, cy): (u32, u32)) -> model::ImageModel {
+        let mut png = Cursor::new(Vec::new());
+        ::image::RgbImage::from_pixel(200, 100, ::image::Rgb([0, 0, 255])).write_to(&mut png, ::image::ImageFormat::Png).unwrap();
+        let pic = docx_rs::Pic::new(png.get_ref()).size(cx, cy);
+        let docx = Docx::new().add_paragraph(Paragraph::new().add_run(Run::new().add_image(pic)));
+        let mut packed = Cursor::new(Vec::new());
+        docx.build().pack(&mut packed).unwrap();
+        let docx = docx_rs::read_docx(packed.get_ref()).unwrap();
+        let model = build_layout_model(&docx, &Config::new("image.docx", "image.pdf")).unwrap();
+        model.pages.iter().flat_map(|page| &page.images).next().unwrap().clone()
+    }
+
+    #[test]
+    fn sizes_images_from_their_extent() {
+        //Two inches by one is 100 dpi, and shown at that size
+        let image = placed_image((2 * 914_400, 914_400));
+        assert!((image.width - 50.8).abs() < 0.01 && (image.height - 25.4).abs() < 0.01, "{} by {}", image.width, image.height);
+        //An extent wider than the text area is scaled down to it, keeping its shape
+        let config = Config::new("image.docx", "image.pdf");
+        let image = placed_image((20 * 914_400, 10 * 914_400));
+        assert!((image.width - (config.page_width - 2.0 * config.margin)).abs() < 0.01);
+        assert!((image.width - 2.0 * image.height).abs() < 0.01);
+    }
+
+    #[test]
+    fn fits_tall_images_to_the_text_area_height_and_centres_them() {
+        let config = Config::new("image.docx", "image.pdf");
+        let image = placed_image((914_400, 20 * 914_400));
+        assert!((image.height - (config.page_height - 2.0 * config.margin)).abs() < 0.01, "{}", image.height);
+        assert!((image.height - 20.0 * image.width).abs() < 0.01);
+        let usable_width = config.page_width - 2.0 * config.margin;
+        assert!((image.x - (config.margin + (usable_width - image.width) / 2.0)).abs() < 0.01);
+        assert!(image.y >= config.margin - 0.01);
+    }
+
+    #[test]
+    fn merges_several_inputs_into_one_pdf() {
+        let directory = temp_directory("merge", &[]);
+        let inputs = ["first", "second"].map(|name| {
+            let path = directory.join(format!("{}.docx", name));
+            fs::write(&path, edited_docx(sample_docx(&format!("Text of {}", name)), |_, content| content)).unwrap();
+            path.to_string_lossy().into_owned()
+        });
+        let output = directory.join("merged.pdf").to_string_lossy().into_owned();
+        let config = Config::from_args(&[inputs[0].clone(), inputs[1].clone(), output.clone()]).unwrap();
+        assert_eq!(config.input_paths, inputs);
+        convert_file(&config).unwrap();
+
+        let document = lopdf::Document::load(&output).unwrap();
+        assert_eq!(document.get_pages().len(), 2);
+        //Each document starts a page of its own
+        assert!(document.extract_text(&[1]).unwrap().contains("Text of first"));
+        assert!(document.extract_text(&[2]).unwrap().contains("Text of second"));
+    }
+
+    #[test]
+    fn keeps_the_lines_of_a_kept_paragraph_together() {
+        //Lines of the last paragraph on each page, with the page nearly full before it
+        let pages_of = |keep_lines: bool| {
+            let mut docx = Docx::new();
+            for _ in 0..24 {
+                docx = docx.add_paragraph(Paragraph::new().add_run(Run::new().add_text("Filler")));
+            }
+            let kept = Paragraph::new().add_run(Run::new().add_text("Kept words ".repeat(60))).keep_lines(keep_lines);
+            let model = build_layout_model(&docx.add_paragraph(kept), &Config::new("kept.docx", "kept.pdf")).unwrap();
+            model
+                .pages
+                .iter()
+                .map(|page| page.lines.iter().filter(|line| line.runs.iter().any(|run| run.text.starts_with("Kept"))).count())
+                .collect::<Vec<_>>()
+        };
+        let split = pages_of(false);
+        assert!(split[0] > 0 && split[1] > 0, "{:?}", split);
+        assert_eq!(pages_of(true), [0, split[0] + split[1]]);
+    }
+
+    #[test]
+    fn line_height_scales_the_advance_between_lines() {
+        let docx = Docx::new().add_paragraph(Paragraph::new().add_run(Run::new().add_text("Spaced out lines ".repeat(40))));
+        let advance = |factor: f32| {
+            let mut config = Config::new("spacing.docx", "spacing.pdf");
+            config.line_height = factor;
+            let runs = laid_out(&docx, &config);
+            runs[0].2 - runs.iter().find(|run| run.2 < runs[0].2).unwrap().2
+        };
+        assert!((advance(1.5) - 1.5 * advance(1.0)).abs() < 0.01);
+        let args = |factor: &str| ["in.docx", "out.pdf", "--line-height", factor].map(String::from);
+        assert_eq!(Config::from_args(&args("1.15")).map(|config| config.line_height).ok(), Some(1.15));
+        assert!(Config::from_args(&args("0")).is_err());
+    }
+
+    #[test]
+    fn shows_the_text_of_unhandled_run_children() {
+        //A stray w:delText outside a w:del is not one of the children handled
+        let run = Run::new()
+            .add_text("Kept ")
+            .add_delete_text("stray")
+            .add_break(docx_rs::BreakType::TextWrapping)
+            .add_field_char(docx_rs::FieldCharType::Begin, false)
+            .add_tab();
+        assert_eq!(run_text(&run), "Kept stray\t");
+        let runs = laid_out(&Docx::new().add_paragraph(Paragraph::new().add_run(Run::new().add_delete_text("Only deleted"))), &Config::new("stray.docx", "stray.pdf"));
+        assert_eq!(runs.iter().map(|run| run.0.as_str()).collect::<String>(), "Only deleted");
+    }
+
+    #[test]
+    fn finds_the_edge_content_reaches_furthest_past() {
+        let config = Config::new("edges.docx", "edges.pdf");
+        let (width, height) = (config.page_width, config.page_height);
+        assert_eq!(overflow(&config, (config.margin, config.margin), 50.0, 10.0), None);
+        //Within the tolerance is not past the edge
+        assert_eq!(overflow(&config, (width - 50.3, 100.0), 50.0, 10.0), None);
+        let (edge, distance) = overflow(&config, (width - 22.5, 100.0), 50.0, 10.0).unwrap();
+        assert_eq!(edge, "right");
+        assert!((distance - 27.5).abs() < 0.01);
+        //Off two edges, the one it is further past is named
+        assert_eq!(overflow(&config, (-2.0, height - 5.0), 10.0, 10.0).map(|(edge, _)| edge), Some("top"));
+        assert_eq!(overflow(&config, (-8.0, -1.0), 10.0, 10.0).map(|(edge, _)| edge), Some("left"));
+    }
+}
@@ -0,0 +1,90 @@
+//! The cap on image size set with `--max-image-pixels`. An image's
+//! dimensions are read from its header, and one over the cap is never
+//! decoded: it is replaced in the archive before docx_rs or the converter
+//! load the media, so a small file declaring a huge bitmap cannot exhaust
+//! memory.
+
+use std::io::{Cursor, Read, Write};
+
+use log::warn;
+use zip::ZipArchive;
+use zip::write::{FileOptions, ZipWriter};
+
+use crate::ConversionError;
+
+/// What an oversized image is replaced with. No decoder accepts it, and it
+/// is long enough for docx_rs, which looks at the first three bytes of
+/// every part for a byte order mark.
+const PLACEHOLDER: &[u8] = b"\0\0\0";
+
+/// Width and height declared by the header of an encoded image, if its
+/// format is recognised.
+fn declared_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    ::image::io::Reader::new(Cursor::new(bytes)).with_guessed_format().ok()?.into_dimensions().ok()
+}
+
+/// Whether the image `name` in `bytes` declares more than `limit` pixels,
+/// warning that it is left out if so.
+pub fn exceeds_limit(name: &str, bytes: &[u8], limit: u64) -> bool {
+    let Some((width, height)) = declared_dimensions(bytes) else { return false };
+    let exceeds = width as u64 * height as u64 > limit;
+    if exceeds {
+        warn!("Image {} is {}x{} pixels, over the limit of {}; it is left out", name, width, height, limit);
+    }
+    exceeds
+}
+
+/// `docx` with every image in `word/media` that declares more than `limit`
+/// pixels replaced by a placeholder.
+pub fn drop_oversized_media(docx: &[u8], limit: u64) -> Result<Vec<u8>, ConversionError> {
+    let mut archive = ZipArchive::new(Cursor::new(docx))?;
+    let mut output = ZipWriter::new(Cursor::new(Vec::new()));
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if !entry.name().starts_with("word/media") {
+            output.raw_copy_file(entry)?;
+            continue;
+        }
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes)?;
+        output.start_file(entry.name(), FileOptions::default())?;
+        let oversized = exceeds_limit(entry.name(), &bytes, limit);
+        output.write_all(if oversized { PLACEHOLDER } else { &bytes })?;
+    }
+    Ok(output.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A PNG of `width` by `height` white pixels.
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        ::image::DynamicImage::ImageLuma8(::image::GrayImage::from_pixel(width, height, ::image::Luma([255])))
+            .write_to(&mut Cursor::new(&mut bytes), ::image::ImageOutputFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn replaces_images_over_the_limit() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, bytes) in [("word/media/small.png", png(10, 10)), ("word/media/large.png", png(40, 30)), ("word/document.xml", b"<w:document/>".to_vec())] {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(&bytes).unwrap();
+        }
+        let docx = writer.finish().unwrap().into_inner();
+
+        let kept = drop_oversized_media(&docx, 1000).unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(kept)).unwrap();
+        let mut read = |name: &str| {
+            let mut bytes = Vec::new();
+            archive.by_name(name).unwrap().read_to_end(&mut bytes).unwrap();
+            bytes
+        };
+        assert_eq!(read("word/media/small.png"), png(10, 10));
+        assert_eq!(read("word/media/large.png"), PLACEHOLDER);
+        assert_eq!(read("word/document.xml"), b"<w:document/>");
+    }
+}
@@ -0,0 +1,173 @@
+//! Bookmarks and internal hyperlinks. Bookmarks become named destinations
+//! and anchor links become link annotations jumping to them. printpdf can
+//! only write URI actions, so both are added to the saved file.
+
+use std::collections::BTreeMap;
+
+use docx_rs::{HyperlinkData, Paragraph, ParagraphChild, Run};
+use log::warn;
+use printpdf::lopdf::{self, Dictionary, Object};
+
+use crate::{ASCENT, ConversionError, DESCENT, Layout, PT_TO_MM};
+
+/// Destinations and links placed during layout, positioned in mm on
+/// zero-based pages.
+#[derive(Debug, Default)]
+pub struct LinkTable {
+    /// Bookmark name, page and the y of the top of its line.
+    destinations: Vec<(String, usize, f32)>,
+    /// Page, `[left, bottom, right, top]` and the bookmark linked to.
+    links: Vec<(usize, [f32; 4], String)>,
+    /// Added to the names of the current document's bookmarks, so documents
+    /// merged into one file keep theirs apart.
+    prefix: String,
+}
+
+impl LinkTable {
+    /// Names what follows as belonging to the `index`th merged document.
+    pub fn start_document(&mut self, index: usize) {
+        self.prefix = if index == 0 { String::new() } else { format!("doc{}:", index) };
+    }
+
+    fn qualify(&self, name: &str) -> String {
+        format!("{}{}", self.prefix, name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.destinations.is_empty() && self.links.is_empty()
+    }
+}
+
+/// Registers a destination called `name` at the line starting at the cursor.
+pub fn add_destination(layout: &mut Layout, name: String) {
+    let top = layout.y_position;
+    let name = layout.links.qualify(&name);
+    layout.links.destinations.push((name, layout.page, top));
+}
+
+/// Registers the bookmarks started in `paragraph` at the cursor.
+pub fn add_bookmarks(layout: &mut Layout, paragraph: &Paragraph) {
+    for child in &paragraph.children {
+        if let ParagraphChild::BookmarkStart(bookmark) = child {
+            add_destination(layout, bookmark.name.clone());
+        }
+    }
+}
+
+/// Page the destination `name` was placed on.
+pub fn destination_page(layout: &Layout, name: &str) -> Option<usize> {
+    let name = layout.links.qualify(name);
+    layout.links.destinations.iter().find(|(destination, ..)| *destination == name).map(|(_, page, _)| *page)
+}
+
+/// The bookmark `run` links to, if it is inside an internal hyperlink.
+pub fn run_anchor<'a>(paragraph: &'a Paragraph, run: &Run) -> Option<&'a str> {
+    paragraph.children.iter().find_map(|child| match child {
+        ParagraphChild::Hyperlink(link) => {
+            let HyperlinkData::Anchor { anchor } = &link.link else { return None };
+            let contains = link.children.iter().any(|child| matches!(child, ParagraphChild::Run(r) if std::ptr::eq(r.as_ref(), run)));
+            contains.then_some(anchor.as_str())
+        }
+        _ => None,
+    })
+}
+
+/// Makes the line box at `x` on the baseline `y` a link to `anchor`.
+pub fn add_link(layout: &mut Layout, anchor: &str, x: f32, y: f32, width: f32) {
+    add_link_on(layout, layout.page, anchor, x, y, width);
+}
+
+/// Like `add_link`, for a line on an earlier `page`.
+pub fn add_link_on(layout: &mut Layout, page: usize, anchor: &str, x: f32, y: f32, width: f32) {
+    let rect = [x, y - DESCENT * layout.line_height, x + width, y + ASCENT * layout.line_height];
+    let anchor = layout.links.qualify(anchor);
+    layout.links.links.push((page, rect, anchor));
+}
+
+fn points(mm: f32) -> Object {
+    Object::Real(mm / PT_TO_MM)
+}
+
+/// Writes the named destinations into the catalog's `/Dests` and the links
+/// as `/Link` annotations on their pages. Links to missing bookmarks are
+/// dropped.
+pub fn write_links(pdf: &[u8], table: &LinkTable) -> Result<Vec<u8>, ConversionError> {
+    let pdf_error = |e: lopdf::Error| ConversionError::Pdf(e.to_string());
+    let mut document = lopdf::Document::load_mem(pdf).map_err(pdf_error)?;
+    let pages: BTreeMap<u32, lopdf::ObjectId> = document.get_pages();
+    let page_id = |index: usize| pages.get(&(index as u32 + 1)).copied();
+
+    let mut destinations = Dictionary::new();
+    for (name, page, top) in &table.destinations {
+        let Some(id) = page_id(*page) else { continue };
+        let target = vec![Object::Reference(id), Object::Name(b"XYZ".to_vec()), Object::Null, points(*top), Object::Null];
+        destinations.set(name.as_bytes().to_vec(), Object::Array(target));
+    }
+
+    for (page, rect, anchor) in &table.links {
+        if !destinations.has(anchor.as_bytes()) {
+            warn!("Link to missing bookmark {} dropped", anchor);
+            continue;
+        }
+        let Some(id) = page_id(*page) else { continue };
+        let mut annotation = Dictionary::new();
+        annotation.set("Type", Object::Name(b"Annot".to_vec()));
+        annotation.set("Subtype", Object::Name(b"Link".to_vec()));
+        annotation.set("Rect", Object::Array(rect.iter().map(|mm| points(*mm)).collect()));
+        annotation.set("Border", Object::Array(vec![0.into(), 0.into(), 0.into()]));
+        annotation.set("Dest", Object::Name(anchor.as_bytes().to_vec()));
+        let annotation_id = document.add_object(annotation);
+
+        let page = document.get_object_mut(id).and_then(Object::as_dict_mut).map_err(pdf_error)?;
+        match page.get_mut(b"Annots") {
+            Ok(Object::Array(annotations)) => annotations.push(Object::Reference(annotation_id)),
+            _ => page.set("Annots", Object::Array(vec![Object::Reference(annotation_id)])),
+        }
+    }
+
+    let destinations_id = document.add_object(destinations);
+    let catalog_id = document.trailer.get(b"Root").and_then(Object::as_reference).map_err(pdf_error)?;
+    let catalog = document.get_object_mut(catalog_id).and_then(Object::as_dict_mut).map_err(pdf_error)?;
+    catalog.set("Dests", Object::Reference(destinations_id));
+
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use docx_rs::{Docx, Hyperlink, HyperlinkType};
+
+    use super::*;
+    use crate::Config;
+    use crate::tests::sample_pdf;
+
+    #[test]
+    fn links_anchors_to_the_pages_of_their_bookmarks() {
+        let link = Hyperlink::new("Details", HyperlinkType::Anchor).add_run(Run::new().add_text("See details"));
+        let mut docx = Docx::new().add_paragraph(Paragraph::new().add_hyperlink(link));
+        //Enough lines to put the bookmark on a later page
+        for _ in 0..60 {
+            docx = docx.add_paragraph(Paragraph::new().add_run(Run::new().add_text("Filler")));
+        }
+        let docx = docx.add_paragraph(Paragraph::new().add_bookmark_start(1, "Details").add_run(Run::new().add_text("Details")).add_bookmark_end(1));
+        let config = Config::new("links.docx", "links.pdf");
+        let pdf = crate::convert_parsed(&docx, &[], &config).unwrap();
+        let document = lopdf::Document::load_mem(&pdf).unwrap();
+        let pages = document.get_pages();
+
+        let catalog = document.catalog().unwrap();
+        let dests = document.dereference(catalog.get(b"Dests").unwrap()).unwrap().1.as_dict().unwrap();
+        let target = dests.get(b"Details").and_then(Object::as_array).unwrap();
+        assert!(pages.len() > 1);
+        assert_eq!(target[0].as_reference().unwrap(), pages[&(pages.len() as u32)]);
+
+        let annotations = document.get_page_annotations(pages[&1]);
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].get(b"Dest").and_then(Object::as_name_str).unwrap(), "Details");
+        //A document without bookmarks or anchors has no destinations
+        let plain = lopdf::Document::load_mem(&sample_pdf("Plain", &config)).unwrap();
+        assert!(plain.catalog().unwrap().get(b"Dests").is_err());
+    }
+}
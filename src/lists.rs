@@ -0,0 +1,382 @@
+//! List items. A paragraph's `w:numPr` points at a numbering instance in
+//! `word/numbering.xml`, whose abstract definition gives each level its
+//! number format, level text and marker font. Bullet levels usually draw
+//! their marker from a symbol font the PDF cannot embed, so the common
+//! shapes are drawn as vector graphics instead. Numbered levels count on
+//! through the document, across paragraphs outside the list, and restart
+//! when a higher level of the same list is used. Each level's `w:ind` sets
+//! how far its text is indented and how far the marker hangs before it.
+
+use std::collections::HashMap;
+
+use docx_rs::Paragraph;
+use printpdf::utils::calculate_points_for_circle;
+use printpdf::{Line, Mm, Polygon};
+use printpdf::path::{PaintMode, WindingOrder};
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+use crate::color::fill_color;
+use crate::text::is_win_ansi;
+use crate::{ConversionError, Layout, PT_TO_MM, TWIP_TO_MM, fill_rect, read_part, xml_attribute};
+
+/// Indent step of list levels without a `w:ind`, and the space their
+/// marker sits in, in mm.
+const LIST_INDENT: f32 = 18.0 * PT_TO_MM;
+
+/// One level of a numbering definition.
+#[derive(Debug, Clone, Default)]
+pub struct ListLevel {
+    /// `w:numFmt`, such as `bullet` or `decimal`.
+    pub format: String,
+    /// `w:lvlText`: the bullet character, or a pattern like `%1.`.
+    pub text: String,
+    /// Font of the marker from the level's `w:rFonts`.
+    pub font: Option<String>,
+    /// `w:start`, the number of the level's first item.
+    pub start: usize,
+    /// `w:lvlRestart`: the level is restarted after items of levels up to
+    /// this one (1-based), and never for 0. Without it any higher level restarts it.
+    pub restart: Option<usize>,
+    /// Left indent of the level's text from `w:ind`, in mm.
+    pub indent: Option<f32>,
+    /// How far the marker hangs left of the text, from `w:ind w:hanging`, in mm.
+    pub hanging: Option<f32>,
+}
+
+/// Numbering instances and the levels of their abstract definitions.
+#[derive(Debug, Default)]
+pub struct Numbering {
+    /// Levels by `w:abstractNumId` and `w:ilvl`.
+    levels: HashMap<(String, usize), ListLevel>,
+    /// The abstract definition of each `w:numId`.
+    instances: HashMap<usize, String>,
+}
+
+impl Numbering {
+    fn level(&self, num_id: usize, ilvl: usize) -> Option<&ListLevel> {
+        let abstract_id = self.instances.get(&num_id)?;
+        self.levels.get(&(abstract_id.clone(), ilvl))
+    }
+}
+
+/// Reads the numbering definitions of the document.
+pub fn read_numbering(docx: &[u8]) -> Result<Numbering, ConversionError> {
+    let Some(xml) = read_part(docx, "word/numbering.xml")? else { return Ok(Numbering::default()) };
+
+    let mut numbering = Numbering::default();
+    let mut reader = Reader::from_reader(&xml[..]);
+    let mut buf = Vec::new();
+    let mut abstract_id: Option<String> = None;
+    //The `w:lvl` being read, by its `w:ilvl`
+    let mut level: Option<(usize, ListLevel)> = None;
+    let mut num_id: Option<usize> = None;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => {
+                let value = xml_attribute(e, b"val");
+                match e.local_name() {
+                    b"abstractNum" => abstract_id = xml_attribute(e, b"abstractNumId"),
+                    b"lvl" if abstract_id.is_some() => {
+                        level = xml_attribute(e, b"ilvl").and_then(|ilvl| ilvl.parse().ok()).map(|ilvl| (ilvl, ListLevel::default()));
+                    }
+                    b"start" => {
+                        if let Some((_, level)) = level.as_mut() {
+                            level.start = value.and_then(|start| start.parse().ok()).unwrap_or(0);
+                        }
+                    }
+                    b"lvlRestart" => {
+                        if let Some((_, level)) = level.as_mut() {
+                            level.restart = value.and_then(|restart| restart.parse().ok());
+                        }
+                    }
+                    b"numFmt" => {
+                        if let Some((_, level)) = level.as_mut() {
+                            level.format = value.unwrap_or_default();
+                        }
+                    }
+                    b"lvlText" => {
+                        if let Some((_, level)) = level.as_mut() {
+                            level.text = value.unwrap_or_default();
+                        }
+                    }
+                    b"ind" => {
+                        if let Some((_, level)) = level.as_mut() {
+                            let twips = |name: &[u8]| {
+                                xml_attribute(e, name).and_then(|value| value.parse::<f32>().ok()).map(|twips| twips * TWIP_TO_MM)
+                            };
+                            level.indent = twips(b"left").or_else(|| twips(b"start"));
+                            level.hanging = twips(b"hanging");
+                        }
+                    }
+                    b"rFonts" => {
+                        if let Some((_, level)) = level.as_mut() {
+                            level.font = xml_attribute(e, b"ascii").or_else(|| xml_attribute(e, b"hAnsi"));
+                        }
+                    }
+                    b"num" => num_id = xml_attribute(e, b"numId").and_then(|id| id.parse().ok()),
+                    b"abstractNumId" => {
+                        if let (Some(id), Some(abstract_id)) = (num_id, value) {
+                            numbering.instances.insert(id, abstract_id);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Ok(Event::End(ref e)) => match e.local_name() {
+                b"lvl" => {
+                    if let (Some((ilvl, list_level)), Some(id)) = (level.take(), abstract_id.as_ref()) {
+                        numbering.levels.insert((id.clone(), ilvl), list_level);
+                    }
+                }
+                b"abstractNum" => abstract_id = None,
+                b"num" => num_id = None,
+                _ => {}
+            },
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(ConversionError::InvalidInput(format!("Malformed word/numbering.xml: {}", e))),
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(numbering)
+}
+
+/// The list level `paragraph` belongs to, with its `w:ilvl`.
+pub fn list_level<'n>(numbering: &'n Numbering, paragraph: &Paragraph) -> Option<(usize, &'n ListLevel)> {
+    let properties = paragraph.property.numbering_property.as_ref()?;
+    let num_id = properties.id.as_ref()?.id;
+    let ilvl = properties.level.as_ref().map_or(0, |level| level.val);
+    numbering.level(num_id, ilvl).map(|level| (ilvl, level))
+}
+
+/// Left indent of the text of `paragraph`, in mm.
+pub fn paragraph_indent(layout: &Layout, paragraph: &Paragraph) -> f32 {
+    list_level(&layout.numbering, paragraph).map_or(0.0, |(ilvl, level)| level.indent.unwrap_or((ilvl + 1) as f32 * LIST_INDENT))
+}
+
+/// Counters of the lists seen so far, by `w:numId` and `w:ilvl`.
+pub type ListCounters = HashMap<(usize, usize), usize>;
+
+/// How a list item's marker is drawn.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ListMarker {
+    Disc,
+    Circle,
+    Square,
+    Text(String),
+}
+
+fn bullet(level: &ListLevel) -> ListMarker {
+    let Some(c) = level.text.chars().next() else { return ListMarker::Disc };
+    //Symbol fonts are stored in the private use area from U+F000
+    let code = match c as u32 {
+        code @ 0xF000..=0xF0FF => char::from((code - 0xF000) as u8),
+        _ => c,
+    };
+    match (level.font.as_deref(), code) {
+        (Some("Symbol"), '\u{B7}') | (Some("Wingdings"), 'l') => ListMarker::Disc,
+        (Some("Wingdings"), 'n' | 'o' | 'q' | '\u{A7}' | '\u{A8}') => ListMarker::Square,
+        (Some("Courier New"), 'o') => ListMarker::Circle,
+        (Some("Symbol" | "Wingdings" | "Wingdings 2" | "Wingdings 3" | "Webdings"), _) => ListMarker::Disc,
+        (_, '•' | '●' | '·') => ListMarker::Disc,
+        (_, '◦' | '○') => ListMarker::Circle,
+        (_, '▪' | '■' | '□' | '◻' | '◼') => ListMarker::Square,
+        (_, c) if is_win_ansi(c) => ListMarker::Text(c.to_string()),
+        _ => ListMarker::Disc,
+    }
+}
+
+fn roman(mut number: usize) -> String {
+    const NUMERALS: &[(usize, &str)] = &[
+        (1000, "m"), (900, "cm"), (500, "d"), (400, "cd"), (100, "c"), (90, "xc"),
+        (50, "l"), (40, "xl"), (10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i"),
+    ];
+    let mut text = String::new();
+    for (value, numeral) in NUMERALS {
+        while number >= *value {
+            text.push_str(numeral);
+            number -= value;
+        }
+    }
+    text
+}
+
+/// `number` in the `w:numFmt` `format`; unsupported formats count in decimal.
+fn format_number(number: usize, format: &str) -> String {
+    //Letters run a..z, then aa..zz and so on
+    let letters = || match number {
+        0 => String::new(),
+        _ => char::from(b'a' + ((number - 1) % 26) as u8).to_string().repeat((number - 1) / 26 + 1),
+    };
+    match format {
+        "lowerLetter" => letters(),
+        "upperLetter" => letters().to_uppercase(),
+        "lowerRoman" => roman(number),
+        "upperRoman" => roman(number).to_uppercase(),
+        "decimalZero" => format!("{:02}", number),
+        "none" => String::new(),
+        _ => number.to_string(),
+    }
+}
+
+/// Counts `paragraph` as the next item of its list and returns the marker
+/// it shows with how far it hangs left of the text, in mm, restarting the
+/// counters of the levels below it.
+pub fn next_list_marker(layout: &mut Layout, paragraph: &Paragraph) -> Option<(ListMarker, f32)> {
+    let numbering = &layout.numbering;
+    let (ilvl, level) = list_level(numbering, paragraph)?;
+    let num_id = paragraph.property.numbering_property.as_ref()?.id.as_ref()?.id;
+    let counters = &mut layout.list_counters;
+
+    let number = counters.get(&(num_id, ilvl)).map_or(level.start, |count| count + 1);
+    counters.insert((num_id, ilvl), number);
+    counters.retain(|&(id, deeper), _| {
+        if id != num_id || deeper <= ilvl {
+            return true;
+        }
+        let restart = numbering.level(num_id, deeper).and_then(|level| level.restart);
+        //`w:lvlRestart` 0 keeps counting; n restarts after levels 1 to n
+        restart.is_some_and(|restart| ilvl >= restart)
+    });
+
+    let hanging = level.hanging.unwrap_or(LIST_INDENT);
+    if level.format == "bullet" {
+        return Some((bullet(level), hanging));
+    }
+    //`%n` in the level text stands for the current number of level n
+    let mut text = level.text.clone();
+    for n in (1..=9).rev() {
+        let pattern = format!("%{}", n);
+        if text.contains(&pattern) {
+            let value = match numbering.level(num_id, n - 1) {
+                Some(other) => format_number(counters.get(&(num_id, n - 1)).copied().unwrap_or(other.start), &other.format),
+                None => String::new(),
+            };
+            text = text.replace(&pattern, &value);
+        }
+    }
+    Some((ListMarker::Text(text), hanging))
+}
+
+/// Draws the marker of a list item starting at `left` on the baseline `y`.
+pub fn draw_list_marker(layout: &Layout, marker: &ListMarker, left: f32, y: f32, font_size: f32) {
+    let size = font_size * PT_TO_MM;
+    //Shapes are centred a little above the baseline, at the height of lowercase letters
+    let (centre_x, centre_y) = (left + 0.2 * size, y + 0.3 * size);
+    let black = fill_color(layout.config.color_space, (0.0, 0.0, 0.0));
+    layout.layer.set_fill_color(black.clone());
+    match marker {
+        ListMarker::Disc => layout.layer.add_polygon(Polygon {
+            rings: vec![calculate_points_for_circle(Mm(0.14 * size), Mm(centre_x), Mm(centre_y))],
+            mode: PaintMode::Fill,
+            winding_order: WindingOrder::NonZero,
+        }),
+        ListMarker::Circle => layout.layer.add_line(Line {
+            points: calculate_points_for_circle(Mm(0.14 * size), Mm(centre_x), Mm(centre_y)),
+            is_closed: true,
+        }),
+        ListMarker::Square => {
+            let side = 0.26 * size;
+            fill_rect(&layout.layer, centre_x - side / 2.0, centre_y - side / 2.0, side, side, black);
+        }
+        ListMarker::Text(text) => {
+            layout.draw_text(text, (left, y), font_size, &layout.fonts.regular, 0.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use docx_rs::{
+        AbstractNumbering, Docx, IndentLevel, Level, LevelJc, LevelText, NumberFormat, NumberingId, Run, RunFonts, SpecialIndentType,
+        Start,
+    };
+
+    use super::*;
+    use crate::Config;
+    use crate::tests::{edited_docx, laid_out_archive};
+
+    /// A level of `format` showing `text`, counting from 1.
+    fn level(ilvl: usize, format: &str, text: &str) -> Level {
+        Level::new(ilvl, Start::new(1), NumberFormat::new(format), LevelText::new(text), LevelJc::new("left"))
+    }
+
+    /// A paragraph of `text` at level `ilvl` of the list `num_id`.
+    fn item(num_id: usize, ilvl: usize, text: &str) -> Paragraph {
+        Paragraph::new().add_run(Run::new().add_text(text)).numbering(NumberingId::new(num_id), IndentLevel::new(ilvl))
+    }
+
+    #[test]
+    fn draws_the_bullet_each_level_defines() {
+        let bullets = AbstractNumbering::new(1)
+            .add_level(level(0, "bullet", "\u{F0B7}").fonts(RunFonts::new().ascii("Symbol")))
+            .add_level(level(1, "bullet", "o").fonts(RunFonts::new().ascii("Courier New")))
+            .add_level(level(2, "bullet", "\u{F0A7}").fonts(RunFonts::new().ascii("Wingdings")))
+            .add_level(level(3, "bullet", "–"));
+        let docx = Docx::new()
+            .add_abstract_numbering(bullets)
+            .add_numbering(docx_rs::Numbering::new(1, 1))
+            .add_paragraph(item(1, 3, "Dashed"));
+        let packed = edited_docx(docx, |_, content| content);
+
+        let numbering = read_numbering(&packed).unwrap();
+        let markers: Vec<ListMarker> = (0..4).map(|ilvl| bullet(numbering.level(1, ilvl).unwrap())).collect();
+        assert_eq!(markers, [ListMarker::Disc, ListMarker::Circle, ListMarker::Square, ListMarker::Text("–".to_string())]);
+        //A character the built-in fonts have is drawn as text before the item
+        let mut runs = laid_out_archive(&packed, &Config::new("bullets.docx", "bullets.pdf"));
+        runs.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let texts: Vec<&str> = runs.iter().map(|(text, ..)| text.as_str()).collect();
+        assert_eq!(texts, ["–", "Dashed"]);
+    }
+
+    #[test]
+    fn numbers_continue_across_paragraphs_and_restart_below_a_higher_level() {
+        let numbered = AbstractNumbering::new(1).add_level(level(0, "decimal", "%1.")).add_level(level(1, "lowerLetter", "%1.%2)"));
+        let docx = Docx::new()
+            .add_abstract_numbering(numbered)
+            .add_numbering(docx_rs::Numbering::new(1, 1))
+            .add_numbering(docx_rs::Numbering::new(2, 1))
+            .add_paragraph(item(1, 0, "One"))
+            .add_paragraph(item(1, 1, "Sub"))
+            .add_paragraph(item(1, 1, "Sub"))
+            .add_paragraph(Paragraph::new().add_run(Run::new().add_text("Between")))
+            .add_paragraph(item(1, 0, "Two"))
+            .add_paragraph(item(1, 1, "Sub"))
+            .add_paragraph(item(2, 0, "Other list"));
+        let packed = edited_docx(docx, |_, content| content);
+        let runs = laid_out_archive(&packed, &Config::new("numbers.docx", "numbers.pdf"));
+        //Each item's marker is drawn after its text
+        let markers: Vec<&str> = runs
+            .windows(2)
+            .filter(|pair| pair[0].2 == pair[1].2 && pair[1].1 < pair[0].1)
+            .map(|pair| pair[1].0.as_str())
+            .collect();
+        assert_eq!(markers, ["1.", "1.a)", "1.b)", "2.", "2.a)", "1."]);
+    }
+
+    #[test]
+    fn indents_each_level_by_its_own_indentation() {
+        let indented = |ilvl: usize| {
+            let left = 720 * (ilvl as i32 + 1);
+            level(ilvl, "decimal", &format!("%{}.", ilvl + 1)).indent(Some(left), Some(SpecialIndentType::Hanging(360)), None, None)
+        };
+        let docx = Docx::new()
+            .add_abstract_numbering((0..3).fold(AbstractNumbering::new(1), |numbered, ilvl| numbered.add_level(indented(ilvl))))
+            .add_numbering(docx_rs::Numbering::new(1, 1))
+            .add_paragraph(item(1, 0, "First"))
+            .add_paragraph(item(1, 1, "Second"))
+            .add_paragraph(item(1, 2, "Third"));
+        let packed = edited_docx(docx, |_, content| content);
+        let config = Config::new("indents.docx", "indents.pdf");
+        let runs = laid_out_archive(&packed, &config);
+        for (ilvl, text) in ["First", "Second", "Third"].into_iter().enumerate() {
+            let item = runs.iter().find(|run| run.0 == text).unwrap();
+            let marker = runs.iter().find(|run| run.2 == item.2 && run.0 != text).unwrap();
+            let left = config.margin + 720.0 * (ilvl + 1) as f32 * TWIP_TO_MM;
+            assert!((item.1 - left).abs() < 0.01, "{} at {}", text, item.1);
+            //The marker hangs into the indent
+            assert!((marker.1 - (left - 360.0 * TWIP_TO_MM)).abs() < 0.01);
+        }
+    }
+}
@@ -0,0 +1,1153 @@
+//! Pre-parse rewriting of `word/document.xml`. docx_rs silently drops what
+//! it has no model for, so before parsing such elements become `w:sym`
+//! markers, which it keeps verbatim, for the renderer to read back:
+//!
+//! - note references, and the preview images of embedded objects and charts;
+//! - the extent, fill and outline of text boxes, with drawing canvases and
+//!   groups split into one drawing per shape;
+//! - the text legacy check box and drop-down form fields show;
+//! - the linear text of equations, when wanted;
+//! - `w:smallCaps`, `w:position` and the language of `w:lang`;
+//! - the instruction of a simple `TOC` field, without its stale entries;
+//! - paragraph shading, and the gradient and pattern fills of shapes.
+//!
+//! A drop cap's line count goes in the `w:hRule` of its `w:framePr`, a
+//! simple `DOCVARIABLE` field is spelled out as a complex one, and a
+//! `w:vanish` switched off with its `w:val` is dropped.
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+use std::ops::Range;
+
+use docx_rs::{Paragraph, Run, RunChild, Sym};
+use log::warn;
+use quick_xml::events::{BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+use zip::ZipArchive;
+use zip::write::{FileOptions, ZipWriter};
+
+use crate::charts::read_chart_images;
+use crate::color::parse_hex;
+use crate::math::{MathMode, MathSymbols, read_linear};
+use crate::theme::{ThemeColors, read_theme_colors};
+use crate::{ConversionError, EMU_TO_MM, PT_TO_MM, TWIP_TO_MM, paragraph_runs, xml_attribute};
+
+/// Prefix of the `w:font` attribute on marker symbols.
+const MARKER_FONT: &str = "word_pdf_c:";
+
+/// Element local names that are rewritten, and the marker kind each becomes.
+const MARKED_ELEMENTS: &[(&[u8], &str)] = &[(b"footnoteReference", "footnote"), (b"endnoteReference", "endnote")];
+
+/// Content recovered from a marker symbol in a parsed run.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Marker {
+    Footnote(u32),
+    Endnote(u32),
+    /// Geometry and paint of the text box drawing before it in the run.
+    TextBox(TextBoxFrame),
+    SmallCaps,
+    /// Shift of the run's baseline from `w:position`, in mm, upwards.
+    Position(f32),
+    /// BCP 47 language of the run's text, from `w:lang`.
+    Lang(String),
+    /// Instruction of a simple `TOC` field.
+    Toc(String),
+    /// Fill of the paragraph's `w:shd`.
+    Shading((f32, f32, f32)),
+    /// An embedded object, shown as its preview image.
+    Object(ObjectPreview),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectPreview {
+    /// Relationship id of the preview image, or for the image of a chart
+    /// part its archive path.
+    pub image_id: String,
+    /// Size the object is shown at, in mm.
+    pub width: f32,
+    pub height: f32,
+}
+
+impl ObjectPreview {
+    /// Parses the `id width height` value, in points, written for a `w:object`.
+    fn parse(value: &str) -> Option<Self> {
+        let mut fields = value.split(' ');
+        let image_id = fields.next()?.to_string();
+        let mut length = || fields.next()?.parse::<f32>().ok().map(|points| points * PT_TO_MM);
+        let (width, height) = (length()?, length()?);
+        Some(ObjectPreview { image_id, width, height })
+    }
+}
+
+/// How a shape is filled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fill {
+    Solid((f32, f32, f32)),
+    /// A linear gradient from its first stop to its last. The angle is in
+    /// degrees clockwise from left to right, as DrawingML gives it.
+    Linear { from: (f32, f32, f32), to: (f32, f32, f32), angle: f32 },
+}
+
+impl Fill {
+    /// Parses a fill written by `frame_marker`: hex for a solid fill, or
+    /// `from>to>angle` for a gradient, the angle in 60000ths of a degree.
+    fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.split('>');
+        let from = parse_hex(parts.next()?)?;
+        match (parts.next(), parts.next()) {
+            (Some(to), Some(angle)) => {
+                Some(Fill::Linear { from, to: parse_hex(to)?, angle: angle.parse::<f32>().ok()? / 60000.0 })
+            }
+            _ => Some(Fill::Solid(from)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextBoxFrame {
+    /// Size of the shape in mm.
+    pub width: f32,
+    pub height: f32,
+    pub fill: Option<Fill>,
+    pub border: Option<(f32, f32, f32)>,
+    /// Set for a shape of a drawing canvas or group.
+    pub canvas: Option<CanvasPlacement>,
+}
+
+/// Where a shape sits on the drawing canvas or group it belongs to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanvasPlacement {
+    /// Offset of the shape from the canvas's top left corner, in mm.
+    pub x: f32,
+    pub y: f32,
+    /// Size of the whole canvas, in mm.
+    pub width: f32,
+    pub height: f32,
+    pub background: Option<(f32, f32, f32)>,
+    /// Whether this is the canvas's first shape, which places the canvas.
+    pub first: bool,
+}
+
+impl TextBoxFrame {
+    /// Parses the `cx cy fill outline` value written by `frame_marker`,
+    /// followed for canvas shapes by `x y canvas_cx canvas_cy background first`.
+    fn parse(value: &str) -> Option<Self> {
+        let mut fields = value.split(' ');
+        let mut length = || fields.next()?.parse::<f32>().ok().map(|emu| emu * EMU_TO_MM);
+        let (width, height) = (length()?, length()?);
+        let fill = Fill::parse(fields.next()?);
+        let border = parse_hex(fields.next()?);
+        let canvas = match fields.next() {
+            Some(x) => {
+                let x = x.parse::<f32>().ok()? * EMU_TO_MM;
+                let mut length = || fields.next()?.parse::<f32>().ok().map(|emu| emu * EMU_TO_MM);
+                let (y, width, height) = (length()?, length()?, length()?);
+                let background = parse_hex(fields.next()?);
+                let first = fields.next()? == "1";
+                Some(CanvasPlacement { x, y, width, height, background, first })
+            }
+            None => None,
+        };
+        Some(TextBoxFrame { width, height, fill, border, canvas })
+    }
+}
+
+/// The text a marker symbol stands for within its run, if any.
+pub fn marker_text(sym: &Sym) -> Option<&str> {
+    matches!(sym.font.strip_prefix(MARKER_FONT)?, "formfield" | "math").then_some(sym.char.as_str())
+}
+
+/// Number of lines a drop-cap paragraph spans, if it is one.
+pub fn drop_cap_lines(paragraph: &Paragraph) -> Option<usize> {
+    let frame = paragraph.property.frame_property.as_ref()?;
+    let lines = frame.h_rule.as_deref()?.strip_prefix(MARKER_FONT)?.strip_prefix("dropcap ")?;
+    lines.parse().ok()
+}
+
+/// The fill `paragraph` is shaded with, carried by its first run.
+pub fn paragraph_shading(paragraph: &Paragraph) -> Option<(f32, f32, f32)> {
+    let run = paragraph_runs(paragraph).next()?;
+    run_markers(run).into_iter().find_map(|marker| match marker {
+        Marker::Shading(rgb) => Some(rgb),
+        _ => None,
+    })
+}
+
+/// How far `run` is raised above the baseline, or lowered below it when
+/// negative, in mm.
+pub fn baseline_shift(run: &Run) -> f32 {
+    run_markers(run)
+        .into_iter()
+        .find_map(|marker| match marker {
+            Marker::Position(shift) => Some(shift),
+            _ => None,
+        })
+        .unwrap_or(0.0)
+}
+
+/// The language `run` gives its text with `w:lang`, if any.
+pub fn run_language(run: &Run) -> Option<String> {
+    run_markers(run).into_iter().find_map(|marker| match marker {
+        Marker::Lang(language) => Some(language),
+        _ => None,
+    })
+}
+
+/// Markers carried by `run`, in order.
+pub fn run_markers(run: &Run) -> Vec<Marker> {
+    run.children
+        .iter()
+        .filter_map(|child| match child {
+            RunChild::Sym(sym) => {
+                let kind = sym.font.strip_prefix(MARKER_FONT)?;
+                match kind {
+                    "footnote" => sym.char.parse().ok().map(Marker::Footnote),
+                    "endnote" => sym.char.parse().ok().map(Marker::Endnote),
+                    "textbox" => TextBoxFrame::parse(&sym.char).map(Marker::TextBox),
+                    "smallcaps" => Some(Marker::SmallCaps),
+                    "position" => sym.char.parse::<f32>().ok().map(|half_points| Marker::Position(half_points / 2.0 * PT_TO_MM)),
+                    "lang" => Some(Marker::Lang(sym.char.clone())),
+                    "toc" => Some(Marker::Toc(sym.char.clone())),
+                    "shading" => parse_hex(&sym.char).map(Marker::Shading),
+                    "object" => ObjectPreview::parse(&sym.char).map(Marker::Object),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns a copy of the docx archive with dropped elements in
+/// `word/document.xml` replaced by marker symbols, and theme colours
+/// resolved.
+pub fn add_markers(docx: &[u8], math: MathMode, symbols: MathSymbols) -> Result<Vec<u8>, ConversionError> {
+    let chart_images = read_chart_images(docx)?;
+    let theme = read_theme_colors(docx)?;
+    let mut archive = ZipArchive::new(Cursor::new(docx))?;
+    let mut output = ZipWriter::new(Cursor::new(Vec::new()));
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if entry.name() == "word/document.xml" {
+            let mut xml = Vec::new();
+            entry.read_to_end(&mut xml)?;
+            output.start_file(entry.name(), FileOptions::default())?;
+            output.write_all(&rewrite_document(&xml, math, symbols, &chart_images, &theme)?)?;
+        } else {
+            output.raw_copy_file(entry)?;
+        }
+    }
+    Ok(output.finish()?.into_inner())
+}
+
+fn xml_error(e: quick_xml::Error) -> ConversionError {
+    ConversionError::InvalidInput(format!("Malformed document.xml: {}", e))
+}
+
+/// An `a:xfrm`: a shape's offset and size, and for a group the range of
+/// child coordinates that size maps to, all in EMU.
+#[derive(Default)]
+struct Transform {
+    offset: [f64; 2],
+    size: [f64; 2],
+    child_offset: [f64; 2],
+    child_size: [f64; 2],
+}
+
+impl Transform {
+    fn read(&mut self, element: &BytesStart) {
+        let value = |name: &[u8]| xml_attribute(element, name).and_then(|v| v.parse().ok()).unwrap_or(0.0);
+        match element.local_name() {
+            b"off" => self.offset = [value(b"x"), value(b"y")],
+            b"ext" => self.size = [value(b"cx"), value(b"cy")],
+            b"chOff" => self.child_offset = [value(b"x"), value(b"y")],
+            b"chExt" => self.child_size = [value(b"cx"), value(b"cy")],
+            _ => {}
+        }
+    }
+
+    /// Maps a group's child offset and size to the coordinates around the group.
+    fn map(&self, [x, y, cx, cy]: [f64; 4]) -> [f64; 4] {
+        let scale = |i: usize| if self.child_size[i] > 0.0 { self.size[i] / self.child_size[i] } else { 1.0 };
+        [
+            self.offset[0] + (x - self.child_offset[0]) * scale(0),
+            self.offset[1] + (y - self.child_offset[1]) * scale(1),
+            cx * scale(0),
+            cy * scale(1),
+        ]
+    }
+}
+
+/// A shape or picture of a drawing canvas or group, located by its range in
+/// the rewritten output.
+#[derive(Default)]
+struct GroupChild {
+    range: Range<usize>,
+    is_shape: bool,
+    /// Namespace prefix of the shape's `wps:wsp`.
+    prefix: String,
+    /// Where an empty text box goes in a shape without one, so that docx_rs
+    /// keeps the shape.
+    text_box_at: Option<usize>,
+    /// Offset and size on the canvas, in EMU.
+    frame: Option<[f64; 4]>,
+    fill: Option<String>,
+    outline: Option<String>,
+}
+
+/// What has been seen so far of a drawing canvas (`wpc:wpc`) or group of
+/// shapes (`wpg:wgp`).
+#[derive(Default)]
+struct GroupScan {
+    /// Range of the canvas or group element in the rewritten output.
+    range: Range<usize>,
+    /// Nesting depth of canvases and groups.
+    depth: usize,
+    background: Option<String>,
+    in_background: bool,
+    /// The transforms of the enclosing groups, outermost first.
+    transforms: Vec<Transform>,
+    /// The `a:xfrm` being read.
+    transform: Option<Transform>,
+    child: Option<GroupChild>,
+    children: Vec<GroupChild>,
+}
+
+/// What has been seen so far of the `w:drawing` being copied.
+#[derive(Default)]
+struct DrawingScan {
+    /// Where the drawing starts in the rewritten output.
+    start: usize,
+    /// The `wp:extent` `cx` and `cy`, in EMU.
+    extent: Option<(String, String)>,
+    fill: Option<String>,
+    outline: Option<String>,
+    in_shape_properties: bool,
+    in_outline: bool,
+    /// The gradient or pattern fill being read.
+    compound_fill: Option<CompoundFill>,
+    has_text_box: bool,
+    group: Option<GroupScan>,
+    /// Relationship id of the chart the drawing shows.
+    chart: Option<String>,
+}
+
+/// What has been read of a shape's `a:gradFill` or `a:pattFill`.
+enum CompoundFill {
+    Gradient {
+        /// The colours of the stops read, with their positions.
+        stops: Vec<(u32, String)>,
+        /// Position of the `a:gs` being read, in thousandths of a percent.
+        position: u32,
+        /// The `a:lin` angle, in 60000ths of a degree.
+        angle: i64,
+    },
+    Pattern {
+        /// Share of the shape the foreground colour covers.
+        share: f32,
+        foreground: Option<String>,
+        background: Option<String>,
+        in_background: bool,
+    },
+}
+
+impl CompoundFill {
+    /// The fill as `frame_marker` writes it.
+    fn value(&self) -> Option<String> {
+        match self {
+            CompoundFill::Gradient { stops, angle, .. } => {
+                let first = stops.iter().min_by_key(|(position, _)| *position)?;
+                let last = stops.iter().max_by_key(|(position, _)| *position)?;
+                if first.1 == last.1 {
+                    return Some(first.1.clone());
+                }
+                Some(format!("{}>{}>{}", first.1, last.1, angle))
+            }
+            CompoundFill::Pattern { share, foreground, background, .. } => {
+                mix_hex(foreground.as_deref()?, background.as_deref().unwrap_or("FFFFFF"), *share)
+            }
+        }
+    }
+}
+
+/// Share of the area the foreground colour of a pattern such as `pct25`
+/// covers. Patterns of lines and grids are taken as half covered.
+fn pattern_share(pattern: &str) -> f32 {
+    match pattern {
+        "solid" => 1.0,
+        "clear" | "nil" => 0.0,
+        _ => pattern.strip_prefix("pct").and_then(|percent| percent.parse::<f32>().ok()).map_or(0.5, |percent| percent / 100.0),
+    }
+}
+
+/// `foreground` laid over `background` at `share`, both as hex.
+fn mix_hex(foreground: &str, background: &str, share: f32) -> Option<String> {
+    let ((fr, fg, fb), (br, bg, bb)) = (parse_hex(foreground)?, parse_hex(background)?);
+    let mix = |f: f32, b: f32| ((f * share + b * (1.0 - share)).clamp(0.0, 1.0) * 255.0).round() as u8;
+    Some(format!("{:02X}{:02X}{:02X}", mix(fr, br), mix(fg, bg), mix(fb, bb)))
+}
+
+impl DrawingScan {
+    /// Takes in an element starting at `at` in the rewritten output.
+    fn start(&mut self, element: &BytesStart, is_empty: bool, at: usize) {
+        let name = element.local_name();
+        if let Some(group) = self.group.as_mut() {
+            match name {
+                b"wgp" if !is_empty => {
+                    group.depth += 1;
+                    group.transforms.push(Transform::default());
+                }
+                b"wsp" | b"pic" if !is_empty && group.child.is_none() => {
+                    group.child = Some(GroupChild {
+                        range: at..at,
+                        is_shape: name == b"wsp",
+                        prefix: prefix(element.name()),
+                        ..GroupChild::default()
+                    });
+                    self.fill = None;
+                    self.outline = None;
+                    self.has_text_box = false;
+                }
+                b"bg" if !is_empty => group.in_background = true,
+                b"srgbClr" if group.in_background => group.background = xml_attribute(element, b"val"),
+                b"xfrm" if !is_empty => group.transform = Some(Transform::default()),
+                _ => {
+                    if let Some(transform) = group.transform.as_mut() {
+                        transform.read(element);
+                    }
+                }
+            }
+        } else if matches!(name, b"wpc" | b"wgp") && !is_empty {
+            self.group = Some(GroupScan { range: at..at, depth: 1, transforms: vec![Transform::default()], ..GroupScan::default() });
+        }
+        match name {
+            b"extent" if self.extent.is_none() => {
+                if let (Some(cx), Some(cy)) = (xml_attribute(element, b"cx"), xml_attribute(element, b"cy")) {
+                    self.extent = Some((cx, cy));
+                }
+            }
+            b"spPr" if !is_empty => self.in_shape_properties = true,
+            b"ln" if self.in_shape_properties => {
+                //An outline without its own fill is drawn in the default black
+                self.outline = Some("000000".to_string());
+                self.in_outline = !is_empty;
+            }
+            b"noFill" if self.in_outline => self.outline = None,
+            b"srgbClr" if self.in_outline => self.outline = xml_attribute(element, b"val"),
+            b"gradFill" if self.in_shape_properties && !is_empty => {
+                self.compound_fill = Some(CompoundFill::Gradient { stops: Vec::new(), position: 0, angle: 0 });
+            }
+            b"pattFill" if self.in_shape_properties && !is_empty => {
+                let share = pattern_share(&xml_attribute(element, b"prst").unwrap_or_default());
+                self.compound_fill = Some(CompoundFill::Pattern { share, foreground: None, background: None, in_background: false });
+            }
+            _ if self.compound_fill.is_some() => match (self.compound_fill.as_mut(), name) {
+                (Some(CompoundFill::Gradient { position, .. }), b"gs") => {
+                    *position = xml_attribute(element, b"pos").and_then(|pos| pos.parse().ok()).unwrap_or(0);
+                }
+                (Some(CompoundFill::Gradient { stops, position, .. }), b"srgbClr") => {
+                    stops.extend(xml_attribute(element, b"val").map(|color| (*position, color)));
+                }
+                (Some(CompoundFill::Gradient { angle, .. }), b"lin") => {
+                    *angle = xml_attribute(element, b"ang").and_then(|ang| ang.parse().ok()).unwrap_or(0);
+                }
+                (Some(CompoundFill::Pattern { in_background, .. }), b"fgClr" | b"bgClr") => *in_background = name == b"bgClr",
+                (Some(CompoundFill::Pattern { foreground, background, in_background, .. }), b"srgbClr") => {
+                    let color = xml_attribute(element, b"val");
+                    if *in_background { *background = color } else { *foreground = color }
+                }
+                _ => {}
+            },
+            b"srgbClr" if self.in_shape_properties => self.fill = xml_attribute(element, b"val"),
+            b"txbx" => self.has_text_box = true,
+            b"chart" if self.chart.is_none() => self.chart = xml_attribute(element, b"id"),
+            _ => {}
+        }
+    }
+
+    /// Takes in an end tag written from `before` to `after` in the rewritten output.
+    fn end(&mut self, local_name: &[u8], before: usize, after: usize) {
+        if let Some(group) = self.group.as_mut() {
+            match local_name {
+                b"bg" => group.in_background = false,
+                b"xfrm" => {
+                    if let Some(transform) = group.transform.take() {
+                        match group.child.as_mut() {
+                            Some(child) if child.frame.is_none() => {
+                                let own = [transform.offset[0], transform.offset[1], transform.size[0], transform.size[1]];
+                                let frame = group.transforms.iter().rev().fold(own, |frame, group| group.map(frame));
+                                child.frame = Some(frame);
+                            }
+                            Some(_) => {}
+                            None => {
+                                if let Some(group_transform) = group.transforms.last_mut() {
+                                    *group_transform = transform;
+                                }
+                            }
+                        }
+                    }
+                }
+                b"wsp" | b"pic" => {
+                    if let Some(mut child) = group.child.take() {
+                        child.range.end = after;
+                        if child.is_shape && !self.has_text_box {
+                            child.text_box_at = Some(before);
+                        }
+                        child.fill = self.fill.take();
+                        child.outline = self.outline.take();
+                        group.children.push(child);
+                    }
+                }
+                b"wpc" | b"wgp" => {
+                    group.depth -= 1;
+                    group.transforms.pop();
+                    if group.depth == 0 {
+                        group.range.end = after;
+                    }
+                }
+                _ => {}
+            }
+        }
+        match local_name {
+            b"spPr" => self.in_shape_properties = false,
+            b"ln" => self.in_outline = false,
+            b"gradFill" | b"pattFill" if !self.in_outline => {
+                if let Some(fill) = self.compound_fill.take() {
+                    self.fill = fill.value();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// What has been read of a legacy form field's `w:ffData`.
+#[derive(Default)]
+struct FormFieldScan {
+    is_check_box: bool,
+    in_check_box: bool,
+    in_drop_down: bool,
+    checked: Option<bool>,
+    default: Option<String>,
+    result: Option<usize>,
+    entries: Vec<String>,
+}
+
+impl FormFieldScan {
+    fn start(&mut self, element: &BytesStart, is_empty: bool) {
+        let value = xml_attribute(element, b"val");
+        match element.local_name() {
+            b"checkBox" => {
+                self.is_check_box = true;
+                self.in_check_box = !is_empty;
+            }
+            b"ddList" => self.in_drop_down = !is_empty,
+            b"checked" if self.in_check_box => self.checked = Some(!matches!(value.as_deref(), Some("0" | "false"))),
+            b"default" if self.in_check_box || self.in_drop_down => self.default = value,
+            b"result" if self.in_drop_down => self.result = value.and_then(|v| v.parse().ok()),
+            b"listEntry" if self.in_drop_down => self.entries.extend(value),
+            _ => {}
+        }
+    }
+
+    /// The text the field shows: a box for check boxes, the chosen entry for
+    /// drop-downs. Text inputs keep their result runs, so they have none.
+    fn value(&self) -> Option<String> {
+        if self.is_check_box {
+            let checked = self.checked.unwrap_or(matches!(self.default.as_deref(), Some("1" | "true")));
+            return Some(if checked { "[X]" } else { "[ ]" }.to_string());
+        }
+        let index = self.result.or_else(|| self.default.as_deref()?.parse().ok()).unwrap_or(0);
+        self.entries.get(index).cloned()
+    }
+}
+
+/// What has been read of an embedded object's `w:object`.
+#[derive(Default)]
+struct ObjectScan {
+    /// `w:dxaOrig` and `w:dyaOrig`, the object's own size in twips.
+    original_size: Option<(f32, f32)>,
+    /// The `style` of its `v:shape`, which gives the size it is shown at.
+    style: Option<String>,
+    image_id: Option<String>,
+}
+
+impl ObjectScan {
+    fn start(&mut self, element: &BytesStart) {
+        match element.local_name() {
+            b"shape" if self.style.is_none() => self.style = xml_attribute(element, b"style"),
+            b"imagedata" if self.image_id.is_none() => self.image_id = xml_attribute(element, b"id"),
+            _ => {}
+        }
+    }
+
+    /// The `id width height` marker value, sizes in points, if the object has a preview.
+    fn value(&self) -> Option<String> {
+        let image_id = self.image_id.as_deref()?;
+        let styled = |property: &str| {
+            let style = self.style.as_deref()?;
+            let value = style.split(';').find_map(|part| part.trim().strip_prefix(property)?.trim_start().strip_prefix(':'))?;
+            css_points(value.trim())
+        };
+        let original = self.original_size.map(|(width, height)| (width * TWIP_TO_MM / PT_TO_MM, height * TWIP_TO_MM / PT_TO_MM));
+        let (width, height) = match (styled("width"), styled("height"), original) {
+            (Some(width), Some(height), _) => (width, height),
+            (_, _, Some(size)) => size,
+            _ => return None,
+        };
+        Some(format!("{} {} {}", image_id, width, height))
+    }
+}
+
+/// A chart drawing whose marker is still to be written. Inside an
+/// `mc:AlternateContent` it waits for the end of it, so an image in its
+/// `mc:Fallback` can be found first.
+struct ChartScan {
+    prefix: String,
+    /// Relationship id of the chart part.
+    id: String,
+    /// The `wp:extent` `cx` and `cy`, in EMU.
+    extent: (String, String),
+    /// Relationship id of the fallback image.
+    fallback: Option<String>,
+}
+
+impl ChartScan {
+    /// The object marker showing the chart as its cached image: the
+    /// fallback, or else the image the chart part relates to, by path.
+    fn marker(&self, chart_images: &HashMap<String, String>) -> Option<BytesStart<'static>> {
+        let Some(image) = self.fallback.as_ref().or(chart_images.get(&self.id)) else {
+            warn!("Chart {} has no cached image and is left out", self.id);
+            return None;
+        };
+        let points = |emu: &str| emu.parse::<f32>().ok().map(|emu| emu * EMU_TO_MM / PT_TO_MM);
+        let (width, height) = (points(&self.extent.0)?, points(&self.extent.1)?);
+        Some(marker_sym(&self.prefix, "object", &format!("{} {} {}", image, width, height)))
+    }
+}
+
+/// A VML length such as `216pt` or `1.5in`, in points.
+fn css_points(value: &str) -> Option<f32> {
+    let split = value.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(value.len());
+    let number: f32 = value[..split].parse().ok()?;
+    let points_per_unit = match &value[split..] {
+        "pt" | "" => 1.0,
+        "in" => 72.0,
+        "cm" => 72.0 / 2.54,
+        "mm" => 72.0 / 25.4,
+        "px" => 0.75,
+        "pc" => 12.0,
+        _ => return None,
+    };
+    Some(number * points_per_unit)
+}
+
+fn rewrite_document(
+    xml: &[u8],
+    math: MathMode,
+    symbols: MathSymbols,
+    chart_images: &HashMap<String, String>,
+    theme: &ThemeColors,
+) -> Result<Vec<u8>, ConversionError> {
+    let mut reader = Reader::from_reader(xml);
+    let mut writer = Writer::new(Vec::new());
+    let mut buf = Vec::new();
+    //Depth inside a marked element written as Start/End, whose content is skipped
+    let mut skipping = 0;
+    //The drawing being copied, with the namespace prefix of its `w:drawing`
+    let mut drawing: Option<(String, DrawingScan)> = None;
+    //The `w:ffData` being read, which is replaced as a whole
+    let mut form_field: Option<(String, FormFieldScan)> = None;
+    //The `w:object` being read, likewise replaced as a whole
+    let mut object: Option<(String, ObjectScan)> = None;
+    //The chart drawn last, the depth inside `mc:AlternateContent` and whether
+    //inside its `mc:Fallback`
+    let mut chart: Option<ChartScan> = None;
+    let mut alternate_depth = 0;
+    let mut in_fallback = false;
+    //Namespace prefix of the body, for the runs that replace equations
+    let mut body_prefix = "w:".to_string();
+    //Whether the last start tag opened a run, whether the `w:rPr` being copied
+    //belongs to one, whether that turns small caps on, and the `w:position` and
+    //language it sets
+    let mut opened_run = false;
+    let mut in_run_properties = false;
+    let mut small_caps = false;
+    let mut position = None;
+    let mut language = None;
+    //Whether the last start tag opened a paragraph, and for the `w:pPr` being
+    //copied, the nesting depth inside it and the fill of its `w:shd`
+    let mut opened_paragraph = false;
+    let mut paragraph_properties: Option<(usize, Option<String>)> = None;
+    //For each `w:fldSimple` open, whether it was spelled out as a complex field
+    let mut simple_fields: Vec<bool> = Vec::new();
+    loop {
+        let event = reader.read_event(&mut buf).map_err(xml_error)?;
+        if skipping > 0 {
+            match event {
+                Event::Start(_) => skipping += 1,
+                Event::End(_) => skipping -= 1,
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+            continue;
+        }
+        if let Some((prefix, scan)) = form_field.as_mut() {
+            match event {
+                Event::Start(ref element) => scan.start(element, false),
+                Event::Empty(ref element) => scan.start(element, true),
+                Event::End(ref element) if element.local_name() == b"checkBox" => scan.in_check_box = false,
+                Event::End(ref element) if element.local_name() == b"ddList" => scan.in_drop_down = false,
+                Event::End(ref element) if element.local_name() == b"ffData" => {
+                    if let Some(value) = scan.value() {
+                        writer.write_event(Event::Empty(marker_sym(prefix, "formfield", &value))).map_err(xml_error)?;
+                    }
+                    form_field = None;
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+            continue;
+        }
+        if let Some((prefix, scan)) = object.as_mut() {
+            match event {
+                Event::Start(ref element) | Event::Empty(ref element) => scan.start(element),
+                Event::End(ref element) if element.name() == format!("{}object", prefix).as_bytes() => {
+                    if let Some(value) = scan.value() {
+                        writer.write_event(Event::Empty(marker_sym(prefix, "object", &value))).map_err(xml_error)?;
+                    }
+                    object = None;
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+            continue;
+        }
+        match event {
+            Event::Start(ref element) if element.local_name() == b"rPr" => in_run_properties = opened_run,
+            Event::Empty(ref element) if in_run_properties && element.local_name() == b"smallCaps" => {
+                small_caps = !matches!(xml_attribute(element, b"val").as_deref(), Some("0" | "false"));
+            }
+            Event::Empty(ref element) if in_run_properties && element.local_name() == b"position" => {
+                position = xml_attribute(element, b"val").filter(|value| value.parse::<f32>().is_ok_and(|shift| shift != 0.0));
+            }
+            Event::Empty(ref element) if in_run_properties && element.local_name() == b"lang" => {
+                language = xml_attribute(element, b"val").filter(|value| !value.is_empty());
+            }
+            _ => {}
+        }
+        if let Some((depth, fill)) = paragraph_properties.as_mut() {
+            match event {
+                Event::Start(_) => *depth += 1,
+                Event::End(_) if *depth > 0 => *depth -= 1,
+                Event::Empty(ref element) if *depth == 0 && element.local_name() == b"shd" => *fill = shading_fill(element),
+                _ => {}
+            }
+        }
+        if let Event::Start(ref element) = event {
+            if opened_paragraph && element.local_name() == b"pPr" {
+                paragraph_properties = Some((0, None));
+            }
+            opened_run = element.name() == format!("{}r", body_prefix).as_bytes();
+            opened_paragraph = element.name() == format!("{}p", body_prefix).as_bytes();
+        }
+        match event {
+            Event::Eof => break,
+            Event::End(ref element) if in_run_properties && element.local_name() == b"rPr" => {
+                writer.write_event(&event).map_err(xml_error)?;
+                if small_caps {
+                    writer.write_event(Event::Empty(marker_sym(&body_prefix, "smallcaps", "1"))).map_err(xml_error)?;
+                }
+                if let Some(position) = position.take() {
+                    writer.write_event(Event::Empty(marker_sym(&body_prefix, "position", &position))).map_err(xml_error)?;
+                }
+                if let Some(language) = language.take() {
+                    writer.write_event(Event::Empty(marker_sym(&body_prefix, "lang", &language))).map_err(xml_error)?;
+                }
+                in_run_properties = false;
+                small_caps = false;
+            }
+            Event::End(ref element)
+                if element.local_name() == b"pPr" && paragraph_properties.as_ref().is_some_and(|(depth, _)| *depth == 0) =>
+            {
+                writer.write_event(&event).map_err(xml_error)?;
+                if let Some((_, Some(fill))) = paragraph_properties.take() {
+                    let run = BytesStart::owned_name(format!("{}r", body_prefix));
+                    writer.write_event(Event::Start(run.to_borrowed())).map_err(xml_error)?;
+                    writer.write_event(Event::Empty(marker_sym(&body_prefix, "shading", &fill))).map_err(xml_error)?;
+                    writer.write_event(Event::End(run.to_end())).map_err(xml_error)?;
+                }
+            }
+            Event::Empty(ref element) if in_run_properties && element.local_name() == b"color" => {
+                writer.write_event(Event::Empty(resolve_theme_color(element, theme))).map_err(xml_error)?;
+            }
+            //docx_rs reads any `w:vanish` as hidden text, so one switched off is dropped
+            Event::Empty(ref element)
+                if in_run_properties
+                    && element.local_name() == b"vanish"
+                    && matches!(xml_attribute(element, b"val").as_deref(), Some("0" | "false" | "off")) => {}
+            Event::Start(ref element) if element.local_name() == b"body" => {
+                body_prefix = prefix(element.name());
+                writer.write_event(&event).map_err(xml_error)?;
+            }
+            Event::Start(ref element) if element.local_name() == b"oMath" && math == MathMode::Text => {
+                let text = read_linear(&mut reader, symbols).map_err(xml_error)?;
+                let run = BytesStart::owned_name(format!("{}r", body_prefix));
+                writer.write_event(Event::Start(run.to_borrowed())).map_err(xml_error)?;
+                writer.write_event(Event::Empty(marker_sym(&body_prefix, "math", &text))).map_err(xml_error)?;
+                writer.write_event(Event::End(run.to_end())).map_err(xml_error)?;
+            }
+            Event::Start(ref element) | Event::Empty(ref element)
+                if element.local_name() == b"fldSimple" && is_toc_field(element) =>
+            {
+                if matches!(event, Event::Start(_)) {
+                    skipping = 1;
+                }
+                let instruction = xml_attribute(element, b"instr").unwrap_or_default();
+                let run = BytesStart::owned_name(format!("{}r", body_prefix));
+                writer.write_event(Event::Start(run.to_borrowed())).map_err(xml_error)?;
+                writer.write_event(Event::Empty(marker_sym(&body_prefix, "toc", &instruction))).map_err(xml_error)?;
+                writer.write_event(Event::End(run.to_end())).map_err(xml_error)?;
+            }
+            Event::Start(ref element) | Event::Empty(ref element) if element.local_name() == b"fldSimple" => {
+                let instruction = xml_attribute(element, b"instr").unwrap_or_default();
+                let variable = instruction.trim_start().starts_with("DOCVARIABLE");
+                if variable {
+                    let prefix = prefix(element.name());
+                    write_field_char(&mut writer, &prefix, "begin")?;
+                    let run = BytesStart::owned_name(format!("{}r", prefix));
+                    let instr_text = BytesStart::owned_name(format!("{}instrText", prefix));
+                    writer.write_event(Event::Start(run.to_borrowed())).map_err(xml_error)?;
+                    writer.write_event(Event::Start(instr_text.to_borrowed())).map_err(xml_error)?;
+                    writer.write_event(Event::Text(BytesText::from_plain_str(&instruction))).map_err(xml_error)?;
+                    writer.write_event(Event::End(instr_text.to_end())).map_err(xml_error)?;
+                    writer.write_event(Event::End(run.to_end())).map_err(xml_error)?;
+                    write_field_char(&mut writer, &prefix, "separate")?;
+                    if matches!(event, Event::Empty(_)) {
+                        write_field_char(&mut writer, &prefix, "end")?;
+                    }
+                } else {
+                    writer.write_event(&event).map_err(xml_error)?;
+                }
+                if matches!(event, Event::Start(_)) {
+                    simple_fields.push(variable);
+                }
+            }
+            Event::End(ref element) if element.local_name() == b"fldSimple" => {
+                if simple_fields.pop().unwrap_or(false) {
+                    write_field_char(&mut writer, &prefix(element.name()), "end")?;
+                } else {
+                    writer.write_event(&event).map_err(xml_error)?;
+                }
+            }
+            Event::Start(ref element) if element.local_name() == b"ffData" => {
+                form_field = Some((prefix(element.name()), FormFieldScan::default()));
+            }
+            Event::Start(ref element) if element.name() == format!("{}object", body_prefix).as_bytes() => {
+                let twips = |name: &[u8]| xml_attribute(element, name).and_then(|value| value.parse::<f32>().ok());
+                let original_size = twips(b"dxaOrig").zip(twips(b"dyaOrig"));
+                object = Some((body_prefix.clone(), ObjectScan { original_size, ..ObjectScan::default() }));
+            }
+            Event::Empty(ref element) if element.local_name() == b"framePr" => {
+                let rewritten = drop_cap_frame(element).map(Event::Empty);
+                writer.write_event(rewritten.as_ref().unwrap_or(&event)).map_err(xml_error)?;
+            }
+            Event::Start(ref element) if element.local_name() == b"drawing" => {
+                let start = writer.inner().len();
+                drawing = Some((prefix(element.name()), DrawingScan { start, ..DrawingScan::default() }));
+                writer.write_event(&event).map_err(xml_error)?;
+            }
+            Event::End(ref element) if element.local_name() == b"drawing" => {
+                writer.write_event(&event).map_err(xml_error)?;
+                if let Some((prefix, scan)) = drawing.take() {
+                    match &scan.group {
+                        Some(group) if !group.children.is_empty() => split_group(&mut writer, &prefix, &scan, group)?,
+                        _ => {
+                            if let Some(marker) = frame_marker(&prefix, &scan) {
+                                writer.write_event(Event::Empty(marker)).map_err(xml_error)?;
+                            }
+                        }
+                    }
+                    if let (Some(id), Some(extent)) = (scan.chart, scan.extent) {
+                        let scan = ChartScan { prefix, id, extent, fallback: None };
+                        if alternate_depth == 0 {
+                            if let Some(marker) = scan.marker(chart_images) {
+                                writer.write_event(Event::Empty(marker)).map_err(xml_error)?;
+                            }
+                        } else {
+                            chart = Some(scan);
+                        }
+                    }
+                }
+            }
+            Event::Empty(ref element) | Event::Start(ref element) => {
+                match element.local_name() {
+                    b"AlternateContent" if matches!(event, Event::Start(_)) => alternate_depth += 1,
+                    b"Fallback" if matches!(event, Event::Start(_)) => in_fallback = true,
+                    b"blip" | b"imagedata" if in_fallback => {
+                        if let Some(chart) = chart.as_mut().filter(|chart| chart.fallback.is_none()) {
+                            chart.fallback = xml_attribute(element, b"embed").or_else(|| xml_attribute(element, b"id"));
+                        }
+                    }
+                    _ => {}
+                }
+                let at = writer.inner().len();
+                if let Some((_, scan)) = drawing.as_mut() {
+                    scan.start(element, matches!(event, Event::Empty(_)), at);
+                }
+                match marker_for(element) {
+                    Some(marker) => {
+                        if matches!(event, Event::Start(_)) {
+                            skipping = 1;
+                        }
+                        writer.write_event(Event::Empty(marker)).map_err(xml_error)?;
+                    }
+                    None => writer.write_event(&event).map_err(xml_error)?,
+                }
+            }
+            Event::End(ref element) => {
+                let before = writer.inner().len();
+                writer.write_event(&event).map_err(xml_error)?;
+                match element.local_name() {
+                    b"Fallback" => in_fallback = false,
+                    b"AlternateContent" => {
+                        alternate_depth -= 1;
+                        if let Some(marker) = chart.take().filter(|_| alternate_depth == 0).and_then(|chart| chart.marker(chart_images)) {
+                            writer.write_event(Event::Empty(marker)).map_err(xml_error)?;
+                        }
+                    }
+                    _ => {}
+                }
+                if let Some((_, scan)) = drawing.as_mut() {
+                    scan.end(element.local_name(), before, writer.inner().len());
+                }
+            }
+            _ => writer.write_event(&event).map_err(xml_error)?,
+        }
+        buf.clear();
+    }
+    Ok(writer.into_inner())
+}
+
+/// The namespace prefix of a qualified name, including its colon.
+fn prefix(name: &[u8]) -> String {
+    match name.iter().position(|b| *b == b':') {
+        Some(colon) => String::from_utf8_lossy(&name[..=colon]).into_owned(),
+        None => String::new(),
+    }
+}
+
+/// A run's `w:color` with any `w:themeColor` it refers to written into its
+/// `w:val`, which is all docx_rs reads, and only as the first attribute.
+/// Colours the theme lacks keep their `w:val`, which Word writes as the
+/// colour last resolved.
+fn resolve_theme_color(element: &BytesStart, theme: &ThemeColors) -> BytesStart<'static> {
+    let unchanged = element.to_owned();
+    let Some(theme_color) = xml_attribute(element, b"themeColor") else { return unchanged };
+    let resolved = theme.resolve(&theme_color, xml_attribute(element, b"themeTint").as_deref(), xml_attribute(element, b"themeShade").as_deref());
+    let Some(resolved) = resolved else { return unchanged };
+    let name = String::from_utf8_lossy(element.name()).into_owned();
+    let value_key = format!("{}val", prefix(name.as_bytes()));
+    let mut color = BytesStart::owned_name(name);
+    color.push_attribute((value_key.as_str(), resolved.as_str()));
+    color.extend_attributes(element.attributes().flatten().filter(|attribute| attribute.key != value_key.as_bytes()));
+    color
+}
+
+fn marker_sym(prefix: &str, kind: &str, value: &str) -> BytesStart<'static> {
+    let mut sym = BytesStart::owned_name(format!("{}sym", prefix));
+    sym.push_attribute((format!("{}font", prefix).as_str(), format!("{}{}", MARKER_FONT, kind).as_str()));
+    sym.push_attribute((format!("{}char", prefix).as_str(), value));
+    sym
+}
+
+/// Writes a run holding a `w:fldChar` of the type `kind`.
+fn write_field_char(writer: &mut Writer<Vec<u8>>, prefix: &str, kind: &str) -> Result<(), ConversionError> {
+    let run = BytesStart::owned_name(format!("{}r", prefix));
+    let mut field_char = BytesStart::owned_name(format!("{}fldChar", prefix));
+    field_char.push_attribute((format!("{}fldCharType", prefix).as_str(), kind));
+    writer.write_event(Event::Start(run.to_borrowed())).map_err(xml_error)?;
+    writer.write_event(Event::Empty(field_char)).map_err(xml_error)?;
+    writer.write_event(Event::End(run.to_end())).map_err(xml_error)?;
+    Ok(())
+}
+
+/// Builds the `w:sym` replacement for `element`, if it is one that gets marked.
+fn marker_for(element: &BytesStart) -> Option<BytesStart<'static>> {
+    let (_, kind) = MARKED_ELEMENTS.iter().find(|(name, _)| element.local_name() == *name)?;
+    let id = xml_attribute(element, b"id").unwrap_or_default();
+    //Reuse the element's own namespace prefix for the replacement
+    Some(marker_sym(&prefix(element.name()), kind, &id))
+}
+
+/// The colour a `w:shd` fills with, as hex, unless it is `auto`. A pattern
+/// is given as its colour laid over the fill at the share it covers.
+fn shading_fill(element: &BytesStart) -> Option<String> {
+    let fill = xml_attribute(element, b"fill").filter(|fill| parse_hex(fill).is_some());
+    let share = pattern_share(&xml_attribute(element, b"val").unwrap_or_default());
+    let Some(color) = xml_attribute(element, b"color").filter(|color| parse_hex(color).is_some() && share > 0.0) else { return fill };
+    mix_hex(&color, fill.as_deref().unwrap_or("FFFFFF"), share)
+}
+
+fn is_toc_field(element: &BytesStart) -> bool {
+    xml_attribute(element, b"instr").is_some_and(|instruction| instruction.trim_start().starts_with("TOC"))
+}
+
+/// Rebuilds a drop cap's `w:framePr` with its line count moved into `w:hRule`.
+fn drop_cap_frame(element: &BytesStart) -> Option<BytesStart<'static>> {
+    if !matches!(xml_attribute(element, b"dropCap").as_deref(), Some("drop" | "margin")) {
+        return None;
+    }
+    let lines = xml_attribute(element, b"lines").unwrap_or_else(|| "1".to_string());
+    let name = element.name();
+    let mut frame = BytesStart::owned_name(name.to_vec());
+    for attribute in element.attributes().flatten() {
+        let local = attribute.key.rsplit(|b| *b == b':').next().unwrap_or_default();
+        if !matches!(local, b"dropCap" | b"lines" | b"hRule") {
+            frame.push_attribute(attribute);
+        }
+    }
+    frame.push_attribute((
+        format!("{}hRule", prefix(name)).as_str(),
+        format!("{}dropcap {}", MARKER_FONT, lines).as_str(),
+    ));
+    Some(frame)
+}
+
+/// Builds the frame marker that follows a text box drawing.
+fn frame_marker(prefix: &str, scan: &DrawingScan) -> Option<BytesStart<'static>> {
+    if !scan.has_text_box {
+        return None;
+    }
+    let (cx, cy) = scan.extent.as_ref()?;
+    let value = format!(
+        "{} {} {} {}",
+        cx,
+        cy,
+        scan.fill.as_deref().unwrap_or("-"),
+        scan.outline.as_deref().unwrap_or("-")
+    );
+    Some(marker_sym(prefix, "textbox", &value))
+}
+
+/// Replaces the canvas or group drawing just written with one drawing per
+/// shape or picture in it. Each copy keeps the original's placement and
+/// extent; shapes are followed by a frame marker locating them on the canvas.
+fn split_group(
+    writer: &mut Writer<Vec<u8>>,
+    prefix: &str,
+    scan: &DrawingScan,
+    group: &GroupScan,
+) -> Result<(), ConversionError> {
+    let Some((canvas_cx, canvas_cy)) = scan.extent.as_ref() else { return Ok(()) };
+    let drawing = writer.inner().split_off(scan.start);
+    let local = |range: &Range<usize>| range.start - scan.start..range.end - scan.start;
+    let group_range = local(&group.range);
+    let (wrapper, suffix) = (&drawing[..group_range.start], &drawing[group_range.end..]);
+    let background = group.background.as_deref().unwrap_or("-");
+    let mut first = true;
+    for child in &group.children {
+        let range = local(&child.range);
+        let output = writer.inner();
+        output.extend_from_slice(wrapper);
+        match child.text_box_at {
+            Some(at) => {
+                let at = at - scan.start;
+                output.extend_from_slice(&drawing[range.start..at]);
+                output.extend_from_slice(format!("<{0}txbx><{1}txbxContent/></{0}txbx>", child.prefix, prefix).as_bytes());
+                output.extend_from_slice(&drawing[at..range.end]);
+            }
+            None => output.extend_from_slice(&drawing[range]),
+        }
+        output.extend_from_slice(suffix);
+        if !child.is_shape {
+            continue;
+        }
+        let [x, y, cx, cy] = child.frame.unwrap_or_default();
+        let value = format!(
+            "{:.0} {:.0} {} {} {:.0} {:.0} {} {} {} {}",
+            cx,
+            cy,
+            child.fill.as_deref().unwrap_or("-"),
+            child.outline.as_deref().unwrap_or("-"),
+            x,
+            y,
+            canvas_cx,
+            canvas_cy,
+            background,
+            u8::from(first)
+        );
+        writer.write_event(Event::Empty(marker_sym(prefix, "textbox", &value))).map_err(xml_error)?;
+        first = false;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use docx_rs::{Docx, Paragraph, Run};
+
+    use super::*;
+    use crate::{Config, read_part};
+    use crate::tests::{archive, edited_docx, laid_out_archive};
+
+    #[test]
+    fn form_fields_show_their_current_value() {
+        let docx = Docx::new()
+            .add_paragraph(Paragraph::new().add_run(Run::new().add_text("CHECK")))
+            .add_paragraph(Paragraph::new().add_run(Run::new().add_text("DROP")));
+        let check_box = r#"<w:fldChar w:fldCharType="begin"><w:ffData><w:checkBox><w:default w:val="0" /><w:checked /></w:checkBox></w:ffData></w:fldChar>"#;
+        let drop_down = r#"<w:fldChar w:fldCharType="begin"><w:ffData><w:ddList><w:result w:val="1" /><w:listEntry w:val="First" /><w:listEntry w:val="Second" /></w:ddList></w:ffData></w:fldChar>"#;
+        let packed = edited_docx(docx, |name, content| match name {
+            "word/document.xml" => content
+                .replace(r#"<w:t xml:space="preserve">CHECK</w:t>"#, check_box)
+                .replace(r#"<w:t xml:space="preserve">DROP</w:t>"#, drop_down),
+            _ => content,
+        });
+        let runs = laid_out_archive(&packed, &Config::new("fields.docx", "fields.pdf"));
+        let texts: Vec<&str> = runs.iter().map(|(text, ..)| text.as_str()).collect();
+        //`w:checked` wins over the unchecked default
+        assert!(texts.contains(&"[X]"), "{:?}", texts);
+        assert!(texts.contains(&"Second"), "{:?}", texts);
+        assert!(!texts.iter().any(|text| text.contains("First")));
+    }
+
+    /// `body` as a document, with markers added.
+    fn marked(body: &str) -> String {
+        let document = format!(
+            r#"<w:document xmlns:w="w" xmlns:wp="wp" xmlns:a="a" xmlns:wps="wps"><w:body>{}</w:body></w:document>"#,
+            body
+        );
+        let packed = add_markers(&archive(&[("word/document.xml", &document)]), MathMode::Omit, MathSymbols::Unicode).unwrap();
+        String::from_utf8(read_part(&packed, "word/document.xml").unwrap().unwrap()).unwrap()
+    }
+
+    /// A text box drawing 1 by 0.5 inches whose shape properties are `properties`.
+    fn text_box(properties: &str) -> String {
+        format!(
+            r#"<w:p><w:r><w:drawing><wp:inline><wp:extent cx="914400" cy="457200" /><a:graphic><a:graphicData><wps:wsp><wps:spPr>{}</wps:spPr><wps:txbx><w:txbxContent><w:p /></w:txbxContent></wps:txbx></wps:wsp></a:graphicData></a:graphic></wp:inline></w:drawing></w:r></w:p>"#,
+            properties
+        )
+    }
+
+    #[test]
+    fn carries_gradient_and_pattern_fills() {
+        //The first and last stops by position, whatever their order, and the angle, escaped in the marker
+        let gradient = r#"<a:gradFill><a:gsLst><a:gs pos="100000"><a:srgbClr val="0000FF" /></a:gs><a:gs pos="0"><a:srgbClr val="FF0000" /></a:gs></a:gsLst><a:lin ang="5400000" /></a:gradFill>"#;
+        let xml = marked(&text_box(gradient));
+        assert!(xml.contains(r#"w:char="914400 457200 FF0000&gt;0000FF&gt;5400000 -""#), "{}", xml);
+        assert_eq!(
+            Fill::parse("FF0000>0000FF>5400000"),
+            Some(Fill::Linear { from: (1.0, 0.0, 0.0), to: (0.0, 0.0, 1.0), angle: 90.0 })
+        );
+
+        //A quarter of black over white, in a shape and in paragraph shading
+        let pattern = r#"<a:pattFill prst="pct25"><a:fgClr><a:srgbClr val="000000" /></a:fgClr><a:bgClr><a:srgbClr val="FFFFFF" /></a:bgClr></a:pattFill>"#;
+        assert!(marked(&text_box(pattern)).contains(r#"w:char="914400 457200 BFBFBF -""#));
+        let shaded = marked(r#"<w:p><w:pPr><w:shd w:val="pct25" w:color="000000" w:fill="FFFFFF" /></w:pPr><w:r><w:t>Shaded</w:t></w:r></w:p>"#);
+        assert!(shaded.contains(r#"w:font="word_pdf_c:shading" w:char="BFBFBF""#), "{}", shaded);
+    }
+}
@@ -0,0 +1,232 @@
+//! Office Math. docx_rs drops `m:oMath` content, so equations are read
+//! from the raw XML and written out in a linear form close to Word's own
+//! UnicodeMath, e.g. `(a+b)/2` or `√(x^2+1)`. The built-in fonts have no
+//! glyphs for its symbols, so unless the document's font does they are
+//! spelled out in ASCII, as in `sqrt(x^2+1)`.
+
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+use crate::xml_attribute;
+
+/// What to do with equations.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MathMode {
+    /// Leave them out, as docx_rs does.
+    #[default]
+    Omit,
+    /// Write their linear form inline as text.
+    Text,
+}
+
+impl std::str::FromStr for MathMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "omit" => Ok(MathMode::Omit),
+            "text" => Ok(MathMode::Text),
+            other => Err(format!("Unknown math mode: {}", other)),
+        }
+    }
+}
+
+/// Symbols of the linear form outside WinAnsiEncoding: radicals,
+/// matrices, equation arrays and the usual n-ary operators.
+pub const UNICODE_SYMBOLS: &str = "√■█∫∑∏";
+
+/// How the symbols of the linear form are written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MathSymbols {
+    /// As UnicodeMath writes them, such as `√x` and `■(a&b)`.
+    Unicode,
+    /// As ASCII words, such as `sqrt(x)` and `matrix(a&b)`.
+    Ascii,
+}
+
+impl MathSymbols {
+    fn radical(self) -> &'static str {
+        match self {
+            MathSymbols::Unicode => "√",
+            MathSymbols::Ascii => "sqrt",
+        }
+    }
+
+    fn matrix(self) -> &'static str {
+        match self {
+            MathSymbols::Unicode => "■",
+            MathSymbols::Ascii => "matrix",
+        }
+    }
+
+    fn equation_array(self) -> &'static str {
+        match self {
+            MathSymbols::Unicode => "█",
+            MathSymbols::Ascii => "eqarray",
+        }
+    }
+
+    /// `operator`, the character of an n-ary operator, as it is written.
+    fn operator(self, operator: &str) -> String {
+        let word = match operator {
+            "∫" => "int",
+            "∑" => "sum",
+            "∏" => "prod",
+            _ => return operator.to_string(),
+        };
+        match self {
+            MathSymbols::Unicode => operator.to_string(),
+            MathSymbols::Ascii => word.to_string(),
+        }
+    }
+}
+
+/// An element of an equation, or a text node (`name` empty).
+#[derive(Default)]
+struct Node {
+    name: Vec<u8>,
+    /// The `m:val` attribute, which carries delimiter and operator characters.
+    value: Option<String>,
+    text: String,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn child(&self, name: &[u8]) -> Option<&Node> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// The `m:val` of the property `name` inside the properties element `properties`.
+    fn property(&self, properties: &[u8], name: &[u8]) -> Option<&str> {
+        self.child(properties)?.child(name)?.value.as_deref()
+    }
+
+    /// Linear form of the child argument `name`, empty if it is missing.
+    fn argument(&self, name: &[u8], symbols: MathSymbols) -> String {
+        self.child(name).map(|child| linear(child, symbols)).unwrap_or_default()
+    }
+}
+
+/// Reads the rest of an `m:oMath` whose start tag has just been read and
+/// returns its linear form, written with `symbols`. The reader is left
+/// after the closing tag.
+pub fn read_linear(reader: &mut Reader<&[u8]>, symbols: MathSymbols) -> Result<String, quick_xml::Error> {
+    let mut stack = vec![Node { name: b"oMath".to_vec(), ..Node::default() }];
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(ref element) => stack.push(Node {
+                name: element.local_name().to_vec(),
+                value: xml_attribute(element, b"val"),
+                ..Node::default()
+            }),
+            Event::Empty(ref element) => {
+                let node = Node { name: element.local_name().to_vec(), value: xml_attribute(element, b"val"), ..Node::default() };
+                stack.last_mut().expect("root node").children.push(node);
+            }
+            //Only `m:t` holds equation text; anything else is layout whitespace
+            Event::Text(ref text) if stack.last().is_some_and(|node| node.name == b"t") => {
+                let text = text.unescape_and_decode(reader)?;
+                stack.last_mut().expect("root node").children.push(Node { text, ..Node::default() });
+            }
+            Event::End(_) => {
+                let node = stack.pop().expect("root node");
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => return Ok(linear(&node, symbols)),
+                }
+            }
+            Event::Eof => return Ok(stack.first().map(|node| linear(node, symbols)).unwrap_or_default()),
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+/// Parenthesises `text` unless it is a single number or identifier.
+fn group(text: String) -> String {
+    if text.chars().all(char::is_alphanumeric) { text } else { format!("({})", text) }
+}
+
+/// Joins the linear forms of every child named `name` with `separator`.
+fn joined(node: &Node, name: &[u8], separator: &str, symbols: MathSymbols) -> String {
+    node.children.iter().filter(|child| child.name == name).map(|child| linear(child, symbols)).collect::<Vec<_>>().join(separator)
+}
+
+fn linear(node: &Node, symbols: MathSymbols) -> String {
+    let argument = |name: &[u8]| group(node.argument(name, symbols));
+    match node.name.as_slice() {
+        b"" => node.text.clone(),
+        b"f" => format!("{}/{}", argument(b"num"), argument(b"den")),
+        b"sSup" => format!("{}^{}", argument(b"e"), argument(b"sup")),
+        b"sSub" => format!("{}_{}", argument(b"e"), argument(b"sub")),
+        b"sSubSup" => format!("{}_{}^{}", argument(b"e"), argument(b"sub"), argument(b"sup")),
+        b"sPre" => format!("_{}^{}{}", argument(b"sub"), argument(b"sup"), argument(b"e")),
+        b"rad" => match (node.argument(b"deg", symbols), symbols) {
+            (degree, MathSymbols::Unicode) if degree.is_empty() => format!("√{}", argument(b"e")),
+            //A word needs brackets even around a single term
+            (degree, MathSymbols::Ascii) if degree.is_empty() => format!("sqrt({})", node.argument(b"e", symbols)),
+            (degree, _) => format!("{}({}&{})", symbols.radical(), degree, node.argument(b"e", symbols)),
+        },
+        b"d" => {
+            let begin = node.property(b"dPr", b"begChr").unwrap_or("(");
+            let end = node.property(b"dPr", b"endChr").unwrap_or(")");
+            let separator = node.property(b"dPr", b"sepChr").unwrap_or("|");
+            format!("{}{}{}", begin, joined(node, b"e", separator, symbols), end)
+        }
+        b"nary" => {
+            let mut text = symbols.operator(node.property(b"naryPr", b"chr").unwrap_or("∫"));
+            for (name, mark) in [(&b"sub"[..], '_'), (&b"sup"[..], '^')] {
+                let limit = node.argument(name, symbols);
+                if !limit.is_empty() {
+                    text.push(mark);
+                    text.push_str(&group(limit));
+                }
+            }
+            format!("{} {}", text, node.argument(b"e", symbols))
+        }
+        b"func" => format!("{} {}", node.argument(b"fName", symbols), argument(b"e")),
+        b"limLow" => format!("{}_{}", node.argument(b"e", symbols), argument(b"lim")),
+        b"limUpp" => format!("{}^{}", node.argument(b"e", symbols), argument(b"lim")),
+        b"m" => {
+            let rows: Vec<String> =
+                node.children.iter().filter(|row| row.name == b"mr").map(|row| joined(row, b"e", "&", symbols)).collect();
+            format!("{}({})", symbols.matrix(), rows.join("@"))
+        }
+        b"eqArr" => format!("{}({})", symbols.equation_array(), joined(node, b"e", "@", symbols)),
+        //Property elements are read by their parents
+        name if name.ends_with(b"Pr") => String::new(),
+        _ => node.children.iter().map(|child| linear(child, symbols)).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The linear form of the content of an `m:oMath`.
+    fn equation(content: &str, symbols: MathSymbols) -> String {
+        let xml = format!("<m:oMath>{}</m:oMath>", content);
+        let mut reader = Reader::from_reader(xml.as_bytes());
+        reader.read_event(&mut Vec::new()).unwrap();
+        read_linear(&mut reader, symbols).unwrap()
+    }
+
+    const RADICAL_SUM_MATRIX: &str = "<m:rad><m:deg/><m:e><m:r><m:t>x</m:t></m:r></m:e></m:rad>\
+        <m:nary><m:naryPr><m:chr m:val=\"∑\"/></m:naryPr><m:sub><m:r><m:t>i</m:t></m:r></m:sub><m:sup/>\
+        <m:e><m:r><m:t>i</m:t></m:r></m:e></m:nary>\
+        <m:m><m:mr><m:e><m:r><m:t>a</m:t></m:r></m:e><m:e><m:r><m:t>b</m:t></m:r></m:e></m:mr></m:m>";
+
+    #[test]
+    fn unicode_symbols() {
+        assert_eq!(equation(RADICAL_SUM_MATRIX, MathSymbols::Unicode), "√x∑_i i■(a&b)");
+    }
+
+    #[test]
+    fn ascii_symbols() {
+        assert_eq!(equation(RADICAL_SUM_MATRIX, MathSymbols::Ascii), "sqrt(x)sum_i imatrix(a&b)");
+        let array = "<m:eqArr><m:e><m:r><m:t>a</m:t></m:r></m:e><m:e><m:r><m:t>b</m:t></m:r></m:e></m:eqArr>";
+        assert_eq!(equation(array, MathSymbols::Ascii), "eqarray(a@b)");
+        assert!(!equation(RADICAL_SUM_MATRIX, MathSymbols::Ascii).contains(|c: char| UNICODE_SYMBOLS.contains(c)));
+    }
+}
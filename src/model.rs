@@ -0,0 +1,111 @@
+//! A record of the laid-out pages for tooling: the size of each page and
+//! the runs of text and pictures on it, with their positions. Layout and
+//! drawing happen together, so the model is what the layout drew, taken
+//! down as it was drawn; editing it does not change the PDF. The model
+//! serializes with serde, e.g. `serde_json::to_string(&model)`.
+
+use serde::Serialize;
+
+use crate::LaidOutRun;
+
+/// The pages of a laid-out document, in order. Positions and sizes are in
+/// mm, from the bottom left corner of the page.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LayoutModel {
+    pub pages: Vec<PageModel>,
+}
+
+/// A page and what is drawn on it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PageModel {
+    pub width: f32,
+    pub height: f32,
+    /// Lines of text, in the order they were drawn.
+    pub lines: Vec<LineModel>,
+    pub images: Vec<ImageModel>,
+}
+
+/// Runs of text sharing a baseline that were drawn one after another, such
+/// as a line of a paragraph, or the lines of table cells side by side.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LineModel {
+    /// The baseline the runs sit on.
+    pub y: f32,
+    pub runs: Vec<RunModel>,
+}
+
+/// A run of text in one font, as `LaidOutRun` reports it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RunModel {
+    pub text: String,
+    /// Left end of the text.
+    pub x: f32,
+    /// Advance width of the text, including any letter spacing.
+    pub width: f32,
+    /// Font size in points.
+    pub font_size: f32,
+    /// PostScript name of the font.
+    pub font: String,
+}
+
+/// A picture drawn on the page.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ImageModel {
+    /// Archive path of the picture, such as `word/media/image1.png`.
+    pub name: String,
+    /// Bottom left corner of the picture.
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Baselines closer than this, in mm, are the same line.
+const SAME_BASELINE: f32 = 0.01;
+
+impl LayoutModel {
+    pub(crate) fn add_page(&mut self, width: f32, height: f32) {
+        self.pages.push(PageModel { width, height, ..PageModel::default() });
+    }
+
+    pub(crate) fn add_run(&mut self, run: &LaidOutRun) {
+        let Some(page) = self.pages.get_mut(run.page) else { return };
+        let text = RunModel {
+            text: run.text.to_string(),
+            x: run.x,
+            width: run.width,
+            font_size: run.font_size,
+            font: run.font.to_string(),
+        };
+        match page.lines.last_mut() {
+            Some(line) if (line.y - run.y).abs() < SAME_BASELINE => line.runs.push(text),
+            _ => page.lines.push(LineModel { y: run.y, runs: vec![text] }),
+        }
+    }
+
+    pub(crate) fn add_image(&mut self, page: usize, image: ImageModel) {
+        if let Some(page) = self.pages.get_mut(page) {
+            page.images.push(image);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_runs_by_line() {
+        let mut model = LayoutModel::default();
+        model.add_page(210.0, 297.0);
+        for (text, x) in [("Hello", 20.0), ("world", 40.0)] {
+            model.add_run(&LaidOutRun { text, page: 0, x, y: 250.0, width: 15.0, font_size: 12.0, font: "Helvetica" });
+        }
+        let json = serde_json::to_value(&model).unwrap();
+        let line = &json["pages"][0]["lines"][0];
+        assert_eq!(line["y"], 250.0);
+        assert_eq!(line["runs"][1]["text"], "world");
+        assert_eq!(line["runs"][0]["font_size"], 12.0);
+        assert_eq!(json["pages"][0]["images"], serde_json::json!([]));
+    }
+}
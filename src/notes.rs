@@ -0,0 +1,210 @@
+//! Footnotes and endnotes. Footnotes are drawn in a reserved area at the
+//! bottom of the page that references them; endnotes are collected and
+//! drawn as a section after the body.
+
+use std::collections::HashMap;
+
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+use crate::{ConversionError, PT_TO_MM, read_part};
+
+/// Font size of footnote text, in points, before `--font-scale`.
+pub const NOTE_FONT_SIZE: f32 = 9.0;
+/// Font size of the superscript reference mark in the body, in points,
+/// before `--font-scale`.
+pub const MARK_FONT_SIZE: f32 = 7.0;
+/// Space between the body and the separator rule above the notes, in mm.
+const SEPARATOR_GAP: f32 = 3.0;
+
+/// The note texts of a document, keyed by `w:id`.
+#[derive(Default)]
+pub struct NoteTexts {
+    pub footnotes: HashMap<u32, String>,
+    pub endnotes: HashMap<u32, String>,
+}
+
+impl NoteTexts {
+    /// Reads the notes, leaving out their hidden text unless `keep_hidden`.
+    pub fn read(docx: &[u8], keep_hidden: bool) -> Result<Self, ConversionError> {
+        Ok(NoteTexts {
+            footnotes: read_notes(docx, "word/footnotes.xml", keep_hidden)?,
+            endnotes: read_notes(docx, "word/endnotes.xml", keep_hidden)?,
+        })
+    }
+}
+
+/// Plain text of each note in a notes part, keyed by `w:id`.
+fn read_notes(docx: &[u8], part: &str, keep_hidden: bool) -> Result<HashMap<u32, String>, ConversionError> {
+    let Some(xml) = read_part(docx, part)? else { return Ok(HashMap::new()) };
+
+    let mut notes = HashMap::new();
+    let mut reader = Reader::from_reader(&xml[..]);
+    let mut buf = Vec::new();
+    //The note being read, if it is a real note rather than a separator
+    let mut current: Option<(u32, Vec<String>)> = None;
+    let mut in_text = false;
+    //Whether the run being read is formatted as hidden text
+    let mut hidden = false;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) if e.local_name() == b"footnote" || e.local_name() == b"endnote" => {
+                let mut id = None;
+                let mut separator = false;
+                for attribute in e.attributes().flatten() {
+                    match attribute.key.rsplit(|b| *b == b':').next() {
+                        Some(b"id") => id = String::from_utf8_lossy(&attribute.value).parse().ok(),
+                        Some(b"type") => separator = &*attribute.value != b"normal",
+                        _ => {}
+                    }
+                }
+                current = id.filter(|_| !separator).map(|id| (id, Vec::new()));
+            }
+            Ok(Event::Start(ref e)) if e.local_name() == b"p" => {
+                if let Some((_, paragraphs)) = current.as_mut() {
+                    paragraphs.push(String::new());
+                }
+            }
+            Ok(Event::Start(ref e)) if e.local_name() == b"r" => hidden = false,
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) if e.local_name() == b"vanish" => hidden = !keep_hidden,
+            Ok(Event::Start(ref e)) if e.local_name() == b"t" => in_text = !hidden,
+            Ok(Event::End(ref e)) if e.local_name() == b"t" => in_text = false,
+            Ok(Event::Text(ref e)) if in_text => {
+                if let Some(paragraph) = current.as_mut().and_then(|(_, p)| p.last_mut()) {
+                    paragraph.push_str(&e.unescape_and_decode(&reader).unwrap_or_default());
+                }
+            }
+            Ok(Event::End(ref e)) if e.local_name() == b"footnote" || e.local_name() == b"endnote" => {
+                if let Some((id, paragraphs)) = current.take() {
+                    notes.insert(id, paragraphs.join(" ").trim().to_string());
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(ConversionError::InvalidInput(format!("Malformed {}: {}", part, e))),
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(notes)
+}
+
+/// Footnotes waiting to be drawn at the bottom of the current page.
+#[derive(Default)]
+pub struct FootnoteArea {
+    texts: HashMap<u32, String>,
+    /// Font size of the note text, in points.
+    pub font_size: f32,
+    /// Number given to the next footnote reference.
+    next_number: usize,
+    /// Wrapped lines of each pending note, numbered.
+    pending: Vec<Vec<String>>,
+    /// Height reserved above the bottom margin for the pending notes, in mm.
+    pub height: f32,
+}
+
+impl FootnoteArea {
+    pub fn new(texts: HashMap<u32, String>, font_size: f32) -> Self {
+        FootnoteArea { texts, font_size, next_number: 1, ..Default::default() }
+    }
+
+    pub fn line_height(&self) -> f32 {
+        self.font_size * 1.2 * PT_TO_MM
+    }
+
+    /// Numbers the note with `id` and returns its number with its text.
+    pub fn reference(&mut self, id: u32) -> (usize, String) {
+        let number = self.next_number;
+        self.next_number += 1;
+        (number, self.texts.get(&id).cloned().unwrap_or_default())
+    }
+
+    /// Extra height reserving `lines` more note lines would take.
+    pub fn height_for(&self, lines: usize) -> f32 {
+        let gap = if self.pending.is_empty() { SEPARATOR_GAP } else { 0.0 };
+        lines as f32 * self.line_height() + gap
+    }
+
+    pub fn push(&mut self, lines: Vec<String>) {
+        self.height += self.height_for(lines.len());
+        self.pending.push(lines);
+    }
+
+    /// Removes the pending notes, returning their lines and the separator's height above the margin.
+    pub fn take(&mut self) -> Option<(Vec<Vec<String>>, f32)> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let height = std::mem::take(&mut self.height);
+        Some((std::mem::take(&mut self.pending), height - SEPARATOR_GAP / 2.0))
+    }
+}
+
+/// Endnotes referenced so far, in the order they are numbered.
+#[derive(Default)]
+pub struct EndnoteList {
+    texts: HashMap<u32, String>,
+    pub entries: Vec<(usize, String)>,
+}
+
+impl EndnoteList {
+    pub fn new(texts: HashMap<u32, String>) -> Self {
+        EndnoteList { texts, entries: Vec::new() }
+    }
+
+    /// Numbers the note with `id` and queues it for the endnotes section.
+    pub fn reference(&mut self, id: u32) -> usize {
+        let number = self.entries.len() + 1;
+        self.entries.push((number, self.texts.get(&id).cloned().unwrap_or_default()));
+        number
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Config;
+    use crate::tests::{edited_docx, laid_out_archive, with_parts};
+    use docx_rs::{Docx, Paragraph, Run};
+
+    const FOOTNOTES: &str = r#"<w:footnotes xmlns:w="w">
+        <w:footnote w:type="separator" w:id="0"><w:p><w:r><w:separator/></w:r></w:p></w:footnote>
+        <w:footnote w:id="1"><w:p><w:r><w:t>See the appendix.</w:t></w:r></w:p></w:footnote>
+    </w:footnotes>"#;
+
+    #[test]
+    fn draws_footnotes_at_the_bottom_of_their_page() {
+        let paragraph = Paragraph::new().add_run(Run::new().add_text("Body text")).add_run(Run::new().add_text("NOTE"));
+        let packed = edited_docx(Docx::new().add_paragraph(paragraph), |_, content| {
+            content.replace(r#"<w:t xml:space="preserve">NOTE</w:t>"#, r#"<w:footnoteReference w:id="1" />"#)
+        });
+        let docx = with_parts(&packed, &[("word/footnotes.xml", FOOTNOTES)]);
+        let config = Config::new("notes.docx", "notes.pdf");
+        let runs = laid_out_archive(&docx, &config);
+
+        //The mark follows the body text, and the note sits just above the bottom margin
+        let body = runs.iter().position(|(text, ..)| text == "Body text").unwrap();
+        assert_eq!(runs[body + 1].0, "1");
+        let note = runs.iter().find(|(text, ..)| text == "1 See the appendix.").unwrap();
+        assert!(note.2 > config.margin && note.2 < config.margin + 10.0, "note at {} mm", note.2);
+    }
+
+    #[test]
+    fn lists_endnotes_on_a_final_page_in_reference_order() {
+        const ENDNOTES: &str = r#"<w:endnotes xmlns:w="w">
+            <w:endnote w:id="4"><w:p><w:r><w:t>Cited first.</w:t></w:r></w:p></w:endnote>
+            <w:endnote w:id="2"><w:p><w:r><w:t>Cited second.</w:t></w:r></w:p></w:endnote>
+        </w:endnotes>"#;
+        let paragraph = Paragraph::new().add_run(Run::new().add_text("Body")).add_run(Run::new().add_text("FIRST")).add_run(Run::new().add_text("SECOND"));
+        let packed = edited_docx(Docx::new().add_paragraph(paragraph), |_, content| {
+            content
+                .replace(r#"<w:t xml:space="preserve">FIRST</w:t>"#, r#"<w:endnoteReference w:id="4" />"#)
+                .replace(r#"<w:t xml:space="preserve">SECOND</w:t>"#, r#"<w:endnoteReference w:id="2" />"#)
+        });
+        let docx = with_parts(&packed, &[("word/endnotes.xml", ENDNOTES)]);
+        let runs = laid_out_archive(&docx, &Config::new("notes.docx", "notes.pdf"));
+
+        let texts: Vec<&str> = runs.iter().map(|(text, ..)| text.as_str()).collect();
+        assert_eq!(texts, ["Body", "1", "2", "Endnotes", "1 Cited first.", "2 Cited second."]);
+        //The heading starts a page of its own, at the top like the body did
+        assert_eq!(runs[3].2, runs[0].2);
+    }
+}
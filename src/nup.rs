@@ -0,0 +1,254 @@
+//! N-up printing. With `--nup`, the laid-out pages are scaled down and
+//! placed several to a sheet, in reading order. Each page becomes a form
+//! XObject painted into its cell of the sheet; its links move with it and
+//! destinations on it are pointed at the sheet.
+//!
+//! A square count of pages (4, 9, ...) is set as a grid on a sheet the size
+//! of the first page. Twice a square (2, 8, ...) turns the sheet, so that
+//! two portrait pages sit side by side on a landscape one.
+
+use std::collections::HashMap;
+
+use printpdf::lopdf::{self, Dictionary, Document, Object, ObjectId, Stream};
+
+use crate::ConversionError;
+use crate::template::media_box;
+
+/// How the pages of one sheet are arranged.
+#[derive(Debug, Clone, Copy)]
+struct Grid {
+    columns: usize,
+    rows: usize,
+    /// The sheet is the first page turned a quarter.
+    turned: bool,
+}
+
+impl Grid {
+    /// The arrangement of `count` pages a sheet, if it has one.
+    fn for_count(count: u32) -> Option<Grid> {
+        let side = |n: u32| {
+            let k = (n as f64).sqrt().round() as u64;
+            (k * k == n as u64).then_some(k as usize)
+        };
+        if count < 2 {
+            return None;
+        }
+        match side(count) {
+            Some(k) => Some(Grid { columns: k, rows: k, turned: false }),
+            None if count.is_multiple_of(2) => side(count / 2).map(|k| Grid { columns: 2 * k, rows: k, turned: true }),
+            None => None,
+        }
+    }
+}
+
+/// Checks that `count` pages can be arranged on a sheet.
+pub fn validate_nup(count: u32) -> Result<u32, String> {
+    Grid::for_count(count).map(|_| count).ok_or_else(|| {
+        format!("--nup needs a square number of pages or twice one, such as 2, 4, 8 or 9: {}", count)
+    })
+}
+
+/// The scale and offset (in points) mapping a page into a cell.
+#[derive(Debug, Clone, Copy)]
+struct Placement {
+    scale: f32,
+    x: f32,
+    y: f32,
+}
+
+impl Placement {
+    fn point(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.x + x * self.scale, self.y + y * self.scale)
+    }
+}
+
+/// A number of `object`, if it is one.
+fn number(object: &Object) -> Option<f32> {
+    object.as_float().ok()
+}
+
+/// The form XObject drawing the page `page_id`, added to `document`.
+fn page_form(document: &mut Document, page_id: ObjectId, bounds: [f32; 4]) -> Result<ObjectId, lopdf::Error> {
+    let content = document.get_page_content(page_id)?;
+    let (resources, inherited) = document.get_page_resources(page_id);
+    let resources = match (resources, inherited.first()) {
+        (Some(resources), _) => Object::Dictionary(resources.clone()),
+        (None, Some(id)) => Object::Reference(*id),
+        (None, None) => Object::Dictionary(Dictionary::new()),
+    };
+    let mut form_dict = Dictionary::new();
+    form_dict.set("Type", Object::Name(b"XObject".to_vec()));
+    form_dict.set("Subtype", Object::Name(b"Form".to_vec()));
+    form_dict.set("BBox", Object::Array(bounds.iter().map(|value| Object::Real(*value)).collect()));
+    form_dict.set("Resources", resources);
+    let mut form = Stream::new(form_dict, content);
+    form.compress()?;
+    Ok(document.add_object(form))
+}
+
+/// Moves the link annotations of the page `page_id` onto a sheet, returning
+/// references to them.
+fn move_annotations(document: &mut Document, page_id: ObjectId, placement: Placement) -> Vec<Object> {
+    let annotations = match document.get_dictionary(page_id).and_then(|page| page.get(b"Annots")) {
+        Ok(Object::Array(annotations)) => annotations.clone(),
+        _ => return Vec::new(),
+    };
+    for annotation in &annotations {
+        let Ok(id) = annotation.as_reference() else { continue };
+        let Ok(annotation) = document.get_dictionary_mut(id) else { continue };
+        if let Ok(Object::Array(rect)) = annotation.get_mut(b"Rect") {
+            let values: Vec<f32> = rect.iter().filter_map(number).collect();
+            if let [left, bottom, right, top] = values[..] {
+                let ((left, bottom), (right, top)) = (placement.point(left, bottom), placement.point(right, top));
+                *rect = [left, bottom, right, top].into_iter().map(Object::Real).collect();
+            }
+        }
+    }
+    annotations
+}
+
+/// Points explicit destinations on the pages at the sheets they are placed
+/// on. A destination at a position keeps it; any other shows the sheet.
+fn retarget_destinations(document: &mut Document, sheets: &HashMap<ObjectId, (ObjectId, Placement)>) {
+    fn retarget(object: &mut Object, sheets: &HashMap<ObjectId, (ObjectId, Placement)>) {
+        match object {
+            Object::Array(items) => {
+                let target = items.first().and_then(|item| item.as_reference().ok()).and_then(|id| sheets.get(&id));
+                if let Some(&(sheet, placement)) = target {
+                    let position = items.get(1).and_then(|kind| kind.as_name().ok()) == Some(b"XYZ".as_slice());
+                    if position {
+                        let (left, top) = (items.get(2).and_then(number), items.get(3).and_then(number));
+                        let (x, y) = placement.point(left.unwrap_or(0.0), top.unwrap_or(0.0));
+                        items[0] = Object::Reference(sheet);
+                        if left.is_some() {
+                            items[2] = Object::Real(x);
+                        }
+                        if top.is_some() {
+                            items[3] = Object::Real(y);
+                        }
+                    } else {
+                        *items = vec![Object::Reference(sheet), Object::Name(b"Fit".to_vec())];
+                    }
+                    return;
+                }
+                items.iter_mut().for_each(|item| retarget(item, sheets));
+            }
+            Object::Dictionary(dictionary) => dictionary.iter_mut().for_each(|(_, value)| retarget(value, sheets)),
+            _ => {}
+        }
+    }
+    for object in document.objects.values_mut() {
+        retarget(object, sheets);
+    }
+}
+
+/// Places the pages of `pdf` `count` to a sheet.
+pub fn impose(pdf: &[u8], count: u32) -> Result<Vec<u8>, ConversionError> {
+    let pdf_error = |e: lopdf::Error| ConversionError::Pdf(e.to_string());
+    let Some(grid) = Grid::for_count(count) else {
+        return Err(ConversionError::Pdf(format!("{} pages cannot be arranged on a sheet", count)));
+    };
+    let mut document = Document::load_mem(pdf).map_err(pdf_error)?;
+    let pages: Vec<ObjectId> = document.get_pages().into_values().collect();
+    let Some(&first) = pages.first() else { return Ok(pdf.to_vec()) };
+    let catalog_id = document.trailer.get(b"Root").and_then(Object::as_reference).map_err(pdf_error)?;
+    let tree_id = document.get_dictionary(catalog_id).and_then(|catalog| catalog.get(b"Pages")).and_then(Object::as_reference).map_err(pdf_error)?;
+
+    let missing_box = || ConversionError::Pdf("A page has no MediaBox".to_string());
+    let [left, bottom, right, top] = media_box(&document, first).ok_or_else(missing_box)?;
+    let (width, height) = if grid.turned { (top - bottom, right - left) } else { (right - left, top - bottom) };
+    //The longer side of the sheet takes the longer side of the grid
+    let (columns, rows) = if (width >= height) == (grid.columns >= grid.rows) {
+        (grid.columns, grid.rows)
+    } else {
+        (grid.rows, grid.columns)
+    };
+    let (cell_width, cell_height) = (width / columns as f32, height / rows as f32);
+
+    let mut sheets = Vec::new();
+    let mut placed = HashMap::new();
+    for group in pages.chunks(columns * rows) {
+        let sheet_id = document.new_object_id();
+        let mut content = String::new();
+        let mut xobjects = Dictionary::new();
+        let mut annotations = Vec::new();
+        for (slot, &page_id) in group.iter().enumerate() {
+            let bounds = media_box(&document, page_id).ok_or_else(missing_box)?;
+            let [page_left, page_bottom, page_right, page_top] = bounds;
+            let (page_width, page_height) = (page_right - page_left, page_top - page_bottom);
+            let scale = (cell_width / page_width).min(cell_height / page_height);
+            //Centred in its cell, cells filled left to right and top to bottom
+            let (column, row) = (slot % columns, slot / columns);
+            let cell_left = column as f32 * cell_width + (cell_width - page_width * scale) / 2.0;
+            let cell_bottom = height - (row + 1) as f32 * cell_height + (cell_height - page_height * scale) / 2.0;
+            let placement = Placement { scale, x: cell_left - page_left * scale, y: cell_bottom - page_bottom * scale };
+
+            let name = format!("Page{}", slot);
+            let form_id = page_form(&mut document, page_id, bounds).map_err(pdf_error)?;
+            xobjects.set(name.as_bytes().to_vec(), Object::Reference(form_id));
+            content.push_str(&format!("q {} 0 0 {} {} {} cm /{} Do Q\n", scale, scale, placement.x, placement.y, name));
+            annotations.extend(move_annotations(&mut document, page_id, placement));
+            placed.insert(page_id, (sheet_id, placement));
+        }
+        let content_id = document.add_object(Stream::new(Dictionary::new(), content.into_bytes()));
+        let mut resources = Dictionary::new();
+        resources.set("XObject", xobjects);
+        let mut sheet = Dictionary::new();
+        sheet.set("Type", Object::Name(b"Page".to_vec()));
+        sheet.set("Parent", Object::Reference(tree_id));
+        sheet.set("MediaBox", Object::Array(vec![0.into(), 0.into(), Object::Real(width), Object::Real(height)]));
+        sheet.set("Contents", Object::Reference(content_id));
+        sheet.set("Resources", resources);
+        if !annotations.is_empty() {
+            sheet.set("Annots", Object::Array(annotations));
+        }
+        document.objects.insert(sheet_id, Object::Dictionary(sheet));
+        sheets.push(Object::Reference(sheet_id));
+    }
+
+    for page_id in &pages {
+        document.objects.remove(page_id);
+    }
+    retarget_destinations(&mut document, &placed);
+    let tree = document.get_dictionary_mut(tree_id).map_err(pdf_error)?;
+    tree.set("Count", Object::Integer(sheets.len() as i64));
+    tree.set("Kids", Object::Array(sheets));
+    document.prune_objects();
+
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use docx_rs::{Docx, Paragraph, Run};
+
+    use super::*;
+    use crate::Config;
+
+    #[test]
+    fn puts_two_pages_side_by_side_on_each_sheet() {
+        let mut docx = Docx::new();
+        //Enough lines for four pages
+        for _ in 0..90 {
+            docx = docx.add_paragraph(Paragraph::new().add_run(Run::new().add_text("Filler")));
+        }
+        let mut config = Config::new("nup.docx", "nup.pdf");
+        let plain = Document::load_mem(&crate::convert_parsed(&docx, &[], &config).unwrap()).unwrap();
+        assert_eq!(plain.get_pages().len(), 4);
+        let [_, _, page_width, page_height] = media_box(&plain, plain.get_pages()[&1]).unwrap();
+
+        config.nup = Some(2);
+        let document = Document::load_mem(&crate::convert_parsed(&docx, &[], &config).unwrap()).unwrap();
+        let sheets = document.get_pages();
+        assert_eq!(sheets.len(), 2);
+        //Each sheet is a page turned, holding two pages
+        for id in sheets.values() {
+            let [_, _, width, height] = media_box(&document, *id).unwrap();
+            assert!((width - page_height).abs() < 0.01 && (height - page_width).abs() < 0.01);
+            let content = String::from_utf8(document.get_page_content(*id).unwrap()).unwrap();
+            assert_eq!(content.matches(" Do Q").count(), 2);
+        }
+    }
+}
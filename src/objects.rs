@@ -0,0 +1,117 @@
+//! Embedded objects such as spreadsheets. Only the preview image Word keeps
+//! for each is drawn, inline at the object's place in the body. Previews
+//! the image decoder cannot read, such as EMF, leave an outline of the
+//! object's size instead.
+
+use docx_rs::Run;
+use log::warn;
+use printpdf::{ImageTransform, Mm};
+
+use crate::color::pdf_image;
+use crate::debug::{DebugBox, draw_debug_box};
+use crate::markup::{Marker, ObjectPreview, run_markers};
+use crate::{Layout, draw_rect, effective_dpi, fit_scale};
+
+/// The embedded objects of `run`, in order.
+pub fn run_objects(run: &Run) -> Vec<ObjectPreview> {
+    run_markers(run)
+        .into_iter()
+        .filter_map(|marker| match marker {
+            Marker::Object(object) => Some(object),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Size `object` is drawn at, scaled down to fit the text area.
+fn display_size(layout: &Layout, object: &ObjectPreview) -> (f32, f32) {
+    let config = layout.config;
+    let usable_width = config.page_width - 2.0 * config.margin - layout.left_indent;
+    let fit = fit_scale(config, usable_width, object.width, object.height);
+    (object.width * fit, object.height * fit)
+}
+
+/// Height `object` pushes the body flow down by.
+pub fn object_height(layout: &Layout, object: &ObjectPreview) -> f32 {
+    display_size(layout, object).1
+}
+
+/// Draws the preview of `object` at the cursor and moves the cursor below it.
+pub fn render_object(layout: &mut Layout, object: &ObjectPreview) {
+    let (width, height) = display_size(layout, object);
+    layout.ensure_space(height);
+    let (left, bottom) = (layout.config.margin + layout.left_indent, layout.y_position - height);
+    match layout.previews.get(&object.image_id) {
+        Some(&(path, image)) => {
+            let dpi = effective_dpi(image.width(), width);
+            let alt = layout.alt_texts.get(&object.image_id).map(String::as_str);
+            let marked = layout.tags.get_mut().begin_figure(&layout.layer, layout.page, alt);
+            pdf_image(image, layout.config.color_space).add_to_layer(
+                layout.layer.clone(),
+                ImageTransform {
+                    translate_x: Some(Mm(left)),
+                    translate_y: Some(Mm(bottom)),
+                    scale_y: Some(height / (image.height() as f32 / dpi * 25.4)),
+                    dpi: Some(dpi),
+                    ..ImageTransform::default()
+                },
+            );
+            layout.tags.get_mut().end_figure(&layout.layer, marked);
+            layout.report_image(path, (left, bottom), width, height);
+            layout.placed_previews.insert(path.to_string());
+        }
+        None => {
+            warn!("The preview of an embedded object ({}) cannot be decoded; drawing its outline", object.image_id);
+            draw_rect(&layout.layer, left, bottom, width, height);
+        }
+    }
+    draw_debug_box(layout, DebugBox::Image, left, bottom, width, height);
+    layout.y_position -= height;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use docx_rs::{Docx, Paragraph, Pic, Run};
+
+    use crate::model::LayoutModel;
+    use crate::tests::edited_docx;
+    use crate::{Config, PT_TO_MM, SourceDocument, parse_document, render_pdf_bytes};
+
+    #[test]
+    fn draws_an_objects_preview_in_its_place() {
+        let mut png = Cursor::new(Vec::new());
+        ::image::RgbImage::from_pixel(20, 10, ::image::Rgb([0, 0, 255])).write_to(&mut png, ::image::ImageFormat::Png).unwrap();
+        let docx = Docx::new()
+            .add_paragraph(Paragraph::new().add_run(Run::new().add_text("Before")))
+            .add_paragraph(Paragraph::new().add_run(Run::new().add_image(Pic::new(png.get_ref()))))
+            .add_paragraph(Paragraph::new().add_run(Run::new().add_text("After")));
+        //The picture docx_rs writes is swapped for an embedded object previewed by the same image
+        let packed = edited_docx(docx, |name, content| match name {
+            "word/document.xml" => {
+                let (start, end) = (content.find("<w:drawing>").unwrap(), content.find("</w:drawing>").unwrap() + "</w:drawing>".len());
+                let id = content[start..end].split("r:embed=\"").nth(1).and_then(|rest| rest.split('"').next()).unwrap();
+                let object = format!(
+                    r#"<w:object w:dxaOrig="2880" w:dyaOrig="1440"><v:shape style="width:144pt;height:72pt"><v:imagedata r:id="{}" /></v:shape><o:OLEObject Type="Embed" ProgID="Excel.Sheet.12" /></w:object>"#,
+                    id
+                );
+                format!("{}{}{}", &content[..start], object, &content[end..])
+            }
+            _ => content,
+        });
+        let config = Config::new("object.docx", "object.pdf");
+        let document = parse_document(&packed, "object.docx", &config).unwrap();
+        let source = SourceDocument { docx: &document.docx, images: &document.images, parts: document.parts, attachment: None };
+        let mut model = LayoutModel::default();
+        render_pdf_bytes(vec![source], &config, None, Some(&mut model)).unwrap();
+
+        let page = &model.pages[0];
+        assert_eq!(page.images.len(), 1);
+        let image = &page.images[0];
+        assert!((image.width - 144.0 * PT_TO_MM).abs() < 0.01 && (image.height - 72.0 * PT_TO_MM).abs() < 0.01);
+        //Between the paragraphs around it, not after the body
+        let baseline = |text: &str| page.lines.iter().find(|line| line.runs[0].text == text).unwrap().y;
+        assert!(baseline("Before") > image.y + image.height && image.y > baseline("After"));
+    }
+}
@@ -0,0 +1,262 @@
+//! Page numbers. A section's `w:pgNumType` sets the style its pages are
+//! numbered in (1, 2, 3 or i, ii, iii, ...) and may restart the count.
+//! Headers and footers are not drawn, so the numbers are written as PDF
+//! page labels, which viewers show in place of page indices, and used by
+//! tables of contents.
+
+use printpdf::lopdf::{self, Dictionary, Object};
+
+use crate::ConversionError;
+
+/// The style of a section's page numbers, from `w:fmt`. Styles page labels
+/// cannot show are numbered in decimal.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum NumberFormat {
+    #[default]
+    Decimal,
+    UpperRoman,
+    LowerRoman,
+    UpperLetter,
+    LowerLetter,
+}
+
+impl NumberFormat {
+    pub fn from_word(value: &str) -> NumberFormat {
+        match value {
+            "upperRoman" => NumberFormat::UpperRoman,
+            "lowerRoman" => NumberFormat::LowerRoman,
+            "upperLetter" => NumberFormat::UpperLetter,
+            "lowerLetter" => NumberFormat::LowerLetter,
+            _ => NumberFormat::Decimal,
+        }
+    }
+
+    /// The `/S` entry of a page label dictionary.
+    fn label_style(self) -> &'static [u8] {
+        match self {
+            NumberFormat::Decimal => b"D",
+            NumberFormat::UpperRoman => b"R",
+            NumberFormat::LowerRoman => b"r",
+            NumberFormat::UpperLetter => b"A",
+            NumberFormat::LowerLetter => b"a",
+        }
+    }
+
+    /// `number` written in this style, as a viewer shows its page label.
+    pub fn format(self, number: u32) -> String {
+        match self {
+            NumberFormat::Decimal => number.to_string(),
+            NumberFormat::UpperRoman => roman(number),
+            NumberFormat::LowerRoman => roman(number).to_lowercase(),
+            //a to z, then aa to zz and so on
+            NumberFormat::UpperLetter | NumberFormat::LowerLetter if number > 0 => {
+                let base = if self == NumberFormat::UpperLetter { b'A' } else { b'a' };
+                let letter = (base + ((number - 1) % 26) as u8) as char;
+                std::iter::repeat_n(letter, ((number - 1) / 26 + 1) as usize).collect()
+            }
+            _ => number.to_string(),
+        }
+    }
+}
+
+fn roman(mut number: u32) -> String {
+    const NUMERALS: [(u32, &str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut numeral = String::new();
+    for (value, digits) in NUMERALS {
+        while number >= value {
+            numeral.push_str(digits);
+            number -= value;
+        }
+    }
+    numeral
+}
+
+/// How a section numbers its pages, from `w:pgNumType`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PageNumbering {
+    pub format: NumberFormat,
+    /// The number of the section's first page; without one, numbering
+    /// continues from the section before.
+    pub start: Option<u32>,
+}
+
+/// Pages from `first_page` on, numbered alike.
+#[derive(Debug, Clone, Copy)]
+struct NumberedRange {
+    first_page: usize,
+    /// `None` for pages without a number, such as a cover.
+    numbering: Option<PageNumbering>,
+}
+
+/// The numbering of the pages laid out so far.
+#[derive(Debug, Clone, Default)]
+pub struct PageNumbers {
+    ranges: Vec<NumberedRange>,
+}
+
+impl PageNumbers {
+    /// Numbers the pages from `first_page` on with `numbering`, or leaves
+    /// them unnumbered with `None`.
+    pub fn number_from(&mut self, first_page: usize, numbering: Option<PageNumbering>) {
+        //A section starting on the page another began on takes it over
+        if self.ranges.last().is_some_and(|range| range.first_page >= first_page) {
+            self.ranges.pop();
+        }
+        self.ranges.push(NumberedRange { first_page, numbering });
+    }
+
+    /// Each range with its format and the number of its first page, or
+    /// `None` where the pages are unnumbered.
+    fn resolved(&self) -> Vec<(usize, Option<(NumberFormat, u32)>)> {
+        let mut resolved = Vec::with_capacity(self.ranges.len());
+        //The range before, if numbered, and the number of its first page
+        let mut previous: Option<(usize, u32)> = None;
+        for (i, range) in self.ranges.iter().enumerate() {
+            let Some(numbering) = range.numbering else {
+                resolved.push((range.first_page, None));
+                continue;
+            };
+            let continued = previous.map_or(1, |(j, number)| {
+                let end = self.ranges.get(j + 1).map_or(range.first_page, |next| next.first_page);
+                number + (end - self.ranges[j].first_page) as u32
+            });
+            let number = numbering.start.unwrap_or(continued);
+            resolved.push((range.first_page, Some((numbering.format, number))));
+            previous = Some((i, number));
+        }
+        resolved
+    }
+
+    /// The number shown for the zero-based page `page`.
+    pub fn label(&self, page: usize) -> String {
+        let resolved = self.resolved();
+        match resolved.iter().rev().find(|(first_page, _)| *first_page <= page) {
+            Some((first_page, Some((format, number)))) => format.format(number + (page - first_page) as u32),
+            Some((_, None)) => String::new(),
+            None => (page + 1).to_string(),
+        }
+    }
+
+    /// Whether every page shows its position, numbered in decimal from 1,
+    /// so the PDF needs no labels.
+    pub fn is_plain(&self) -> bool {
+        self.resolved().iter().all(|(first_page, numbering)| {
+            *numbering == Some((NumberFormat::Decimal, *first_page as u32 + 1))
+        })
+    }
+}
+
+/// Labels the pages of `pdf` with their numbers.
+pub fn write_page_labels(pdf: &[u8], numbers: &PageNumbers) -> Result<Vec<u8>, ConversionError> {
+    let pdf_error = |e: lopdf::Error| ConversionError::Pdf(e.to_string());
+    let mut document = lopdf::Document::load_mem(pdf).map_err(pdf_error)?;
+    let page_count = document.get_pages().len();
+
+    let mut ranges = vec![(0, Some((NumberFormat::Decimal, 1)))];
+    ranges.extend(numbers.resolved());
+    //Ranges the document ends before are left out, and a later one at the same page wins
+    ranges.retain(|(first_page, _)| *first_page < page_count);
+    ranges.dedup_by(|later, earlier| {
+        let same_page = later.0 == earlier.0;
+        if same_page {
+            *earlier = *later;
+        }
+        same_page
+    });
+    let mut numbers = Vec::with_capacity(2 * ranges.len());
+    for (first_page, numbering) in ranges {
+        //A label dictionary without a style gives its pages an empty label
+        let mut label = Dictionary::new();
+        if let Some((format, number)) = numbering {
+            label.set("S", Object::Name(format.label_style().to_vec()));
+            if number > 1 {
+                label.set("St", Object::Integer(number as i64));
+            }
+        }
+        numbers.push(Object::Integer(first_page as i64));
+        numbers.push(Object::Dictionary(label));
+    }
+    let mut labels = Dictionary::new();
+    labels.set("Nums", Object::Array(numbers));
+
+    let catalog_id = document.trailer.get(b"Root").and_then(Object::as_reference).map_err(pdf_error)?;
+    let catalog = document.get_dictionary_mut(catalog_id).map_err(pdf_error)?;
+    catalog.set("PageLabels", labels);
+
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use docx_rs::{Docx, Paragraph, Run};
+
+    use super::*;
+    use crate::tests::edited_docx;
+    use crate::{Config, SourceDocument, parse_document, render_pdf_bytes};
+
+    #[test]
+    fn numbers_roman_front_matter_then_a_decimal_body() {
+        //Two pages of front matter, then the body on a page of its own
+        let mut docx = Docx::new();
+        for _ in 0..40 {
+            docx = docx.add_paragraph(Paragraph::new().add_run(Run::new().add_text("Preface")));
+        }
+        let docx = docx
+            .add_paragraph(Paragraph::new().add_run(Run::new().add_text("Front end")))
+            .add_paragraph(Paragraph::new().add_run(Run::new().add_text("Body")));
+        //docx_rs writes no paragraph w:sectPr or w:pgNumType, so they are added to its output
+        let run = r#"<w:r><w:rPr /><w:t xml:space="preserve">Front end</w:t>"#;
+        let packed = edited_docx(docx, |name, content| match name {
+            "word/document.xml" => content
+                .replace(
+                    &format!("<w:pPr><w:rPr /></w:pPr>{}", run),
+                    &format!(r#"<w:pPr><w:rPr /><w:sectPr><w:pgNumType w:fmt="lowerRoman" /></w:sectPr></w:pPr>{}"#, run),
+                )
+                .replace("</w:sectPr></w:body>", r#"<w:pgNumType w:start="1" /></w:sectPr></w:body>"#),
+            _ => content,
+        });
+        let config = Config::new("numbers.docx", "numbers.pdf");
+        let document = parse_document(&packed, "numbers.docx", &config).unwrap();
+        let source = SourceDocument { docx: &document.docx, images: &document.images, parts: document.parts, attachment: None };
+        let pdf = lopdf::Document::load_mem(&render_pdf_bytes(vec![source], &config, None, None).unwrap()).unwrap();
+        assert_eq!(pdf.get_pages().len(), 3);
+        assert!(pdf.extract_text(&[3]).unwrap().contains("Body"));
+
+        let labels = pdf.catalog().unwrap().get(b"PageLabels").and_then(Object::as_dict).unwrap();
+        let numbers = labels.get(b"Nums").and_then(Object::as_array).unwrap();
+        let ranges: Vec<(i64, &str)> = numbers
+            .chunks(2)
+            .map(|range| (range[0].as_i64().unwrap(), range[1].as_dict().unwrap().get(b"S").and_then(Object::as_name_str).unwrap()))
+            .collect();
+        assert_eq!(ranges, [(0, "r"), (2, "D")]);
+        //The body restarts at 1, so its range needs no start number
+        assert!(numbers[3].as_dict().unwrap().get(b"St").is_err());
+    }
+
+    #[test]
+    fn continues_the_count_into_a_section_without_a_start() {
+        let mut numbers = PageNumbers::default();
+        numbers.number_from(0, Some(PageNumbering { format: NumberFormat::LowerRoman, start: None }));
+        numbers.number_from(3, Some(PageNumbering { format: NumberFormat::UpperLetter, start: None }));
+        let labels: Vec<String> = (0..5).map(|page| numbers.label(page)).collect();
+        assert_eq!(labels, ["i", "ii", "iii", "D", "E"]);
+        assert!(!numbers.is_plain());
+        assert_eq!(NumberFormat::LowerLetter.format(28), "bb");
+    }
+}
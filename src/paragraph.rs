@@ -0,0 +1,666 @@
+//! Body paragraphs: run text, reference marks and the drawings anchored in
+//! their runs.
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+use docx_rs::{Break, BreakType, Paragraph, Run, RunChild};
+use printpdf::{IndirectFontRef, Line, Mm, Point};
+
+use crate::dropcap::render_drop_cap;
+use crate::links::{add_bookmarks, add_link, run_anchor};
+use crate::lists::{ListMarker, draw_list_marker, list_level, next_list_marker, paragraph_indent};
+use crate::markup::{Marker, baseline_shift, drop_cap_lines, paragraph_shading, run_language, run_markers};
+use crate::objects::{object_height, render_object, run_objects};
+use crate::color::{fill_color, parse_hex};
+use crate::debug::{begin_paragraph, draw_line_box, end_paragraph};
+use crate::caps::{display_text, is_small_caps};
+use crate::icons::{draw_icon, icon_segments, icon_text, run_icon};
+use crate::tabs::{TabStop, TabStops, TextStyle, paragraph_tab_stops, use_tabbed_text};
+use crate::text::{WrapAlgorithm, wrap_text};
+use crate::textbox::{flow_height, render_text_box, run_text_boxes};
+use crate::toc::{add_heading_destination, heading_level};
+use crate::{Layout, PT_TO_MM, TWIP_TO_MM, fill_rect, paragraph_runs, property_json};
+
+/// Extra advance after each character from the run's `w:spacing`, in mm.
+pub fn run_tracking(run: &docx_rs::Run) -> f32 {
+    let twips = property_json(&run.run_property)["characterSpacing"].as_i64().unwrap_or(0);
+    twips as f32 * TWIP_TO_MM
+}
+
+/// Underline and strikethrough offsets from the baseline, as fractions of the font size.
+const UNDERLINE_OFFSET: f32 = -0.12;
+const STRIKE_OFFSET: f32 = 0.28;
+
+/// Draws the underline and strikethrough of `run` under a line of text
+/// starting at `x` on the baseline `y`, in the run's colour.
+fn draw_decorations(layout: &Layout, run: &Run, x: f32, y: f32, width: f32, font_size: f32) {
+    let properties = property_json(&run.run_property);
+    let underline = properties["underline"].as_str().is_some_and(|kind| kind != "none");
+    let strike = properties["strike"].as_bool().unwrap_or(false);
+    if !underline && !strike {
+        return;
+    }
+    let rgb = properties["color"].as_str().and_then(parse_hex).unwrap_or((0.0, 0.0, 0.0));
+    layout.layer.set_outline_color(fill_color(layout.config.color_space, rgb));
+    let offsets = [(underline, UNDERLINE_OFFSET), (strike, STRIKE_OFFSET)];
+    for (_, offset) in offsets.iter().filter(|(drawn, _)| *drawn) {
+        let line_y = y + offset * font_size * PT_TO_MM;
+        layout.layer.add_line(Line {
+            points: vec![(Point::new(Mm(x), Mm(line_y)), false), (Point::new(Mm(x + width), Mm(line_y)), false)],
+            is_closed: false,
+        });
+    }
+    layout.layer.set_outline_color(fill_color(layout.config.color_space, (0.0, 0.0, 0.0)));
+}
+
+/// Extra space after each space of a line of `text` and `natural` mm that
+/// stretches it to `width`, in mm. Lines with tabs are left as they are.
+fn justified_word_spacing(text: &str, natural: f32, width: f32) -> f32 {
+    let spaces = text.matches(' ').count();
+    let slack = width - natural;
+    if spaces == 0 || slack <= 0.0 || text.contains('\t') { 0.0 } else { slack / spaces as f32 }
+}
+
+/// What a flow follows in its paragraph.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FlowStart {
+    Paragraph,
+    /// A column break (`w:br w:type="column"`).
+    Column,
+    /// A newline in the text, or a line break (`w:br`) with `--preserve-breaks`.
+    Line,
+}
+
+/// `run` split at its column breaks and the newlines in its text, and at
+/// its line breaks if `preserve_breaks`. Each segment after the first is
+/// paired with the break before it.
+fn break_segments(run: &Run, preserve_breaks: bool) -> Vec<(Option<FlowStart>, Cow<'_, Run>)> {
+    let starts = |child: &RunChild| match child {
+        RunChild::Text(text) if text.text.contains('\n') => Some(FlowStart::Line),
+        _ if *child == RunChild::Break(Break::new(BreakType::Column)) => Some(FlowStart::Column),
+        _ if preserve_breaks && *child == RunChild::Break(Break::new(BreakType::TextWrapping)) => Some(FlowStart::Line),
+        _ => None,
+    };
+    if !run.children.iter().any(|child| starts(child).is_some()) {
+        return vec![(None, Cow::Borrowed(run))];
+    }
+    let mut segments: Vec<(Option<FlowStart>, Vec<RunChild>)> = vec![(None, Vec::new())];
+    for child in &run.children {
+        match (child, starts(child)) {
+            (RunChild::Text(text), Some(_)) => {
+                for (i, line) in text.text.split('\n').enumerate() {
+                    if i > 0 {
+                        segments.push((Some(FlowStart::Line), Vec::new()));
+                    }
+                    if !line.is_empty() {
+                        let mut piece = text.clone();
+                        piece.text = line.to_string();
+                        segments.last_mut().expect("segments start with one").1.push(RunChild::Text(piece));
+                    }
+                }
+            }
+            (_, Some(start)) => segments.push((Some(start), Vec::new())),
+            (_, None) => segments.last_mut().expect("segments start with one").1.push(child.clone()),
+        }
+    }
+    segments
+        .into_iter()
+        .map(|(start, children)| (start, Cow::Owned(Run { run_property: run.run_property.clone(), children })))
+        .collect()
+}
+
+/// The runs of a paragraph between breaks, wrapped together as one text so
+/// formatting can change mid-line.
+struct Flow<'p> {
+    start: FlowStart,
+    /// Each run as drawn, with the run of the paragraph it comes from.
+    runs: Vec<(&'p Run, Cow<'p, Run>)>,
+    text: String,
+    /// Index into `runs` of the run each byte of `text` comes from.
+    owners: Vec<usize>,
+    /// Offset in `text` after the last non-blank character of each run.
+    ends: Vec<usize>,
+    /// Language of each run, from its `w:lang`.
+    languages: Vec<Option<String>>,
+}
+
+impl<'p> Flow<'p> {
+    fn new(start: FlowStart) -> Self {
+        Flow { start, runs: Vec::new(), text: String::new(), owners: Vec::new(), ends: Vec::new(), languages: Vec::new() }
+    }
+
+    fn push(&mut self, layout: &Layout, source: &'p Run, run: Cow<'p, Run>) {
+        let text = match run_icon(layout, &run) {
+            Some(icon) => icon_text(&icon, layout.font_size),
+            None => layout.auto_space.apply(self.text.chars().next_back(), &display_text(&run)),
+        };
+        let index = self.runs.len();
+        self.ends.push(self.text.len() + text.trim_end().len());
+        self.owners.extend(std::iter::repeat_n(index, text.len()));
+        self.text.push_str(&text);
+        self.languages.push(run_language(source));
+        self.runs.push((source, run));
+    }
+
+    /// Byte offsets where `word`, found at `offset` in this flow's text,
+    /// may be hyphenated, in the language of the run it comes from.
+    fn break_points(&self, layout: &Layout, offset: usize, word: &str) -> Vec<usize> {
+        let language = self.owners.get(offset).and_then(|owner| self.languages[*owner].as_deref());
+        layout.break_points(language, word)
+    }
+
+    /// The fonts of the runs, for `styles`: that of each and of its East
+    /// Asian characters.
+    fn fonts(&self, layout: &Layout) -> Vec<(IndirectFontRef, Option<IndirectFontRef>)> {
+        self.runs.iter().map(|(_, run)| (layout.fonts.for_run(run).clone(), layout.fonts.east_asian_for_run(run).cloned())).collect()
+    }
+
+    /// How each run is set, in `fonts`.
+    fn styles<'s>(
+        &self,
+        layout: &Layout,
+        fonts: &'s [(IndirectFontRef, Option<IndirectFontRef>)],
+        tab_stops: &'s [TabStop],
+    ) -> Vec<TextStyle<'s>> {
+        self.runs
+            .iter()
+            .zip(fonts)
+            .map(|((_, run), (font, east_asian))| TextStyle {
+                font,
+                east_asian: east_asian.as_ref(),
+                size: layout.font_size,
+                tracking: run_tracking(run),
+                small_caps: is_small_caps(run),
+                rise: baseline_shift(run),
+                tab_stops,
+            })
+            .collect()
+    }
+
+    /// Width of `text`, found at `offset` in this flow's text, with the
+    /// part each run sets measured in its `styles`.
+    fn width(&self, layout: &Layout, styles: &[TextStyle], offset: usize, text: &str) -> f32 {
+        let owner = |i: usize| self.owners.get(offset + i).or(self.owners.last()).copied().unwrap_or(0);
+        let mut width = 0.0;
+        let mut start = 0;
+        while start < text.len() {
+            let end = text[start..]
+                .char_indices()
+                .map(|(i, _)| start + i)
+                .find(|&i| owner(i) != owner(start))
+                .unwrap_or(text.len());
+            width += styles[owner(start)].width(layout, &text[start..end]);
+            start = end;
+        }
+        width
+    }
+
+    /// Splits `line`, wrapped from this flow's text, into the parts each
+    /// run sets, with the offset in the text each part starts at and
+    /// whether it falls in the `redacted` ranges of the text. `pos` is where
+    /// the line starts in the text and is moved past it.
+    fn line_parts(&self, line: &str, pos: &mut usize, redacted: &[Range<usize>]) -> Vec<LinePart> {
+        let skip_blanks = |pos: &mut usize| {
+            while let Some(c) = self.text[*pos..].chars().next().filter(|c| c.is_whitespace() && *c != '\t') {
+                *pos += c.len_utf8();
+            }
+        };
+        let is_redacted = |offset: usize| redacted.iter().any(|range| range.contains(&offset));
+        skip_blanks(pos);
+        let mut parts: Vec<LinePart> = Vec::new();
+        for c in line.chars() {
+            let start = *pos;
+            let (owner, hidden) = if c == ' ' {
+                //Runs of blanks between words are set as one space
+                skip_blanks(pos);
+                (self.owners.get(start).copied(), is_redacted(start))
+            } else if self.text[start..].starts_with(c) {
+                *pos += c.len_utf8();
+                //Tabs only move the text on, so there is nothing of them to hide
+                (self.owners.get(start).copied(), c != '\t' && is_redacted(start))
+            } else {
+                //The hyphen added where a word is broken, hidden with the letters before it
+                (None, parts.last().is_some_and(|part| part.redacted))
+            };
+            let owner = owner.or(parts.last().map(|part| part.owner)).unwrap_or(0);
+            match parts.last_mut() {
+                Some(part) if part.owner == owner && part.redacted == hidden => part.text.push(c),
+                _ => parts.push(LinePart { owner, start, text: c.to_string(), redacted: hidden }),
+            }
+        }
+        parts
+    }
+}
+
+/// Text of a wrapped line that one run sets.
+struct LinePart {
+    /// Index into the flow's `runs` of the run.
+    owner: usize,
+    /// Offset in the flow's text the part starts at.
+    start: usize,
+    text: String,
+    /// The part is redacted, so a box is drawn in its place.
+    redacted: bool,
+}
+
+/// The runs of `paragraph` as flows, a new one starting at each column
+/// break and newline, and at each line break with `--preserve-breaks`.
+fn paragraph_flows<'p>(layout: &Layout, paragraph: &'p Paragraph) -> Vec<Flow<'p>> {
+    let mut flows = vec![Flow::new(FlowStart::Paragraph)];
+    for run in paragraph_runs(paragraph) {
+        for (start, segment) in break_segments(run, layout.config.preserve_breaks) {
+            if let Some(start) = start {
+                flows.push(Flow::new(start));
+            }
+            for piece in icon_segments(layout, segment) {
+                flows.last_mut().expect("flows start with one").push(layout, run, piece);
+            }
+        }
+    }
+    flows
+}
+
+/// Height `paragraph` takes in the body flow, including the gap after it.
+/// Drop-cap frames float beside the next paragraph and take none.
+pub fn measure_paragraph(layout: &Layout, paragraph: &Paragraph) -> f32 {
+    if drop_cap_lines(paragraph).is_some() {
+        return 0.0;
+    }
+    paragraph_content_height(layout, paragraph) + layout.line_height
+}
+
+/// Height of `paragraph` without the gap after it, measured the way
+/// `render_paragraph` lays it out: its lines followed by its inline text
+/// boxes and objects.
+pub fn paragraph_content_height(layout: &Layout, paragraph: &Paragraph) -> f32 {
+    let config = layout.config;
+    let indent = paragraph_indent(layout, paragraph);
+    let usable_width = config.page_width - 2.0 * config.margin - indent;
+    let tab_stops = paragraph_tab_stops(paragraph);
+    let tabs = TabStops { explicit: &tab_stops, origin: -indent, interval: layout.tab_stop };
+    let mut height = 0.0;
+    for flow in paragraph_flows(layout, paragraph) {
+        let fonts = flow.fonts(layout);
+        let styles = flow.styles(layout, &fonts, &tab_stops);
+        let measure = |offset, text: &str| flow.width(layout, &styles, offset, text);
+        let hyphenate = |offset, word: &str| flow.break_points(layout, offset, word);
+        let lines = wrap_text(&flow.text, usable_width, &measure, tabs, config.wrap_algorithm, &hyphenate);
+        //A line break followed by nothing, or by another, still leaves its line
+        let line_count = lines.len().max(usize::from(flow.start == FlowStart::Line));
+        height += line_count as f32 * layout.line_height;
+        for (_, run) in &flow.runs {
+            height += run_text_boxes(run).into_iter().map(|(text_box, frame)| flow_height(text_box, frame.as_ref())).sum::<f32>();
+            height += run_objects(run).iter().map(|object| object_height(layout, object)).sum::<f32>();
+        }
+    }
+    height
+}
+
+/// Text of a line in a table cell that one run sets.
+pub struct CellPart<'p> {
+    pub run: &'p Run,
+    pub text: String,
+    /// The part is redacted, so a box is drawn in its place.
+    pub redacted: bool,
+}
+
+/// The lines of `paragraph` in a table cell, its runs flowing together as
+/// in the body, each split into the parts its runs set. Lines are wrapped
+/// to `width` mm, or not at all without one, as for vertical text; `font`,
+/// if given, sets every run, as in a bold header row.
+pub fn cell_lines<'p>(layout: &Layout, paragraph: &'p Paragraph, width: Option<f32>, font: Option<&IndirectFontRef>) -> Vec<Vec<CellPart<'p>>> {
+    let (max_width, algorithm) = match width {
+        Some(width) => (width, layout.config.wrap_algorithm),
+        None => (f32::INFINITY, WrapAlgorithm::Greedy),
+    };
+    let mut lines = Vec::new();
+    for flow in paragraph_flows(layout, paragraph) {
+        let mut fonts = flow.fonts(layout);
+        if let Some(font) = font {
+            fonts.iter_mut().for_each(|(run_font, _)| *run_font = font.clone());
+        }
+        //Cell lines are set from the cell's edge, so tabs use the default stops
+        let styles = flow.styles(layout, &fonts, &[]);
+        let measure = |offset, text: &str| flow.width(layout, &styles, offset, text);
+        let hyphenate = |offset, word: &str| flow.break_points(layout, offset, word);
+        let tabs = TabStops::defaults(layout.tab_stop);
+        let mut wrapped = wrap_text(&flow.text, max_width, &measure, tabs, algorithm, &hyphenate);
+        //A line break followed by nothing, or by another, still leaves its line
+        if wrapped.is_empty() && flow.start == FlowStart::Line {
+            wrapped.push(String::new());
+        }
+        let redacted = layout.redactor.matches(&flow.text);
+        let mut pos = 0;
+        for line in wrapped {
+            let parts = flow.line_parts(&line, &mut pos, &redacted);
+            lines.push(
+                parts
+                    .into_iter()
+                    .map(|LinePart { owner, text, redacted, .. }| CellPart { run: flow.runs[owner].0, text, redacted })
+                    .collect(),
+            );
+        }
+    }
+    lines
+}
+
+/// Structure type `paragraph` is tagged with: a heading level (the PDF
+/// standard stops at six), a list item or a plain paragraph.
+fn structure_role(layout: &Layout, paragraph: &Paragraph) -> String {
+    if let Some(level) = heading_level(paragraph) {
+        format!("H{}", level.min(6))
+    } else if list_level(&layout.numbering, paragraph).is_some() {
+        "LI".to_string()
+    } else {
+        "P".to_string()
+    }
+}
+
+/// Draws `paragraph` at the layout cursor, breaking pages as lines run out of room.
+pub fn render_paragraph(layout: &mut Layout, paragraph: &Paragraph) {
+    let role = structure_role(layout, paragraph);
+    layout.tags.get_mut().begin(&role);
+    render_paragraph_content(layout, paragraph);
+    layout.tags.get_mut().end();
+}
+
+fn render_paragraph_content(layout: &mut Layout, paragraph: &Paragraph) {
+    if let Some(lines) = drop_cap_lines(paragraph) {
+        render_drop_cap(layout, paragraph, lines);
+        return;
+    }
+    begin_paragraph(layout);
+    add_bookmarks(layout, paragraph);
+    add_heading_destination(layout, paragraph);
+    layout.left_indent = paragraph_indent(layout, paragraph);
+    //Drawn beside the paragraph's first line
+    let mut list_marker = next_list_marker(layout, paragraph);
+    for flow in &paragraph_flows(layout, paragraph) {
+        if flow.start == FlowStart::Column {
+            layout.next_column();
+        }
+        render_flow(layout, paragraph, flow, &mut list_marker);
+    }
+    //A paragraph shorter than the drop cap still clears the letter
+    if let Some(drop_cap) = layout.drop_cap.take() {
+        layout.y_position -= drop_cap.lines as f32 * layout.line_height;
+    }
+    layout.left_indent = 0.0;
+    end_paragraph(layout);
+    layout.y_position -= layout.line_height;
+}
+
+/// Draws the lines of `flow`, switching font, colour and decorations where
+/// its runs change. The reference marks of a run follow its last character;
+/// its text boxes and objects go below the line it ends on.
+fn render_flow(layout: &mut Layout, paragraph: &Paragraph, flow: &Flow, list_marker: &mut Option<(ListMarker, f32)>) {
+    let config = layout.config;
+    let font_size = layout.font_size;
+    let line_height = layout.line_height;
+    //Filled line by line behind the text, so a paragraph split over pages is shaded on each
+    let shading = paragraph_shading(paragraph).map(|rgb| fill_color(config.color_space, rgb));
+    let tab_stops = paragraph_tab_stops(paragraph);
+    //Justified lines fill the width, except the last before a break; distributed ones all do
+    let alignment = paragraph.property.alignment.as_ref().map(|alignment| alignment.val.as_str());
+    let (justified, stretch_last) = (matches!(alignment, Some("both" | "distribute")), alignment == Some("distribute"));
+    let fonts = flow.fonts(layout);
+    let styles = flow.styles(layout, &fonts, &tab_stops);
+    let anchors: Vec<_> = flow.runs.iter().map(|(source, _)| run_anchor(paragraph, source)).collect();
+
+    //Runs are finished in order once the text has passed their last character
+    let mut finished = 0;
+    let mut pos = 0;
+    //Matched across the whole flow, so a match spanning runs or a broken word is caught
+    let redacted = layout.redactor.matches(&flow.text);
+    let measure = |layout: &Layout, offset, text: &str| flow.width(layout, &styles, offset, text);
+    let hyphenate = |layout: &Layout, offset, word: &str| flow.break_points(layout, offset, word);
+    let mut lines = layout.wrap_body(&flow.text, &measure, &hyphenate, &tab_stops);
+    //A line break followed by nothing, or by another, still leaves its line
+    if lines.is_empty() && flow.start == FlowStart::Line {
+        lines.push((layout.left_indent, String::new()));
+    }
+    let line_count = lines.len();
+    for (i, (indent, line)) in lines.into_iter().enumerate() {
+        //Checks if the data has a page break
+        layout.ensure_space(line_height);
+        if let Some(color) = &shading {
+            let bottom = layout.y_position - line_height;
+            fill_rect(&layout.layer, config.margin, bottom, config.page_width - 2.0 * config.margin, line_height, color.clone());
+        }
+        let baseline = layout.baseline();
+        let parts = flow.line_parts(&line, &mut pos, &redacted);
+        if justified && (stretch_last || i + 1 < line_count) {
+            let natural = parts.iter().map(|part| styles[part.owner].width(layout, &part.text)).sum();
+            layout.word_spacing = justified_word_spacing(&line, natural, config.page_width - 2.0 * config.margin - indent);
+            layout.layer.set_word_spacing(layout.word_spacing / PT_TO_MM);
+        }
+        let x = config.margin + indent;
+        let mut cursor = x;
+        let mut ended = Vec::new();
+        for LinePart { owner, start, text, redacted } in &parts {
+            cursor = finish_runs(layout, flow, &mut finished, *start, (cursor, baseline), &mut ended);
+            let (run, style) = (flow.runs[*owner].1.as_ref(), &styles[*owner]);
+            layout.set_run_style(run);
+            if style.tracking != 0.0 {
+                layout.layer.set_character_spacing(style.tracking / PT_TO_MM);
+            }
+            let width = match run_icon(layout, run) {
+                _ if *redacted => {
+                    let advance = style.width(layout, text) + layout.word_spacing * text.matches(' ').count() as f32;
+                    layout.draw_redaction_box(&layout.layer, (cursor, baseline + style.rise), advance, style.size);
+                    advance
+                }
+                Some(icon) => {
+                    let advance = style.width(layout, text);
+                    draw_icon(layout, &icon, (cursor, baseline), advance);
+                    advance
+                }
+                None => use_tabbed_text(layout, text, style, (cursor, baseline), config.margin),
+            };
+            if style.tracking != 0.0 {
+                layout.layer.set_character_spacing(0.0);
+            }
+            draw_decorations(layout, run, cursor, baseline + style.rise, width, font_size);
+            if let Some(anchor) = anchors[*owner] {
+                add_link(layout, anchor, cursor, baseline, width);
+            }
+            cursor += width;
+        }
+        if layout.word_spacing != 0.0 {
+            layout.word_spacing = 0.0;
+            layout.layer.set_word_spacing(0.0);
+        }
+        cursor = finish_runs(layout, flow, &mut finished, pos, (cursor, baseline), &mut ended);
+        if let Some((marker, hanging)) = list_marker.take() {
+            draw_list_marker(layout, &marker, x - hanging, baseline, font_size);
+        }
+        draw_line_box(layout, x, baseline, cursor - x);
+        layout.last_line_end = Some((cursor, baseline));
+        layout.y_position -= line_height;
+        render_inline_drawings(layout, &ended);
+    }
+    //Runs with nothing left to set, such as those holding only a drawing
+    let mut ended = Vec::new();
+    let end = layout.last_line_end.unwrap_or((config.margin, layout.baseline()));
+    finish_runs(layout, flow, &mut finished, flow.text.len(), end, &mut ended);
+    render_inline_drawings(layout, &ended);
+}
+
+/// Marks the notes referenced by the runs of `flow` whose text ends by
+/// offset `pos`, from `finished` on, drawing their marks at `(x, y)`. The
+/// runs are added to `ended` for their drawings, and the end of the marks
+/// is returned.
+fn finish_runs<'f>(
+    layout: &mut Layout,
+    flow: &'f Flow,
+    finished: &mut usize,
+    pos: usize,
+    (x, y): (f32, f32),
+    ended: &mut Vec<&'f Run>,
+) -> f32 {
+    let mut x = x;
+    while *finished < flow.runs.len() && flow.ends[*finished] <= pos {
+        let run = flow.runs[*finished].1.as_ref();
+        for marker in &run_markers(run) {
+            layout.last_line_end = Some((x, y));
+            match marker {
+                Marker::Footnote(id) => layout.add_footnote(*id),
+                Marker::Endnote(id) => {
+                    let number = layout.endnotes.reference(*id);
+                    layout.draw_reference_mark(number);
+                }
+                Marker::Object(_) | Marker::TextBox(_) | Marker::SmallCaps | Marker::Position(_) | Marker::Lang(_) | Marker::Toc(_) | Marker::Shading(_) => {}
+            }
+            x = layout.last_line_end.map_or(x, |(end, _)| end);
+        }
+        ended.push(run);
+        *finished += 1;
+    }
+    x
+}
+
+/// Draws the text boxes and embedded objects of `runs` at the cursor.
+fn render_inline_drawings(layout: &mut Layout, runs: &[&Run]) {
+    for run in runs {
+        for (text_box, frame) in run_text_boxes(run) {
+            render_text_box(layout, text_box, frame.as_ref());
+        }
+        for marker in &run_markers(run) {
+            if let Marker::Object(object) = marker {
+                render_object(layout, object);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use docx_rs::{BreakType, Docx, Paragraph, Run, RunChild, Table, TableCell, TableRow, Text};
+
+    use printpdf::lopdf;
+
+    use crate::compression::Compression;
+    use crate::tests::{edited_docx, laid_out, laid_out_archive};
+    use crate::{Config, PT_TO_MM, SourceDocument, TWIP_TO_MM, parse_document, render_pdf_bytes};
+
+    #[test]
+    fn expanded_spacing_widens_each_character() {
+        let text = "Tracked";
+        let docx = Docx::new()
+            .add_paragraph(Paragraph::new().add_run(Run::new().add_text(text)))
+            .add_paragraph(Paragraph::new().add_run(Run::new().add_text(text).character_spacing(40)));
+        let runs = laid_out(&docx, &Config::new("spacing.docx", "spacing.pdf"));
+        let (plain, tracked) = (runs[0].3, runs[1].3);
+        let expected = plain + text.chars().count() as f32 * 40.0 * TWIP_TO_MM;
+        assert!((tracked - expected).abs() < 0.01, "{} against {}", tracked, expected);
+    }
+
+    #[test]
+    fn shades_a_paragraph_on_every_page_it_spans() {
+        let docx = Docx::new().add_paragraph(Paragraph::new().add_run(Run::new().add_text("Shaded text runs on. ".repeat(400))));
+        let packed = edited_docx(docx, |name, content| match name {
+            "word/document.xml" => content.replacen("<w:pPr>", r#"<w:pPr><w:shd w:val="clear" w:color="auto" w:fill="D9D9D9" />"#, 1),
+            _ => content,
+        });
+        let mut config = Config::new("shaded.docx", "shaded.pdf");
+        config.compression = Some(Compression::None);
+        let lines = laid_out_archive(&packed, &config).len();
+        let document = parse_document(&packed, "shaded.docx", &config).unwrap();
+        let source = SourceDocument { docx: &document.docx, images: &document.images, parts: document.parts, attachment: None };
+        let pdf = lopdf::Document::load_mem(&render_pdf_bytes(vec![source], &config, None, None).unwrap()).unwrap();
+
+        //One filled band behind each line, whichever page it is on
+        let pages = pdf.get_pages();
+        assert!(pages.len() > 1);
+        let mut bands = 0;
+        for page_id in pages.values() {
+            let content = lopdf::content::Content::decode(&pdf.get_page_content(*page_id).unwrap()).unwrap();
+            let operators: Vec<&str> = content.operations.iter().map(|operation| operation.operator.as_str()).collect();
+            let page_bands = operators.windows(2).filter(|pair| pair == &["re", "f"]).count();
+            assert!(page_bands > 0);
+            bands += page_bands;
+        }
+        assert_eq!(bands, lines);
+    }
+
+    #[test]
+    fn shifts_runs_by_their_position_without_resizing() {
+        let paragraph = Paragraph::new()
+            .add_run(Run::new().add_text("base "))
+            .add_run(Run::new().add_text("up ").bold())
+            .add_run(Run::new().add_text("down").italic());
+        //docx_rs writes no w:position, so the bold and italic are swapped for one each
+        let packed = edited_docx(Docx::new().add_paragraph(paragraph), |name, content| match name {
+            "word/document.xml" => content.replace("<w:b />", r#"<w:position w:val="6" />"#).replace("<w:i />", r#"<w:position w:val="-6" />"#),
+            _ => content,
+        });
+        let config = Config::new("position.docx", "position.pdf");
+        let runs = laid_out_archive(&packed, &config);
+        let texts: Vec<&str> = runs.iter().map(|(text, ..)| text.as_str()).collect();
+        assert_eq!(texts, ["base ", "up ", "down"]);
+        //Six half-points is three points either way
+        let baseline = runs[0].2;
+        assert!((runs[1].2 - (baseline + 3.0 * PT_TO_MM)).abs() < 0.01);
+        assert!((runs[2].2 - (baseline - 3.0 * PT_TO_MM)).abs() < 0.01);
+        let plain = laid_out(&Docx::new().add_paragraph(Paragraph::new().add_run(Run::new().add_text("down"))), &config);
+        assert!((runs[2].3 - plain[0].3).abs() < 0.01);
+    }
+
+    #[test]
+    fn preserved_breaks_start_new_lines() {
+        let paragraph = Paragraph::new().add_run(
+            Run::new()
+                .add_text("Roses are red,")
+                .add_break(BreakType::TextWrapping)
+                .add_text("Violets are blue.")
+                .add_break(BreakType::TextWrapping)
+                .add_break(BreakType::TextWrapping)
+                .add_text("A long closing line ".repeat(12)),
+        );
+        let docx = Docx::new().add_paragraph(paragraph);
+        let mut config = Config::new("poem.docx", "poem.pdf");
+        //The baselines of the runs, top down, with the text on each
+        let lines = |config: &Config| {
+            let mut lines: Vec<(f32, String)> = Vec::new();
+            for (text, _, y, _) in laid_out(&docx, config) {
+                match lines.last_mut() {
+                    Some((baseline, line)) if (*baseline - y).abs() < 0.01 => line.push_str(&text),
+                    _ => lines.push((y, text)),
+                }
+            }
+            lines
+        };
+
+        //Run on, the first two lines share one
+        let run_on = lines(&config);
+        assert!(run_on[0].1.starts_with("Roses are red,Violets"), "{:?}", run_on);
+
+        config.preserve_breaks = true;
+        let kept = lines(&config);
+        assert_eq!((kept[0].1.as_str(), kept[1].1.as_str()), ("Roses are red,", "Violets are blue."));
+        //The double break leaves an empty line, and the long line still wraps
+        let advance = kept[0].0 - kept[1].0;
+        assert!((kept[1].0 - kept[2].0 - 2.0 * advance).abs() < 0.01, "{:?}", kept);
+        assert!(kept[2].1.starts_with("A long") && kept.len() > 3);
+        assert!(kept[3..].iter().all(|(_, line)| line.contains("closing")));
+    }
+
+    #[test]
+    fn newlines_in_run_text_start_new_lines() {
+        //As a substituted document variable leaves it; Run::add_text would escape the newlines
+        let address = Text { text: "Acme & Co\n12 High Street\n\nSpringfield".to_string(), preserve_space: true };
+        let paragraph = || Paragraph::new().add_run(Run { children: vec![RunChild::Text(address.clone())], ..Run::new() });
+        let table = Table::new(vec![TableRow::new(vec![TableCell::new().add_paragraph(paragraph())])]);
+        let config = Config::new("address.docx", "address.pdf");
+        for docx in [Docx::new().add_paragraph(paragraph()), Docx::new().add_table(table)] {
+            let runs = laid_out(&docx, &config);
+            let texts: Vec<&str> = runs.iter().map(|(text, ..)| text.as_str()).collect();
+            assert_eq!(texts, ["Acme & Co", "12 High Street", "Springfield"]);
+            //The empty line between them is kept
+            let advance = runs[0].2 - runs[1].2;
+            assert!(advance > 0.0 && (runs[1].2 - runs[2].2 - 2.0 * advance).abs() < 0.01, "{:?}", runs);
+            assert!(runs.iter().all(|run| (run.1 - runs[0].1).abs() < 0.01));
+        }
+    }
+}
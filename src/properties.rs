@@ -0,0 +1,48 @@
+//! Document properties from `docProps/core.xml`, such as the title and
+//! author Word shows under File > Info. docx_rs does not read the part.
+
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+use crate::{ConversionError, read_part};
+
+/// The core properties the converter uses.
+#[derive(Debug, Clone, Default)]
+pub struct CoreProperties {
+    /// `dc:title`.
+    pub title: Option<String>,
+    /// `dc:creator`, the author.
+    pub author: Option<String>,
+}
+
+/// Reads the non-blank core properties of `docx`.
+pub fn read_core_properties(docx: &[u8]) -> Result<CoreProperties, ConversionError> {
+    let mut properties = CoreProperties::default();
+    let Some(xml) = read_part(docx, "docProps/core.xml")? else { return Ok(properties) };
+
+    let mut reader = Reader::from_reader(&xml[..]);
+    let mut buf = Vec::new();
+    //The property whose text is being read
+    let mut current: Option<Vec<u8>> = None;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) => current = Some(e.local_name().to_vec()),
+            Ok(Event::End(_)) => current = None,
+            Ok(Event::Text(ref e)) => {
+                let text = e.unescape_and_decode(&reader).unwrap_or_default().trim().to_string();
+                let field = match current.as_deref() {
+                    Some(b"title") => Some(&mut properties.title),
+                    Some(b"creator") => Some(&mut properties.author),
+                    _ => None,
+                };
+                if let Some(field) = field.filter(|_| !text.is_empty()) {
+                    *field = Some(text);
+                }
+            }
+            Ok(Event::Eof) => return Ok(properties),
+            Err(e) => return Err(ConversionError::InvalidInput(format!("Malformed docProps/core.xml: {}", e))),
+            _ => {}
+        }
+        buf.clear();
+    }
+}
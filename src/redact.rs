@@ -0,0 +1,140 @@
+//! Redaction. Text matching a `--redact` pattern never reaches the content
+//! streams: each drawn line is cut around the matches, and a black box is
+//! drawn over the space the matched text would have taken. Body
+//! paragraphs are matched as a whole before they are wrapped, so a match
+//! can span runs and lines; other text is matched line by line.
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+use regex::Regex;
+
+use crate::ConversionError;
+
+#[derive(Debug, Default)]
+pub struct Redactor {
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    pub fn new(patterns: &[String]) -> Result<Self, ConversionError> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern))
+            .collect::<Result<_, _>>()
+            .map_err(|e| ConversionError::InvalidInput(format!("Invalid --redact pattern: {}", e)))?;
+        Ok(Redactor { patterns })
+    }
+
+    /// Byte ranges of `text` to leave out, in order and merged where they overlap.
+    pub fn matches(&self, text: &str) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = self
+            .patterns
+            .iter()
+            .flat_map(|pattern| pattern.find_iter(text).map(|found| found.range()))
+            .filter(|range| !range.is_empty())
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+
+    /// `text` with the redacted characters blanked, for text drawn where no
+    /// box can be placed over it.
+    pub fn blank<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        let ranges = self.matches(text);
+        if ranges.is_empty() {
+            return Cow::Borrowed(text);
+        }
+        let mut blanked = String::with_capacity(text.len());
+        let mut kept = 0;
+        for range in ranges {
+            blanked.push_str(&text[kept..range.start]);
+            blanked.extend(text[range.clone()].chars().map(|_| ' '));
+            kept = range.end;
+        }
+        blanked.push_str(&text[kept..]);
+        Cow::Owned(blanked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use crate::compression::Compression;
+    use crate::tests::sample_pdf;
+    use docx_rs::{Paragraph, Run};
+
+    /// Whether `pdf` shows `text` with a `Tj` in an uncompressed content stream.
+    fn shows(pdf: &[u8], text: &str) -> bool {
+        let hex: String = text.bytes().map(|byte| format!("{:02X}", byte)).collect();
+        let pdf = String::from_utf8_lossy(pdf);
+        let mut shown = pdf.split("> Tj").filter_map(|before| before.rsplit_once('<')).map(|(_, operand)| operand);
+        //Whole bytes only, so a match cannot straddle two of them
+        shown.any(|operand| operand.match_indices(&hex).any(|(i, _)| i % 2 == 0))
+    }
+
+    #[test]
+    fn merges_overlapping_matches() {
+        let redactor = Redactor::new(&["ab+".to_string(), "bc".to_string()]).unwrap();
+        assert_eq!(redactor.matches("xabbcx ab"), [1..5, 7..9]);
+        assert_eq!(redactor.blank("xabbcx"), "x    x");
+    }
+
+    #[test]
+    fn redacted_text_is_not_in_the_pdf() {
+        let mut config = Config::new("redact.docx", "redact.pdf");
+        config.redact = vec![r"\d{3}-\d{4}".to_string()];
+        config.compression = Some(Compression::None);
+        let pdf = sample_pdf("Call 555-0142 today", &config);
+        assert!(shows(&pdf, "Call "));
+        assert!(shows(&pdf, " today"));
+        assert!(!shows(&pdf, "555"));
+        assert!(!shows(&pdf, "0142"));
+    }
+
+    /// `docx` converted with `config`, its content left uncompressed.
+    fn convert(docx: &docx_rs::Docx, config: &mut Config) -> Vec<u8> {
+        config.compression = Some(Compression::None);
+        crate::convert_parsed(docx, &[], config).unwrap()
+    }
+
+    #[test]
+    fn redacts_a_match_spanning_runs() {
+        let paragraph = Paragraph::new().add_run(Run::new().add_text("Call 555-")).add_run(Run::new().add_text("0142 today").bold());
+        let docx = docx_rs::Docx::new().add_paragraph(paragraph);
+        let mut config = Config::new("redact.docx", "redact.pdf");
+        config.redact = vec![r"\d{3}-\d{4}".to_string()];
+        let pdf = convert(&docx, &mut config);
+        assert!(shows(&pdf, "Call "));
+        assert!(shows(&pdf, " today"));
+        assert!(!shows(&pdf, "555"));
+        assert!(!shows(&pdf, "0142"));
+    }
+
+    #[test]
+    fn redacts_a_match_broken_by_a_hyphen() {
+        let docx = crate::tests::sample_docx("Words before understanding");
+        let mut config = Config::new("redact.docx", "redact.pdf");
+        let patterns = crate::tests::temp_directory("redact-hyphen", &[("hyph-en-us.pat.txt", "1stand")]);
+        config.hyphenate = Some(patterns.to_string_lossy().into_owned());
+        config.page_width = 2.0 * config.margin + 45.0;
+        let pdf = convert(&docx, &mut config);
+        assert!(!shows(&pdf, "understanding") && shows(&pdf, "-"), "the word should be broken");
+
+        config.redact = vec!["understanding".to_string()];
+        let pdf = convert(&docx, &mut config);
+        assert!(shows(&pdf, "Words before"));
+        //Neither half of the word, nor the hyphen between them
+        assert!(!shows(&pdf, "un"));
+        assert!(!shows(&pdf, "ing"));
+        assert!(!shows(&pdf, "-"));
+    }
+}
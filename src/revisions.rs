@@ -0,0 +1,163 @@
+//! Tracked changes. docx_rs keeps `w:ins` and `w:del` runs apart from the
+//! paragraph's own runs; they are folded back in according to the mode.
+
+use docx_rs::{
+    DeleteChild, DocumentChild, InsertChild, Paragraph, ParagraphChild, Run, RunChild, Table, TableCellContent,
+    TableChild, TableRowChild, Text,
+};
+
+use crate::property_json;
+
+/// Colour revision marks are shown in, as Word does for a single author.
+const REVISION_COLOR: &str = "C00000";
+
+/// How tracked insertions and deletions are rendered.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum RevisionMode {
+    /// Keep insertions and drop deletions, as if every change were accepted.
+    #[default]
+    Accept,
+    /// Drop insertions and keep deletions, as if every change were rejected.
+    Reject,
+    /// Keep both, with insertions underlined and deletions struck through.
+    Show,
+}
+
+impl std::str::FromStr for RevisionMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "accept" => Ok(RevisionMode::Accept),
+            "reject" => Ok(RevisionMode::Reject),
+            "show" => Ok(RevisionMode::Show),
+            other => Err(format!("Unknown revision mode: {}", other)),
+        }
+    }
+}
+
+/// Copy of the body with every paragraph's revisions resolved.
+pub fn resolve_revisions(children: &[DocumentChild], mode: RevisionMode) -> Vec<DocumentChild> {
+    let mut children = children.to_vec();
+    for child in &mut children {
+        match child {
+            DocumentChild::Paragraph(paragraph) => resolve_paragraph(paragraph, mode),
+            DocumentChild::Table(table) => resolve_table(table, mode),
+            _ => {}
+        }
+    }
+    children
+}
+
+fn resolve_table(table: &mut Table, mode: RevisionMode) {
+    for TableChild::TableRow(row) in &mut table.rows {
+        for TableRowChild::TableCell(cell) in &mut row.cells {
+            for content in &mut cell.children {
+                match content {
+                    TableCellContent::Paragraph(paragraph) => resolve_paragraph(paragraph, mode),
+                    TableCellContent::Table(table) => resolve_table(table, mode),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+fn resolve_paragraph(paragraph: &mut Paragraph, mode: RevisionMode) {
+    paragraph.children = resolve_children(std::mem::take(&mut paragraph.children), mode);
+}
+
+/// `paragraph_children` with their revisions resolved, including those
+/// inside hyperlinks.
+fn resolve_children(paragraph_children: Vec<ParagraphChild>, mode: RevisionMode) -> Vec<ParagraphChild> {
+    let mut children = Vec::with_capacity(paragraph_children.len());
+    for child in paragraph_children {
+        match child {
+            ParagraphChild::Hyperlink(mut link) => {
+                link.children = resolve_children(std::mem::take(&mut link.children), mode);
+                children.push(ParagraphChild::Hyperlink(link));
+            }
+            ParagraphChild::Insert(insert) => {
+                for child in insert.children {
+                    match child {
+                        InsertChild::Run(run) if mode != RevisionMode::Reject => {
+                            children.push(ParagraphChild::Run(Box::new(inserted(*run, mode))));
+                        }
+                        //Text inserted and then deleted is gone either way; only show keeps it
+                        InsertChild::Delete(delete) if mode == RevisionMode::Show => {
+                            children.extend(deleted_runs(delete.children, mode));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            ParagraphChild::Delete(delete) if mode != RevisionMode::Accept => {
+                children.extend(deleted_runs(delete.children, mode));
+            }
+            ParagraphChild::Delete(_) => {}
+            child => children.push(child),
+        }
+    }
+    children
+}
+
+fn inserted(mut run: Run, mode: RevisionMode) -> Run {
+    if mode == RevisionMode::Show {
+        run.run_property = run.run_property.underline("single").color(REVISION_COLOR);
+    }
+    run
+}
+
+/// The runs of a deletion with their `w:delText` turned back into text.
+fn deleted_runs(children: Vec<DeleteChild>, mode: RevisionMode) -> impl Iterator<Item = ParagraphChild> {
+    children.into_iter().filter_map(move |child| {
+        let DeleteChild::Run(mut run) = child else { return None };
+        for child in &mut run.children {
+            if let RunChild::DeleteText(deleted) = child {
+                let mut text = Text::new("");
+                text.text = property_json(deleted)["text"].as_str().unwrap_or_default().to_string();
+                *child = RunChild::Text(text);
+            }
+        }
+        if mode == RevisionMode::Show {
+            run.run_property = run.run_property.strike().color(REVISION_COLOR);
+        }
+        Some(ParagraphChild::Run(Box::new(run)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use docx_rs::{Delete, Insert};
+
+    use super::*;
+    use crate::{paragraph_runs, run_text};
+
+    /// Each run of a paragraph with one insertion and one deletion resolved
+    /// with `mode`: its text, and whether it is underlined and struck through.
+    fn resolved(mode: RevisionMode) -> Vec<(String, bool, bool)> {
+        let revised = Paragraph::new()
+            .add_run(Run::new().add_text("kept "))
+            .add_insert(Insert::new(Run::new().add_text("new")))
+            .add_delete(Delete::new().add_run(Run::new().add_delete_text("old")));
+        let children = resolve_revisions(&[DocumentChild::Paragraph(Box::new(revised))], mode);
+        let DocumentChild::Paragraph(paragraph) = &children[0] else { unreachable!() };
+        paragraph_runs(paragraph)
+            .map(|run| {
+                let properties = property_json(&run.run_property);
+                (run_text(run), !properties["underline"].is_null(), !properties["strike"].is_null())
+            })
+            .collect()
+    }
+
+    fn run(text: &str, underlined: bool, struck: bool) -> (String, bool, bool) {
+        (text.to_string(), underlined, struck)
+    }
+
+    #[test]
+    fn accepts_rejects_or_shows_tracked_changes() {
+        assert_eq!(resolved(RevisionMode::Accept), [run("kept ", false, false), run("new", false, false)]);
+        assert_eq!(resolved(RevisionMode::Reject), [run("kept ", false, false), run("old", false, false)]);
+        assert_eq!(resolved(RevisionMode::Show), [run("kept ", false, false), run("new", true, false), run("old", false, true)]);
+    }
+}
@@ -0,0 +1,240 @@
+//! Section properties docx_rs does not model, read from the `w:sectPr`
+//! elements of `word/document.xml` in document order.
+
+use std::ops::Range;
+
+use docx_rs::DocumentChild;
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+use crate::pagenumbers::{NumberFormat, PageNumbering};
+use crate::{ConversionError, Layout, read_part, xml_attribute};
+
+/// Vertical placement of a section's content on its page, from `w:vAlign`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PageAlign {
+    #[default]
+    Top,
+    Center,
+    /// Spread the paragraphs out to fill the page.
+    Both,
+    Bottom,
+}
+
+/// Where a section starts relative to the one before, from `w:type`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SectionStart {
+    /// Also used for `nextColumn`, as the body is laid out in one column.
+    #[default]
+    NextPage,
+    Continuous,
+    OddPage,
+    EvenPage,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SectionExtras {
+    pub v_align: PageAlign,
+    pub start: SectionStart,
+    /// `w:pgSz` turns the section's pages to landscape.
+    pub landscape: bool,
+    /// Page number style and restart, from `w:pgNumType`.
+    pub numbering: PageNumbering,
+    /// `w:textDirection` of `tbRl` sets the section in vertical columns.
+    pub vertical: bool,
+}
+
+/// One entry per section, in the order the sections appear.
+pub fn read_sections(docx: &[u8]) -> Result<Vec<SectionExtras>, ConversionError> {
+    let Some(xml) = read_part(docx, "word/document.xml")? else { return Ok(Vec::new()) };
+
+    let mut sections = Vec::new();
+    let mut reader = Reader::from_reader(&xml[..]);
+    let mut buf = Vec::new();
+    //Nesting depth of `w:sectPr`; the copies inside `w:sectPrChange` are not sections
+    let mut depth = 0;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) if e.local_name() == b"sectPr" => {
+                if depth == 0 {
+                    sections.push(SectionExtras::default());
+                }
+                depth += 1;
+            }
+            Ok(Event::Empty(ref e)) if e.local_name() == b"sectPr" && depth == 0 => {
+                sections.push(SectionExtras::default());
+            }
+            Ok(Event::End(ref e)) if e.local_name() == b"sectPr" => depth -= 1,
+            Ok(Event::Empty(ref e)) if e.local_name() == b"vAlign" && depth == 1 => {
+                if let Some(section) = sections.last_mut() {
+                    section.v_align = match xml_attribute(e, b"val").as_deref() {
+                        Some("center") => PageAlign::Center,
+                        Some("both") => PageAlign::Both,
+                        Some("bottom") => PageAlign::Bottom,
+                        _ => PageAlign::Top,
+                    };
+                }
+            }
+            Ok(Event::Empty(ref e)) if e.local_name() == b"type" && depth == 1 => {
+                if let Some(section) = sections.last_mut() {
+                    section.start = match xml_attribute(e, b"val").as_deref() {
+                        Some("continuous") => SectionStart::Continuous,
+                        Some("oddPage") => SectionStart::OddPage,
+                        Some("evenPage") => SectionStart::EvenPage,
+                        _ => SectionStart::NextPage,
+                    };
+                }
+            }
+            Ok(Event::Empty(ref e)) if e.local_name() == b"pgSz" && depth == 1 => {
+                if let Some(section) = sections.last_mut() {
+                    section.landscape = xml_attribute(e, b"orient").as_deref() == Some("landscape");
+                }
+            }
+            Ok(Event::Empty(ref e)) if e.local_name() == b"textDirection" && depth == 1 => {
+                if let Some(section) = sections.last_mut() {
+                    section.vertical = matches!(xml_attribute(e, b"val").as_deref(), Some("tbRl" | "tbRlV"));
+                }
+            }
+            Ok(Event::Empty(ref e)) if e.local_name() == b"pgNumType" && depth == 1 => {
+                if let Some(section) = sections.last_mut() {
+                    section.numbering = PageNumbering {
+                        format: xml_attribute(e, b"fmt").map_or(NumberFormat::Decimal, |value| NumberFormat::from_word(&value)),
+                        start: xml_attribute(e, b"start").and_then(|value| value.parse().ok()),
+                    };
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(ConversionError::InvalidInput(format!("Malformed word/document.xml: {}", e))),
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(sections)
+}
+
+/// Splits body children into sections. Every section but the last ends
+/// with the paragraph carrying its `w:sectPr`.
+pub fn section_ranges(children: &[DocumentChild]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for (i, child) in children.iter().enumerate() {
+        if let DocumentChild::Paragraph(paragraph) = child
+            && paragraph.property.section_property.is_some()
+        {
+            ranges.push(start..i + 1);
+            start = i + 1;
+        }
+    }
+    ranges.push(start..children.len());
+    ranges
+}
+
+/// Moves the layout to where a section starting with `start` begins. A
+/// section whose page size differs from the page before always starts a
+/// new page; odd and even page starts leave a blank page when needed.
+pub fn begin_section(layout: &mut Layout, start: SectionStart, resized: bool) {
+    if start == SectionStart::Continuous && !resized {
+        return;
+    }
+    let at_page_top = layout.y_position >= layout.config.page_height - layout.config.margin;
+    if resized || !at_page_top {
+        layout.new_page();
+    }
+    //`page` is zero-based, so odd page numbers have even indices
+    let needs_blank = match start {
+        SectionStart::OddPage => !layout.page.is_multiple_of(2),
+        SectionStart::EvenPage => layout.page.is_multiple_of(2),
+        _ => false,
+    };
+    if needs_blank {
+        layout.new_page();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use docx_rs::{Docx, Paragraph, Run};
+
+    use crate::model::LayoutModel;
+    use crate::tests::{edited_docx, laid_out_archive, sample_docx};
+    use crate::{Config, SourceDocument, parse_document, render_pdf_bytes};
+
+    /// Baseline of the only line of a one-paragraph document whose section has `v_align`.
+    fn title_baseline(v_align: &str, config: &Config) -> f32 {
+        let packed = edited_docx(sample_docx("Title"), |name, content| match name {
+            "word/document.xml" => content.replace("</w:sectPr>", &format!(r#"<w:vAlign w:val="{}" /></w:sectPr>"#, v_align)),
+            _ => content,
+        });
+        laid_out_archive(&packed, config)[0].2
+    }
+
+    #[test]
+    fn centers_a_short_page_vertically() {
+        let config = Config::new("title.docx", "title.pdf");
+        let top = title_baseline("top", &config);
+        let centered = title_baseline("center", &config);
+        let bottom = title_baseline("bottom", &config);
+        assert!(top > config.page_height - config.margin - 10.0);
+        assert!((centered - config.page_height / 2.0).abs() < 5.0, "{}", centered);
+        assert!(bottom < config.margin + 10.0, "{}", bottom);
+    }
+
+    /// The pages the docx archive `packed` is laid out on.
+    fn layout_model(packed: &[u8], config: &Config) -> LayoutModel {
+        let document = parse_document(packed, "test.docx", config).unwrap();
+        let source = SourceDocument { docx: &document.docx, images: &document.images, parts: document.parts, attachment: None };
+        let mut model = LayoutModel::default();
+        render_pdf_bytes(vec![source], config, None, Some(&mut model)).unwrap();
+        model
+    }
+
+    #[test]
+    fn turns_only_the_pages_of_landscape_sections() {
+        let docx = Docx::new()
+            .add_paragraph(Paragraph::new().add_run(Run::new().add_text("Portrait")))
+            .add_paragraph(Paragraph::new().add_run(Run::new().add_text("Landscape")));
+        //The first paragraph ends a portrait section; the body's own section is turned
+        let packed = edited_docx(docx, |name, content| match name {
+            "word/document.xml" => content
+                .replace(r#"<w:pgSz w:w="11906" w:h="16838" />"#, r#"<w:pgSz w:w="16838" w:h="11906" w:orient="landscape" />"#)
+                .replacen("<w:pPr>", r#"<w:pPr><w:sectPr><w:pgSz w:w="11906" w:h="16838" /></w:sectPr>"#, 1),
+            _ => content,
+        });
+        let config = Config::new("turned.docx", "turned.pdf");
+        let model = layout_model(&packed, &config);
+        let sizes: Vec<(f32, f32)> = model.pages.iter().map(|page| (page.width, page.height)).collect();
+        assert_eq!(sizes, [(config.page_width, config.page_height), (config.page_height, config.page_width)]);
+        assert_eq!(model.pages[1].lines[0].runs[0].text, "Landscape");
+    }
+
+    #[test]
+    fn starts_sections_on_the_pages_their_breaks_ask_for() {
+        let docx = ["One", "Two", "Three"]
+            .into_iter()
+            .fold(Docx::new(), |docx, text| docx.add_paragraph(Paragraph::new().add_run(Run::new().add_text(text))));
+        //docx_rs writes no paragraph w:sectPr or w:type, so they are added to its output
+        let section_end = |text: &str, start: &str| {
+            let run = format!(r#"<w:r><w:rPr /><w:t xml:space="preserve">{}</w:t>"#, text);
+            (
+                format!("<w:pPr><w:rPr /></w:pPr>{}", run),
+                format!(r#"<w:pPr><w:rPr /><w:sectPr><w:type w:val="{}" /></w:sectPr></w:pPr>{}"#, start, run),
+            )
+        };
+        //"Two" starts an odd-page section, and "Three" carries on after it
+        let packed = edited_docx(docx, |name, content| match name {
+            "word/document.xml" => [section_end("One", "nextPage"), section_end("Two", "oddPage")]
+                .iter()
+                .fold(content, |content, (from, to)| content.replace(from, to))
+                .replace("</w:sectPr></w:body>", r#"<w:type w:val="continuous" /></w:sectPr></w:body>"#),
+            _ => content,
+        });
+        let model = layout_model(&packed, &Config::new("breaks.docx", "breaks.pdf"));
+        let pages: Vec<Vec<&str>> = model
+            .pages
+            .iter()
+            .map(|page| page.lines.iter().flat_map(|line| &line.runs).map(|run| run.text.as_str()).collect())
+            .collect();
+        //The second page is left blank so "Two" starts on the third, an odd page
+        assert_eq!(pages, [vec!["One"], vec![], vec!["Two", "Three"]]);
+    }
+}
@@ -0,0 +1,279 @@
+//! Splitting the PDF into several files with `--split-by`: where sections
+//! start, every so many pages, or before a file would grow past a size.
+//! Each part is the finished PDF with only its own pages left in the page
+//! tree. Links and structure elements pointing at pages of other parts
+//! point at nothing, and what only those pages used is dropped.
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use printpdf::lopdf::{self, Dictionary, Document, Object, ObjectId};
+
+use crate::ConversionError;
+
+/// Where `--split-by` starts each new file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SplitBy {
+    /// At the first page of each section and of each input document.
+    /// Pages before the first section, such as a title page, go with it.
+    Section,
+    /// Every this many pages.
+    PageCount(usize),
+    /// Before the file would grow past this many bytes, by an estimate of
+    /// the objects its pages use. A page larger than that is a file alone.
+    Size(u64),
+}
+
+impl std::str::FromStr for SplitBy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid --split-by value: {}", value);
+        match value.split_once(':') {
+            None if value == "section" => Ok(SplitBy::Section),
+            Some(("page-count", count)) => count.parse().ok().filter(|count| *count > 0).map(SplitBy::PageCount).ok_or_else(invalid),
+            Some(("size", megabytes)) => {
+                let megabytes: f64 = megabytes.parse().ok().filter(|megabytes: &f64| *megabytes > 0.0).ok_or_else(invalid)?;
+                Ok(SplitBy::Size((megabytes * 1_000_000.0) as u64))
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// The path part `number` (from 1) is written to: `out.pdf` becomes
+/// `out-1.pdf`.
+pub fn part_path(path: &Path, number: usize) -> PathBuf {
+    let stem = path.file_stem().map_or("output".into(), |stem| stem.to_string_lossy());
+    match path.extension() {
+        Some(extension) => path.with_file_name(format!("{}-{}.{}", stem, number, extension.to_string_lossy())),
+        None => path.with_file_name(format!("{}-{}", stem, number)),
+    }
+}
+
+/// Estimated bytes an object takes in the file beyond its stream data.
+const OBJECT_OVERHEAD: u64 = 40;
+
+/// The objects `page` uses, not following its parent, with their ids.
+fn page_objects(document: &Document, page: ObjectId) -> HashSet<ObjectId> {
+    let mut found = HashSet::from([page]);
+    let mut pending = vec![page];
+    while let Some(id) = pending.pop() {
+        let Ok(object) = document.get_object(id) else { continue };
+        let mut references = Vec::new();
+        collect_references(object, &mut references);
+        for reference in references {
+            if found.insert(reference) {
+                pending.push(reference);
+            }
+        }
+    }
+    found
+}
+
+/// The references held in `object`, except a page's reference to its parent.
+fn collect_references(object: &Object, references: &mut Vec<ObjectId>) {
+    let dictionary = |dict: &Dictionary, references: &mut Vec<ObjectId>| {
+        for (key, value) in dict.iter() {
+            if key.as_slice() != b"Parent" {
+                collect_references(value, references);
+            }
+        }
+    };
+    match object {
+        Object::Reference(id) => references.push(*id),
+        Object::Array(items) => items.iter().for_each(|item| collect_references(item, references)),
+        Object::Dictionary(dict) => dictionary(dict, references),
+        Object::Stream(stream) => dictionary(&stream.dict, references),
+        _ => {}
+    }
+}
+
+/// Estimated sizes of parts, from the objects their pages use between them
+/// and those every part has, such as the catalog. The estimates are scaled
+/// so that those of all the objects add up to the size of the whole file.
+struct SizeEstimate {
+    page_objects: Vec<HashSet<ObjectId>>,
+    sizes: HashMap<ObjectId, u64>,
+    /// Of the objects no page uses.
+    shared: u64,
+}
+
+impl SizeEstimate {
+    fn new(document: &Document, pages: &[ObjectId], file_size: usize) -> SizeEstimate {
+        let estimate = |object: &Object| match object {
+            Object::Stream(stream) => stream.content.len() as u64 + OBJECT_OVERHEAD,
+            _ => OBJECT_OVERHEAD,
+        };
+        let total: u64 = document.objects.values().map(estimate).sum();
+        let scale = file_size as f64 / total.max(1) as f64;
+        let sizes: HashMap<ObjectId, u64> = document.objects.iter().map(|(id, object)| (*id, (estimate(object) as f64 * scale) as u64)).collect();
+        let page_objects: Vec<HashSet<ObjectId>> = pages.iter().map(|page| page_objects(document, *page)).collect();
+        let on_pages: HashSet<&ObjectId> = page_objects.iter().flatten().collect();
+        let shared = sizes.iter().filter(|(id, _)| !on_pages.contains(id)).map(|(_, size)| size).sum();
+        SizeEstimate { page_objects, sizes, shared }
+    }
+
+    /// The end of the longest part from `start` estimated to fit in
+    /// `limit` bytes, taking at least one page.
+    fn end(&self, start: usize, limit: u64) -> usize {
+        let mut used = HashSet::new();
+        let mut size = self.shared;
+        for (i, objects) in self.page_objects.iter().enumerate().skip(start) {
+            size += objects.difference(&used).map(|id| self.sizes.get(id).copied().unwrap_or(0)).sum::<u64>();
+            if i > start && size > limit {
+                return i;
+            }
+            used.extend(objects);
+        }
+        self.page_objects.len()
+    }
+}
+
+/// `document` with only `kept` left in its page tree, directly under its
+/// root, and the objects nothing reachable uses any more removed.
+fn keep_pages(mut document: Document, tree_id: ObjectId, kept: &[ObjectId], dropped: &HashSet<ObjectId>) -> Document {
+    if let Ok(tree) = document.get_object_mut(tree_id).and_then(Object::as_dict_mut) {
+        tree.set("Kids", kept.iter().map(|id| Object::Reference(*id)).collect::<Vec<_>>());
+        tree.set("Count", kept.len() as i64);
+    }
+    for id in kept {
+        if let Ok(page) = document.get_object_mut(*id).and_then(Object::as_dict_mut) {
+            page.set("Parent", Object::Reference(tree_id));
+        }
+    }
+    //Walked from the trailer, so pages of other parts and what only they use are left behind
+    let mut reachable = HashSet::new();
+    let mut pending = Vec::new();
+    for (_, value) in document.trailer.iter_mut() {
+        null_dropped(value, dropped, &mut pending);
+    }
+    while let Some(id) = pending.pop() {
+        if reachable.insert(id)
+            && let Some(object) = document.objects.get_mut(&id)
+        {
+            null_dropped(object, dropped, &mut pending);
+        }
+    }
+    document.objects.retain(|id, _| reachable.contains(id));
+    document
+}
+
+/// Replaces the references in `object` to `dropped` objects with null,
+/// collecting the others.
+fn null_dropped(object: &mut Object, dropped: &HashSet<ObjectId>, references: &mut Vec<ObjectId>) {
+    match object {
+        Object::Reference(id) if dropped.contains(id) => *object = Object::Null,
+        Object::Reference(id) => references.push(*id),
+        Object::Array(items) => items.iter_mut().for_each(|item| null_dropped(item, dropped, references)),
+        Object::Dictionary(dict) => dict.iter_mut().for_each(|(_, value)| null_dropped(value, dropped, references)),
+        Object::Stream(stream) => stream.dict.iter_mut().for_each(|(_, value)| null_dropped(value, dropped, references)),
+        _ => {}
+    }
+}
+
+/// The catalog and page tree root of a document being split, with its pages.
+struct Pages {
+    catalog_id: ObjectId,
+    tree_id: ObjectId,
+    ids: Vec<ObjectId>,
+}
+
+/// Writes the pages `range` of `document` as a PDF of their own.
+fn write_part(document: &Document, pages: &Pages, range: Range<usize>) -> Result<Vec<u8>, ConversionError> {
+    let dropped: HashSet<ObjectId> = pages.ids[..range.start].iter().chain(&pages.ids[range.end..]).copied().collect();
+    let mut part = keep_pages(document.clone(), pages.tree_id, &pages.ids[range.clone()], &dropped);
+    //Page labels count from the first page of the whole document
+    if range.start > 0
+        && let Ok(catalog) = part.get_object_mut(pages.catalog_id).and_then(Object::as_dict_mut)
+    {
+        catalog.remove(b"PageLabels");
+    }
+    let mut bytes = Vec::new();
+    part.save_to(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Splits `pdf` as `split_by` asks. `section_starts` are the zero-based
+/// first pages of the sections laid out, in order.
+pub fn split_pdf(pdf: &[u8], split_by: SplitBy, section_starts: &[usize]) -> Result<Vec<Vec<u8>>, ConversionError> {
+    let pdf_error = |e: lopdf::Error| ConversionError::Pdf(e.to_string());
+    let document = Document::load_mem(pdf).map_err(pdf_error)?;
+    let catalog_id = document.trailer.get(b"Root").and_then(Object::as_reference).map_err(pdf_error)?;
+    let tree_id = document.get_dictionary(catalog_id).and_then(|catalog| catalog.get(b"Pages")).and_then(Object::as_reference).map_err(pdf_error)?;
+    let pages = Pages { catalog_id, tree_id, ids: document.get_pages().into_values().collect() };
+    let count = pages.ids.len();
+
+    let mut starts: Vec<usize> = match split_by {
+        SplitBy::Section => std::iter::once(0).chain(section_starts.iter().skip(1).copied()).collect(),
+        SplitBy::PageCount(per_part) => (0..count).step_by(per_part).collect(),
+        SplitBy::Size(limit) => {
+            let estimate = SizeEstimate::new(&document, &pages.ids, pdf.len());
+            let mut parts = Vec::new();
+            let mut start = 0;
+            while start < count {
+                let mut end = estimate.end(start, limit);
+                //The estimate can fall short, so a part too large is written again a page shorter
+                let mut bytes = write_part(&document, &pages, start..end)?;
+                while bytes.len() as u64 > limit && end - start > 1 {
+                    end -= 1;
+                    bytes = write_part(&document, &pages, start..end)?;
+                }
+                parts.push(bytes);
+                start = end;
+            }
+            return Ok(parts);
+        }
+    };
+    //Sections that start on the same page, or after the last, make no part
+    starts.retain(|start| *start < count.max(1));
+    starts.dedup();
+    let ends = starts.iter().skip(1).copied().chain(std::iter::once(count));
+    starts.iter().zip(ends).map(|(start, end)| write_part(&document, &pages, *start..end)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use docx_rs::{Docx, Paragraph, Run};
+
+    /// A PDF of `pages` pages of numbered paragraphs, 25 fitting on a page.
+    fn pages_pdf(pages: usize) -> Vec<u8> {
+        let mut docx = Docx::new();
+        for line in 0..pages * 24 {
+            docx = docx.add_paragraph(Paragraph::new().add_run(Run::new().add_text(format!("Line {}", line + 1))));
+        }
+        crate::convert_parsed(&docx, &[], &Config::new("split.docx", "split.pdf")).unwrap()
+    }
+
+    fn page_counts(parts: &[Vec<u8>]) -> Vec<usize> {
+        parts.iter().map(|part| Document::load_mem(part).unwrap().get_pages().len()).collect()
+    }
+
+    #[test]
+    fn writes_one_file_per_section() {
+        let pdf = pages_pdf(5);
+        assert_eq!(Document::load_mem(&pdf).unwrap().get_pages().len(), 5);
+        //A title page before the first section goes with it
+        let parts = split_pdf(&pdf, SplitBy::Section, &[1, 3, 3, 4]).unwrap();
+        assert_eq!(page_counts(&parts), [3, 1, 1]);
+    }
+
+    #[test]
+    fn splits_every_so_many_pages() {
+        let parts = split_pdf(&pages_pdf(5), SplitBy::PageCount(2), &[]).unwrap();
+        assert_eq!(page_counts(&parts), [2, 2, 1]);
+    }
+
+    #[test]
+    fn parses_split_by_and_names_parts() {
+        assert_eq!("section".parse(), Ok(SplitBy::Section));
+        assert_eq!("page-count:10".parse(), Ok(SplitBy::PageCount(10)));
+        assert_eq!("size:1.5".parse(), Ok(SplitBy::Size(1_500_000)));
+        assert!("page-count:0".parse::<SplitBy>().is_err());
+        assert_eq!(part_path(Path::new("out/report.pdf"), 2), Path::new("out/report-2.pdf"));
+        assert_eq!(part_path(Path::new("report"), 1), Path::new("report-1"));
+    }
+}
@@ -0,0 +1,74 @@
+//! Reading a docx from a pipe. `ZipArchive` finds entries through the
+//! central directory at the end of the file, which needs `Seek`; here the
+//! entries are instead read one after another from their local headers as
+//! they arrive. Only the parts the converter uses are kept, so extended
+//! metadata, custom XML and embedded objects never reach memory.
+//!
+//! The tradeoff is that entries cannot be revisited or read out of order,
+//! and entries whose sizes only follow their data (written with a data
+//! descriptor, as some streaming zip writers do) cannot be read at all.
+
+use std::io::{Cursor, Read, Write};
+
+use zip::CompressionMethod;
+use zip::read::read_zipfile_from_stream;
+use zip::write::{FileOptions, ZipWriter};
+
+use crate::ConversionError;
+
+/// Input path that reads the document from standard input.
+pub const STDIN_PATH: &str = "-";
+
+/// Whether the archive entry `name` is read by the converter.
+fn is_needed(name: &str) -> bool {
+    name == "[Content_Types].xml"
+        || name.ends_with(".rels")
+        //The title and author, for the PDF's properties and `--title-page`
+        || name == "docProps/core.xml"
+        || (name.starts_with("word/") && !name.starts_with("word/embeddings/"))
+}
+
+/// Reads the docx streamed by `reader` and returns an archive holding just
+/// the parts the converter needs, stored uncompressed.
+pub fn read_docx_stream<R: Read>(reader: &mut R) -> Result<Vec<u8>, ConversionError> {
+    let mut output = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let mut content = Vec::new();
+    while let Some(mut entry) = read_zipfile_from_stream(reader)? {
+        if entry.is_dir() || !is_needed(entry.name()) {
+            //Dropping the entry skips over its data
+            continue;
+        }
+        content.clear();
+        entry.read_to_end(&mut content)?;
+        output.start_file(entry.name(), options)?;
+        output.write_all(&content)?;
+    }
+    Ok(output.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::properties::read_core_properties;
+    use crate::tests::archive;
+
+    #[test]
+    fn keeps_core_properties() {
+        let core = "<cp:coreProperties xmlns:cp=\"x\" xmlns:dc=\"y\">\
+            <dc:title>Annual report</dc:title><dc:creator>A. Author</dc:creator></cp:coreProperties>";
+        let docx = archive(&[
+            ("[Content_Types].xml", "<Types/>"),
+            ("docProps/core.xml", core),
+            ("docProps/app.xml", "<Properties/>"),
+            ("word/document.xml", "<w:document/>"),
+        ]);
+        let kept = read_docx_stream(&mut &docx[..]).unwrap();
+        let properties = read_core_properties(&kept).unwrap();
+        assert_eq!(properties.title.as_deref(), Some("Annual report"));
+        assert_eq!(properties.author.as_deref(), Some("A. Author"));
+
+        let names: Vec<String> = zip::ZipArchive::new(Cursor::new(kept)).unwrap().file_names().map(String::from).collect();
+        assert!(!names.iter().any(|name| name == "docProps/app.xml"));
+    }
+}
@@ -0,0 +1,178 @@
+//! Table style conditional formatting, read from `word/styles.xml`.
+//!
+//! docx_rs parses table styles but drops their `w:tblStylePr` blocks, which
+//! carry the header row and banded row formatting Word applies. It drops
+//! `w:lang` too, so the document's default language is read here as well.
+
+use std::collections::HashMap;
+
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+use crate::color::parse_hex;
+use crate::{ConversionError, read_part, xml_attribute};
+
+/// Formatting a table style applies to a whole row.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RowFormat {
+    pub fill: Option<(f32, f32, f32)>,
+    pub bold: bool,
+    pub color: Option<(f32, f32, f32)>,
+}
+
+/// The row-level conditional formats of one table style.
+#[derive(Debug, Default)]
+pub struct TableStyle {
+    first_row: Option<RowFormat>,
+    /// Odd-numbered body rows.
+    band1: Option<RowFormat>,
+    /// Even-numbered body rows.
+    band2: Option<RowFormat>,
+}
+
+impl TableStyle {
+    /// Formatting for row `index` of a table using this style. When the
+    /// style formats a header row, banding starts on the row after it.
+    pub fn row_format(&self, index: usize) -> RowFormat {
+        let band_index = match self.first_row {
+            Some(format) if index == 0 => return format,
+            Some(_) => index - 1,
+            None => index,
+        };
+        let band = if band_index % 2 == 0 { self.band1 } else { self.band2 };
+        band.unwrap_or_default()
+    }
+}
+
+/// Table styles keyed by `w:styleId`.
+pub type TableStyles = HashMap<String, TableStyle>;
+
+/// Reads the conditional formatting of every table style in the document.
+pub fn read_table_styles(docx: &[u8]) -> Result<TableStyles, ConversionError> {
+    let Some(xml) = read_part(docx, "word/styles.xml")? else { return Ok(TableStyles::new()) };
+
+    let mut styles = TableStyles::new();
+    let mut reader = Reader::from_reader(&xml[..]);
+    let mut buf = Vec::new();
+    let mut style: Option<(String, TableStyle)> = None;
+    //The `w:tblStylePr` being read, by its `w:type`
+    let mut conditional: Option<(String, RowFormat)> = None;
+    let mut in_run_properties = false;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) if e.local_name() == b"rPr" => in_run_properties = true,
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => match e.local_name() {
+                b"style" if xml_attribute(e, b"type").as_deref() == Some("table") => {
+                    style = xml_attribute(e, b"styleId").map(|id| (id, TableStyle::default()));
+                }
+                b"tblStylePr" if style.is_some() => {
+                    conditional = xml_attribute(e, b"type").map(|kind| (kind, RowFormat::default()));
+                }
+                b"b" if in_run_properties => {
+                    if let Some((_, format)) = conditional.as_mut() {
+                        format.bold = !matches!(xml_attribute(e, b"val").as_deref(), Some("0" | "false"));
+                    }
+                }
+                b"color" if in_run_properties => {
+                    if let Some((_, format)) = conditional.as_mut() {
+                        format.color = xml_attribute(e, b"val").as_deref().and_then(parse_hex);
+                    }
+                }
+                b"shd" if !in_run_properties => {
+                    if let Some((_, format)) = conditional.as_mut() {
+                        format.fill = xml_attribute(e, b"fill").as_deref().and_then(parse_hex);
+                    }
+                }
+                _ => {}
+            },
+            Ok(Event::End(ref e)) => match e.local_name() {
+                b"rPr" => in_run_properties = false,
+                b"tblStylePr" => {
+                    if let (Some((kind, format)), Some((_, table_style))) = (conditional.take(), style.as_mut()) {
+                        match kind.as_str() {
+                            "firstRow" => table_style.first_row = Some(format),
+                            "band1Horz" => table_style.band1 = Some(format),
+                            "band2Horz" => table_style.band2 = Some(format),
+                            _ => {}
+                        }
+                    }
+                }
+                b"style" => {
+                    if let Some((id, table_style)) = style.take() {
+                        styles.insert(id, table_style);
+                    }
+                }
+                _ => {}
+            },
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(ConversionError::InvalidInput(format!("Malformed word/styles.xml: {}", e))),
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(styles)
+}
+
+/// The font family of the document's default run properties, for runs
+/// that name none.
+pub fn read_default_font(docx: &[u8]) -> Result<Option<String>, ConversionError> {
+    let Some(xml) = read_part(docx, "word/styles.xml")? else { return Ok(None) };
+
+    let mut reader = Reader::from_reader(&xml[..]);
+    let mut buf = Vec::new();
+    let mut in_defaults = false;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) if e.local_name() == b"docDefaults" => in_defaults = true,
+            Ok(Event::End(ref e)) if e.local_name() == b"docDefaults" => return Ok(None),
+            Ok(Event::Empty(ref e)) if in_defaults && e.local_name() == b"rFonts" => return Ok(xml_attribute(e, b"ascii")),
+            Ok(Event::Eof) => return Ok(None),
+            Err(e) => return Err(ConversionError::InvalidInput(format!("Malformed word/styles.xml: {}", e))),
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+/// The language of the document's default run properties, as a BCP 47 tag.
+pub fn read_default_language(docx: &[u8]) -> Result<Option<String>, ConversionError> {
+    let Some(xml) = read_part(docx, "word/styles.xml")? else { return Ok(None) };
+
+    let mut reader = Reader::from_reader(&xml[..]);
+    let mut buf = Vec::new();
+    let mut in_defaults = false;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) if e.local_name() == b"docDefaults" => in_defaults = true,
+            Ok(Event::End(ref e)) if e.local_name() == b"docDefaults" => return Ok(None),
+            Ok(Event::Empty(ref e)) if in_defaults && e.local_name() == b"lang" => return Ok(xml_attribute(e, b"val")),
+            Ok(Event::Eof) => return Ok(None),
+            Err(e) => return Err(ConversionError::InvalidInput(format!("Malformed word/styles.xml: {}", e))),
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::archive;
+
+    #[test]
+    fn bands_the_rows_after_the_header() {
+        let styles = r#"<w:styles xmlns:w="w"><w:style w:type="table" w:styleId="Grid">
+            <w:tblStylePr w:type="firstRow"><w:rPr><w:b/><w:color w:val="FFFFFF"/></w:rPr><w:tcPr><w:shd w:fill="000000"/></w:tcPr></w:tblStylePr>
+            <w:tblStylePr w:type="band1Horz"><w:tcPr><w:shd w:fill="DDDDDD"/></w:tcPr></w:tblStylePr>
+        </w:style></w:styles>"#;
+        let styles = read_table_styles(&archive(&[("word/styles.xml", styles)])).unwrap();
+        let grid = &styles["Grid"];
+        let header = RowFormat { fill: Some((0.0, 0.0, 0.0)), bold: true, color: Some((1.0, 1.0, 1.0)) };
+        let banded = RowFormat { fill: parse_hex("DDDDDD"), ..RowFormat::default() };
+        assert_eq!(grid.row_format(0), header);
+        //Banding counts from the first row under the header; band2 is unstyled
+        assert_eq!(grid.row_format(1), banded);
+        assert_eq!(grid.row_format(2), RowFormat::default());
+        assert_eq!(grid.row_format(3), banded);
+    }
+}
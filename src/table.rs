@@ -0,0 +1,339 @@
+//! Table layout. Every row is measured before anything is drawn so its
+//! height is known up front; cells may hold further tables, which are
+//! measured and drawn recursively within the cell's width. Header and
+//! banded row formatting comes from the table's style.
+
+use docx_rs::{Run, Table, TableCell, TableCellContent, TableChild, TableRow, TableRowChild};
+
+use crate::caps::is_small_caps;
+use crate::color::{fill_color, parse_hex};
+use crate::markup::baseline_shift;
+use crate::paragraph::{CellPart, cell_lines, run_tracking};
+use crate::styles::RowFormat;
+use crate::tabs::{TextStyle, use_tabbed_text};
+use crate::{ASCENT, Layout, TWIP_TO_MM, draw_rect, fill_rect, property_json};
+
+/// Inner padding between a table cell border and its content, in mm.
+const CELL_PADDING: f32 = 1.5;
+
+/// A measured row, ready to draw.
+struct RowLayout<'t> {
+    cells: Vec<CellLayout<'t>>,
+    height: f32,
+    format: RowFormat,
+}
+
+struct CellLayout<'t> {
+    /// Offset of the cell's left edge from the table's left edge, in mm.
+    offset: f32,
+    width: f32,
+    rotation: Option<f32>,
+    /// Share of the row's spare height placed above the content, from `w:vAlign`.
+    align: f32,
+    /// The cell's own `w:shd` fill, which wins over the row's style fill.
+    fill: Option<(f32, f32, f32)>,
+    blocks: Vec<CellBlock<'t>>,
+    height: f32,
+}
+
+enum CellBlock<'t> {
+    /// A line of a paragraph, as the parts its runs set.
+    Line(Vec<CellPart<'t>>),
+    Table(Vec<RowLayout<'t>>),
+}
+
+/// Rotation, in degrees counter-clockwise, for a cell's `w:textDirection`.
+/// `btLr` reads bottom-to-top; the `tb*` variants read top-to-bottom.
+fn text_rotation(cell: &TableCell) -> Option<f32> {
+    match property_json(&cell.property)["textDirection"].as_str()? {
+        "btLr" => Some(90.0),
+        "tbRl" | "tbRlV" | "tb" | "tbV" => Some(270.0),
+        _ => None,
+    }
+}
+
+/// Vertical placement for a cell's `w:vAlign`, as the share of spare height above the content.
+fn vertical_align(cell: &TableCell) -> f32 {
+    match property_json(&cell.property)["verticalAlign"].as_str() {
+        Some("center") => 0.5,
+        Some("bottom") => 1.0,
+        _ => 0.0,
+    }
+}
+
+/// Widths of each grid column in mm, shrunk proportionally to fit `available_width`.
+fn column_widths(table: &Table, available_width: f32) -> Vec<f32> {
+    if table.grid.is_empty() {
+        let columns = table
+            .rows
+            .iter()
+            .map(|TableChild::TableRow(row)| row.cells.len())
+            .max()
+            .unwrap_or(1)
+            .max(1);
+        return vec![available_width / columns as f32; columns];
+    }
+    let widths: Vec<f32> = table.grid.iter().map(|w| *w as f32 * TWIP_TO_MM).collect();
+    let total: f32 = widths.iter().sum();
+    if total > available_width {
+        widths.iter().map(|w| w * available_width / total).collect()
+    } else {
+        widths
+    }
+}
+
+fn measure_table<'t>(layout: &Layout, table: &'t Table, available_width: f32) -> Vec<RowLayout<'t>> {
+    let widths = column_widths(table, available_width);
+    let style = property_json(&table.property)["style"]
+        .as_str()
+        .and_then(|id| layout.table_styles.get(id));
+    table
+        .rows
+        .iter()
+        .enumerate()
+        .map(|(index, TableChild::TableRow(row))| {
+            let format = style.map(|style| style.row_format(index)).unwrap_or_default();
+            measure_row(layout, row, &widths, format)
+        })
+        .collect()
+}
+
+fn measure_row<'t>(layout: &Layout, row: &'t TableRow, widths: &[f32], format: RowFormat) -> RowLayout<'t> {
+    let mut cells = Vec::new();
+    let mut column = 0;
+    for TableRowChild::TableCell(cell) in &row.cells {
+        let span = (property_json(&cell.property)["gridSpan"].as_u64().unwrap_or(1) as usize).max(1);
+        let offset = widths.iter().take(column).sum::<f32>();
+        let width: f32 = widths.iter().skip(column).take(span).sum();
+        column += span;
+        cells.push(measure_cell(layout, cell, offset, width, format.bold));
+    }
+
+    //`w:trHeight` is treated as a minimum whatever its rule
+    let min_height = property_json(&row.property)["rowHeight"].as_f64().unwrap_or(0.0) as f32 * TWIP_TO_MM;
+    let height = cells
+        .iter()
+        .map(|cell| cell.height)
+        .fold((layout.line_height + 2.0 * CELL_PADDING).max(min_height), f32::max);
+    RowLayout { cells, height, format }
+}
+
+/// How the text of `run` is set, in a row whose style may make it bold.
+fn part_style<'l>(layout: &'l Layout, run: &Run, bold_row: bool) -> TextStyle<'l> {
+    TextStyle {
+        font: if bold_row { &layout.fonts.bold } else { layout.fonts.for_run(run) },
+        east_asian: layout.fonts.east_asian_for_run(run),
+        size: layout.font_size,
+        tracking: run_tracking(run),
+        small_caps: is_small_caps(run),
+        rise: baseline_shift(run),
+        tab_stops: &[],
+    }
+}
+
+/// Width of a line of a cell, in mm.
+fn line_width(layout: &Layout, parts: &[CellPart], bold_row: bool) -> f32 {
+    parts.iter().map(|part| part_style(layout, part.run, bold_row).width(layout, &part.text)).sum()
+}
+
+fn measure_cell<'t>(layout: &Layout, cell: &'t TableCell, offset: f32, width: f32, bold_row: bool) -> CellLayout<'t> {
+    let rotation = text_rotation(cell);
+    let inner_width = width - 2.0 * CELL_PADDING;
+    let bold = bold_row.then_some(&layout.fonts.bold);
+    let mut blocks = Vec::new();
+    for content in &cell.children {
+        match content {
+            //Vertical text runs along the cell, so its lines are not wrapped to the width
+            TableCellContent::Paragraph(paragraph) => {
+                let width = if rotation.is_some() { None } else { Some(inner_width) };
+                blocks.extend(cell_lines(layout, paragraph, width, bold).into_iter().map(CellBlock::Line));
+            }
+            //Vertical cells only carry text, so nested tables are laid out horizontally only
+            TableCellContent::Table(table) if rotation.is_none() => {
+                blocks.push(CellBlock::Table(measure_table(layout, table, inner_width)));
+            }
+            _ => {}
+        }
+    }
+
+    let content_height = match rotation {
+        //Vertical text runs along the cell's height
+        Some(_) => blocks
+            .iter()
+            .map(|block| match block {
+                CellBlock::Line(parts) => line_width(layout, parts, bold_row),
+                CellBlock::Table(_) => 0.0,
+            })
+            .fold(0.0, f32::max),
+        None => blocks
+            .iter()
+            .map(|block| match block {
+                CellBlock::Line(_) => layout.line_height,
+                CellBlock::Table(rows) => rows.iter().map(|row| row.height).sum(),
+            })
+            .sum(),
+    };
+    let fill = property_json(&cell.property)["shading"]["fill"].as_str().and_then(parse_hex);
+    CellLayout {
+        offset,
+        width,
+        rotation,
+        align: vertical_align(cell),
+        fill,
+        blocks,
+        height: content_height + 2.0 * CELL_PADDING,
+    }
+}
+
+/// Draws a line of a cell in `row` from `x` on the baseline `y`.
+fn draw_line(layout: &Layout, parts: &[CellPart], row: &RowLayout, (x, y): (f32, f32)) {
+    let text_color = row.format.color.unwrap_or((0.0, 0.0, 0.0));
+    let mut cursor = x;
+    for part in parts {
+        let style = part_style(layout, part.run, row.format.bold);
+        layout.set_run_style_or(part.run, text_color);
+        cursor += if part.redacted {
+            let width = style.width(layout, &part.text);
+            layout.draw_redaction_box(&layout.layer, (cursor, y + style.rise), width, style.size);
+            width
+        } else {
+            use_tabbed_text(layout, &part.text, &style, (cursor, y), x)
+        };
+    }
+}
+
+fn draw_row(layout: &Layout, row: &RowLayout, left: f32, top: f32) {
+    let bottom = top - row.height;
+    layout.tags.borrow_mut().begin("TR");
+    for cell in &row.cells {
+        layout.tags.borrow_mut().begin("TD");
+        let x = left + cell.offset;
+        if let Some(rgb) = cell.fill.or(row.format.fill) {
+            fill_rect(&layout.layer, x, bottom, cell.width, row.height, fill_color(layout.config.color_space, rgb));
+        }
+        draw_rect(&layout.layer, x, bottom, cell.width, row.height);
+
+        if let Some(angle) = cell.rotation {
+            //Vertical text stacks its lines across the cell instead of down it
+            for (i, block) in cell.blocks.iter().enumerate() {
+                let CellBlock::Line(parts) = block else { continue };
+                let origin = if angle == 90.0 {
+                    (x + CELL_PADDING + (i as f32 + 0.8) * layout.line_height, bottom + CELL_PADDING)
+                } else {
+                    (x + CELL_PADDING + (i as f32 + 0.2) * layout.line_height, top - CELL_PADDING)
+                };
+                layout.rotation.set(Some((angle, origin)));
+                draw_line(layout, parts, row, origin);
+                layout.rotation.set(None);
+            }
+            layout.tags.borrow_mut().end();
+            continue;
+        }
+
+        let mut cursor = top - CELL_PADDING - (row.height - cell.height) * cell.align;
+        for block in &cell.blocks {
+            match block {
+                CellBlock::Line(parts) => {
+                    draw_line(layout, parts, row, (x + CELL_PADDING, cursor - ASCENT * layout.line_height));
+                    cursor -= layout.line_height;
+                }
+                CellBlock::Table(rows) => {
+                    layout.tags.borrow_mut().begin("Table");
+                    for nested in rows {
+                        draw_row(layout, nested, x + CELL_PADDING, cursor);
+                        cursor -= nested.height;
+                    }
+                    layout.tags.borrow_mut().end();
+                }
+            }
+        }
+        layout.tags.borrow_mut().end();
+    }
+    layout.tags.borrow_mut().end();
+}
+
+/// Total height `table` takes when drawn across the usable width.
+pub fn table_height(layout: &Layout, table: &Table) -> f32 {
+    let config = layout.config;
+    let rows = measure_table(layout, table, config.page_width - 2.0 * config.margin);
+    rows.iter().map(|row| row.height).sum()
+}
+
+/// Draws `table` at the layout cursor, starting a new page before any row
+/// that would cross the bottom margin.
+pub fn render_table(layout: &mut Layout, table: &Table) {
+    let config = layout.config;
+    let rows = measure_table(layout, table, config.page_width - 2.0 * config.margin);
+    layout.tags.get_mut().begin("Table");
+    for row in &rows {
+        layout.ensure_space(row.height);
+        draw_row(layout, row, config.margin, layout.y_position);
+        layout.y_position -= row.height;
+    }
+    layout.tags.get_mut().end();
+}
+
+#[cfg(test)]
+mod tests {
+    use docx_rs::{Docx, Paragraph, Run, Table, TableCell, TableRow, TextDirectionType, VAlignType};
+
+    use crate::Config;
+    use crate::compression::Compression;
+    use crate::tests::laid_out;
+
+    #[test]
+    fn flows_the_runs_of_a_cell_together() {
+        let paragraph = Paragraph::new().add_run(Run::new().add_text("Total ").bold()).add_run(Run::new().add_text("due"));
+        let table = Table::new(vec![TableRow::new(vec![TableCell::new().add_paragraph(paragraph)])]);
+        let docx = Docx::new().add_table(table);
+        let runs = laid_out(&docx, &Config::new("cell.docx", "cell.pdf"));
+        let total = runs.iter().find(|(text, ..)| text.starts_with("Total")).unwrap();
+        let due = runs.iter().find(|(text, ..)| text == "due").unwrap();
+        //Both runs share the line, the second starting where the first ends
+        assert_eq!(total.2, due.2);
+        assert!((total.1 + total.3 - due.1).abs() < 0.01);
+    }
+
+    #[test]
+    fn turns_vertical_text_in_a_nested_table() {
+        let paragraph = Paragraph::new().add_run(Run::new().add_text("Up").bold()).add_run(Run::new().add_text("ward"));
+        let vertical = TableCell::new().text_direction(TextDirectionType::BtLr).add_paragraph(paragraph);
+        let nested = Table::new(vec![TableRow::new(vec![vertical])]);
+        let docx = Docx::new().add_table(Table::new(vec![TableRow::new(vec![TableCell::new().add_table(nested)])]));
+        let mut config = Config::new("vertical.docx", "vertical.pdf");
+        let runs = laid_out(&docx, &config);
+        let up = runs.iter().find(|(text, ..)| text == "Up").unwrap();
+        let ward = runs.iter().find(|(text, ..)| text == "ward").unwrap();
+        //The second run goes on up the page from where the first ends
+        assert!((up.1 - ward.1).abs() < 0.01);
+        assert!((up.2 + up.3 - ward.2).abs() < 0.01);
+
+        config.compression = Some(Compression::None);
+        config.redact = vec!["pwa".to_string()];
+        let pdf = String::from_utf8_lossy(&crate::convert_parsed(&docx, &[], &config).unwrap()).into_owned();
+        assert!(pdf.contains("<55> Tj") && pdf.contains("<7264> Tj"));
+        assert!(!pdf.contains("<5570> Tj") && !pdf.contains("<77617264> Tj"));
+        //Text and redaction boxes are both turned a quarter
+        let turned = |operator: &str| pdf.lines().filter(|line| line.contains(" 1 -1 ") && line.ends_with(operator)).count();
+        assert_eq!(turned(" Tm"), 2);
+        assert_eq!(turned(" cm"), 2);
+    }
+
+    #[test]
+    fn places_cell_content_by_its_vertical_alignment() {
+        let text = |text: &str| Paragraph::new().add_run(Run::new().add_text(text));
+        let tall = TableCell::new().add_paragraph(text("one")).add_paragraph(text("two")).add_paragraph(text("three"));
+        let row = |align| TableRow::new(vec![tall.clone(), TableCell::new().vertical_align(align).add_paragraph(text("short"))]);
+        let baseline = |align| {
+            let runs = laid_out(&Docx::new().add_table(Table::new(vec![row(align)])), &Config::new("align.docx", "align.pdf"));
+            let line = |wanted: &str| runs.iter().find(|(text, ..)| text == wanted).unwrap().2;
+            (line("short"), line("one"), line("two"), line("three"))
+        };
+        let (top, one, _, _) = baseline(VAlignType::Top);
+        assert_eq!(top, one);
+        let (center, _, two, _) = baseline(VAlignType::Center);
+        assert!((center - two).abs() < 0.01);
+        let (bottom, _, _, three) = baseline(VAlignType::Bottom);
+        assert!((bottom - three).abs() < 0.01);
+    }
+}
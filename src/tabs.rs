@@ -0,0 +1,300 @@
+//! Tab characters. A tab advances to the paragraph's next explicit stop
+//! from `w:tabs`, whose leader fills the gap it leaves; past the last one
+//! Word uses multiples of the document's default tab width, measured from
+//! the left edge of the paragraph.
+
+use docx_rs::{Paragraph, TabLeaderType, TabValueType};
+use printpdf::IndirectFontRef;
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+use crate::caps::{SMALL_CAPS_SCALE, small_caps_spans};
+use crate::{ConversionError, Layout, PT_TO_MM, TWIP_TO_MM, read_part, script_segments, xml_attribute};
+
+/// Word's fallback default tab width of half an inch, in mm.
+pub const DEFAULT_TAB_STOP: f32 = 36.0 * PT_TO_MM;
+
+/// Reads `w:defaultTabStop` from `word/settings.xml`, in mm.
+pub fn read_default_tab_stop(docx: &[u8]) -> Result<f32, ConversionError> {
+    let Some(xml) = read_part(docx, "word/settings.xml")? else { return Ok(DEFAULT_TAB_STOP) };
+
+    let mut reader = Reader::from_reader(&xml[..]);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) if e.local_name() == b"defaultTabStop" => {
+                let twips = xml_attribute(e, b"val").and_then(|value| value.parse::<f32>().ok());
+                //A zero interval would never advance
+                return Ok(twips.filter(|twips| *twips > 0.0).map_or(DEFAULT_TAB_STOP, |twips| twips * TWIP_TO_MM));
+            }
+            Ok(Event::Eof) => return Ok(DEFAULT_TAB_STOP),
+            Err(e) => return Err(ConversionError::InvalidInput(format!("Malformed word/settings.xml: {}", e))),
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+/// Position of the first default tab stop after `x`, with stops every
+/// `interval` mm from `origin`.
+fn next_tab_stop(x: f32, origin: f32, interval: f32) -> f32 {
+    origin + ((x - origin) / interval).floor() * interval + interval
+}
+
+/// How the text after a tab lines up with its stop.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TabAlignment {
+    /// Also used for `num` stops.
+    Left,
+    Center,
+    Right,
+    /// The first `.` lines up with the stop; text without one ends at it.
+    Decimal,
+}
+
+/// An explicit tab stop from a paragraph's `w:tabs`.
+#[derive(Debug, Clone, Copy)]
+pub struct TabStop {
+    /// Distance from the left edge of the paragraph, in mm.
+    pub position: f32,
+    pub alignment: TabAlignment,
+    /// Character repeated across the gap before the stop.
+    pub leader: Option<char>,
+}
+
+/// The explicit tab stops of `paragraph`, left to right. Cleared stops
+/// and bar tabs, which only draw a rule, do not stop the text.
+pub fn paragraph_tab_stops(paragraph: &Paragraph) -> Vec<TabStop> {
+    let mut stops: Vec<TabStop> = paragraph
+        .property
+        .tabs
+        .iter()
+        .filter_map(|tab| {
+            let alignment = match tab.val {
+                Some(TabValueType::Clear | TabValueType::Bar) => return None,
+                Some(TabValueType::Center) => TabAlignment::Center,
+                Some(TabValueType::Right | TabValueType::End) => TabAlignment::Right,
+                Some(TabValueType::Decimal) => TabAlignment::Decimal,
+                _ => TabAlignment::Left,
+            };
+            let leader = match tab.leader {
+                Some(TabLeaderType::Dot) => Some('.'),
+                Some(TabLeaderType::MiddleDot) => Some('·'),
+                Some(TabLeaderType::Hyphen) => Some('-'),
+                Some(TabLeaderType::Underscore | TabLeaderType::Heavy) => Some('_'),
+                Some(TabLeaderType::None) | None => None,
+            };
+            Some(TabStop { position: tab.pos? as f32 * TWIP_TO_MM, alignment, leader })
+        })
+        .collect();
+    stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+    stops
+}
+
+/// The stops the tabs of a line move to: the paragraph's explicit stops,
+/// then default ones every `interval` mm, all measured from `origin`.
+#[derive(Debug, Clone, Copy)]
+pub struct TabStops<'a> {
+    pub explicit: &'a [TabStop],
+    pub origin: f32,
+    pub interval: f32,
+}
+
+impl<'a> TabStops<'a> {
+    /// Default stops only, every `interval` mm from the start of the line.
+    pub fn defaults(interval: f32) -> TabStops<'a> {
+        TabStops { explicit: &[], origin: 0.0, interval }
+    }
+
+    /// Where `segment`, the text after a tab reached at `x`, starts, and the
+    /// explicit stop it is set against, if any. The stop is the first one
+    /// strictly past `x`, however far the text before the tab reached.
+    /// `width` measures text.
+    pub fn start(&self, x: f32, segment: &str, width: impl Fn(&str) -> f32) -> (f32, Option<&'a TabStop>) {
+        let Some(stop) = self.explicit.iter().find(|stop| self.origin + stop.position > x) else {
+            return (next_tab_stop(x, self.origin, self.interval), None);
+        };
+        let position = self.origin + stop.position;
+        //Text that would reach back past the cursor starts at it instead
+        let start = match stop.alignment {
+            TabAlignment::Left => position,
+            TabAlignment::Center => position - width(segment) / 2.0,
+            TabAlignment::Right => position - width(segment),
+            TabAlignment::Decimal => {
+                let whole = segment.find('.').map_or(segment, |point| &segment[..point]);
+                position - width(whole)
+            }
+        };
+        (start.max(x), Some(stop))
+    }
+}
+
+/// How a line of text is set.
+pub struct TextStyle<'a> {
+    pub font: &'a IndirectFontRef,
+    /// Font of the East Asian characters, if there is one for them.
+    pub east_asian: Option<&'a IndirectFontRef>,
+    pub size: f32,
+    /// Extra advance after each character, in mm.
+    pub tracking: f32,
+    pub small_caps: bool,
+    /// Raise of the text above the line's baseline, in mm.
+    pub rise: f32,
+    /// Explicit stops of the paragraph the line belongs to.
+    pub tab_stops: &'a [TabStop],
+}
+
+impl TextStyle<'_> {
+    fn spans(&self, text: &str) -> Vec<(bool, String)> {
+        if self.small_caps { small_caps_spans(text) } else { vec![(false, text.to_string())] }
+    }
+
+    fn span_size(&self, small: bool) -> f32 {
+        if small { self.size * SMALL_CAPS_SCALE } else { self.size }
+    }
+
+    /// Width `text` takes when written, in mm.
+    pub fn width(&self, layout: &Layout, text: &str) -> f32 {
+        let glyphs = |span: &str, size: f32| -> f32 {
+            script_segments(span, self.east_asian.is_some())
+                .into_iter()
+                .map(|(east_asian, segment)| {
+                    let font = if east_asian { self.east_asian.unwrap_or(self.font) } else { self.font };
+                    layout.fonts.text_width(font, segment, size)
+                })
+                .sum()
+        };
+        self.spans(text).iter().map(|(small, span)| glyphs(span, self.span_size(*small)) + self.tracking * span.chars().count() as f32).sum()
+    }
+
+    /// Writes `text` at `x` on the line whose baseline is `y` and returns
+    /// its width in mm.
+    fn write(&self, layout: &Layout, text: &str, x: f32, y: f32) -> f32 {
+        let (mut cursor, y) = (x, y + self.rise);
+        for (small, span) in self.spans(text) {
+            let size = self.span_size(small);
+            cursor += layout.draw_text(&span, (cursor, y), size, self.font, self.tracking);
+        }
+        cursor - x
+    }
+}
+
+/// Repeats `leader` across the gap from `from` to `to` on the baseline `y`,
+/// ending flush with `to`.
+fn draw_leader(layout: &Layout, style: &TextStyle, leader: char, from: f32, to: f32, y: f32) {
+    let leader = leader.to_string();
+    let width = layout.fonts.text_width(style.font, &leader, style.size);
+    let count = ((to - from) / width).floor().max(0.0) as usize;
+    if count > 0 {
+        let x = to - count as f32 * width;
+        layout.draw_text(&leader.repeat(count), (x, y + style.rise), style.size, style.font, 0.0);
+    }
+}
+
+/// Writes `line` starting at `x` on the baseline `y`, moving each tab to the
+/// next stop from `origin`. Returns the width written, in mm.
+pub fn use_tabbed_text(layout: &Layout, line: &str, style: &TextStyle, (x, y): (f32, f32), origin: f32) -> f32 {
+    let tabs = TabStops { explicit: style.tab_stops, origin, interval: layout.tab_stop };
+    let mut cursor = x;
+    for (i, segment) in line.split('\t').enumerate() {
+        if i > 0 {
+            let (start, stop) = tabs.start(cursor, segment, |text| style.width(layout, text));
+            if let Some(leader) = stop.and_then(|stop| stop.leader) {
+                draw_leader(layout, style, leader, cursor, start, y);
+            }
+            cursor = start;
+        }
+        if !segment.is_empty() {
+            cursor += style.write(layout, segment, cursor, y);
+        }
+    }
+    cursor - x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use crate::tests::{archive, laid_out};
+    use docx_rs::{Docx, Run, Tab};
+
+    fn settings(xml: &str) -> Vec<u8> {
+        archive(&[("word/settings.xml", xml)])
+    }
+
+    #[test]
+    fn reads_the_default_tab_stop() {
+        let tab_stop = read_default_tab_stop(&settings("<w:settings xmlns:w=\"w\"><w:defaultTabStop w:val=\"720\"/></w:settings>")).unwrap();
+        assert!((tab_stop - 720.0 * TWIP_TO_MM).abs() < 1e-4);
+        //A zero interval and a missing setting fall back to Word's half inch
+        assert_eq!(read_default_tab_stop(&settings("<w:settings xmlns:w=\"w\"><w:defaultTabStop w:val=\"0\"/></w:settings>")).unwrap(), DEFAULT_TAB_STOP);
+        assert_eq!(read_default_tab_stop(&settings("<w:settings xmlns:w=\"w\"/>")).unwrap(), DEFAULT_TAB_STOP);
+        assert_eq!(read_default_tab_stop(&archive(&[("word/document.xml", "<w:document/>")])).unwrap(), DEFAULT_TAB_STOP);
+    }
+
+    #[test]
+    fn starts_text_from_the_tab_position() {
+        //Every character 2 mm wide
+        let width = |text: &str| text.chars().count() as f32 * 2.0;
+        let defaults = TabStops::defaults(10.0);
+        assert_eq!(defaults.start(3.0, "a", width).0, 10.0);
+        assert!(defaults.start(3.0, "a", width).1.is_none());
+        //A tab reached exactly at a stop moves on to the next one
+        assert_eq!(defaults.start(10.0, "a", width).0, 20.0);
+
+        let stop = |position, alignment| TabStop { position, alignment, leader: None };
+        let explicit = [stop(30.0, TabAlignment::Right), stop(60.0, TabAlignment::Decimal)];
+        let tabs = TabStops { explicit: &explicit, origin: 5.0, interval: 10.0 };
+        let (start, stop) = tabs.start(0.0, "abc", width);
+        assert_eq!(start, 29.0);
+        assert_eq!(stop.map(|stop| stop.alignment), Some(TabAlignment::Right));
+        assert_eq!(tabs.start(40.0, "12.50", width).0, 61.0);
+        //Text too wide for the gap starts at the cursor
+        assert_eq!(tabs.start(36.0, "123456789.5", width).0, 47.0);
+        assert_eq!(tabs.start(36.0, "123456789012345.5", width).0, 36.0);
+        //Past the last explicit stop come the default ones from the origin
+        let (start, stop) = tabs.start(66.0, "a", width);
+        assert_eq!(start, 75.0);
+        assert!(stop.is_none());
+    }
+
+    #[test]
+    fn fills_the_gap_before_a_stop_with_its_leader() {
+        let stop = Tab::new().val(TabValueType::Right).leader(TabLeaderType::Dot).pos(4000);
+        let paragraph = Paragraph::new().add_tab(stop).add_run(Run::new().add_text("Chapter").add_tab().add_text("12"));
+        let config = Config::new("leader.docx", "leader.pdf");
+        let runs = laid_out(&Docx::new().add_paragraph(paragraph), &config);
+        let texts: Vec<&str> = runs.iter().map(|(text, ..)| text.as_str()).collect();
+        assert_eq!(texts.len(), 3, "{:?}", texts);
+        assert_eq!((texts[0], texts[2]), ("Chapter", "12"));
+        assert!(texts[1].len() > 3 && texts[1].chars().all(|c| c == '.'));
+
+        //The dots run from after the text right up to the number, which ends at the stop
+        let (chapter, dots, number) = (&runs[0], &runs[1], &runs[2]);
+        let dot_width = dots.3 / texts[1].len() as f32;
+        assert!(dots.1 >= chapter.1 + chapter.3 && dots.1 < chapter.1 + chapter.3 + dot_width);
+        assert!((dots.1 + dots.3 - number.1).abs() < 0.01);
+        assert!((number.1 + number.3 - (config.margin + 4000.0 * TWIP_TO_MM)).abs() < 0.01);
+    }
+
+    #[test]
+    fn lines_numbers_up_on_their_decimal_points() {
+        let numbers = ["1234.50", "3.75", "12045.2", "17"];
+        let docx = numbers.iter().fold(Docx::new(), |docx, number| {
+            let stop = Tab::new().val(TabValueType::Decimal).pos(5000);
+            docx.add_paragraph(Paragraph::new().add_tab(stop).add_run(Run::new().add_tab().add_text(*number)))
+        });
+        let config = Config::new("decimal.docx", "decimal.pdf");
+        let runs = laid_out(&docx, &config);
+        assert_eq!(runs.iter().map(|(text, ..)| text.as_str()).collect::<Vec<_>>(), numbers);
+        let stop = config.margin + 5000.0 * TWIP_TO_MM;
+        //Digits are all as wide, so the point of each number is that many digits from its start
+        let digit = runs[3].3 / 2.0;
+        for (text, x, ..) in &runs[..3] {
+            let point = x + text.find('.').unwrap() as f32 * digit;
+            assert!((point - stop).abs() < 0.01, "{} has its point at {}", text, point);
+        }
+        //A number without a point ends at the stop
+        assert!((runs[3].1 + runs[3].3 - stop).abs() < 0.01);
+    }
+}
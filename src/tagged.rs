@@ -0,0 +1,239 @@
+//! Tagged PDF. With `--tagged`, the body is recorded as structure elements
+//! while it is laid out: headings, paragraphs, list items, tables, figures
+//! and notes. Every stretch of text drawn inside one is marked as its
+//! content with an MCID. printpdf cannot write a structure tree, so the
+//! tree is added to the saved file under a `Document` root.
+//!
+//! Images with alternative text carry it in the property list of the
+//! marked content around them, tagged or not, and on their `Figure`.
+
+use std::collections::BTreeMap;
+
+use printpdf::lopdf::content::Operation;
+use printpdf::lopdf::{self, Dictionary, Document, Object, ObjectId};
+use printpdf::PdfLayerReference;
+
+use crate::ConversionError;
+use crate::attachments::text_string;
+
+/// A structure element and the marked content it owns.
+#[derive(Debug)]
+struct Element {
+    role: String,
+    /// Index of the enclosing element, or `None` under the document root.
+    parent: Option<usize>,
+    /// Page and MCID of each marked-content sequence, in order.
+    content: Vec<(usize, usize)>,
+    alt: Option<String>,
+}
+
+/// The structure elements recorded during layout.
+#[derive(Debug, Default)]
+pub struct StructureTree {
+    enabled: bool,
+    elements: Vec<Element>,
+    /// The elements open, innermost last.
+    open: Vec<usize>,
+    /// The list consecutive list items are gathered into.
+    list: Option<usize>,
+    /// Next MCID to give out, by page.
+    next_mcid: Vec<usize>,
+}
+
+impl StructureTree {
+    pub fn new(enabled: bool) -> Self {
+        StructureTree { enabled, ..StructureTree::default() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Opens an element with the standard structure type `role` inside the
+    /// element open, if any. List items (`LI`) in a row share one `L`.
+    pub fn begin(&mut self, role: &str) {
+        if !self.enabled {
+            return;
+        }
+        let mut parent = self.open.last().copied();
+        if role == "LI" && parent.is_none() {
+            let list = *self.list.get_or_insert_with(|| {
+                self.elements.push(Element { role: "L".to_string(), parent: None, content: Vec::new(), alt: None });
+                self.elements.len() - 1
+            });
+            parent = Some(list);
+        } else if parent.is_none() {
+            self.list = None;
+        }
+        self.elements.push(Element { role: role.to_string(), parent, content: Vec::new(), alt: None });
+        self.open.push(self.elements.len() - 1);
+    }
+
+    /// Closes the innermost open element.
+    pub fn end(&mut self) {
+        self.open.pop();
+    }
+
+    /// Opens a `Figure` described by `alt` and starts the marked content of
+    /// its image, returning whether it was started. Untagged, only images
+    /// with alternative text are marked.
+    pub fn begin_figure(&mut self, layer: &PdfLayerReference, page: usize, alt: Option<&str>) -> bool {
+        if !self.enabled {
+            let Some(alt) = alt else { return false };
+            let mut properties = Dictionary::new();
+            properties.set("Alt", text_string(alt));
+            layer.add_operation(Operation::new("BDC", vec![Object::Name(b"Figure".to_vec()), Object::Dictionary(properties)]));
+            return true;
+        }
+        self.begin("Figure");
+        if let Some(&index) = self.open.last() {
+            self.elements[index].alt = alt.map(str::to_string);
+        }
+        self.begin_content(layer, page)
+    }
+
+    /// Ends the image and closes the figure `begin_figure` opened.
+    pub fn end_figure(&mut self, layer: &PdfLayerReference, marked: bool) {
+        if marked {
+            StructureTree::end_content(layer);
+        }
+        self.end();
+    }
+
+    /// Starts a marked-content sequence on `layer` of `page` belonging to
+    /// the innermost open element, returning whether one was started.
+    pub fn begin_content(&mut self, layer: &PdfLayerReference, page: usize) -> bool {
+        let Some(&index) = self.open.last() else { return false };
+        if self.next_mcid.len() <= page {
+            self.next_mcid.resize(page + 1, 0);
+        }
+        let mcid = self.next_mcid[page];
+        self.next_mcid[page] += 1;
+        let element = &mut self.elements[index];
+        element.content.push((page, mcid));
+        let mut properties = Dictionary::new();
+        properties.set("MCID", Object::Integer(mcid as i64));
+        if let Some(alt) = &element.alt {
+            properties.set("Alt", text_string(alt));
+        }
+        let role = Object::Name(element.role.as_bytes().to_vec());
+        layer.add_operation(Operation::new("BDC", vec![role, Object::Dictionary(properties)]));
+        true
+    }
+
+    /// Ends the sequence `begin_content` started.
+    pub fn end_content(layer: &PdfLayerReference) {
+        layer.add_operation(Operation::new("EMC", Vec::new()));
+    }
+}
+
+/// Adds the structure tree of `tree` to `pdf` and marks the file as tagged.
+pub fn write_structure(pdf: &[u8], tree: &StructureTree) -> Result<Vec<u8>, ConversionError> {
+    let pdf_error = |e: lopdf::Error| ConversionError::Pdf(e.to_string());
+    let mut document = Document::load_mem(pdf).map_err(pdf_error)?;
+    let pages: Vec<ObjectId> = document.get_pages().into_values().collect();
+
+    let root_id = document.new_object_id();
+    let document_id = document.new_object_id();
+    let ids: Vec<ObjectId> = tree.elements.iter().map(|_| document.new_object_id()).collect();
+    let parent_id = |element: &Element| element.parent.map_or(document_id, |parent| ids[parent]);
+
+    //Each page's marked content, by MCID, points back to its element
+    let mut parents: BTreeMap<usize, BTreeMap<usize, ObjectId>> = BTreeMap::new();
+    let mut children: Vec<Vec<Object>> = vec![Vec::new(); tree.elements.len()];
+    let mut top_level = Vec::new();
+    for (index, element) in tree.elements.iter().enumerate() {
+        match element.parent {
+            Some(parent) => children[parent].push(Object::Reference(ids[index])),
+            None => top_level.push(Object::Reference(ids[index])),
+        }
+        for &(page, mcid) in &element.content {
+            parents.entry(page).or_default().insert(mcid, ids[index]);
+        }
+    }
+
+    for (index, element) in tree.elements.iter().enumerate() {
+        let mut kids = std::mem::take(&mut children[index]);
+        for &(page, mcid) in &element.content {
+            let Some(&page_id) = pages.get(page) else { continue };
+            let mut reference = Dictionary::new();
+            reference.set("Type", Object::Name(b"MCR".to_vec()));
+            reference.set("Pg", Object::Reference(page_id));
+            reference.set("MCID", Object::Integer(mcid as i64));
+            kids.push(Object::Dictionary(reference));
+        }
+        let mut dictionary = Dictionary::new();
+        dictionary.set("Type", Object::Name(b"StructElem".to_vec()));
+        dictionary.set("S", Object::Name(element.role.as_bytes().to_vec()));
+        dictionary.set("P", Object::Reference(parent_id(element)));
+        dictionary.set("K", Object::Array(kids));
+        if let Some(alt) = &element.alt {
+            dictionary.set("Alt", text_string(alt));
+        }
+        document.objects.insert(ids[index], Object::Dictionary(dictionary));
+    }
+
+    let mut root_element = Dictionary::new();
+    root_element.set("Type", Object::Name(b"StructElem".to_vec()));
+    root_element.set("S", Object::Name(b"Document".to_vec()));
+    root_element.set("P", Object::Reference(root_id));
+    root_element.set("K", Object::Array(top_level));
+    document.objects.insert(document_id, Object::Dictionary(root_element));
+
+    let mut numbers = Vec::new();
+    for (page, mcids) in &parents {
+        numbers.push(Object::Integer(*page as i64));
+        //MCIDs are handed out from 0 without gaps, so the array index is the MCID
+        numbers.push(Object::Array(mcids.values().map(|id| Object::Reference(*id)).collect()));
+    }
+    let mut parent_tree = Dictionary::new();
+    parent_tree.set("Nums", Object::Array(numbers));
+    let mut root = Dictionary::new();
+    root.set("Type", Object::Name(b"StructTreeRoot".to_vec()));
+    root.set("K", Object::Reference(document_id));
+    root.set("ParentTree", Object::Dictionary(parent_tree));
+    root.set("ParentTreeNextKey", Object::Integer(pages.len() as i64));
+    document.objects.insert(root_id, Object::Dictionary(root));
+
+    for (index, page_id) in pages.iter().enumerate() {
+        let page = document.get_dictionary_mut(*page_id).map_err(pdf_error)?;
+        page.set("StructParents", Object::Integer(index as i64));
+    }
+    let catalog_id = document.trailer.get(b"Root").and_then(Object::as_reference).map_err(pdf_error)?;
+    let catalog = document.get_dictionary_mut(catalog_id).map_err(pdf_error)?;
+    let mut mark_info = Dictionary::new();
+    mark_info.set("Marked", Object::Boolean(true));
+    catalog.set("MarkInfo", mark_info);
+    catalog.set("StructTreeRoot", Object::Reference(root_id));
+
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use crate::tests::sample_pdf;
+
+    #[test]
+    fn tags_paragraphs_under_the_structure_tree_root() {
+        let mut config = Config::new("tagged.docx", "tagged.pdf");
+        config.tagged = true;
+        let document = Document::load_mem(&sample_pdf("Tagged text", &config)).unwrap();
+        let catalog = document.catalog().unwrap();
+        let marked = catalog.get(b"MarkInfo").and_then(Object::as_dict).and_then(|info| info.get(b"Marked")).and_then(Object::as_bool);
+        assert!(marked.unwrap());
+
+        let root = catalog.get(b"StructTreeRoot").and_then(Object::as_reference).and_then(|id| document.get_dictionary(id)).unwrap();
+        assert!(root.type_is(b"StructTreeRoot"));
+        let top = root.get(b"K").and_then(Object::as_reference).and_then(|id| document.get_dictionary(id)).unwrap();
+        assert_eq!(top.get(b"S").and_then(Object::as_name).unwrap(), b"Document");
+        let kids = top.get(b"K").and_then(Object::as_array).unwrap();
+        let paragraph = document.get_dictionary(kids[0].as_reference().unwrap()).unwrap();
+        assert_eq!(paragraph.get(b"S").and_then(Object::as_name).unwrap(), b"P");
+        let content = paragraph.get(b"K").and_then(Object::as_array).unwrap();
+        assert_eq!(content[0].as_dict().and_then(|reference| reference.get(b"MCID")).and_then(Object::as_i64).unwrap(), 0);
+    }
+}
@@ -0,0 +1,175 @@
+//! Page templates such as letterheads. The first page of another PDF is
+//! copied in as a form XObject, which every generated page paints,
+//! stretched to its size, before its own content.
+
+use std::fs;
+
+use printpdf::lopdf::{self, Dictionary, Document, Object, ObjectId, Stream};
+
+use crate::ConversionError;
+
+/// Resource name the template is painted under.
+const TEMPLATE_NAME: &str = "WordPdfTemplate";
+
+/// Reads the PDF at `path`, rejecting files without a page to draw.
+pub fn read_template(path: &str) -> Result<Document, ConversionError> {
+    let data = fs::read(path)?;
+    let invalid = |reason: String| ConversionError::InvalidInput(format!("{} is not a usable template: {}", path, reason));
+    let template = Document::load_mem(&data).map_err(|e| invalid(e.to_string()))?;
+    if template.get_pages().is_empty() {
+        return Err(invalid("it has no pages".to_string()));
+    }
+    Ok(template)
+}
+
+/// `[left, bottom, right, top]` of the page `page_id`, which may be
+/// inherited from the page tree.
+pub fn media_box(document: &Document, page_id: ObjectId) -> Option<[f32; 4]> {
+    let mut node = document.get_dictionary(page_id).ok()?;
+    loop {
+        if let Ok(Object::Array(values)) = node.get(b"MediaBox").and_then(|value| document.dereference(value)).map(|(_, value)| value) {
+            let numbers: Vec<f32> = values.iter().filter_map(|value| value.as_float().ok()).collect();
+            return numbers.try_into().ok();
+        }
+        node = node.get(b"Parent").and_then(Object::as_reference).and_then(|id| document.get_dictionary(id)).ok()?;
+    }
+}
+
+/// A copy of the dictionary `object` holds or refers to, or an empty one.
+fn dictionary_copy(document: &Document, object: Option<&Object>) -> Dictionary {
+    match object.map(|object| document.dereference(object)) {
+        Some(Ok((_, Object::Dictionary(dictionary)))) => dictionary.clone(),
+        _ => Dictionary::new(),
+    }
+}
+
+/// Lists the optional content groups `layers` in the catalog's
+/// `/OCProperties` as shown, so viewers draw the content they mark.
+fn register_layers(document: &mut Document, layers: &[ObjectId]) -> Result<(), lopdf::Error> {
+    let catalog_id = document.trailer.get(b"Root").and_then(Object::as_reference)?;
+    let catalog = document.get_dictionary_mut(catalog_id)?;
+    let Ok(Object::Dictionary(properties)) = catalog.get_mut(b"OCProperties") else { return Ok(()) };
+    let references = layers.iter().map(|id| Object::Reference(*id));
+    if let Ok(Object::Array(groups)) = properties.get_mut(b"OCGs") {
+        groups.extend(references.clone());
+    }
+    if let Ok(Object::Dictionary(defaults)) = properties.get_mut(b"D")
+        && let Ok(Object::Array(shown)) = defaults.get_mut(b"ON")
+    {
+        shown.extend(references);
+    }
+    Ok(())
+}
+
+/// Draws the first page of `template` behind the content of every page of `pdf`.
+pub fn add_template(pdf: &[u8], mut template: Document) -> Result<Vec<u8>, ConversionError> {
+    let pdf_error = |e: lopdf::Error| ConversionError::Pdf(e.to_string());
+    let mut document = Document::load_mem(pdf).map_err(pdf_error)?;
+
+    //Renumbered past the document's objects so both can share one table
+    template.renumber_objects_with(document.max_id + 1);
+    let invalid = |reason: &str| ConversionError::InvalidInput(format!("Unusable template: {}", reason));
+    let template_page = *template.get_pages().values().next().ok_or_else(|| invalid("it has no pages"))?;
+    let bounds = media_box(&template, template_page).ok_or_else(|| invalid("its first page has no MediaBox"))?;
+    let content = template.get_page_content(template_page).map_err(pdf_error)?;
+    let (resources, inherited) = template.get_page_resources(template_page);
+    let resources = match (resources, inherited.first()) {
+        (Some(resources), _) => Object::Dictionary(resources.clone()),
+        (None, Some(id)) => Object::Reference(*id),
+        (None, None) => Object::Dictionary(Dictionary::new()),
+    };
+    //The template's layers, which its content may be marked as belonging to
+    let layers: Vec<ObjectId> = template
+        .objects
+        .iter()
+        .filter(|(_, object)| object.as_dict().and_then(|dict| dict.get(b"Type")).and_then(Object::as_name).is_ok_and(|name| name == b"OCG"))
+        .map(|(id, _)| *id)
+        .collect();
+    document.max_id = document.max_id.max(template.max_id);
+    document.objects.extend(template.objects);
+    register_layers(&mut document, &layers).map_err(pdf_error)?;
+
+    let mut form_dict = Dictionary::new();
+    form_dict.set("Type", Object::Name(b"XObject".to_vec()));
+    form_dict.set("Subtype", Object::Name(b"Form".to_vec()));
+    form_dict.set("BBox", Object::Array(bounds.iter().map(|value| Object::Real(*value)).collect()));
+    form_dict.set("Resources", resources);
+    let mut form = Stream::new(form_dict, content);
+    form.compress().map_err(pdf_error)?;
+    let form_id = document.add_object(form);
+
+    let [left, bottom, right, top] = bounds;
+    for page_id in document.get_pages().into_values() {
+        let Some([page_left, page_bottom, page_right, page_top]) = media_box(&document, page_id) else { continue };
+        let (scale_x, scale_y) = ((page_right - page_left) / (right - left), (page_top - page_bottom) / (top - bottom));
+        let stamp = format!(
+            "q {} 0 0 {} {} {} cm /{} Do Q\n",
+            scale_x,
+            scale_y,
+            page_left - left * scale_x,
+            page_bottom - bottom * scale_y,
+            TEMPLATE_NAME,
+        );
+        let stamp_id = document.add_object(Stream::new(Dictionary::new(), stamp.into_bytes()));
+
+        //Painted first, so the page's own content covers it
+        let mut contents = vec![Object::Reference(stamp_id)];
+        contents.extend(document.get_page_contents(page_id).into_iter().map(Object::Reference));
+        let page = document.get_dictionary(page_id).map_err(pdf_error)?;
+        let mut page_resources = dictionary_copy(&document, page.get(b"Resources").ok());
+        let mut xobjects = dictionary_copy(&document, page_resources.get(b"XObject").ok());
+        xobjects.set(TEMPLATE_NAME, Object::Reference(form_id));
+        page_resources.set("XObject", xobjects);
+
+        let page = document.get_dictionary_mut(page_id).map_err(pdf_error)?;
+        page.set("Contents", Object::Array(contents));
+        page.set("Resources", page_resources);
+    }
+    //The template's own catalog and page tree are no longer reachable
+    document.prune_objects();
+
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use docx_rs::{Docx, Paragraph, Run};
+
+    use super::*;
+    use crate::tests::{sample_pdf, temp_directory};
+    use crate::{Config, convert_parsed};
+
+    #[test]
+    fn paints_the_template_behind_every_page() {
+        let directory = temp_directory("template", &[]);
+        let path = directory.join("letterhead.pdf");
+        fs::write(&path, sample_pdf("Letterhead", &Config::new("letterhead.docx", "letterhead.pdf"))).unwrap();
+        assert!(read_template(&directory.join("missing.pdf").to_string_lossy()).is_err());
+
+        let mut docx = Docx::new();
+        for _ in 0..40 {
+            docx = docx.add_paragraph(Paragraph::new().add_run(Run::new().add_text("Body")));
+        }
+        let mut config = Config::new("templated.docx", "templated.pdf");
+        config.template = Some(path.to_string_lossy().into_owned());
+        let document = Document::load_mem(&convert_parsed(&docx, &[], &config).unwrap()).unwrap();
+        let pages = document.get_pages();
+        assert!(pages.len() > 1);
+        for page_id in pages.into_values() {
+            let contents = document.get_page_contents(page_id);
+            assert!(contents.len() > 1);
+            //The stamp comes first, so the page's own content is drawn over it
+            let stamp = document.get_object(contents[0]).and_then(Object::as_stream).unwrap();
+            assert!(String::from_utf8_lossy(&stamp.content).ends_with(&format!("/{} Do Q\n", TEMPLATE_NAME)));
+            let (resources, _) = document.get_page_resources(page_id);
+            let xobjects = dictionary_copy(&document, resources.unwrap().get(b"XObject").ok());
+            let form = document.dereference(xobjects.get(TEMPLATE_NAME.as_bytes()).unwrap()).unwrap().1.as_stream().unwrap();
+            assert_eq!(form.dict.get(b"Subtype").and_then(Object::as_name_str).unwrap(), "Form");
+            //The letterhead's own text is what the form draws
+            let drawn = form.decompressed_content().unwrap_or_else(|_| form.content.clone());
+            assert!(drawn.windows(2).any(|window| window == b"Tj"));
+        }
+    }
+}
@@ -0,0 +1,441 @@
+//! Text measurement and line breaking.
+
+use printpdf::TextRenderingMode;
+
+use crate::PT_TO_MM;
+use crate::tabs::TabStops;
+
+/// Advance widths of the printable ASCII range (space to `~`) in Helvetica,
+/// in thousandths of an em, taken from the standard AFM metrics.
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, // space - /
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, // 0 - ?
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778, // @ - O
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556, // P - _
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556, // ` - o
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584, // p - ~
+];
+
+/// The same for Helvetica-Bold.
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, // space - /
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611, // 0 - ?
+    975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722, 778, // @ - O
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556, // P - _
+    333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611, // ` - o
+    611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584, // p - ~
+];
+
+/// Width used for characters outside the ASCII tables.
+const DEFAULT_WIDTH: u16 = 556;
+
+/// Characters WinAnsiEncoding gives a code to outside the ASCII and
+/// Latin-1 ranges.
+const WIN_ANSI_EXTRAS: &str = "€‚ƒ„…†‡ˆ‰Š‹ŒŽ‘’“”•–—˜™š›œžŸ";
+
+/// Whether the built-in fonts can show `c`. They are written with
+/// WinAnsiEncoding, and characters it has no code for are dropped.
+pub fn is_win_ansi(c: char) -> bool {
+    (' '..='~').contains(&c) || ('\u{A0}'..='\u{FF}').contains(&c) || WIN_ANSI_EXTRAS.contains(c)
+}
+
+/// Whether `c` is a Chinese, Japanese or Korean character, set in the
+/// East Asian font rather than Helvetica.
+pub fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x1100..=0x11FF // Hangul Jamo
+        | 0x2E80..=0x2FDF // radicals
+        | 0x3000..=0x33FF // punctuation, kana, Bopomofo, compatibility Jamo and enclosed forms
+        | 0x3400..=0x4DBF // ideographs, extension A
+        | 0x4E00..=0x9FFF // unified ideographs
+        | 0xA960..=0xA97F | 0xAC00..=0xD7FF // Hangul
+        | 0xF900..=0xFAFF // compatibility ideographs
+        | 0xFE10..=0xFE1F // vertical forms
+        | 0xFE30..=0xFE4F // compatibility forms
+        | 0xFF00..=0xFFEF // half and full width forms
+        | 0x20000..=0x3134F // ideographs, extensions B onwards
+    )
+}
+
+/// Width of `text` in mm when set in Helvetica at `font_size` points, with
+/// East Asian characters a full em wide.
+pub fn text_width(text: &str, font_size: f32) -> f32 {
+    afm_width(&HELVETICA_WIDTHS, text, font_size)
+}
+
+/// Like `text_width`, in Helvetica-Bold.
+pub fn bold_text_width(text: &str, font_size: f32) -> f32 {
+    afm_width(&HELVETICA_BOLD_WIDTHS, text, font_size)
+}
+
+fn afm_width(widths: &[u16; 95], text: &str, font_size: f32) -> f32 {
+    let units: u32 = text
+        .chars()
+        .map(|c| match c as u32 {
+            code @ 32..=126 => widths[(code - 32) as usize] as u32,
+            _ if is_cjk(c) => 1000,
+            _ => DEFAULT_WIDTH as u32,
+        })
+        .sum();
+    units as f32 / 1000.0 * font_size * PT_TO_MM
+}
+
+/// How a paragraph's words are distributed over lines.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WrapAlgorithm {
+    /// Fill each line as far as it goes before starting the next.
+    Greedy,
+    /// Choose breaks that minimise the squared slack of every line but the last.
+    Balanced,
+}
+
+impl std::str::FromStr for WrapAlgorithm {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "greedy" => Ok(WrapAlgorithm::Greedy),
+            "balanced" => Ok(WrapAlgorithm::Balanced),
+            other => Err(format!("Unknown wrap algorithm: {}", other)),
+        }
+    }
+}
+
+/// How the glyphs of text are painted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextRenderMode {
+    Fill,
+    /// Only the outlines of the glyphs, leaving them hollow.
+    Stroke,
+    /// The glyphs filled and their outlines stroked over the fill.
+    Outline,
+}
+
+impl TextRenderMode {
+    pub fn rendering_mode(self) -> TextRenderingMode {
+        match self {
+            TextRenderMode::Fill => TextRenderingMode::Fill,
+            TextRenderMode::Stroke => TextRenderingMode::Stroke,
+            TextRenderMode::Outline => TextRenderingMode::FillStroke,
+        }
+    }
+}
+
+impl std::str::FromStr for TextRenderMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "fill" => Ok(TextRenderMode::Fill),
+            "stroke" => Ok(TextRenderMode::Stroke),
+            "outline" => Ok(TextRenderMode::Outline),
+            other => Err(format!("Unknown text render mode: {}", other)),
+        }
+    }
+}
+
+/// The words of `content`. Tabs stay inside their word rather than separating
+/// words, so the renderer can move them to a tab stop.
+pub fn words(content: &str) -> impl Iterator<Item = &str> {
+    content.split(|c: char| c.is_whitespace() && c != '\t').filter(|word| !word.is_empty())
+}
+
+/// Characters a line may not start with: closing brackets and East Asian
+/// stops, and the small kana and marks that belong with the character
+/// before them.
+const NO_BREAK_BEFORE: &str = "、。，．・：；？！）］｝」』】〕〉》〙〗ー…‥ぁぃぅぇぉっゃゅょゎァィゥェォッャュョヮヵヶ々〻゛゜ゝゞヽヾ";
+
+/// Characters a line may not end with: opening brackets.
+const NO_BREAK_AFTER: &str = "（［｛「『【〔〈《〘〖([{";
+
+/// Whether a line may break between `before` and `after`, two characters of
+/// one word. East Asian text has no spaces, so it breaks between any two
+/// characters where either is East Asian, except around brackets and
+/// punctuation.
+fn breaks_between(before: char, after: char) -> bool {
+    (is_cjk(before) || is_cjk(after))
+        && !NO_BREAK_BEFORE.contains(after)
+        && !NO_BREAK_AFTER.contains(before)
+        && !after.is_ascii_punctuation()
+        && before != '\t'
+        && after != '\t'
+}
+
+/// `word` split where a line may break inside it, with the offset of each
+/// part in the word.
+fn word_parts(word: &str) -> Vec<(usize, &str)> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut chars = word.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        if let Some(&(i, next)) = chars.peek()
+            && breaks_between(c, next)
+        {
+            parts.push((start, &word[start..i]));
+            start = i;
+        }
+    }
+    parts.push((start, &word[start..]));
+    parts
+}
+
+/// What a piece is joined to the next one with when they share a line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Join {
+    /// A space, between words.
+    Space,
+    /// Nothing, inside a word hyphenation splits; a break here needs a hyphen.
+    Hyphen,
+    /// Nothing, between East Asian characters, which break without one.
+    Direct,
+}
+
+/// A word, or a part of one that a line may break after.
+struct Piece<'a> {
+    text: &'a str,
+    /// Offset of the text in the content being wrapped.
+    start: usize,
+    width: f32,
+    join: Join,
+}
+
+/// Measures text for `wrap_text`: the width in mm, letter spacing
+/// included, of text found at a byte offset in the content being wrapped.
+/// The space between two words is measured at the blank after the first,
+/// and a hyphen at the last character before it.
+pub type Measure<'m> = dyn Fn(usize, &str) -> f32 + 'm;
+
+/// Hyphenates words for `wrap_text`: the byte offsets where a hyphen may
+/// break a word found at a byte offset in the content being wrapped, so
+/// each word can be hyphenated in the language of the run it comes from.
+pub type Hyphenate<'h> = dyn Fn(usize, &str) -> Vec<usize> + 'h;
+
+/// `Hyphenate` for text that is never hyphenated.
+pub fn no_hyphenation(_: usize, _: &str) -> Vec<usize> {
+    Vec::new()
+}
+
+/// Breaks `content` into lines no wider than `max_width` mm. A single word
+/// wider than the line is placed on its own line rather than split, unless
+/// it holds East Asian text or `hyphenate` offers a break point inside it.
+/// Tabs are measured to the `tabs` they move to, placed as if each line
+/// started at 0.
+pub fn wrap_text(
+    content: &str,
+    max_width: f32,
+    measure: &Measure,
+    tabs: TabStops,
+    algorithm: WrapAlgorithm,
+    hyphenate: &Hyphenate,
+) -> Vec<String> {
+    let mut pieces = Vec::new();
+    for word in words(content) {
+        let word_start = word.as_ptr() as usize - content.as_ptr() as usize;
+        let parts = word_parts(word);
+        for (index, &(offset, part)) in parts.iter().enumerate() {
+            //Hyphenation patterns are for alphabetic words
+            let points = if part.chars().any(is_cjk) { Vec::new() } else { hyphenate(word_start + offset, part) };
+            let mut start = 0;
+            for end in points.into_iter().chain(std::iter::once(part.len())) {
+                let text = &part[start..end];
+                let join = match end < part.len() {
+                    true => Join::Hyphen,
+                    false if index + 1 < parts.len() => Join::Direct,
+                    false => Join::Space,
+                };
+                let start_in_content = word_start + offset + start;
+                pieces.push(Piece { text, start: start_in_content, width: measure(start_in_content, text), join });
+                start = end;
+            }
+        }
+    }
+    if pieces.is_empty() {
+        return Vec::new();
+    }
+    let metrics = Metrics { measure, tabs };
+
+    let breaks = match algorithm {
+        WrapAlgorithm::Greedy => greedy_breaks(&pieces, &metrics, max_width),
+        WrapAlgorithm::Balanced => balanced_breaks(&pieces, &metrics, max_width),
+    };
+
+    let mut lines = Vec::with_capacity(breaks.len());
+    let mut start = 0;
+    for end in breaks {
+        let mut line = String::new();
+        for (i, piece) in pieces[start..end].iter().enumerate() {
+            if i > 0 && pieces[start + i - 1].join == Join::Space {
+                line.push(' ');
+            }
+            line.push_str(piece.text);
+        }
+        if pieces[end - 1].join == Join::Hyphen {
+            line.push('-');
+        }
+        lines.push(line);
+        start = end;
+    }
+    lines
+}
+
+struct Metrics<'a> {
+    measure: &'a Measure<'a>,
+    tabs: TabStops<'a>,
+}
+
+impl Metrics<'_> {
+    /// Gap inserted before `pieces[i]` when it shares a line with its predecessor.
+    fn gap(&self, pieces: &[Piece], i: usize) -> f32 {
+        let previous = &pieces[i - 1];
+        match previous.join {
+            Join::Space => (self.measure)(previous.start + previous.text.len(), " "),
+            Join::Hyphen | Join::Direct => 0.0,
+        }
+    }
+
+    /// Where `piece` ends when it starts at `x`. A tab in it moves on to
+    /// its stop rather than taking a width of its own.
+    fn end(&self, piece: &Piece, x: f32) -> f32 {
+        if !piece.text.contains('\t') {
+            return x + piece.width;
+        }
+        let mut segments = piece.text.split('\t');
+        let first = segments.next().unwrap_or_default();
+        let mut cursor = x + (self.measure)(piece.start, first);
+        let mut offset = piece.start + first.len() + 1;
+        for segment in segments {
+            let width = |text: &str| (self.measure)(offset, text);
+            cursor = self.tabs.start(cursor, segment, width).0 + width(segment);
+            offset += segment.len() + 1;
+        }
+        cursor
+    }
+
+    /// Extra width when a line ends after `piece`.
+    fn line_end(&self, piece: &Piece) -> f32 {
+        match piece.text.char_indices().next_back() {
+            Some((last, _)) if piece.join == Join::Hyphen => (self.measure)(piece.start + last, "-"),
+            _ => 0.0,
+        }
+    }
+}
+
+/// Returns the exclusive end index of each line.
+fn greedy_breaks(pieces: &[Piece], metrics: &Metrics, max_width: f32) -> Vec<usize> {
+    let mut breaks = Vec::new();
+    let mut line_start = 0;
+    let mut line_width = 0.0;
+    for (i, piece) in pieces.iter().enumerate() {
+        if i > line_start {
+            let width = metrics.end(piece, line_width + metrics.gap(pieces, i));
+            if width + metrics.line_end(piece) > max_width {
+                breaks.push(i);
+                line_start = i;
+                line_width = metrics.end(piece, 0.0);
+            } else {
+                line_width = width;
+            }
+        } else {
+            line_width = metrics.end(piece, 0.0);
+        }
+    }
+    breaks.push(pieces.len());
+    breaks
+}
+
+/// Minimum-raggedness line breaking: a dynamic programme over break points
+/// where each line costs its squared slack, except the last which is free.
+/// Breaking inside a word adds a flat penalty so whole words are preferred.
+fn balanced_breaks(pieces: &[Piece], metrics: &Metrics, max_width: f32) -> Vec<usize> {
+    let hyphen_penalty = (max_width * 0.1).powi(2);
+    let count = pieces.len();
+    let mut cost = vec![f32::INFINITY; count + 1];
+    let mut previous = vec![0; count + 1];
+    cost[0] = 0.0;
+
+    for start in 0..count {
+        if cost[start].is_infinite() {
+            continue;
+        }
+        let mut line_width = 0.0;
+        for end in start + 1..=count {
+            let last = end - 1;
+            if last > start {
+                line_width += metrics.gap(pieces, last);
+            }
+            line_width = metrics.end(&pieces[last], line_width);
+            let total = line_width + metrics.line_end(&pieces[last]);
+            if total > max_width && last > start {
+                break;
+            }
+            let slack = (max_width - total).max(0.0);
+            let mut line_cost = if end == count { 0.0 } else { slack * slack };
+            if pieces[last].join == Join::Hyphen {
+                line_cost += hyphen_penalty;
+            }
+            if cost[start] + line_cost < cost[end] {
+                cost[end] = cost[start] + line_cost;
+                previous[end] = start;
+            }
+        }
+    }
+
+    let mut breaks = Vec::new();
+    let mut end = count;
+    while end > 0 {
+        breaks.push(end);
+        end = previous[end];
+    }
+    breaks.reverse();
+    breaks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrap(content: &str, max_width: f32, measure: &Measure) -> Vec<String> {
+        wrap_text(content, max_width, measure, TabStops::defaults(10.0), WrapAlgorithm::Greedy, &no_hyphenation)
+    }
+
+    #[test]
+    fn breaks_between_east_asian_characters() {
+        let one_each = |_, text: &str| text.chars().count() as f32;
+        assert_eq!(wrap("日本語の文章です", 3.0, &one_each), ["日本語", "の文章", "です"]);
+        //Not before a stop or closing bracket, nor after an opening one
+        assert_eq!(wrap("これは「本」です。", 3.0, &one_each), ["これは", "「本」", "です。"]);
+        assert_eq!(wrap("Rust言語とC言語", 6.0, &one_each), ["Rust言語", "とC言語"]);
+    }
+
+    #[test]
+    fn bold_is_wider() {
+        assert!(bold_text_width("bold", 12.0) > text_width("bold", 12.0));
+        assert_eq!(bold_text_width("1 2", 12.0), text_width("1 2", 12.0));
+    }
+
+    #[test]
+    fn measures_each_part_at_its_offset() {
+        //One unit a character, doubled from offset 8 on, as a second run set wider
+        let measure = |offset: usize, text: &str| {
+            text.char_indices().map(|(i, _)| if offset + i >= 8 { 2.0 } else { 1.0 }).sum::<f32>()
+        };
+        assert_eq!(wrap("aaa bbb ccc ddd", 11.0, &|_, text: &str| text.len() as f32), ["aaa bbb ccc", "ddd"]);
+        assert_eq!(wrap("aaa bbb ccc ddd", 11.0, &measure), ["aaa bbb", "ccc", "ddd"]);
+    }
+
+    #[test]
+    fn balanced_lines_are_more_even_than_greedy_ones() {
+        let one_each = |_, text: &str| text.chars().count() as f32;
+        let wrap_with = |algorithm| wrap_text("aaa bb cc ddddd", 6.0, &one_each, TabStops::defaults(10.0), algorithm, &no_hyphenation);
+        let greedy = wrap_with(WrapAlgorithm::Greedy);
+        let balanced = wrap_with(WrapAlgorithm::Balanced);
+        assert_eq!(greedy, ["aaa bb", "cc", "ddddd"]);
+        assert_eq!(balanced, ["aaa", "bb cc", "ddddd"]);
+        //The spread of widths over the lines before the last, which is free to be short
+        let spread = |lines: &[String]| {
+            let widths: Vec<usize> = lines[..lines.len() - 1].iter().map(|line| line.chars().count()).collect();
+            widths.iter().max().unwrap() - widths.iter().min().unwrap()
+        };
+        assert!(spread(&balanced) < spread(&greedy));
+    }
+}
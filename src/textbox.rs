@@ -0,0 +1,172 @@
+//! Text boxes: rectangular shapes whose paragraphs are laid out inside the
+//! shape's own frame rather than in the body flow. The shapes of a drawing
+//! canvas or group arrive as separate text boxes placed on the canvas.
+
+use docx_rs::{
+    DrawingData, DrawingPosition, DrawingPositionType, RelativeFromHType, RelativeFromVType, Run, RunChild, TextBox,
+    TextBoxContentChild,
+};
+
+use crate::caps::{display_text, is_small_caps};
+use crate::color::fill_color;
+use crate::markup::{Marker, TextBoxFrame, baseline_shift, run_markers};
+use crate::tabs::{TextStyle, paragraph_tab_stops, use_tabbed_text};
+use crate::{ASCENT, EMU_TO_MM, Layout, draw_rect, fill_rect, fill_shape, paragraph_runs};
+
+/// Word's default text box insets, in mm.
+const INSET_X: f32 = 2.54;
+const INSET_Y: f32 = 1.27;
+
+fn offset(position: &DrawingPosition) -> f32 {
+    match position {
+        DrawingPosition::Offset(emu) => *emu as f32 * EMU_TO_MM,
+        DrawingPosition::Align(_) => 0.0,
+    }
+}
+
+/// The text box drawings of `run`, each with the frame marker that follows it.
+pub fn run_text_boxes(run: &Run) -> Vec<(&TextBox, Option<TextBoxFrame>)> {
+    let mut frames = run_markers(run).into_iter().filter_map(|marker| match marker {
+        Marker::TextBox(frame) => Some(frame),
+        _ => None,
+    });
+    run.children
+        .iter()
+        .filter_map(|child| match child {
+            RunChild::Drawing(drawing) => match &drawing.data {
+                Some(DrawingData::TextBox(text_box)) => Some(text_box),
+                _ => None,
+            },
+            _ => None,
+        })
+        .map(|text_box| (text_box, frames.next()))
+        .collect()
+}
+
+/// Height `text_box` pushes the body flow down by: nothing for floating
+/// boxes and for canvas shapes after the first.
+pub fn flow_height(text_box: &TextBox, frame: Option<&TextBoxFrame>) -> f32 {
+    if text_box.position_type != DrawingPositionType::Inline {
+        return 0.0;
+    }
+    match frame {
+        Some(TextBoxFrame { canvas: Some(canvas), .. }) => if canvas.first { canvas.height } else { 0.0 },
+        Some(frame) => frame.height,
+        None => text_box.size.1 as f32 * EMU_TO_MM,
+    }
+}
+
+/// Top left corner of a drawing of `height` placed as `text_box` is.
+/// Floating drawings are placed at their anchored position and leave the
+/// body flow alone; inline ones sit at the cursor and push the following
+/// content down.
+fn place(layout: &mut Layout, text_box: &TextBox, height: f32) -> (f32, f32) {
+    let config = layout.config;
+    if text_box.position_type == DrawingPositionType::Inline {
+        layout.ensure_space(height);
+        let top = layout.y_position;
+        layout.y_position -= height;
+        (config.margin, top)
+    } else {
+        let x = match text_box.relative_from_h {
+            RelativeFromHType::Page => 0.0,
+            RelativeFromHType::Character => layout.last_line_end.map_or(config.margin, |(x, _)| x),
+            _ => config.margin,
+        };
+        let y = match text_box.relative_from_v {
+            RelativeFromVType::Page => config.page_height,
+            RelativeFromVType::Paragraph | RelativeFromVType::Line => layout.y_position,
+            _ => config.page_height - config.margin,
+        };
+        (x + offset(&text_box.position_h), y - offset(&text_box.position_v))
+    }
+}
+
+/// Draws `text_box` with its fill, outline and text. The first shape of a
+/// canvas places the canvas and paints its background; the canvas's shapes
+/// are then drawn at their offsets on it.
+pub fn render_text_box(layout: &mut Layout, text_box: &TextBox, frame: Option<&TextBoxFrame>) {
+    let config = layout.config;
+    let (width, height) = match frame {
+        Some(frame) => (frame.width, frame.height),
+        None => (text_box.size.0 as f32 * EMU_TO_MM, text_box.size.1 as f32 * EMU_TO_MM),
+    };
+
+    let (left, top) = match frame.and_then(|frame| frame.canvas.as_ref()) {
+        Some(canvas) => {
+            if canvas.first {
+                let (left, top) = place(layout, text_box, canvas.height);
+                if let Some(rgb) = canvas.background {
+                    let color = fill_color(config.color_space, rgb);
+                    fill_rect(&layout.layer, left, top - canvas.height, canvas.width, canvas.height, color);
+                }
+                layout.canvas_origin = (left, top);
+            }
+            let (canvas_left, canvas_top) = layout.canvas_origin;
+            (canvas_left + canvas.x, canvas_top - canvas.y)
+        }
+        None => place(layout, text_box, height),
+    };
+
+    if let Some(fill) = frame.and_then(|frame| frame.fill) {
+        fill_shape(&layout.layer, (left, top - height), width, height, fill, config.color_space);
+    }
+    if let Some(rgb) = frame.and_then(|frame| frame.border) {
+        layout.layer.set_outline_color(fill_color(config.color_space, rgb));
+        draw_rect(&layout.layer, left, top - height, width, height);
+        layout.layer.set_outline_color(fill_color(config.color_space, (0.0, 0.0, 0.0)));
+    }
+
+    let mut baseline = top - INSET_Y - ASCENT * layout.line_height;
+    for child in &text_box.children {
+        //Only paragraphs are laid out; tables inside text boxes are skipped
+        let TextBoxContentChild::Paragraph(paragraph) = child else { continue };
+        let tab_stops = paragraph_tab_stops(paragraph);
+        for run in paragraph_runs(paragraph) {
+            let font = layout.fonts.for_run(run);
+            let east_asian = layout.fonts.east_asian_for_run(run);
+            let style = TextStyle {
+                font,
+                east_asian,
+                size: layout.font_size,
+                tracking: 0.0,
+                small_caps: is_small_caps(run),
+                rise: baseline_shift(run),
+                tab_stops: &tab_stops,
+            };
+            for line in layout.wrap(&display_text(run), width - 2.0 * INSET_X, layout.font_size, font) {
+                layout.set_run_style(run);
+                let origin = left + INSET_X;
+                use_tabbed_text(layout, &line, &style, (origin, baseline), origin);
+                baseline -= layout.line_height;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use crate::tests::laid_out;
+    use docx_rs::{Docx, Drawing, Paragraph};
+
+    #[test]
+    fn lays_out_an_inline_text_box_in_its_frame() {
+        let text = |text: &str| Paragraph::new().add_run(Run::new().add_text(text));
+        //50 mm by 20 mm
+        let text_box = TextBox { children: vec![TextBoxContentChild::Paragraph(Box::new(text("Boxed")))], ..TextBox::new().size(1_800_000, 720_000) };
+        let run = Run { children: vec![RunChild::Drawing(Box::new(Drawing::new().text_box(text_box)))], ..Run::new() };
+        let docx = Docx::new().add_paragraph(text("Before")).add_paragraph(Paragraph::new().add_run(run)).add_paragraph(text("After"));
+        let config = Config::new("box.docx", "box.pdf");
+        let runs = laid_out(&docx, &config);
+        let line = |wanted: &str| runs.iter().find(|(text, ..)| text == wanted).unwrap().clone();
+        let (before, boxed, after) = (line("Before"), line("Boxed"), line("After"));
+
+        //Inset from the frame's corner, below the paragraph before it
+        assert!((boxed.1 - (config.margin + INSET_X)).abs() < 0.01);
+        assert!(boxed.2 < before.2);
+        //The inline box pushes the next paragraph below its full height
+        assert!(boxed.2 - after.2 > 20.0, "{} mm between the box text and the next paragraph", boxed.2 - after.2);
+    }
+}
@@ -0,0 +1,119 @@
+//! The document theme's colour scheme (`a:clrScheme` in the theme part),
+//! which runs refer to with `w:color w:themeColor="accent1"` and the like.
+//! docx_rs keeps only `w:val`, so theme colours are resolved to hex before
+//! the document is parsed.
+
+use std::collections::HashMap;
+
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+use crate::charts::{relationships, resolve_target};
+use crate::color::parse_hex;
+use crate::{ConversionError, read_part, xml_attribute};
+
+/// Relationship type of the theme part.
+const THEME_RELATIONSHIP: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships/theme";
+
+/// The colours of the theme's scheme, as hex, by their names there
+/// (`dk1`, `lt1`, `accent1`, `hlink` and so on).
+#[derive(Debug, Clone, Default)]
+pub struct ThemeColors {
+    colors: HashMap<String, String>,
+}
+
+impl ThemeColors {
+    /// The colour a `w:themeColor` value names, lightened by `w:themeTint`
+    /// or darkened by `w:themeShade` if given, as hex. Word mixes them in
+    /// HSL; here each channel is mixed toward white or black.
+    pub fn resolve(&self, theme_color: &str, tint: Option<&str>, shade: Option<&str>) -> Option<String> {
+        //The mapping Word uses unless settings.xml remaps it
+        let name = match theme_color {
+            "dark1" | "text1" => "dk1",
+            "light1" | "background1" => "lt1",
+            "dark2" | "text2" => "dk2",
+            "light2" | "background2" => "lt2",
+            "hyperlink" => "hlink",
+            "followedHyperlink" => "folHlink",
+            other => other,
+        };
+        let (r, g, b) = parse_hex(self.colors.get(name)?)?;
+        let fraction = |value: Option<&str>| value.and_then(|value| u8::from_str_radix(value, 16).ok()).map(|value| value as f32 / 255.0);
+        let adjust = |channel: f32| {
+            let channel = fraction(tint).map_or(channel, |tint| channel * tint + (1.0 - tint));
+            fraction(shade).map_or(channel, |shade| channel * shade)
+        };
+        let byte = |channel: f32| (adjust(channel).clamp(0.0, 1.0) * 255.0).round() as u8;
+        Some(format!("{:02X}{:02X}{:02X}", byte(r), byte(g), byte(b)))
+    }
+}
+
+/// Reads the colour scheme of the theme `word/document.xml` relates to.
+pub fn read_theme_colors(docx: &[u8]) -> Result<ThemeColors, ConversionError> {
+    let theme = relationships(docx, "word/_rels/document.xml.rels")?
+        .into_iter()
+        .find(|(_, kind, _)| kind == THEME_RELATIONSHIP)
+        .map(|(_, _, target)| resolve_target("word", &target));
+    let Some(part) = theme else { return Ok(ThemeColors::default()) };
+    let Some(xml) = read_part(docx, &part)? else { return Ok(ThemeColors::default()) };
+
+    let mut colors = HashMap::new();
+    let mut reader = Reader::from_reader(&xml[..]);
+    let mut buf = Vec::new();
+    //Inside the scheme, the colour being read
+    let mut in_scheme = false;
+    let mut name: Option<String> = None;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) if e.local_name() == b"clrScheme" => in_scheme = true,
+            Ok(Event::End(ref e)) if e.local_name() == b"clrScheme" => break,
+            Ok(Event::Start(ref e)) if in_scheme && name.is_none() => {
+                name = Some(String::from_utf8_lossy(e.local_name()).into_owned());
+            }
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) if name.is_some() => {
+                //A system colour carries the value it last had
+                let value = match e.local_name() {
+                    b"srgbClr" => xml_attribute(e, b"val"),
+                    b"sysClr" => xml_attribute(e, b"lastClr"),
+                    _ => None,
+                };
+                if let (Some(name), Some(value)) = (&name, value) {
+                    colors.entry(name.clone()).or_insert(value);
+                }
+            }
+            Ok(Event::End(ref e)) if name.as_deref().is_some_and(|name| name.as_bytes() == e.local_name()) => name = None,
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(ConversionError::InvalidInput(format!("Malformed {}: {}", part, e))),
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(ThemeColors { colors })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::archive;
+
+    #[test]
+    fn resolves_theme_colors_with_tint_and_shade() {
+        let rels = "<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\
+            <Relationship Id=\"rId1\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/theme\" Target=\"theme/theme1.xml\"/>\
+            </Relationships>";
+        let theme = "<a:theme xmlns:a=\"a\"><a:themeElements><a:clrScheme name=\"Office\">\
+            <a:dk1><a:sysClr val=\"windowText\" lastClr=\"000000\"/></a:dk1>\
+            <a:accent1><a:srgbClr val=\"4472C4\"/></a:accent1>\
+            <a:accent2><a:srgbClr val=\"FF0000\"/></a:accent2>\
+            </a:clrScheme></a:themeElements></a:theme>";
+        let docx = archive(&[("word/_rels/document.xml.rels", rels), ("word/theme/theme1.xml", theme)]);
+        let colors = read_theme_colors(&docx).unwrap();
+
+        assert_eq!(colors.resolve("accent1", None, None).as_deref(), Some("4472C4"));
+        assert_eq!(colors.resolve("text1", None, None).as_deref(), Some("000000"));
+        assert_eq!(colors.resolve("accent2", Some("80"), None).as_deref(), Some("FF7F7F"));
+        assert_eq!(colors.resolve("accent2", None, Some("80")).as_deref(), Some("800000"));
+        assert_eq!(colors.resolve("accent6", None, None), None);
+        assert!(read_theme_colors(&archive(&[("word/document.xml", "<w:document/>")])).unwrap().resolve("accent1", None, None).is_none());
+    }
+}
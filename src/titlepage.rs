@@ -0,0 +1,89 @@
+//! The cover added with `--title-page`: the document's title and author,
+//! centred on a page of their own before the body. The cover is not
+//! numbered; page labels number the body from 1 on the page after it.
+
+use crate::properties::CoreProperties;
+use crate::tabs::{DEFAULT_TAB_STOP, TabStops};
+use crate::text::{no_hyphenation, wrap_text};
+use crate::{Layout, PT_TO_MM};
+
+/// Font size of the title, in points.
+const TITLE_SIZE: f32 = 28.0;
+/// Font size of the author line, in points.
+const AUTHOR_SIZE: f32 = 14.0;
+
+/// Draws the cover on the current page, which must be empty, and starts
+/// the body on the next. Without a title in the properties, `fallback` is
+/// shown; the author is left off unless `with_author`.
+pub fn render_title_page(layout: &mut Layout, properties: &CoreProperties, fallback: &str, with_author: bool) {
+    let config = layout.config;
+    let (title_size, author_size) = (TITLE_SIZE * config.font_scale, AUTHOR_SIZE * config.font_scale);
+    let usable_width = config.page_width - 2.0 * config.margin;
+    let title = properties.title.as_deref().unwrap_or(fallback);
+    let (bold, regular) = (layout.fonts.bold.clone(), layout.fonts.regular.clone());
+    let title_width = |_, text: &str| layout.fonts.text_width(&bold, text, title_size);
+    let title_lines = wrap_text(title, usable_width, &title_width, TabStops::defaults(DEFAULT_TAB_STOP), config.wrap_algorithm, &no_hyphenation);
+    let author = properties.author.as_deref().filter(|_| with_author);
+    let line_height = |size: f32| size * 1.2 * PT_TO_MM * config.line_height;
+
+    //The block of title and author is centred on the page as a whole
+    let author_height = author.map_or(0.0, |_| 2.0 * line_height(author_size));
+    let block_height = title_lines.len() as f32 * line_height(title_size) + author_height;
+    let mut top = (config.page_height + block_height) / 2.0;
+    let centred = |width: f32| config.margin + (usable_width - width).max(0.0) / 2.0;
+
+    layout.tags.get_mut().begin("H1");
+    for line in &title_lines {
+        top -= line_height(title_size);
+        let x = centred(layout.fonts.text_width(&bold, line, title_size));
+        layout.draw_text(line, (x, top), title_size, &bold, 0.0);
+    }
+    layout.tags.get_mut().end();
+    if let Some(author) = author {
+        top -= author_height;
+        let x = centred(layout.fonts.text_width(&regular, author, author_size));
+        layout.tags.get_mut().begin("P");
+        layout.draw_text(author, (x, top), author_size, &regular, 0.0);
+        layout.tags.get_mut().end();
+    }
+    layout.page_numbers.number_from(layout.page, None);
+    layout.new_page();
+}
+
+#[cfg(test)]
+mod tests {
+    use printpdf::lopdf::{self, Object};
+
+    use crate::tests::{edited_docx, sample_docx};
+    use crate::{Config, SourceDocument, parse_document, render_pdf_bytes};
+
+    #[test]
+    fn adds_an_unnumbered_cover_before_the_body() {
+        let packed = edited_docx(sample_docx("Body text"), |name, content| match name {
+            "docProps/core.xml" => content
+                .replace(">unknown</dc:creator>", ">Jane Roe</dc:creator>")
+                .replace("</cp:coreProperties>", "<dc:title>Annual Report</dc:title></cp:coreProperties>"),
+            _ => content,
+        });
+        let mut config = Config::new("report.docx", "report.pdf");
+        config.title_page = true;
+        let document = parse_document(&packed, "report.docx", &config).unwrap();
+        let source = SourceDocument { docx: &document.docx, images: &document.images, parts: document.parts, attachment: None };
+        let pdf = lopdf::Document::load_mem(&render_pdf_bytes(vec![source], &config, None, None).unwrap()).unwrap();
+
+        assert_eq!(pdf.get_pages().len(), 2);
+        let cover = pdf.extract_text(&[1]).unwrap();
+        assert!(cover.contains("Annual Report") && cover.contains("Jane Roe") && !cover.contains("Body text"), "{}", cover);
+        assert!(pdf.extract_text(&[2]).unwrap().contains("Body text"));
+
+        //The cover has an empty label and the body is numbered from 1
+        let labels = pdf.catalog().unwrap().get(b"PageLabels").and_then(Object::as_dict).unwrap();
+        let numbers = labels.get(b"Nums").and_then(Object::as_array).unwrap();
+        assert_eq!(numbers[0].as_i64().unwrap(), 0);
+        assert!(numbers[1].as_dict().unwrap().get(b"S").is_err());
+        assert_eq!(numbers[2].as_i64().unwrap(), 1);
+        let body = numbers[3].as_dict().unwrap();
+        assert_eq!(body.get(b"S").and_then(Object::as_name_str).unwrap(), "D");
+        assert!(body.get(b"St").is_err());
+    }
+}
@@ -0,0 +1,319 @@
+//! Tables of contents. Word only stores the entries cached when the field
+//! was last updated, so a `TOC` field is replaced by one generated from the
+//! document's headings. Each entry links to its heading; the page numbers
+//! are only known once the whole body is laid out, so the entries' lines are
+//! reserved first and filled in at the end.
+
+use docx_rs::{DocumentChild, FieldCharType, Paragraph, RunChild, TableOfContents};
+use printpdf::{Mm, PdfLayerReference};
+
+use crate::caps::display_text;
+use crate::color::fill_color;
+use crate::links::{add_destination, add_link_on, destination_page};
+use crate::markup::{Marker, drop_cap_lines, run_markers};
+use crate::text::text_width;
+use crate::{Layout, paragraph_runs};
+
+/// Indent per heading level below the table's top level, in mm.
+const LEVEL_INDENT: f32 = 5.0;
+/// Space kept between the leader dots and the text on either side, in mm.
+const LEADER_GAP: f32 = 1.0;
+
+/// A heading listed in tables of contents.
+#[derive(Debug, Clone)]
+pub struct Heading {
+    pub level: usize,
+    pub text: String,
+}
+
+/// A reserved table of contents line, filled once page numbers are known.
+pub struct TocLine {
+    layer: PdfLayerReference,
+    page: usize,
+    /// Baseline of the line, in mm.
+    y: f32,
+    indent: f32,
+    text: String,
+    destination: String,
+}
+
+/// Outline level of a heading paragraph, from its `HeadingN` style or its
+/// `w:outlineLvl`.
+pub fn heading_level(paragraph: &Paragraph) -> Option<usize> {
+    let property = &paragraph.property;
+    let style = property.style.as_ref().map(|style| style.val.to_lowercase().replace(' ', ""));
+    let from_style = style.and_then(|style| style.strip_prefix("heading")?.parse().ok());
+    //Level 9 is body text
+    let from_outline = property.outline_lvl.as_ref().map(|outline| outline.v + 1).filter(|level| *level <= 9);
+    from_style.or(from_outline).filter(|level| (1..=9).contains(level))
+}
+
+fn destination_name(index: usize) -> String {
+    format!("_Heading{}", index)
+}
+
+/// The body's headings, in order.
+pub fn collect_headings(children: &[DocumentChild]) -> Vec<Heading> {
+    children
+        .iter()
+        .filter_map(|child| match child {
+            //Drop-cap frames are drawn apart from the flow and get no destination
+            DocumentChild::Paragraph(paragraph) if drop_cap_lines(paragraph).is_none() => {
+                let level = heading_level(paragraph)?;
+                let text: String = paragraph_runs(paragraph).map(display_text).collect();
+                Some(Heading { level, text: text.replace('\t', " ").trim().to_string() })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The body from the heading whose text is `title`, ignoring case and
+/// spacing, up to the next heading of the same or a higher level, with the
+/// number of sections that end before it. `None` if no heading matches.
+pub fn heading_subtree(children: &[DocumentChild], title: &str) -> Option<(Vec<DocumentChild>, usize)> {
+    let normalize = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    let title = normalize(title);
+    let heading = |child: &DocumentChild| match child {
+        DocumentChild::Paragraph(paragraph) => {
+            let level = heading_level(paragraph)?;
+            Some((level, normalize(&paragraph_runs(paragraph).map(display_text).collect::<String>())))
+        }
+        _ => None,
+    };
+    let (start, level) = children.iter().enumerate().find_map(|(i, child)| {
+        heading(child).filter(|(_, text)| *text == title).map(|(level, _)| (i, level))
+    })?;
+    let end = children[start + 1..]
+        .iter()
+        .position(|child| heading(child).is_some_and(|(other, _)| other <= level))
+        .map_or(children.len(), |offset| start + 1 + offset);
+    let sections_before = children[..start]
+        .iter()
+        .filter(|child| matches!(child, DocumentChild::Paragraph(paragraph) if paragraph.property.section_property.is_some()))
+        .count();
+    Some((children[start..end].to_vec(), sections_before))
+}
+
+/// Places the destination the table of contents links to, if `paragraph` is
+/// a heading.
+pub fn add_heading_destination(layout: &mut Layout, paragraph: &Paragraph) {
+    if heading_level(paragraph).is_some() {
+        let name = destination_name(layout.headings_placed);
+        layout.headings_placed += 1;
+        add_destination(layout, name);
+    }
+}
+
+/// The instruction of a `TOC` field starting in `paragraph`, either a simple
+/// field or the first part of a complex one.
+fn toc_instruction(paragraph: &Paragraph) -> Option<String> {
+    let runs: Vec<_> = paragraph_runs(paragraph).collect();
+    let simple = runs.iter().flat_map(|run| run_markers(run)).find_map(|marker| match marker {
+        Marker::Toc(instruction) => Some(instruction),
+        _ => None,
+    });
+    if simple.is_some() {
+        return simple;
+    }
+    let instruction: String = runs
+        .iter()
+        .flat_map(|run| &run.children)
+        .filter_map(|child| match child {
+            RunChild::InstrTextString(text) => Some(text.as_str()),
+            _ => None,
+        })
+        .collect();
+    instruction.trim_start().starts_with("TOC").then_some(instruction)
+}
+
+/// Change in field nesting depth over `paragraph`.
+fn field_depth_change(paragraph: &Paragraph) -> i32 {
+    paragraph_runs(paragraph)
+        .flat_map(|run| &run.children)
+        .map(|child| match child {
+            RunChild::FieldChar(field) if field.field_char_type == FieldCharType::Begin => 1,
+            RunChild::FieldChar(field) if field.field_char_type == FieldCharType::End => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Replaces each `TOC` field, along with the stale entries cached in it,
+/// with a `TableOfContents` carrying its instruction.
+pub fn replace_toc_fields(children: Vec<DocumentChild>) -> Vec<DocumentChild> {
+    let mut replaced = Vec::with_capacity(children.len());
+    //Nesting depth of the complex field whose cached result is being dropped
+    let mut depth = 0;
+    for child in children {
+        let DocumentChild::Paragraph(paragraph) = &child else {
+            if depth == 0 {
+                replaced.push(child);
+            }
+            continue;
+        };
+        if depth > 0 {
+            depth += field_depth_change(paragraph);
+            continue;
+        }
+        match toc_instruction(paragraph) {
+            Some(instruction) => {
+                depth = field_depth_change(paragraph).max(0);
+                replaced.push(DocumentChild::TableOfContents(Box::new(TableOfContents::with_instr_text(&instruction))));
+            }
+            None => replaced.push(child),
+        }
+    }
+    replaced
+}
+
+/// The headings `toc` lists, with the index of each among all headings.
+fn toc_entries<'h>(toc: &TableOfContents, headings: &'h [Heading]) -> impl Iterator<Item = (usize, &'h Heading)> {
+    let (first, last) = toc.instr.heading_styles_range.unwrap_or((1, 9));
+    headings
+        .iter()
+        .enumerate()
+        .filter(move |(_, heading)| (first..=last).contains(&heading.level) && !heading.text.is_empty())
+}
+
+/// Height `toc` takes in the body flow, including the gap after it.
+pub fn toc_height(layout: &Layout, toc: &TableOfContents) -> f32 {
+    (toc_entries(toc, &layout.headings).count() + 1) as f32 * layout.line_height
+}
+
+/// Reserves a line at the layout cursor for each entry of `toc`.
+pub fn render_toc(layout: &mut Layout, toc: &TableOfContents) {
+    let (first, _) = toc.instr.heading_styles_range.unwrap_or((1, 9));
+    let entries: Vec<(usize, Heading)> =
+        toc_entries(toc, &layout.headings).map(|(index, heading)| (index, heading.clone())).collect();
+    for (index, heading) in entries {
+        layout.ensure_space(layout.line_height);
+        layout.toc_lines.push(TocLine {
+            layer: layout.layer.clone(),
+            page: layout.page,
+            y: layout.baseline(),
+            indent: heading.level.saturating_sub(first) as f32 * LEVEL_INDENT,
+            text: heading.text,
+            destination: destination_name(index),
+        });
+        layout.y_position -= layout.line_height;
+    }
+    layout.y_position -= layout.line_height;
+}
+
+/// Writes the reserved entries with dot leaders and right-aligned page
+/// numbers, and links each to its heading.
+pub fn fill_toc_lines(layout: &mut Layout) {
+    let config = layout.config;
+    let font = layout.fonts.regular.clone();
+    let size = layout.font_size;
+    let right = config.page_width - config.margin;
+    let dot_width = text_width(".", size);
+    let black = fill_color(config.color_space, (0.0, 0.0, 0.0));
+    let lines = std::mem::take(&mut layout.toc_lines);
+    if !lines.is_empty() {
+        layout.tags.get_mut().begin("TOC");
+    }
+    for line in &lines {
+        let Some(page) = destination_page(layout, &line.destination) else { continue };
+        layout.tags.get_mut().begin("TOCI");
+        let number = layout.page_numbers.label(page);
+        let x = config.margin + line.indent;
+        let number_x = right - text_width(&number, size);
+        let text_end = x + text_width(&line.text, size);
+        line.layer.set_fill_color(black.clone());
+        layout.draw_text_on((&line.layer, line.page), &line.text, (x, line.y), size, &font, 0.0);
+        let dots = ((number_x - text_end - 2.0 * LEADER_GAP) / dot_width).floor().max(0.0) as usize;
+        if dots > 0 {
+            let leader_x = number_x - LEADER_GAP - dots as f32 * dot_width;
+            line.layer.use_text(".".repeat(dots), size, Mm(leader_x), Mm(line.y), &font);
+        }
+        layout.draw_text_on((&line.layer, line.page), &number, (number_x, line.y), size, &font, 0.0);
+        add_link_on(layout, line.page, &line.destination, x, line.y, right - x);
+        layout.tags.get_mut().end();
+    }
+    if !lines.is_empty() {
+        layout.tags.get_mut().end();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use crate::tests::{edited_docx, laid_out_archive};
+    use docx_rs::{Docx, Run, SectionProperty};
+
+    fn heading(level: usize, text: &str) -> DocumentChild {
+        let paragraph = Paragraph::new().style(&format!("Heading{}", level)).add_run(Run::new().add_text(text));
+        DocumentChild::Paragraph(Box::new(paragraph))
+    }
+
+    fn body(text: &str) -> DocumentChild {
+        DocumentChild::Paragraph(Box::new(Paragraph::new().add_run(Run::new().add_text(text))))
+    }
+
+    fn texts(children: &[DocumentChild]) -> Vec<String> {
+        children
+            .iter()
+            .map(|child| match child {
+                DocumentChild::Paragraph(paragraph) => paragraph_runs(paragraph).map(display_text).collect(),
+                _ => String::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn takes_a_heading_and_what_comes_under_it() {
+        let mut section_end = Paragraph::new().add_run(Run::new().add_text("Preface"));
+        section_end.property.section_property = Some(SectionProperty::new());
+        let children = vec![
+            heading(1, "Introduction"),
+            DocumentChild::Paragraph(Box::new(section_end)),
+            heading(1, "Getting  Started"),
+            body("Install it."),
+            heading(2, "Requirements"),
+            body("A computer."),
+            heading(1, "Reference"),
+        ];
+        let (subtree, sections_before) = heading_subtree(&children, "getting started").unwrap();
+        assert_eq!(texts(&subtree), ["Getting  Started", "Install it.", "Requirements", "A computer."]);
+        assert_eq!(sections_before, 1);
+
+        let (subtree, _) = heading_subtree(&children, "Requirements").unwrap();
+        assert_eq!(texts(&subtree), ["Requirements", "A computer."]);
+        assert!(heading_subtree(&children, "Appendix").is_none());
+    }
+
+    #[test]
+    fn lists_the_headings_with_their_pages() {
+        let heading = |level: usize, text: &str| Paragraph::new().style(&format!("Heading{}", level)).add_run(Run::new().add_text(text));
+        let mut docx = Docx::new().add_paragraph(Paragraph::new().add_run(Run::new().add_text("CONTENTS"))).add_paragraph(heading(1, "Intro"));
+        //Enough lines to put the next heading on a later page
+        for _ in 0..30 {
+            docx = docx.add_paragraph(Paragraph::new().add_run(Run::new().add_text("Filler")));
+        }
+        let docx = docx.add_paragraph(heading(2, "Detail")).add_paragraph(heading(3, "Deep"));
+        let packed = edited_docx(docx, |name, content| match name {
+            "word/document.xml" => content.replace(
+                r#"<w:r><w:rPr /><w:t xml:space="preserve">CONTENTS</w:t></w:r>"#,
+                r#"<w:fldSimple w:instr=" TOC \o &quot;1-2&quot; \h "><w:r><w:t>Stale entry</w:t></w:r></w:fldSimple>"#,
+            ),
+            _ => content,
+        });
+        let config = Config::new("toc.docx", "toc.pdf");
+        let runs = laid_out_archive(&packed, &config);
+        assert!(!runs.iter().any(|(text, ..)| text.contains("Stale")));
+
+        //The entries are filled in last, each line its text then its right-aligned page number
+        let entries = &runs[runs.len() - 4..];
+        let texts: Vec<(&str, f32)> = entries.iter().step_by(2).map(|(text, x, ..)| (text.as_str(), *x)).collect();
+        assert_eq!(texts, [("Intro", config.margin), ("Detail", config.margin + LEVEL_INDENT)]);
+        let pages: Vec<&str> = entries.iter().skip(1).step_by(2).map(|(text, ..)| text.as_str()).collect();
+        assert_eq!(pages[0], "1");
+        assert_ne!(pages[1], "1");
+        let right = config.page_width - config.margin;
+        assert!(entries.iter().skip(1).step_by(2).all(|(_, x, _, width)| (x + width - right).abs() < 0.01));
+        assert_eq!(runs.iter().filter(|(text, ..)| text == "Deep").count(), 1);
+    }
+}
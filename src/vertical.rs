@@ -0,0 +1,123 @@
+//! Vertical writing, as in Japanese and Chinese books, for sections whose
+//! `w:textDirection` is `tbRl`: characters run top to bottom in columns,
+//! and the columns from the right of the page to the left. Characters are
+//! set upright, East Asian ones a full em apart and others by their width,
+//! and punctuation that has a vertical presentation form is drawn in it.
+//! Each paragraph starts a new column. Tables in the section are left out.
+
+use docx_rs::DocumentChild;
+use log::warn;
+
+use crate::text::is_cjk;
+use crate::{Layout, PT_TO_MM, paragraph_runs, run_text};
+
+/// Share of the em box above the baseline of an East Asian font.
+const EM_ASCENT: f32 = 0.88;
+
+/// The vertical presentation form of `c`, for punctuation that turns or
+/// moves when set in a column.
+fn vertical_form(c: char) -> char {
+    match c {
+        '，' => '\u{FE10}',
+        '、' => '\u{FE11}',
+        '。' => '\u{FE12}',
+        '：' => '\u{FE13}',
+        '；' => '\u{FE14}',
+        '！' => '\u{FE15}',
+        '？' => '\u{FE16}',
+        '〖' => '\u{FE17}',
+        '〗' => '\u{FE18}',
+        '…' => '\u{FE19}',
+        'ー' | '—' => '\u{FE31}',
+        '–' => '\u{FE32}',
+        '（' => '\u{FE35}',
+        '）' => '\u{FE36}',
+        '｛' => '\u{FE37}',
+        '｝' => '\u{FE38}',
+        '〔' => '\u{FE39}',
+        '〕' => '\u{FE3A}',
+        '【' => '\u{FE3B}',
+        '】' => '\u{FE3C}',
+        '《' => '\u{FE3D}',
+        '》' => '\u{FE3E}',
+        '〈' => '\u{FE3F}',
+        '〉' => '\u{FE40}',
+        '「' => '\u{FE41}',
+        '」' => '\u{FE42}',
+        '『' => '\u{FE43}',
+        '』' => '\u{FE44}',
+        other => other,
+    }
+}
+
+/// Where the next character of a vertical section goes.
+struct Column {
+    /// Right edge of the column, in mm.
+    right: f32,
+    /// Top of the next character.
+    y: f32,
+    /// Whether anything has been set in the column.
+    used: bool,
+}
+
+impl Column {
+    fn first(layout: &Layout) -> Column {
+        Column { right: layout.config.page_width - layout.config.margin, y: layout.config.page_height - layout.config.margin, used: false }
+    }
+
+    /// Moves to the top of the column to the left, or of the first column
+    /// of a new page once the columns reach the left margin.
+    fn advance(&mut self, layout: &mut Layout) {
+        let pitch = layout.line_height;
+        *self = Column { right: self.right - pitch, ..Column::first(layout) };
+        if self.right - pitch < layout.config.margin {
+            layout.new_page();
+            *self = Column::first(layout);
+        }
+    }
+}
+
+/// Lays out `children`, the content of a vertical section, in columns.
+pub fn render_vertical_section(layout: &mut Layout, children: &[DocumentChild]) {
+    if layout.y_position < layout.config.page_height - layout.config.margin {
+        layout.new_page();
+    }
+    let (font_size, pitch) = (layout.font_size, layout.line_height);
+    let em = font_size * PT_TO_MM;
+    let bottom = layout.config.margin;
+    let mut column = Column::first(layout);
+    let mut skipped = 0;
+    for child in children {
+        let DocumentChild::Paragraph(paragraph) = child else {
+            skipped += usize::from(matches!(child, DocumentChild::Table(_)));
+            continue;
+        };
+        if column.used {
+            column.advance(layout);
+        }
+        layout.tags.get_mut().begin("P");
+        for run in paragraph_runs(paragraph) {
+            layout.set_run_style(run);
+            let font = layout.fonts.for_run(run).clone();
+            for c in run_text(run).chars().filter(|c| !c.is_control()).map(vertical_form) {
+                let glyph = c.to_string();
+                let advance = if is_cjk(c) { em } else { layout.fonts.text_width(&font, &glyph, font_size) };
+                if column.y - advance < bottom {
+                    column.advance(layout);
+                }
+                //Centred across the column, its em box hanging from the top
+                let x = column.right - (pitch + advance) / 2.0;
+                layout.draw_text(&glyph, (x, column.y - EM_ASCENT * em), font_size, &font, 0.0);
+                column.y -= advance;
+            }
+        }
+        layout.tags.get_mut().end();
+        //An empty paragraph takes a column too
+        column.used = true;
+    }
+    if skipped > 0 {
+        warn!("{} table(s) in a vertical section are left out", skipped);
+    }
+    //What follows starts on a new page
+    layout.y_position = layout.config.margin;
+}
@@ -0,0 +1,125 @@
+//! How viewers first show the document: the zoom of the open action and
+//! the page layout, both set in the catalog of the saved file.
+
+use printpdf::lopdf::{self, Object};
+
+use crate::ConversionError;
+
+/// Zoom the document opens at, on its first page.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Zoom {
+    /// The whole page in the window.
+    Fit,
+    /// The page width across the window.
+    Width,
+    /// A magnification, in percent.
+    Percent(f32),
+}
+
+impl std::str::FromStr for Zoom {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "fit" => Ok(Zoom::Fit),
+            "width" => Ok(Zoom::Width),
+            other => match other.trim_end_matches('%').parse::<f32>() {
+                Ok(percent) if percent > 0.0 => Ok(Zoom::Percent(percent)),
+                _ => Err(format!("Unknown zoom: {} (expected fit, width or a percentage)", other)),
+            },
+        }
+    }
+}
+
+/// How pages are arranged when the document opens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PageLayout {
+    /// One page at a time.
+    Single,
+    /// One column of pages, scrolled.
+    Continuous,
+    /// Two columns of pages, odd pages on the left, scrolled.
+    TwoUp,
+}
+
+impl PageLayout {
+    /// The catalog's `/PageLayout` name.
+    fn name(self) -> &'static [u8] {
+        match self {
+            PageLayout::Single => b"SinglePage",
+            PageLayout::Continuous => b"OneColumn",
+            PageLayout::TwoUp => b"TwoColumnLeft",
+        }
+    }
+}
+
+impl std::str::FromStr for PageLayout {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "single" => Ok(PageLayout::Single),
+            "continuous" => Ok(PageLayout::Continuous),
+            "two-up" => Ok(PageLayout::TwoUp),
+            other => Err(format!("Unknown page layout: {}", other)),
+        }
+    }
+}
+
+/// Sets the catalog's `/OpenAction` to show the first page at `zoom` and
+/// its `/PageLayout` to `page_layout`, leaving out those not given.
+pub fn set_view(pdf: &[u8], zoom: Option<Zoom>, page_layout: Option<PageLayout>) -> Result<Vec<u8>, ConversionError> {
+    let pdf_error = |e: lopdf::Error| ConversionError::Pdf(e.to_string());
+    let mut document = lopdf::Document::load_mem(pdf).map_err(pdf_error)?;
+    let first_page = document.get_pages().values().next().copied();
+
+    let catalog_id = document.trailer.get(b"Root").and_then(Object::as_reference).map_err(pdf_error)?;
+    let catalog = document.get_dictionary_mut(catalog_id).map_err(pdf_error)?;
+    if let (Some(zoom), Some(page_id)) = (zoom, first_page) {
+        let mut destination = vec![Object::Reference(page_id)];
+        match zoom {
+            Zoom::Fit => destination.push(Object::Name(b"Fit".to_vec())),
+            Zoom::Width => destination.extend([Object::Name(b"FitH".to_vec()), Object::Null]),
+            Zoom::Percent(percent) => {
+                destination.extend([Object::Name(b"XYZ".to_vec()), Object::Null, Object::Null, Object::Real(percent / 100.0)])
+            }
+        }
+        catalog.set("OpenAction", Object::Array(destination));
+    }
+    if let Some(page_layout) = page_layout {
+        catalog.set("PageLayout", Object::Name(page_layout.name().to_vec()));
+    }
+
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use crate::tests::sample_pdf;
+
+    #[test]
+    fn parses_zoom() {
+        assert_eq!("fit".parse(), Ok(Zoom::Fit));
+        assert_eq!("150%".parse(), Ok(Zoom::Percent(150.0)));
+        assert!("0".parse::<Zoom>().is_err());
+    }
+
+    #[test]
+    fn sets_open_action_and_page_layout() {
+        let pdf = sample_pdf("Viewed", &Config::new("view.docx", "view.pdf"));
+        let document = lopdf::Document::load_mem(&set_view(&pdf, Some(Zoom::Percent(150.0)), Some(PageLayout::TwoUp)).unwrap()).unwrap();
+        let catalog = document.catalog().unwrap();
+        assert_eq!(catalog.get(b"PageLayout").and_then(Object::as_name).unwrap(), b"TwoColumnLeft");
+        let action = catalog.get(b"OpenAction").and_then(Object::as_array).unwrap();
+        assert_eq!(action[0].as_reference().unwrap(), *document.get_pages().values().next().unwrap());
+        assert_eq!(action[1].as_name().unwrap(), b"XYZ");
+        assert_eq!(action[4].as_float().unwrap(), 1.5);
+
+        let document = lopdf::Document::load_mem(&set_view(&pdf, None, Some(PageLayout::Single)).unwrap()).unwrap();
+        assert!(document.catalog().unwrap().get(b"OpenAction").is_err());
+    }
+}
@@ -0,0 +1,333 @@
+//! Watermarks, which Word keeps in the page headers as a VML shape: either
+//! WordArt text (`PowerPlusWaterMarkObject`) or a washed-out picture
+//! (`WordPictureWatermark`). Headers are not drawn, so the watermark is
+//! read on its own and drawn under the content of every page, centred in
+//! the page. PDF 1.3 has no transparency, so a semitransparent colour is
+//! drawn mixed with the white of the page instead. Watermark text is not
+//! reported, highlighted or tagged, as it is not part of the body.
+
+use ::image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use log::warn;
+use printpdf::{ImageTransform, Mm, TextMatrix};
+use quick_xml::Reader;
+use quick_xml::escape::unescape;
+use quick_xml::events::Event;
+use zip::ZipArchive;
+
+use std::io::Cursor;
+
+use crate::charts::{relationships, resolve_target};
+use crate::color::{fill_color, parse_hex, pdf_image};
+use crate::{ConversionError, Layout, PT_TO_MM, read_part, xml_attribute};
+
+/// What the watermark shows.
+pub enum Mark {
+    Text { text: String, color: (f32, f32, f32) },
+    /// The picture at `path` in the archive, with its washout applied.
+    Picture { path: String, image: DynamicImage },
+}
+
+/// A watermark and the size of its shape in mm, before it is rotated.
+pub struct Watermark {
+    pub mark: Mark,
+    width: f32,
+    height: f32,
+    /// Clockwise rotation in degrees.
+    rotation: f32,
+}
+
+impl Watermark {
+    /// The archive path of the watermark's picture, which is not drawn again
+    /// after the body.
+    pub fn picture_path(&self) -> Option<&str> {
+        match &self.mark {
+            Mark::Picture { path, .. } => Some(path),
+            Mark::Text { .. } => None,
+        }
+    }
+}
+
+/// The shape of a watermark being read from a header.
+#[derive(Default)]
+struct Shape {
+    style: String,
+    fill_color: Option<String>,
+    opacity: Option<f32>,
+    text: Option<String>,
+    /// Relationship id of the picture, with its `gain` and `blacklevel`.
+    picture: Option<(String, Option<String>, Option<String>)>,
+}
+
+/// A length in a VML style, such as `527.85pt`, in mm.
+fn style_length(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let (number, to_mm) = if let Some(number) = value.strip_suffix("pt") {
+        (number, PT_TO_MM)
+    } else if let Some(number) = value.strip_suffix("in") {
+        (number, 25.4)
+    } else if let Some(number) = value.strip_suffix("mm") {
+        (number, 1.0)
+    } else if let Some(number) = value.strip_suffix("cm") {
+        (number, 10.0)
+    } else {
+        //Bare numbers are pixels at 96 per inch
+        (value.strip_suffix("px").unwrap_or(value), 25.4 / 96.0)
+    };
+    number.trim().parse::<f32>().ok().map(|number| number * to_mm)
+}
+
+/// The value of `property` in a VML `style` attribute.
+fn style_property<'s>(style: &'s str, property: &str) -> Option<&'s str> {
+    style.split(';').find_map(|declaration| {
+        let (name, value) = declaration.split_once(':')?;
+        (name.trim() == property).then_some(value.trim())
+    })
+}
+
+/// A VML fraction such as `.5`, or `19661f` in 65536ths.
+fn vml_fraction(value: &str) -> Option<f32> {
+    match value.strip_suffix('f') {
+        Some(fixed) => fixed.parse::<f32>().ok().map(|fixed| fixed / 65536.0),
+        None => value.parse().ok(),
+    }
+}
+
+/// A VML colour: `#rrggbb`, `#rgb` or one of the named colours Word offers.
+fn vml_color(value: &str) -> Option<(f32, f32, f32)> {
+    //Word may follow the colour with a palette index, as in "#c0c0c0 [3212]"
+    let value = value.split_whitespace().next()?;
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() == 3 {
+            return parse_hex(&hex.chars().flat_map(|c| [c, c]).collect::<String>());
+        }
+        return parse_hex(hex);
+    }
+    let hex = match value.to_ascii_lowercase().as_str() {
+        "black" => "000000",
+        "white" => "ffffff",
+        "silver" => "c0c0c0",
+        "gray" | "grey" => "808080",
+        "red" => "ff0000",
+        "maroon" => "800000",
+        "yellow" => "ffff00",
+        "olive" => "808000",
+        "lime" => "00ff00",
+        "green" => "008000",
+        "aqua" => "00ffff",
+        "teal" => "008080",
+        "blue" => "0000ff",
+        "navy" => "000080",
+        "fuchsia" => "ff00ff",
+        "purple" => "800080",
+        _ => return None,
+    };
+    parse_hex(hex)
+}
+
+/// `image` with VML's picture `gain` (contrast) and `blacklevel`
+/// (brightness) applied, as Word does to wash a picture out.
+fn wash_out(image: &DynamicImage, gain: f32, black_level: f32) -> DynamicImage {
+    let (width, height) = image.dimensions();
+    let mut washed = RgbaImage::new(width, height);
+    let adjust = |channel: u8| {
+        let value = (channel as f32 / 255.0 - 0.5) * gain + 0.5 + black_level;
+        (value.clamp(0.0, 1.0) * 255.0).round() as u8
+    };
+    for (x, y, Rgba([r, g, b, a])) in image.pixels() {
+        washed.put_pixel(x, y, Rgba([adjust(r), adjust(g), adjust(b), a]));
+    }
+    DynamicImage::ImageRgba8(washed)
+}
+
+/// The watermark in the headers of `docx`, if it has one. Word puts the
+/// same watermark in each of a section's headers, so the first is used.
+pub fn read_watermark(docx: &[u8]) -> Result<Option<Watermark>, ConversionError> {
+    let archive = ZipArchive::new(Cursor::new(docx))?;
+    let mut headers: Vec<String> = archive
+        .file_names()
+        .filter(|name| name.starts_with("word/header") && name.ends_with(".xml"))
+        .map(str::to_string)
+        .collect();
+    headers.sort();
+    for header in headers {
+        let Some(xml) = read_part(docx, &header)? else { continue };
+        if let Some(shape) = read_watermark_shape(&xml, &header)?
+            && let Some(watermark) = resolve_shape(docx, &header, shape)?
+        {
+            return Ok(Some(watermark));
+        }
+    }
+    Ok(None)
+}
+
+/// The first watermark shape in the header part `xml`, named `part`.
+fn read_watermark_shape(xml: &[u8], part: &str) -> Result<Option<Shape>, ConversionError> {
+    let mut reader = Reader::from_reader(xml);
+    let mut buf = Vec::new();
+    let mut shape: Option<Shape> = None;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => match (e.local_name(), shape.as_mut()) {
+                (b"shape", None) => {
+                    let id = xml_attribute(e, b"id").unwrap_or_default();
+                    if id.starts_with("PowerPlusWaterMarkObject") || id.starts_with("WordPictureWatermark") {
+                        shape = Some(Shape {
+                            style: xml_attribute(e, b"style").unwrap_or_default(),
+                            fill_color: xml_attribute(e, b"fillcolor"),
+                            ..Shape::default()
+                        });
+                    }
+                }
+                (b"fill", Some(shape)) => shape.opacity = xml_attribute(e, b"opacity").and_then(|value| vml_fraction(&value)),
+                (b"textpath", Some(shape)) => {
+                    let attribute = e.attributes().flatten().find(|a| a.key == b"string");
+                    shape.text = attribute.and_then(|a| Some(String::from_utf8_lossy(&unescape(&a.value).ok()?).into_owned()));
+                }
+                (b"imagedata", Some(shape)) => {
+                    shape.picture = xml_attribute(e, b"id")
+                        .map(|id| (id, xml_attribute(e, b"gain"), xml_attribute(e, b"blacklevel")));
+                }
+                _ => {}
+            },
+            Ok(Event::End(ref e)) if e.local_name() == b"shape" && shape.is_some() => return Ok(shape),
+            Ok(Event::Eof) => return Ok(None),
+            Err(e) => return Err(ConversionError::InvalidInput(format!("Malformed {}: {}", part, e))),
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+/// The watermark `shape` in the header `part` describes, with its picture
+/// read from `docx`.
+fn resolve_shape(docx: &[u8], part: &str, shape: Shape) -> Result<Option<Watermark>, ConversionError> {
+    let length = |property: &str| style_property(&shape.style, property).and_then(style_length).unwrap_or(0.0);
+    let (width, height) = (length("width"), length("height"));
+    let rotation = style_property(&shape.style, "rotation").and_then(|value| value.parse().ok()).unwrap_or(0.0);
+
+    let mark = if let Some(text) = shape.text.filter(|text| !text.trim().is_empty()) {
+        let (r, g, b) = shape.fill_color.as_deref().and_then(vml_color).unwrap_or((0.75, 0.75, 0.75));
+        let opacity = shape.opacity.unwrap_or(1.0).clamp(0.0, 1.0);
+        let over_white = |channel: f32| 1.0 - opacity * (1.0 - channel);
+        Mark::Text { text, color: (over_white(r), over_white(g), over_white(b)) }
+    } else if let Some((id, gain, black_level)) = shape.picture {
+        let file_name = part.rsplit('/').next().unwrap_or(part);
+        let rels = format!("word/_rels/{}.rels", file_name);
+        let target = relationships(docx, &rels)?.into_iter().find(|(rel_id, ..)| *rel_id == id);
+        let Some(path) = target.map(|(_, _, target)| resolve_target("word", &target)) else { return Ok(None) };
+        let Some(data) = read_part(docx, &path)? else { return Ok(None) };
+        let Ok(image) = ::image::load_from_memory(&data) else {
+            warn!("The watermark picture {} cannot be decoded and is left out", path);
+            return Ok(None);
+        };
+        let gain = gain.as_deref().and_then(vml_fraction).unwrap_or(1.0);
+        let black_level = black_level.as_deref().and_then(vml_fraction).unwrap_or(0.0);
+        Mark::Picture { path, image: wash_out(&image, gain, black_level) }
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(Watermark { mark, width, height, rotation }))
+}
+
+/// Draws the watermark of the document being laid out, if it has one, on
+/// the current page. Called before anything else is drawn on the page, so
+/// the content covers it.
+pub fn draw_watermark(layout: &Layout) {
+    let Some(watermark) = &layout.watermark else { return };
+    let config = layout.config;
+    let (centre_x, centre_y) = (config.page_width / 2.0, config.page_height / 2.0);
+    //Never larger than the text area
+    let usable = (config.page_width - 2.0 * config.margin, config.page_height - 2.0 * config.margin);
+    match &watermark.mark {
+        Mark::Text { text, color } => {
+            let font = &layout.fonts.regular;
+            let text = layout.redactor.blank(text);
+            let text = layout.printable(&text, font);
+            //The text is stretched across its shape, as WordArt is
+            let unit_width = layout.fonts.text_width(font, &text, 1.0);
+            let fitted = if watermark.width > 0.0 { watermark.width } else { usable.0 };
+            let mut font_size = fitted.min(usable.0.max(usable.1)) / unit_width.max(f32::EPSILON);
+            if watermark.height > 0.0 {
+                font_size = font_size.min(watermark.height / PT_TO_MM);
+            }
+            let text_width = unit_width * font_size;
+            //Rotated about its centre: the baseline starts half the width back, a little below
+            let angle = -watermark.rotation;
+            let (sin, cos) = angle.to_radians().sin_cos();
+            let (dx, dy) = (-text_width / 2.0, -0.35 * font_size * PT_TO_MM);
+            let (x, y) = (centre_x + dx * cos - dy * sin, centre_y + dx * sin + dy * cos);
+
+            let layer = &layout.layer;
+            layer.save_graphics_state();
+            layer.set_fill_color(fill_color(config.color_space, *color));
+            layer.begin_text_section();
+            layer.set_font(font, font_size);
+            layer.set_text_matrix(TextMatrix::TranslateRotate(Mm(x).into_pt(), Mm(y).into_pt(), angle));
+            layer.write_text(text, font);
+            layer.end_text_section();
+            layer.restore_graphics_state();
+        }
+        Mark::Picture { image, .. } => {
+            let (pixels_wide, pixels_high) = image.dimensions();
+            let (mut width, mut height) = (watermark.width, watermark.height);
+            if width <= 0.0 || height <= 0.0 {
+                (width, height) = (usable.0, usable.0 * pixels_high as f32 / pixels_wide as f32);
+            }
+            let fit = (usable.0 / width).min(usable.1 / height).min(1.0);
+            let (width, height) = (width * fit, height * fit);
+            let dpi = pixels_wide as f32 * 25.4 / width;
+            pdf_image(image, config.color_space).add_to_layer(
+                layout.layer.clone(),
+                ImageTransform {
+                    translate_x: Some(Mm(centre_x - width / 2.0)),
+                    translate_y: Some(Mm(centre_y - height / 2.0)),
+                    scale_y: Some(height / (pixels_high as f32 / dpi * 25.4)),
+                    dpi: Some(dpi),
+                    ..ImageTransform::default()
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use docx_rs::{Docx, Paragraph, Run};
+    use printpdf::lopdf::{self, Object};
+
+    use crate::compression::Compression;
+    use crate::tests::{edited_docx, with_parts};
+    use crate::{Config, SourceDocument, parse_document, render_pdf_bytes};
+
+    const HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:hdr xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main" xmlns:v="urn:schemas-microsoft-com:vml"><w:p><w:r><w:pict><v:shape id="PowerPlusWaterMarkObject1" style="position:absolute;width:400pt;height:100pt;rotation:315" fillcolor="silver"><v:fill opacity=".5" /><v:textpath string="CONFIDENTIAL" /></v:shape></w:pict></w:r></w:p></w:hdr>"#;
+
+    #[test]
+    fn draws_the_header_watermark_under_every_page() {
+        let mut docx = Docx::new();
+        for _ in 0..40 {
+            docx = docx.add_paragraph(Paragraph::new().add_run(Run::new().add_text("Body")));
+        }
+        let packed = with_parts(&edited_docx(docx, |_, content| content), &[("word/header1.xml", HEADER)]);
+        let mut config = Config::new("watermark.docx", "watermark.pdf");
+        config.compression = Some(Compression::None);
+        let document = parse_document(&packed, "watermark.docx", &config).unwrap();
+        let source = SourceDocument { docx: &document.docx, images: &document.images, parts: document.parts, attachment: None };
+        let pdf = lopdf::Document::load_mem(&render_pdf_bytes(vec![source], &config, None, None).unwrap()).unwrap();
+
+        let pages = pdf.get_pages();
+        assert_eq!(pages.len(), 2);
+        for (number, id) in pages {
+            assert!(pdf.extract_text(&[number]).unwrap().contains("CONFIDENTIAL"));
+            let operations = lopdf::content::Content::decode(&pdf.get_page_content(id).unwrap()).unwrap().operations;
+            let operands = |operator: &str| operations.iter().find(|operation| operation.operator == operator).unwrap().operands.clone();
+            let numbers = |operands: Vec<Object>| operands.iter().map(|operand| operand.as_float().unwrap()).collect::<Vec<f32>>();
+            //The first text drawn is the watermark: silver at half opacity, turned 45 degrees up
+            let color = numbers(operands("rg"));
+            assert!(color.iter().all(|channel| (channel - (1.0 - 0.5 * (1.0 - 192.0 / 255.0))).abs() < 0.01), "{:?}", color);
+            let matrix = numbers(operands("Tm"));
+            let diagonal = std::f32::consts::FRAC_1_SQRT_2;
+            assert!((matrix[0] - diagonal).abs() < 0.01 && (matrix[1] - diagonal).abs() < 0.01, "{:?}", matrix);
+        }
+    }
+}
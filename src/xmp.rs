@@ -0,0 +1,144 @@
+//! The XMP metadata packet, for `--meta key=value` entries that document
+//! management systems index. printpdf writes no packet for the documents
+//! the converter makes, so one is added to the saved file, repeating the
+//! title and producer of the document information dictionary so the two
+//! agree. The packet has no dates or random IDs, so it is the same every
+//! time for the same entries.
+
+use std::borrow::Cow;
+
+use printpdf::lopdf::{self, Dictionary, Object, Stream};
+use quick_xml::escape::escape;
+
+use crate::ConversionError;
+
+/// Namespace the entries are written in, the one Acrobat keeps a
+/// document's custom properties in.
+const CUSTOM_NAMESPACE: &str = "http://ns.adobe.com/pdfx/1.3/";
+
+/// Splits a `--meta` value into its key and value. The key becomes an XML
+/// element name, so it is a letter or `_` followed by letters, digits, `-`,
+/// `_` and `.`.
+pub fn parse_meta(entry: &str) -> Result<(String, String), String> {
+    let invalid = || format!("--meta needs key=value with a key of letters, digits, -, _ and .: {}", entry);
+    let (key, value) = entry.split_once('=').ok_or_else(invalid)?;
+    let mut chars = key.chars();
+    let starts_well = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    if !starts_well || !chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) {
+        return Err(invalid());
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// `text` escaped for XML content.
+fn xml_text(text: &str) -> Cow<'_, str> {
+    match escape(text.as_bytes()) {
+        Cow::Borrowed(_) => Cow::Borrowed(text),
+        Cow::Owned(bytes) => Cow::Owned(String::from_utf8_lossy(&bytes).into_owned()),
+    }
+}
+
+/// The text of a PDF text string: UTF-16BE after a byte order mark, or
+/// else one byte to a character.
+fn decode_text(bytes: &[u8]) -> String {
+    match bytes.strip_prefix(&[0xFE, 0xFF]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        None => bytes.iter().map(|&byte| byte as char).collect(),
+    }
+}
+
+/// The packet for `entries`, with what `info` holds of the title, creator
+/// and producer.
+fn packet(info: Option<&Dictionary>, entries: &[(String, String)]) -> String {
+    let info_text = |key: &[u8]| {
+        let text = info.and_then(|info| info.get(key).ok()).and_then(|value| value.as_str().ok()).map(decode_text);
+        text.filter(|text| !text.is_empty())
+    };
+    let mut properties = vec!["<dc:format>application/pdf</dc:format>".to_string()];
+    if let Some(title) = info_text(b"Title") {
+        properties.push(format!("<dc:title><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:title>", xml_text(&title)));
+    }
+    if let Some(creator) = info_text(b"Creator") {
+        properties.push(format!("<xmp:CreatorTool>{}</xmp:CreatorTool>", xml_text(&creator)));
+    }
+    if let Some(producer) = info_text(b"Producer") {
+        properties.push(format!("<pdf:Producer>{}</pdf:Producer>", xml_text(&producer)));
+    }
+    for (key, value) in entries {
+        properties.push(format!("<pdfx:{0}>{1}</pdfx:{0}>", key, xml_text(value)));
+    }
+    let properties: String = properties.iter().map(|property| format!("    {}\n", property)).collect();
+    format!(
+        "<?xpacket begin=\"\u{FEFF}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+         <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+         \x20 <rdf:Description rdf:about=\"\" \
+         xmlns:dc=\"http://purl.org/dc/elements/1.1/\" \
+         xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\" \
+         xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\" \
+         xmlns:pdfx=\"{}\">\n\
+         {}\
+         \x20 </rdf:Description>\n\
+         </rdf:RDF>\n\
+         </x:xmpmeta>\n\
+         <?xpacket end=\"r\"?>",
+        CUSTOM_NAMESPACE, properties
+    )
+}
+
+/// Adds an XMP packet holding `entries`, in order, as the catalog's
+/// `/Metadata`, replacing any packet already there.
+pub fn write_xmp(pdf: &[u8], entries: &[(String, String)]) -> Result<Vec<u8>, ConversionError> {
+    let pdf_error = |e: lopdf::Error| ConversionError::Pdf(e.to_string());
+    let mut document = lopdf::Document::load_mem(pdf).map_err(pdf_error)?;
+    let info = document.trailer.get(b"Info").and_then(Object::as_reference).and_then(|id| document.get_dictionary(id)).ok();
+    let xml = packet(info, entries);
+
+    let mut stream_dict = Dictionary::new();
+    stream_dict.set("Type", Object::Name(b"Metadata".to_vec()));
+    stream_dict.set("Subtype", Object::Name(b"XML".to_vec()));
+    //Left uncompressed, so indexers reading the file's bytes find it
+    let metadata_id = document.add_object(Stream::new(stream_dict, xml.into_bytes()).with_compression(false));
+    let catalog_id = document.trailer.get(b"Root").and_then(Object::as_reference).map_err(pdf_error)?;
+    document.get_dictionary_mut(catalog_id).map_err(pdf_error)?.set("Metadata", Object::Reference(metadata_id));
+
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use crate::tests::sample_pdf;
+
+    #[test]
+    fn parses_meta_entries() {
+        assert_eq!(parse_meta("dept.code=R&D = 7"), Ok(("dept.code".to_string(), "R&D = 7".to_string())));
+        assert!(parse_meta("no-separator").is_err());
+        assert!(parse_meta("1st=value").is_err());
+        assert!(parse_meta("has space=value").is_err());
+    }
+
+    #[test]
+    fn writes_each_entry_under_the_catalog_metadata() {
+        let pdf = sample_pdf("Indexed", &Config::new("meta.docx", "meta.pdf"));
+        let entries = [("project".to_string(), "Apollo & Co".to_string()), ("dept.code".to_string(), "R7".to_string())];
+        let pdf = write_xmp(&pdf, &entries).unwrap();
+
+        let document = lopdf::Document::load_mem(&pdf).unwrap();
+        let metadata_id = document.catalog().and_then(|catalog| catalog.get(b"Metadata")).and_then(Object::as_reference).unwrap();
+        let metadata = document.get_object(metadata_id).and_then(Object::as_stream).unwrap();
+        assert!(metadata.dict.type_is(b"Metadata"));
+        assert!(metadata.dict.get(b"Filter").is_err());
+        let xml = String::from_utf8(metadata.content.clone()).unwrap();
+        assert!(xml.contains(&format!("xmlns:pdfx=\"{}\"", CUSTOM_NAMESPACE)));
+        let project = xml.find("<pdfx:project>Apollo &amp; Co</pdfx:project>").unwrap();
+        let dept = xml.find("<pdfx:dept.code>R7</pdfx:dept.code>").unwrap();
+        assert!(project < dept);
+    }
+}